@@ -0,0 +1,187 @@
+use std::fmt;
+
+use transport::elizabeth::Capability;
+use transport::registry::{DeviceConfig, Registry};
+use transport::state::StateResponse;
+use transport::{DeviceId, DeviceType};
+
+use crate::StateCache;
+
+/// A difference between the registry and what the gateways report.
+#[derive(Debug, PartialEq)]
+pub enum Mismatch {
+    /// The gateway never reported a state of a registry device.
+    Silent(DeviceId, &'static str),
+    /// A gateway reports a device the registry doesn't have.
+    Unknown(DeviceId),
+    /// The device reports a value it isn't declared with.
+    Undeclared(DeviceId, String),
+    /// The device doesn't report a value it is declared with.
+    Missing(DeviceId, &'static str),
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Silent(id, gateway) => write!(f, "{gateway} never reported a state of {id}"),
+            Self::Unknown(id) => write!(f, "{id} reports a state but is not in the registry"),
+            Self::Undeclared(id, value) => {
+                write!(f, "{id} reports {value} it is not declared with")
+            }
+            Self::Missing(id, value) => write!(f, "{id} doesn't report {value}"),
+        }
+    }
+}
+
+/// Compares the devices of the registry with the states the gateways have
+/// reported so far.
+pub fn validate(registry: &Registry, cache: &StateCache) -> Vec<Mismatch> {
+    let mut mismatches = vec![];
+
+    for config in registry.devices() {
+        let id = config.id();
+
+        match cache.last_known(&id) {
+            Some(state) => mismatches.extend(validate_state(config, &state)),
+            None => mismatches.push(Mismatch::Silent(id, config.backend.gateway())),
+        }
+    }
+
+    for id in cache.devices() {
+        if registry.device(&id).is_none() {
+            mismatches.push(Mismatch::Unknown(id));
+        }
+    }
+
+    mismatches
+}
+
+fn validate_state(config: &DeviceConfig, state: &StateResponse) -> Vec<Mismatch> {
+    let id = config.id();
+    let mut mismatches = vec![];
+
+    match state {
+        StateResponse::Elisa(state) => {
+            if !config.work_speeds().contains(&state.work_speed) {
+                mismatches.push(Mismatch::Undeclared(
+                    id,
+                    format!("work speed {:?}", state.work_speed),
+                ));
+            }
+        }
+        StateResponse::Elizabeth(state) => {
+            let has = |matches: fn(&Capability) -> bool| state.capabilities.iter().any(matches);
+
+            if !has(|capability| matches!(capability, Capability::IsEnabled(_))) {
+                mismatches.push(Mismatch::Missing(id.clone(), "whether it is enabled"));
+            }
+
+            match config.device_type {
+                DeviceType::Thermostat
+                    if !has(|capability| matches!(capability, Capability::Temperature(_))) =>
+                {
+                    mismatches.push(Mismatch::Missing(id, "the target temperature"));
+                }
+                DeviceType::Recuperator => {
+                    for capability in &state.capabilities {
+                        if let Capability::FanSpeed(speed) = capability {
+                            if !config.fan_speeds().contains(speed) {
+                                mismatches.push(Mismatch::Undeclared(
+                                    id.clone(),
+                                    format!("fan speed {speed:?}"),
+                                ));
+                            }
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+        StateResponse::Elisheba(_) | StateResponse::Isabel(_) | StateResponse::Unknown(_) => (),
+    }
+
+    mismatches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use transport::elizabeth::{CurrentState, FanSpeed};
+    use transport::{elisheba, Room};
+
+    const REGISTRY: &str = r#"
+        [[rooms]]
+        id = "living_room"
+        name = "Зал"
+
+        [[devices]]
+        type = "light"
+        room = "living_room"
+        gateway = "elisheba"
+        device_id = "1002074ed2"
+
+        [[devices]]
+        type = "recuperator"
+        room = "living_room"
+        gateway = "elizabeth"
+        page_id = "ef8f4a07-6fc4-4b7e-99e2-d1c71f4fd96d"
+        fan_speeds = ["low", "high"]
+
+        [[devices]]
+        type = "thermostat"
+        room = "living_room"
+        gateway = "elizabeth"
+        page_id = "ef8f4a07-6fc4-4b7e-99e2-d1c71f4fd96d"
+    "#;
+
+    #[test]
+    fn test_validate() {
+        let registry: Registry = REGISTRY.parse().unwrap();
+        let cache = StateCache::default();
+
+        let living_room = Room::new("living_room");
+
+        cache.restore(
+            DeviceId::recuperator_at_room(living_room.clone()),
+            StateResponse::Elizabeth(CurrentState {
+                room: living_room.clone(),
+                device_type: DeviceType::Recuperator,
+                capabilities: vec![
+                    Capability::IsEnabled(true),
+                    Capability::FanSpeed(FanSpeed::Medium),
+                ],
+            }),
+        );
+        cache.restore(
+            DeviceId::thermostat_at_room(living_room.clone()),
+            StateResponse::Elizabeth(CurrentState {
+                room: living_room.clone(),
+                device_type: DeviceType::Thermostat,
+                capabilities: vec![Capability::IsEnabled(false)],
+            }),
+        );
+        cache.update(
+            DeviceId::light_at_room(Room::new("nursery")),
+            StateResponse::Elisheba(elisheba::State {
+                is_enabled: true,
+                room: Room::new("nursery"),
+            }),
+        );
+
+        assert_eq!(
+            validate(&registry, &cache),
+            vec![
+                Mismatch::Silent(DeviceId::light_at_room(living_room.clone()), "elisheba"),
+                Mismatch::Undeclared(
+                    DeviceId::recuperator_at_room(living_room.clone()),
+                    "fan speed Medium".to_string()
+                ),
+                Mismatch::Missing(
+                    DeviceId::thermostat_at_room(living_room),
+                    "the target temperature"
+                ),
+                Mismatch::Unknown(DeviceId::light_at_room(Room::new("nursery"))),
+            ]
+        );
+    }
+}
@@ -7,6 +7,12 @@ pub enum Error {
     Io(std::io::Error),
     Join(tokio::task::JoinError),
     Http(Box<chipp_http::Error>),
+    Registry(transport::registry::Error),
+    Toml(toml::de::Error),
+    Rusqlite(rusqlite::Error),
+    Jwt(jsonwebtoken::errors::Error),
+    InvalidJwtKeys(&'static str),
+    NoUsers,
 }
 
 impl From<paho_mqtt::Error> for Error {
@@ -39,6 +45,30 @@ impl From<chipp_http::Error> for Error {
     }
 }
 
+impl From<transport::registry::Error> for Error {
+    fn from(err: transport::registry::Error) -> Self {
+        Self::Registry(err)
+    }
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(err: toml::de::Error) -> Self {
+        Self::Toml(err)
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(err: rusqlite::Error) -> Self {
+        Self::Rusqlite(err)
+    }
+}
+
+impl From<jsonwebtoken::errors::Error> for Error {
+    fn from(err: jsonwebtoken::errors::Error) -> Self {
+        Self::Jwt(err)
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -47,6 +77,12 @@ impl fmt::Display for Error {
             Self::Io(err) => write!(f, "io error: {err}"),
             Self::Join(err) => write!(f, "join error: {err}"),
             Self::Http(err) => write!(f, "http error: {err}"),
+            Self::Registry(err) => write!(f, "registry error: {err}"),
+            Self::Toml(err) => write!(f, "toml error: {err}"),
+            Self::Rusqlite(err) => write!(f, "rusqlite error: {err}"),
+            Self::Jwt(err) => write!(f, "jwt error: {err}"),
+            Self::InvalidJwtKeys(reason) => write!(f, "invalid jwt keys: {reason}"),
+            Self::NoUsers => write!(f, "no users are configured"),
         }
     }
 }
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use log::info;
+use transport::availability::{Availability, HEARTBEAT_INTERVAL};
+use transport::registry::Registry;
+use transport::DeviceId;
+
+/// A gateway missing this many heartbeats is considered dead even if the
+/// broker has not published its last will yet.
+const MISSED_HEARTBEATS: u32 = 3;
+
+/// Last known availability of the gateway services.
+#[derive(Default)]
+pub struct Gateways {
+    statuses: RwLock<HashMap<String, Status>>,
+}
+
+struct Status {
+    availability: Availability,
+    updated_at: Instant,
+}
+
+impl Gateways {
+    pub fn update(&self, service: String, availability: Availability) {
+        let mut statuses = self.statuses.write().unwrap();
+
+        let was_online = statuses
+            .get(&service)
+            .map(|status| status.availability.is_online());
+
+        if was_online != Some(availability.is_online()) {
+            info!("{service} is {}", status_name(&availability));
+        }
+
+        statuses.insert(
+            service,
+            Status {
+                availability,
+                updated_at: Instant::now(),
+            },
+        );
+    }
+
+    /// Gateways that never announced themselves are assumed to be online.
+    pub fn is_online(&self, service: &str) -> bool {
+        self.is_online_at(service, Instant::now())
+    }
+
+    /// Gateway serving `id` if it is known to be offline.
+    pub fn offline_gateway(&self, registry: &Registry, id: &DeviceId) -> Option<&'static str> {
+        let gateway = registry.device(id)?.backend.gateway();
+
+        if self.is_online(gateway) {
+            None
+        } else {
+            Some(gateway)
+        }
+    }
+
+    fn is_online_at(&self, service: &str, now: Instant) -> bool {
+        let statuses = self.statuses.read().unwrap();
+
+        match statuses.get(service) {
+            Some(status) => {
+                let timeout: Duration = HEARTBEAT_INTERVAL * MISSED_HEARTBEATS;
+
+                status.availability.is_online()
+                    && now.saturating_duration_since(status.updated_at) < timeout
+            }
+            None => true,
+        }
+    }
+}
+
+fn status_name(availability: &Availability) -> &'static str {
+    if availability.is_online() {
+        "online"
+    } else {
+        "offline"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn online() -> Availability {
+        Availability::Online {
+            version: Some("0.1.0".to_string()),
+            uptime: Some(60),
+        }
+    }
+
+    #[test]
+    fn test_availability() {
+        let gateways = Gateways::default();
+
+        assert!(gateways.is_online("elisa"));
+
+        gateways.update("elisa".to_string(), online());
+        assert!(gateways.is_online("elisa"));
+
+        gateways.update("elisa".to_string(), Availability::Offline);
+        assert!(!gateways.is_online("elisa"));
+        assert!(gateways.is_online("elisheba"));
+    }
+
+    #[test]
+    fn test_missed_heartbeats() {
+        let gateways = Gateways::default();
+        gateways.update("elisa".to_string(), online());
+
+        let now = Instant::now();
+
+        assert!(gateways.is_online_at("elisa", now + HEARTBEAT_INTERVAL));
+        assert!(!gateways.is_online_at("elisa", now + HEARTBEAT_INTERVAL * 4));
+    }
+}
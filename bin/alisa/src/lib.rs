@@ -1,8 +1,17 @@
+pub mod catalogue;
+mod gateways;
 mod reporter;
+mod state_cache;
+mod token_store;
+mod users;
 mod web_service;
 
+pub use gateways::Gateways;
 pub use reporter::Reporter;
-pub use web_service::router;
+pub use state_cache::StateCache;
+pub use token_store::TokenStore;
+pub use users::{User, Users};
+pub use web_service::{routers, ApiKeys, JwtKeys, Language, Routers, TrustedProxy};
 
 mod error;
 pub use error::Error;
@@ -1,16 +1,44 @@
-use alisa::{router, Reporter, Result};
-use transport::state::StateUpdate;
-use transport::{connect_mqtt, Topic};
+use alisa::{
+    catalogue, routers, ApiKeys, Gateways, JwtKeys, Language, Reporter, Result, StateCache,
+    TokenStore, TrustedProxy, Users,
+};
+use transport::action::ErrorKind;
+use transport::availability::Availability;
+use transport::registry::Registry;
+use transport::rpc::{self, Responder};
+use transport::scene;
+use transport::state::{StateResponse, StateUpdate};
+use transport::{envelope, MqttSession, Topic};
 
+use std::future::IntoFuture;
+use std::net::SocketAddr;
+use std::sync::Arc;
 use std::time::Duration;
 
-use futures_util::StreamExt;
-use log::{error, info};
-use paho_mqtt::AsyncClient as MqClient;
+use log::{error, info, warn};
 use tokio::signal::unix::{signal, SignalKind};
-use tokio::{task, time};
+use tokio::sync::broadcast;
+use tokio::task;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
+const SCENE_DEADLINE: Duration = Duration::from_secs(10);
+
+/// State updates kept for slow local API clients.
+const UPDATES_CAPACITY: usize = 64;
+
+/// Time for the gateways to report their devices before the registry is
+/// compared with them.
+const CATALOGUE_CHECK_DELAY: Duration = Duration::from_secs(60);
+
+/// Alice skill, published by the proxy.
+const ALICE_ADDRESS: &str = "0.0.0.0:8080";
+
+/// Local API, kept off the proxy and reachable only where the port is
+/// published.
+const LOCAL_API_ADDRESS: &str = "0.0.0.0:8081";
+
+/// Retained last known state of every device.
+const DEVICE_STATE_FILTER: &str = "state/+/+";
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -18,18 +46,116 @@ async fn main() -> Result<()> {
 
     info!("alisa version {VERSION}");
 
+    let registry_path = std::env::var("REGISTRY_PATH").expect("set ENV variable REGISTRY_PATH");
+    let registry = Arc::new(Registry::load(registry_path)?);
+
     let mqtt_address = std::env::var("MQTT_ADDRESS").expect("set ENV variable MQTT_ADDRESS");
     let mqtt_username = std::env::var("MQTT_USER").expect("set ENV variable MQTT_USER");
     let mqtt_password = std::env::var("MQTT_PASS").expect("set ENV variable MQTT_PASS");
-    let mqtt_client = connect_mqtt(mqtt_address, mqtt_username, mqtt_password, "alisa").await?;
+    let mqtt_session = MqttSession::connect(
+        mqtt_address.clone(),
+        mqtt_username.clone(),
+        mqtt_password.clone(),
+        "alisa",
+    )
+    .await?;
+    let rpc_client =
+        rpc::Client::connect(mqtt_address, mqtt_username, mqtt_password, "alisa_rpc").await?;
     info!("connected mqtt");
 
     let skill_id = std::env::var("ALICE_SKILL_ID").expect("skill id is required");
     let token = std::env::var("ALICE_TOKEN").expect("token is required");
-    let reporter = Reporter::new(skill_id, token);
 
-    let web_handle = task::spawn(listen_web());
-    let state_handle = task::spawn(subscribe_state(mqtt_client, reporter));
+    let api_keys = match std::env::var("API_KEYS_PATH") {
+        Ok(path) => {
+            let api_keys = ApiKeys::load(path)?;
+            info!("loaded {} local API keys", api_keys.len());
+            Some(api_keys)
+        }
+        Err(_) => {
+            info!("API_KEYS_PATH is not set, local API is disabled");
+            None
+        }
+    };
+
+    let db_path = std::env::var("DB_PATH").expect("set ENV variable DB_PATH");
+    let tokens = Arc::new(TokenStore::open(db_path)?);
+
+    let users_path = std::env::var("USERS_PATH").expect("set ENV variable USERS_PATH");
+    let users = Arc::new(Users::load(users_path)?);
+    info!("loaded {} users", users.len());
+
+    let jwt_keys = match std::env::var("JWT_KEYS_PATH") {
+        Ok(path) => {
+            let jwt_keys = JwtKeys::load(path)?;
+            info!("signing tokens with key {:?}", jwt_keys.signing_kid());
+
+            match std::env::var("JWT_SECRET") {
+                Ok(secret) => jwt_keys.with_legacy_secret(&secret),
+                Err(_) => jwt_keys,
+            }
+        }
+        Err(_) => {
+            let secret = std::env::var("JWT_SECRET").expect("set ENV variable JWT_SECRET");
+            info!("JWT_KEYS_PATH is not set, signing tokens with JWT_SECRET");
+            JwtKeys::hmac(&secret)
+        }
+    };
+
+    let trusted_proxy = match std::env::var("TRUSTED_PROXY") {
+        Ok(proxy) => proxy
+            .parse()
+            .expect("set ENV variable TRUSTED_PROXY to an address or a network"),
+        Err(_) => {
+            info!("TRUSTED_PROXY is not set, ignoring X-Real-IP");
+            TrustedProxy::default()
+        }
+    };
+
+    let language = std::env::var("ALICE_LANGUAGE")
+        .map(Language)
+        .unwrap_or_default();
+
+    let reporter = Arc::new(Reporter::new(
+        skill_id,
+        token,
+        registry.clone(),
+        users.clone(),
+        tokens.clone(),
+    ));
+
+    let gateways = Arc::new(Gateways::default());
+    let cache = Arc::new(StateCache::default());
+    let (updates, _) = broadcast::channel(UPDATES_CAPACITY);
+
+    task::spawn(check_catalogue(registry.clone(), cache.clone()));
+    task::spawn({
+        let reporter = reporter.clone();
+        async move { reporter.run().await }
+    });
+
+    let web_handle = task::spawn(listen_web(
+        registry.clone(),
+        gateways.clone(),
+        cache.clone(),
+        rpc_client.clone(),
+        api_keys.map(Arc::new),
+        tokens,
+        users,
+        Arc::new(jwt_keys),
+        trusted_proxy,
+        language,
+        updates.clone(),
+    ));
+    let state_handle = task::spawn(subscribe_state(
+        mqtt_session,
+        reporter,
+        gateways,
+        cache,
+        rpc_client,
+        registry,
+        updates,
+    ));
 
     tokio::select! {
         _ = try_join(web_handle, state_handle) => {},
@@ -54,68 +180,163 @@ async fn try_join(
     Ok(())
 }
 
-async fn listen_web() -> Result<()> {
-    let router = router();
+async fn check_catalogue(registry: Arc<Registry>, cache: Arc<StateCache>) {
+    tokio::time::sleep(CATALOGUE_CHECK_DELAY).await;
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:8080").await?;
-    axum::serve(listener, router).await?;
+    let mismatches = catalogue::validate(&registry, &cache);
 
-    Ok(())
+    if mismatches.is_empty() {
+        info!("all registry devices match their gateways");
+    }
+
+    for mismatch in mismatches {
+        warn!("registry mismatch: {mismatch}");
+    }
 }
 
-async fn subscribe_state(mut mqtt: MqClient, reporter: Reporter) -> Result<()> {
-    let mut stream = mqtt.get_stream(None);
+#[allow(clippy::too_many_arguments)]
+async fn listen_web(
+    registry: Arc<Registry>,
+    gateways: Arc<Gateways>,
+    cache: Arc<StateCache>,
+    rpc_client: rpc::Client,
+    api_keys: Option<Arc<ApiKeys>>,
+    tokens: Arc<TokenStore>,
+    users: Arc<Users>,
+    jwt_keys: Arc<JwtKeys>,
+    trusted_proxy: TrustedProxy,
+    language: Language,
+    updates: broadcast::Sender<StateUpdate>,
+) -> Result<()> {
+    let local_api = api_keys.is_some();
+    let routers = routers(
+        registry,
+        gateways,
+        cache,
+        rpc_client,
+        api_keys.unwrap_or_default(),
+        tokens,
+        users,
+        jwt_keys,
+        trusted_proxy,
+        language,
+        updates,
+    );
 
-    let topic = Topic::StateUpdate.to_string();
-    mqtt.subscribe(&topic, paho_mqtt::QOS_1).await?;
-    info!("Subscribe to topic: {}", topic);
+    let listener = tokio::net::TcpListener::bind(ALICE_ADDRESS).await?;
+    let alice = axum::serve(
+        listener,
+        routers
+            .alice
+            .into_make_service_with_connect_info::<SocketAddr>(),
+    );
 
-    while let Some(msg_opt) = stream.next().await {
-        if let Some(msg) = msg_opt {
-            if let Some(event) = parse_update(&msg) {
-                match reporter.report_update(event).await {
-                    Ok(_) => (),
-                    Err(err) => error!("Error updating state: {}", err),
-                }
-            } else {
-                error!("unable to parse topic {}", msg.topic());
-            }
-        } else {
-            time::sleep(Duration::from_secs(1)).await;
-            error!("Lost MQTT connection. Attempting reconnect.");
+    if !local_api {
+        alice.await?;
+        return Ok(());
+    }
 
-            loop {
-                match time::timeout(Duration::from_secs(10), mqtt.reconnect()).await {
-                    Ok(Ok(response)) => {
-                        info!("Reconnected to MQTT! {}", response.reason_code());
+    let listener = tokio::net::TcpListener::bind(LOCAL_API_ADDRESS).await?;
+    info!("serving local API on {LOCAL_API_ADDRESS}");
+    let local = axum::serve(
+        listener,
+        routers
+            .local
+            .into_make_service_with_connect_info::<SocketAddr>(),
+    );
 
-                        let topic = Topic::StateUpdate.to_string();
-                        mqtt.subscribe(&topic, paho_mqtt::QOS_1).await?;
-                        info!("Subscribe to topic: {}", topic);
+    tokio::try_join!(alice.into_future(), local.into_future())?;
 
-                        break;
-                    }
-                    Ok(Err(err)) => {
-                        error!("Error MQTT reconnecting: {}", err);
-                        time::sleep(Duration::from_secs(5)).await;
-                    }
-                    Err(err) => {
-                        error!("Error MQTT reconnecting: {}", err);
-                        time::sleep(Duration::from_secs(5)).await;
+    Ok(())
+}
+
+async fn subscribe_state(
+    mut mqtt: MqttSession,
+    reporter: Arc<Reporter>,
+    gateways: Arc<Gateways>,
+    cache: Arc<StateCache>,
+    rpc_client: rpc::Client,
+    registry: Arc<Registry>,
+    updates: broadcast::Sender<StateUpdate>,
+) -> Result<()> {
+    mqtt.subscribe(Topic::StateUpdate).await?;
+    mqtt.subscribe(Topic::Availability("+".to_string())).await?;
+    mqtt.subscribe(Topic::SceneRun).await?;
+    mqtt.subscribe(DEVICE_STATE_FILTER).await?;
+
+    while let Some((topic, msg)) = mqtt.next().await {
+        match topic {
+            Topic::StateUpdate => match envelope::decode::<StateUpdate>(msg.payload()) {
+                Ok(event) => {
+                    // Nobody listening to the local API is fine.
+                    let _ = updates.send(event.clone());
+
+                    reporter.report_update(event);
+                }
+                Err(err) => error!("unable to parse update on {}: {}", msg.topic(), err),
+            },
+            Topic::DeviceState(device_id) => {
+                match envelope::decode::<StateResponse>(msg.payload()) {
+                    Ok(state) if msg.retained() => cache.restore(device_id, state),
+                    Ok(state) => cache.update(device_id, state),
+                    Err(err) => error!("unable to parse state on {}: {}", msg.topic(), err),
+                }
+            }
+            Topic::Availability(service) => match envelope::decode::<Availability>(msg.payload()) {
+                Ok(availability) => {
+                    if !availability.is_online() {
+                        cache.expire_gateway(&service, &registry);
                     }
+
+                    gateways.update(service, availability);
+                }
+                Err(err) => error!("unable to parse availability of {}: {}", service, err),
+            },
+            Topic::SceneRun => {
+                if let Some((request, responder)) = rpc::serve(&msg, mqtt.client()) {
+                    task::spawn(run_scene(
+                        request,
+                        responder,
+                        rpc_client.clone(),
+                        registry.clone(),
+                    ));
                 }
             }
+            _ => (),
         }
     }
 
     Ok(())
 }
 
-fn parse_update(msg: &paho_mqtt::Message) -> Option<StateUpdate> {
-    if let Topic::StateUpdate = msg.topic().parse().ok()? {
-        let update: StateUpdate = serde_json::from_slice(msg.payload()).ok()?;
-        Some(update)
-    } else {
-        None
+async fn run_scene(
+    request: scene::Request,
+    responder: Responder,
+    rpc_client: rpc::Client,
+    registry: Arc<Registry>,
+) {
+    let Some(scene) = registry.scene(&request.scene) else {
+        error!("unknown scene {}", request.scene);
+
+        let message = format!("unknown scene {}", request.scene);
+        let report = scene::Report::failure(request.scene, ErrorKind::InvalidValue, message);
+        responder.respond(&report).await;
+        return;
+    };
+
+    info!("running scene {}", scene.id);
+
+    match scene::run(&rpc_client, &registry, scene, SCENE_DEADLINE).await {
+        Ok(report) => {
+            if let Some(step) = report.first_failure() {
+                error!(
+                    "scene {} failed at {}: {:?}",
+                    scene.id, step.device, step.result
+                );
+            }
+
+            responder.respond(&report).await;
+        }
+        Err(err) => error!("Error running scene {}: {}", scene.id, err),
     }
 }
@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+
+use alice::StateDevice;
+use transport::DeviceId;
+
+/// Updates waiting to be reported to Alice, merged per device, and the
+/// values every user has already been told about.
+#[derive(Default)]
+pub struct Batch {
+    pending: Vec<StateDevice>,
+    reported: HashMap<(String, DeviceId), StateDevice>,
+}
+
+impl Batch {
+    pub fn push(&mut self, device: StateDevice) {
+        match self
+            .pending
+            .iter_mut()
+            .find(|pending| pending.id() == device.id())
+        {
+            Some(pending) => pending.merge(device),
+            None => self.pending.push(device),
+        }
+    }
+
+    pub fn take(&mut self) -> Vec<StateDevice> {
+        std::mem::take(&mut self.pending)
+    }
+
+    /// Values of `devices` that differ from the ones the user was told about.
+    pub fn changes(&self, user_id: &str, devices: &[StateDevice]) -> Vec<StateDevice> {
+        devices
+            .iter()
+            .filter_map(|device| {
+                match self
+                    .reported
+                    .get(&(user_id.to_string(), device.id().clone()))
+                {
+                    Some(reported) => device.changes_since(reported),
+                    None => Some(device.clone()),
+                }
+            })
+            .collect()
+    }
+
+    /// Remembers the values Alice has accepted for the user. Values she
+    /// failed to receive are never marked, so they are reported with the
+    /// next update of their devices.
+    pub fn mark_reported(&mut self, user_id: &str, devices: Vec<StateDevice>) {
+        for device in devices {
+            match self
+                .reported
+                .get_mut(&(user_id.to_string(), device.id().clone()))
+            {
+                Some(reported) => reported.merge(device),
+                None => {
+                    let key = (user_id.to_string(), device.id().clone());
+                    self.reported.insert(key, device);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alice::{Mode, ModeFunction, StateCapability, StateProperty};
+    use transport::Room;
+
+    fn sensor(temperature: f32, humidity: f32) -> StateDevice {
+        StateDevice::new_with_properties(
+            DeviceId::temperature_sensor_at_room(Room::new("nursery")),
+            vec![
+                StateProperty::temperature(temperature),
+                StateProperty::humidity(humidity),
+            ],
+        )
+    }
+
+    fn recuperator(speed: Mode) -> StateDevice {
+        StateDevice::new_with_capabilities(
+            DeviceId::recuperator_at_room(Room::new("living_room")),
+            vec![StateCapability::mode(ModeFunction::FanSpeed, speed)],
+        )
+    }
+
+    fn to_json(devices: Vec<StateDevice>) -> serde_json::Value {
+        serde_json::to_value(devices).unwrap()
+    }
+
+    #[test]
+    fn test_coalescing() {
+        let mut batch = Batch::default();
+
+        batch.push(sensor(22.0, 40.0));
+        batch.push(recuperator(Mode::Low));
+        batch.push(sensor(22.5, 40.0));
+
+        assert_eq!(
+            to_json(batch.take()),
+            to_json(vec![sensor(22.5, 40.0), recuperator(Mode::Low)])
+        );
+        assert!(batch.take().is_empty());
+    }
+
+    #[test]
+    fn test_unchanged_values() {
+        let mut batch = Batch::default();
+
+        batch.mark_reported("chipp", vec![sensor(22.0, 40.0)]);
+
+        assert_eq!(
+            to_json(batch.changes("chipp", &[sensor(22.0, 40.0), recuperator(Mode::Low)])),
+            to_json(vec![recuperator(Mode::Low)])
+        );
+        assert_eq!(
+            to_json(batch.changes("chipp", &[sensor(22.0, 45.0)])),
+            to_json(vec![StateDevice::new_with_properties(
+                DeviceId::temperature_sensor_at_room(Room::new("nursery")),
+                vec![StateProperty::humidity(45.0)],
+            )])
+        );
+    }
+
+    #[test]
+    fn test_per_user() {
+        let mut batch = Batch::default();
+
+        batch.mark_reported("chipp", vec![sensor(22.0, 40.0)]);
+
+        assert!(batch.changes("chipp", &[sensor(22.0, 40.0)]).is_empty());
+        assert_eq!(
+            to_json(batch.changes("guest", &[sensor(22.0, 40.0)])),
+            to_json(vec![sensor(22.0, 40.0)])
+        );
+    }
+
+    #[test]
+    fn test_undelivered() {
+        let mut batch = Batch::default();
+
+        batch.mark_reported("chipp", vec![recuperator(Mode::Low)]);
+
+        // Alice failed to receive the change, so it isn't marked reported.
+        let changes = batch.changes("chipp", &[recuperator(Mode::High)]);
+        assert_eq!(to_json(changes), to_json(vec![recuperator(Mode::High)]));
+
+        assert_eq!(
+            to_json(batch.changes("chipp", &[recuperator(Mode::High)])),
+            to_json(vec![recuperator(Mode::High)])
+        );
+    }
+}
@@ -1,3 +1,4 @@
+mod batch;
 mod light;
 mod recuperator;
 mod temperature_sensor;
@@ -6,86 +7,202 @@ mod vacuum_cleaner;
 
 pub use light::prepare_light_update;
 pub use recuperator::{prepare_recuperator_current_state, prepare_recuperator_update};
-pub use temperature_sensor::prepare_sensor_update;
+pub use temperature_sensor::{prepare_sensor_current_state, prepare_sensor_update};
 pub use thermostat::{prepare_thermostat_current_state, prepare_thermostat_update};
 pub use vacuum_cleaner::prepare_vacuum_updates;
 
-use crate::Result;
+pub(crate) use recuperator::map_fan_speed;
+pub(crate) use vacuum_cleaner::{map_cleanup_mode, map_work_speed};
+
+use crate::{Result, TokenStore, Users};
 use alice::{StateDevice, StateResponse};
+use batch::Batch;
 use transport::elizabeth::State as ElizabethState;
+use transport::isabel::Property;
+use transport::registry::Registry;
 use transport::state::StateUpdate;
 use transport::DeviceType;
 
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
 use chipp_http::{HttpClient, HttpMethod, NoInterceptor};
 use chrono::Utc;
-use log::{debug, error};
+use log::{debug, error, warn};
+use tokio::sync::Notify;
+
+/// Time to gather a burst of updates into one notification.
+const BATCH_WINDOW: Duration = Duration::from_secs(1);
+/// Attempts to deliver a notification, waiting twice as long after every
+/// failed one.
+const MAX_ATTEMPTS: u32 = 4;
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(1);
 
 pub struct Reporter {
     inner: HttpClient<NoInterceptor>,
     skill_id: String,
     token: String,
+    registry: Arc<Registry>,
+    users: Arc<Users>,
+    tokens: Arc<TokenStore>,
+    batch: Mutex<Batch>,
+    pending: Notify,
 }
 
 impl Reporter {
-    pub fn new(skill_id: String, token: String) -> Self {
+    pub fn new(
+        skill_id: String,
+        token: String,
+        registry: Arc<Registry>,
+        users: Arc<Users>,
+        tokens: Arc<TokenStore>,
+    ) -> Self {
         let inner = HttpClient::new("https://dialogs.yandex.net/api/v1").unwrap();
 
         Self {
             inner,
             skill_id,
             token,
+            registry,
+            users,
+            tokens,
+            batch: Mutex::default(),
+            pending: Notify::new(),
         }
     }
 
-    pub async fn report_update(&self, update: StateUpdate) -> Result<()> {
-        let devices = if let Some(devices) = device_from_update(update) {
-            devices
-        } else {
-            return Ok(());
+    /// Queues the update until [`Reporter::run`] reports it.
+    pub fn report_update(&self, update: StateUpdate) {
+        let Some(devices) = device_from_update(update, &self.registry) else {
+            return;
         };
 
+        let mut batch = self.batch.lock().unwrap();
+        for device in devices {
+            batch.push(device);
+        }
+        drop(batch);
+
+        self.pending.notify_one();
+    }
+
+    /// Reports the queued updates, letting more of them come first so a
+    /// burst of them is merged into one notification.
+    pub async fn run(&self) {
+        loop {
+            self.pending.notified().await;
+            tokio::time::sleep(BATCH_WINDOW).await;
+
+            let devices = self.batch.lock().unwrap().take();
+
+            if devices.is_empty() {
+                continue;
+            }
+
+            if let Err(err) = self.report(devices).await {
+                error!("Error updating state: {}", err);
+            }
+        }
+    }
+
+    /// Notifies every linked user who can see the updated devices.
+    async fn report(&self, devices: Vec<StateDevice>) -> Result<()> {
+        let linked_users = self.tokens.linked_users()?;
+
+        for user_id in linked_users {
+            let Some(user) = self.users.user(&user_id) else {
+                continue;
+            };
+
+            let visible: Vec<_> = devices
+                .iter()
+                .filter(|device| user.can_see(&device.id().room))
+                .cloned()
+                .collect();
+            let changes = self.batch.lock().unwrap().changes(&user_id, &visible);
+
+            if !changes.is_empty() && self.notify(user_id.clone(), changes.clone()).await {
+                self.batch.lock().unwrap().mark_reported(&user_id, changes);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether Alice has accepted the notification, retrying the failures
+    /// that may pass later.
+    async fn notify(&self, user_id: String, devices: Vec<StateDevice>) -> bool {
         let now = Utc::now();
-        let body = StateResponse::notification_body(now.timestamp(), "chipp", devices);
+        let body = StateResponse::notification_body(now.timestamp(), user_id, devices);
 
         debug!(
             "state update: {}",
             serde_json::to_string_pretty(&body).unwrap()
         );
 
-        let mut request = self
-            .inner
-            .new_request(["skills", &self.skill_id, "callback", "state"]);
+        let mut delay = FIRST_RETRY_DELAY;
 
-        request.method = HttpMethod::Post;
-        request.set_json_body(&body);
-        request.add_header("Authorization", format!("OAuth {}", self.token));
+        for attempt in 1..=MAX_ATTEMPTS {
+            let mut request =
+                self.inner
+                    .new_request(["skills", &self.skill_id, "callback", "state"]);
 
-        let result = self
-            .inner
-            .perform_request(request, parse_state_report_response)
-            .await;
+            request.method = HttpMethod::Post;
+            request.set_json_body(&body);
+            request.add_header("Authorization", format!("OAuth {}", self.token));
 
-        match result {
-            Ok(_) => {
-                debug!("successfully notified alice about changes");
-            }
-            Err(err) => {
-                if let chipp_http::ErrorKind::HttpError(ref res) = err.kind {
-                    error!("unable to report state changes {}", res.status_code);
-                    debug!("{:#?}", res);
+            let result = self
+                .inner
+                .perform_request(request, parse_state_report_response)
+                .await;
 
-                    if let Ok(json) = std::str::from_utf8(&res.body) {
-                        debug!("{}", json);
+            match result {
+                Ok(_) => {
+                    debug!("successfully notified alice about changes");
+                    return true;
+                }
+                Err(err) if attempt < MAX_ATTEMPTS && is_retriable(&err) => {
+                    warn!(
+                        "unable to report state changes, retrying in {}s: {}",
+                        delay.as_secs(),
+                        err
+                    );
+
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                }
+                Err(err) => {
+                    if let chipp_http::ErrorKind::HttpError(ref res) = err.kind {
+                        error!("unable to report state changes {}", res.status_code);
+                        debug!("{:#?}", res);
+
+                        if let Ok(json) = std::str::from_utf8(&res.body) {
+                            debug!("{}", json);
+                        } else {
+                            error!("unable to report state changes {}", err)
+                        }
                     } else {
                         error!("unable to report state changes {}", err)
                     }
-                } else {
-                    error!("unable to report state changes {}", err)
+
+                    return false;
                 }
             }
         }
 
-        Ok(())
+        false
+    }
+}
+
+/// Network failures, throttling and server errors, but not the rejections
+/// of the notification itself.
+fn is_retriable(err: &chipp_http::Error) -> bool {
+    match err.kind {
+        chipp_http::ErrorKind::HttpError(ref res) => {
+            res.status_code == 429 || res.status_code >= 500
+        }
+        chipp_http::ErrorKind::CurlError(_) => true,
+        chipp_http::ErrorKind::JsonParseError(_) => false,
     }
 }
 
@@ -101,12 +218,22 @@ fn parse_state_report_response(
     }
 }
 
-fn device_from_update(update: StateUpdate) -> Option<Vec<StateDevice>> {
+fn device_from_update(update: StateUpdate, registry: &Registry) -> Option<Vec<StateDevice>> {
     match update {
         StateUpdate::Elizabeth(state) => Some(vec![prepare_elizabeth_device(state)?]),
-        StateUpdate::Elisa(state) => Some(prepare_vacuum_updates(state)),
-        StateUpdate::Isabel(state) => Some(vec![prepare_sensor_update(state)]),
+        StateUpdate::Elisa(state) => Some(prepare_vacuum_updates(state, registry)),
+        StateUpdate::Isabel(state) => match state.property {
+            Property::Unknown(property) => {
+                warn!("skipping unknown sensor property {property}");
+                None
+            }
+            _ => Some(vec![prepare_sensor_update(state)]),
+        },
         StateUpdate::Elisheba(state) => Some(vec![prepare_light_update(state)]),
+        StateUpdate::Unknown(update) => {
+            warn!("skipping unknown state update {update}");
+            None
+        }
     }
 }
 
@@ -40,7 +40,7 @@ pub fn prepare_recuperator_current_state(state: CurrentState) -> StateDevice {
     StateDevice::new_with_capabilities(device_id, state_capabilities)
 }
 
-fn map_fan_speed(speed: transport::elizabeth::FanSpeed) -> Mode {
+pub(crate) fn map_fan_speed(speed: transport::elizabeth::FanSpeed) -> Mode {
     match speed {
         transport::elizabeth::FanSpeed::Low => Mode::Low,
         transport::elizabeth::FanSpeed::Medium => Mode::Medium,
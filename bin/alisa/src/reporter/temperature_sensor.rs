@@ -1,5 +1,5 @@
 use alice::{StateDevice, StateProperty};
-use transport::isabel::State;
+use transport::isabel::{CurrentState, State};
 use transport::DeviceId;
 
 pub fn prepare_sensor_update(state: State) -> StateDevice {
@@ -20,6 +20,26 @@ pub fn prepare_sensor_update(state: State) -> StateDevice {
             properties.push(StateProperty::temperature(temperature));
             properties.push(StateProperty::humidity(humidity));
         }
+        transport::isabel::Property::Unknown(_) => (),
+    }
+
+    StateDevice::new_with_properties(device_id, properties)
+}
+
+pub fn prepare_sensor_current_state(state: CurrentState) -> StateDevice {
+    let device_id = DeviceId::temperature_sensor_at_room(state.room);
+    let mut properties = vec![];
+
+    if let Some(temperature) = state.temperature {
+        properties.push(StateProperty::temperature(temperature));
+    }
+
+    if let Some(humidity) = state.humidity {
+        properties.push(StateProperty::humidity(humidity));
+    }
+
+    if let Some(battery) = state.battery {
+        properties.push(StateProperty::battery_level(battery as f32));
     }
 
     StateDevice::new_with_properties(device_id, properties)
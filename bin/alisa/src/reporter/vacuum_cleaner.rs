@@ -2,11 +2,15 @@ use alice::{
     Mode, ModeFunction::*, StateCapability, StateDevice, StateProperty, ToggleFunction::Pause,
 };
 use transport::elisa::State;
-use transport::{DeviceId, Room};
+use transport::registry::Registry;
+use transport::{DeviceId, DeviceType, Room};
 
-pub fn prepare_vacuum_updates(state: State) -> Vec<StateDevice> {
+pub fn prepare_vacuum_updates(state: State, registry: &Registry) -> Vec<StateDevice> {
     let mut devices = vec![];
-    let all_rooms = vacuum_rooms();
+    let all_rooms = registry
+        .devices_of_type(DeviceType::VacuumCleaner)
+        .map(|device| device.room.clone())
+        .collect::<Vec<_>>();
 
     let state_rooms: &[Room] = if state.is_enabled && !state.rooms.is_empty() {
         &state.rooms
@@ -14,8 +18,8 @@ pub fn prepare_vacuum_updates(state: State) -> Vec<StateDevice> {
         &all_rooms
     };
 
-    for room in all_rooms {
-        let device_id = DeviceId::vacuum_cleaner_at_room(room);
+    for room in &all_rooms {
+        let device_id = DeviceId::vacuum_cleaner_at_room(room.clone());
 
         let properties = vec![StateProperty::battery_level(state.battery_level.into())];
 
@@ -29,7 +33,7 @@ pub fn prepare_vacuum_updates(state: State) -> Vec<StateDevice> {
             map_cleanup_mode(state.cleanup_mode),
         ));
 
-        if state_rooms.contains(&room) {
+        if state_rooms.contains(room) {
             capabilities.push(StateCapability::on_off(state.is_enabled));
             capabilities.push(StateCapability::toggle(Pause, state.is_paused));
         } else {
@@ -47,18 +51,7 @@ pub fn prepare_vacuum_updates(state: State) -> Vec<StateDevice> {
     devices
 }
 
-fn vacuum_rooms() -> [transport::Room; 6] {
-    [
-        Room::Bedroom,
-        Room::Corridor,
-        Room::Hallway,
-        Room::HomeOffice,
-        Room::Kitchen,
-        Room::LivingRoom,
-    ]
-}
-
-fn map_work_speed(speed: transport::elisa::WorkSpeed) -> Mode {
+pub(crate) fn map_work_speed(speed: transport::elisa::WorkSpeed) -> Mode {
     match speed {
         transport::elisa::WorkSpeed::Min => Mode::Low,
         transport::elisa::WorkSpeed::Silent => Mode::Quiet,
@@ -68,7 +61,7 @@ fn map_work_speed(speed: transport::elisa::WorkSpeed) -> Mode {
     }
 }
 
-fn map_cleanup_mode(mode: transport::elisa::CleanupMode) -> Mode {
+pub(crate) fn map_cleanup_mode(mode: transport::elisa::CleanupMode) -> Mode {
     match mode {
         transport::elisa::CleanupMode::DryCleaning => Mode::DryCleaning,
         transport::elisa::CleanupMode::WetCleaning => Mode::WetCleaning,
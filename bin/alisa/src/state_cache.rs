@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use transport::registry::Registry;
+use transport::state::StateResponse;
+use transport::{DeviceId, DeviceType};
+
+/// States older than this are queried from the gateways again.
+const MAX_AGE: Duration = Duration::from_secs(10 * 60);
+
+/// Last known state of every device, fed by the retained `state/<device>`
+/// topics and the gateways' answers to state requests.
+#[derive(Default)]
+pub struct StateCache {
+    entries: RwLock<HashMap<DeviceId, Entry>>,
+}
+
+struct Entry {
+    state: StateResponse,
+    /// `None` for retained states of unknown age.
+    updated_at: Option<Instant>,
+}
+
+impl StateCache {
+    /// Stores a state the gateway has just published.
+    pub fn update(&self, id: DeviceId, state: StateResponse) {
+        self.insert(id, state, Some(Instant::now()));
+    }
+
+    /// Stores a state retained by the broker. It may predate a restart of
+    /// the gateway, so it only serves as a last resort.
+    pub fn restore(&self, id: DeviceId, state: StateResponse) {
+        self.insert(id, state, None);
+    }
+
+    /// Stores a gateway's answer to a state request for every device it
+    /// describes.
+    pub fn update_with_response(&self, response: &StateResponse, registry: &Registry) {
+        for id in described_devices(response, registry) {
+            self.update(id, response.clone());
+        }
+    }
+
+    /// State of `id` if it is recent enough to answer without asking the
+    /// gateway.
+    pub fn fresh(&self, id: &DeviceId) -> Option<StateResponse> {
+        self.fresh_at(id, Instant::now())
+    }
+
+    /// State of `id` regardless of its age.
+    pub fn last_known(&self, id: &DeviceId) -> Option<StateResponse> {
+        let entries = self.entries.read().unwrap();
+        entries.get(id).map(|entry| entry.state.clone())
+    }
+
+    /// Devices with a known state.
+    pub fn devices(&self) -> Vec<DeviceId> {
+        let entries = self.entries.read().unwrap();
+        entries.keys().cloned().collect()
+    }
+
+    /// Marks the states of the devices behind `gateway` as outdated, they
+    /// might change while it is offline.
+    pub fn expire_gateway(&self, gateway: &str, registry: &Registry) {
+        let mut entries = self.entries.write().unwrap();
+
+        for config in registry.devices() {
+            if config.backend.gateway() != gateway {
+                continue;
+            }
+
+            if let Some(entry) = entries.get_mut(&config.id()) {
+                entry.updated_at = None;
+            }
+        }
+    }
+
+    fn insert(&self, id: DeviceId, state: StateResponse, updated_at: Option<Instant>) {
+        let mut entries = self.entries.write().unwrap();
+        entries.insert(id, Entry { state, updated_at });
+    }
+
+    fn fresh_at(&self, id: &DeviceId, now: Instant) -> Option<StateResponse> {
+        let entries = self.entries.read().unwrap();
+        let entry = entries.get(id)?;
+
+        let updated_at = entry.updated_at?;
+
+        if now.saturating_duration_since(updated_at) < MAX_AGE {
+            Some(entry.state.clone())
+        } else {
+            None
+        }
+    }
+}
+
+/// Devices whose state `response` holds. The vacuum cleaner answers for all
+/// of its rooms at once.
+fn described_devices(response: &StateResponse, registry: &Registry) -> Vec<DeviceId> {
+    match response {
+        StateResponse::Elisa(_) => registry
+            .devices_of_type(DeviceType::VacuumCleaner)
+            .map(|config| config.id())
+            .collect(),
+        StateResponse::Elisheba(state) => vec![DeviceId::light_at_room(state.room.clone())],
+        StateResponse::Elizabeth(state) => vec![DeviceId {
+            room: state.room.clone(),
+            device_type: state.device_type,
+        }],
+        StateResponse::Isabel(state) => {
+            vec![DeviceId::temperature_sensor_at_room(state.room.clone())]
+        }
+        StateResponse::Unknown(_) => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use transport::{elisa, elisheba, Room};
+
+    fn registry() -> Registry {
+        include_str!("../../../conf/registry.toml").parse().unwrap()
+    }
+
+    fn light(is_enabled: bool) -> StateResponse {
+        StateResponse::Elisheba(elisheba::State {
+            is_enabled,
+            room: Room::new("corridor"),
+        })
+    }
+
+    #[test]
+    fn test_freshness() {
+        let cache = StateCache::default();
+        let id = DeviceId::light_at_room(Room::new("corridor"));
+
+        assert_eq!(cache.fresh(&id), None);
+
+        cache.restore(id.clone(), light(false));
+        assert_eq!(cache.fresh(&id), None);
+        assert_eq!(cache.last_known(&id), Some(light(false)));
+
+        cache.update(id.clone(), light(true));
+        assert_eq!(cache.fresh(&id), Some(light(true)));
+
+        let later = Instant::now() + MAX_AGE;
+        assert_eq!(cache.fresh_at(&id, later), None);
+        assert_eq!(cache.last_known(&id), Some(light(true)));
+    }
+
+    #[test]
+    fn test_expire_gateway() {
+        let registry = registry();
+        let cache = StateCache::default();
+        let id = DeviceId::light_at_room(Room::new("corridor"));
+
+        cache.update(id.clone(), light(true));
+
+        cache.expire_gateway("elizabeth", &registry);
+        assert_eq!(cache.fresh(&id), Some(light(true)));
+
+        cache.expire_gateway("elisheba", &registry);
+        assert_eq!(cache.fresh(&id), None);
+        assert_eq!(cache.last_known(&id), Some(light(true)));
+    }
+
+    #[test]
+    fn test_vacuum_response() {
+        let registry = registry();
+        let cache = StateCache::default();
+
+        let response = StateResponse::Elisa(elisa::State {
+            battery_level: 100,
+            is_enabled: false,
+            is_paused: false,
+            work_speed: elisa::WorkSpeed::Standard,
+            cleanup_mode: elisa::CleanupMode::DryCleaning,
+            rooms: vec![],
+            consumables: vec![],
+        });
+
+        cache.update_with_response(&response, &registry);
+
+        for config in registry.devices_of_type(DeviceType::VacuumCleaner) {
+            assert_eq!(cache.fresh(&config.id()), Some(response.clone()));
+        }
+    }
+}
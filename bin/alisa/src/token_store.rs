@@ -0,0 +1,239 @@
+use std::path::Path;
+use std::sync::Mutex;
+
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::Result;
+
+/// Authorization codes waiting to be exchanged and the grants they are
+/// exchanged for. A grant lives from linking the account with Yandex until
+/// unlinking it; every refresh bumps its version so only the latest refresh
+/// token can be used again.
+pub struct TokenStore {
+    connection: Mutex<Connection>,
+}
+
+/// A grant as a token refers to it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Grant {
+    pub id: String,
+    pub user_id: String,
+    pub version: u32,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Rotation {
+    Rotated(Grant),
+    /// An outdated refresh token was used, the grant has been revoked since
+    /// the token has likely leaked.
+    Reused,
+    Revoked,
+}
+
+impl TokenStore {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<TokenStore> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    #[cfg(test)]
+    pub fn in_memory() -> TokenStore {
+        Self::with_connection(Connection::open_in_memory().unwrap()).unwrap()
+    }
+
+    fn with_connection(connection: Connection) -> Result<TokenStore> {
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS codes (
+                id TEXT PRIMARY KEY,
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+            );
+            CREATE TABLE IF NOT EXISTS grants (
+                id TEXT PRIMARY KEY,
+                user_id TEXT NOT NULL,
+                version INTEGER NOT NULL,
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                revoked_at TIMESTAMP
+            );",
+        )?;
+
+        Ok(TokenStore {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    /// Remembers an authorization code until it is exchanged. Codes expire
+    /// in seconds, those never exchanged are dropped an hour later.
+    pub fn save_code(&self, id: &str) -> Result<()> {
+        let connection = self.connection.lock().unwrap();
+
+        connection.execute(
+            "DELETE FROM codes WHERE created_at < datetime('now', '-1 hour')",
+            [],
+        )?;
+        connection.execute("INSERT INTO codes (id) VALUES (?)", [id])?;
+
+        Ok(())
+    }
+
+    /// Forgets the code, returning whether it was there to be exchanged.
+    pub fn redeem_code(&self, id: &str) -> Result<bool> {
+        let connection = self.connection.lock().unwrap();
+        let deleted = connection.execute("DELETE FROM codes WHERE id = ?", [id])?;
+
+        Ok(deleted == 1)
+    }
+
+    pub fn create_grant(&self, user_id: &str) -> Result<Grant> {
+        let grant = Grant {
+            id: uuid::Uuid::new_v4().to_string(),
+            user_id: user_id.to_string(),
+            version: 0,
+        };
+
+        let connection = self.connection.lock().unwrap();
+        connection.execute(
+            "INSERT INTO grants (id, user_id, version) VALUES (?, ?, ?)",
+            params![grant.id, grant.user_id, grant.version],
+        )?;
+
+        Ok(grant)
+    }
+
+    /// Exchanges the grant version of a refresh token for the next one.
+    pub fn rotate(&self, grant: &Grant) -> Result<Rotation> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+
+        let version: Option<u32> = transaction
+            .query_row(
+                "SELECT version FROM grants
+                 WHERE id = ? AND user_id = ? AND revoked_at IS NULL",
+                [&grant.id, &grant.user_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        let rotation = match version {
+            None => Rotation::Revoked,
+            Some(version) if version != grant.version => {
+                transaction.execute(
+                    "UPDATE grants SET revoked_at = CURRENT_TIMESTAMP WHERE id = ?",
+                    [&grant.id],
+                )?;
+
+                Rotation::Reused
+            }
+            Some(version) => {
+                transaction.execute(
+                    "UPDATE grants SET version = ? WHERE id = ?",
+                    params![version + 1, grant.id],
+                )?;
+
+                Rotation::Rotated(Grant {
+                    version: version + 1,
+                    ..grant.clone()
+                })
+            }
+        };
+
+        transaction.commit()?;
+
+        Ok(rotation)
+    }
+
+    pub fn is_active(&self, grant_id: &str) -> Result<bool> {
+        let connection = self.connection.lock().unwrap();
+
+        let active = connection
+            .query_row(
+                "SELECT 1 FROM grants WHERE id = ? AND revoked_at IS NULL",
+                [grant_id],
+                |_| Ok(()),
+            )
+            .optional()?;
+
+        Ok(active.is_some())
+    }
+
+    /// Users with at least one active grant, those Alice expects state
+    /// notifications for.
+    pub fn linked_users(&self) -> Result<Vec<String>> {
+        let connection = self.connection.lock().unwrap();
+        let mut select =
+            connection.prepare("SELECT DISTINCT user_id FROM grants WHERE revoked_at IS NULL")?;
+
+        let users = select
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(users)
+    }
+
+    pub fn revoke(&self, grant_id: &str) -> Result<()> {
+        let connection = self.connection.lock().unwrap();
+        connection.execute(
+            "UPDATE grants SET revoked_at = CURRENT_TIMESTAMP
+             WHERE id = ? AND revoked_at IS NULL",
+            [grant_id],
+        )?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes_are_single_use() {
+        let store = TokenStore::in_memory();
+
+        store.save_code("code").unwrap();
+
+        assert!(store.redeem_code("code").unwrap());
+        assert!(!store.redeem_code("code").unwrap());
+        assert!(!store.redeem_code("unknown").unwrap());
+    }
+
+    #[test]
+    fn test_rotation() {
+        let store = TokenStore::in_memory();
+
+        let grant = store.create_grant("chipp").unwrap();
+        assert!(store.is_active(&grant.id).unwrap());
+
+        let stolen = Grant {
+            user_id: "guest".to_string(),
+            ..grant.clone()
+        };
+        assert_eq!(store.rotate(&stolen).unwrap(), Rotation::Revoked);
+
+        let Rotation::Rotated(rotated) = store.rotate(&grant).unwrap() else {
+            panic!("grant is not rotated");
+        };
+        assert_eq!(rotated.version, grant.version + 1);
+        assert!(store.is_active(&grant.id).unwrap());
+
+        assert_eq!(store.rotate(&grant).unwrap(), Rotation::Reused);
+        assert!(!store.is_active(&grant.id).unwrap());
+
+        assert_eq!(store.rotate(&rotated).unwrap(), Rotation::Revoked);
+    }
+
+    #[test]
+    fn test_revoke() {
+        let store = TokenStore::in_memory();
+
+        let grant = store.create_grant("chipp").unwrap();
+        let other = store.create_grant("guest").unwrap();
+
+        assert_eq!(store.linked_users().unwrap().len(), 2);
+
+        store.revoke(&grant.id).unwrap();
+
+        assert_eq!(store.linked_users().unwrap(), vec!["guest".to_string()]);
+        assert!(!store.is_active(&grant.id).unwrap());
+        assert!(store.is_active(&other.id).unwrap());
+        assert_eq!(store.rotate(&grant).unwrap(), Rotation::Revoked);
+    }
+}
@@ -0,0 +1,226 @@
+use std::path::Path;
+use std::str::FromStr;
+
+use argon2::{Argon2, PasswordHash, PasswordVerifier};
+use serde::Deserialize;
+use transport::registry::Registry;
+use transport::scene::Scene;
+use transport::{DeviceId, Room};
+
+use crate::{Error, Result};
+
+/// Hash of a random password with the default parameters, checked against
+/// for unknown users.
+const DUMMY_HASH: &str = "$argon2id$v=19$m=19456,t=2,p=1$9JqvKd3cJ3XBxHyiyc4tJA$2vlY8zB1v5Ea7x1f3DyCM1m0oMLVoRrJd0qLfgJbN7g";
+
+/// Household accounts that can link lisa to their Yandex accounts, with
+/// Argon2id hashes of their passwords in the PHC string format.
+///
+/// ```toml
+/// [[users]]
+/// id = "chipp"
+/// password_hash = "$argon2id$v=19$m=19456,t=2,p=1$..."
+/// # Rooms whose devices the user controls, all rooms when omitted.
+/// rooms = ["nursery", "bedroom"]
+/// ```
+#[derive(Debug, Default, Deserialize)]
+pub struct Users {
+    #[serde(default)]
+    users: Vec<User>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct User {
+    pub id: String,
+    password_hash: String,
+    rooms: Option<Vec<Room>>,
+}
+
+impl Users {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Users> {
+        std::fs::read_to_string(path)?.parse()
+    }
+
+    pub fn len(&self) -> usize {
+        self.users.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.users.is_empty()
+    }
+
+    pub fn user(&self, id: &str) -> Option<&User> {
+        self.users.iter().find(|user| user.id == id)
+    }
+
+    /// The user if `password` matches their hash. Unknown users take as
+    /// long to check as known ones, so timing doesn't tell them apart.
+    pub fn verify(&self, id: &str, password: &str) -> Option<&User> {
+        let user = self.user(id);
+        let hash = user.map_or(DUMMY_HASH, |user| &user.password_hash);
+        let hash = PasswordHash::new(hash).ok()?;
+
+        Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .ok()?;
+
+        user
+    }
+}
+
+impl FromStr for Users {
+    type Err = Error;
+
+    /// Nobody could link lisa without users, so an empty list is an error.
+    fn from_str(s: &str) -> Result<Self> {
+        let users: Users = toml::from_str(s)?;
+
+        if users.is_empty() {
+            return Err(Error::NoUsers);
+        }
+
+        Ok(users)
+    }
+}
+
+impl User {
+    pub fn can_see(&self, room: &Room) -> bool {
+        match &self.rooms {
+            Some(rooms) => rooms.contains(room),
+            None => true,
+        }
+    }
+
+    /// Whether the user can see the device, or the scene together with all
+    /// the devices it controls. The room of a scene id is its slug, not a
+    /// room, so scenes are looked up in the `registry`.
+    pub fn can_see_device(&self, id: &DeviceId, registry: &Registry) -> bool {
+        match id.scene_id() {
+            Some(scene_id) => registry
+                .scene(&scene_id)
+                .is_some_and(|scene| self.can_see_scene(scene)),
+            None => self.can_see(&id.room),
+        }
+    }
+
+    pub fn can_see_scene(&self, scene: &Scene) -> bool {
+        self.can_see(&scene.room)
+            && scene
+                .steps
+                .iter()
+                .all(|step| self.can_see(&step.device.room))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use transport::SceneId;
+
+    /// Hash of "kek".
+    const USERS: &str = r#"
+        [[users]]
+        id = "chipp"
+        password_hash = "$argon2id$v=19$m=19456,t=2,p=1$7y2a4YwMTB4Atf6NUBGxUA$X8bKikCPA+sa9luwSwmMbijwgoIpPqi8CypTqL0XtDs"
+
+        [[users]]
+        id = "guest"
+        password_hash = "$argon2id$v=19$m=19456,t=2,p=1$7y2a4YwMTB4Atf6NUBGxUA$X8bKikCPA+sa9luwSwmMbijwgoIpPqi8CypTqL0XtDs"
+        rooms = ["nursery"]
+    "#;
+
+    #[test]
+    fn test_verify() {
+        let users: Users = toml::from_str(USERS).unwrap();
+
+        assert_eq!(users.len(), 2);
+        assert_eq!(users.verify("chipp", "kek").unwrap().id, "chipp");
+        assert!(users.verify("chipp", "lol").is_none());
+        assert!(users.verify("stranger", "kek").is_none());
+    }
+
+    #[test]
+    fn test_no_users() {
+        assert!(matches!("".parse::<Users>(), Err(Error::NoUsers)));
+        assert!(matches!(
+            "# [[users]]\n# id = \"chipp\"".parse::<Users>(),
+            Err(Error::NoUsers)
+        ));
+        assert_eq!(USERS.parse::<Users>().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_dummy_hash() {
+        assert!(PasswordHash::new(DUMMY_HASH).is_ok());
+    }
+
+    #[test]
+    fn test_visibility() {
+        let users: Users = toml::from_str(USERS).unwrap();
+
+        let chipp = users.user("chipp").unwrap();
+        assert!(chipp.can_see(&Room::new("nursery")));
+        assert!(chipp.can_see(&Room::new("bedroom")));
+
+        let guest = users.user("guest").unwrap();
+        assert!(guest.can_see(&Room::new("nursery")));
+        assert!(!guest.can_see(&Room::new("bedroom")));
+    }
+
+    #[test]
+    fn test_scene_visibility() {
+        let registry: Registry = r#"
+            [[rooms]]
+            id = "nursery"
+            name = "Детская"
+
+            [[rooms]]
+            id = "bedroom"
+            name = "Спальня"
+
+            [[devices]]
+            type = "light"
+            room = "nursery"
+            gateway = "elisheba"
+            device_id = "1002074ed2"
+
+            [[devices]]
+            type = "light"
+            room = "bedroom"
+            gateway = "elisheba"
+            device_id = "1002074ed3"
+
+            [[scenes]]
+            id = "bedtime"
+            name = "Сон"
+            room = "nursery"
+            steps = [{ device = "light/nursery", action = "turn_off" }]
+
+            [[scenes]]
+            id = "night"
+            name = "Ночь"
+            room = "nursery"
+            steps = [
+                { device = "light/nursery", action = "turn_off" },
+                { device = "light/bedroom", action = "turn_off" },
+            ]
+        "#
+        .parse()
+        .unwrap();
+
+        let users: Users = toml::from_str(USERS).unwrap();
+        let guest = users.user("guest").unwrap();
+
+        let bedtime = DeviceId::scene(&SceneId::new("bedtime"));
+        let night = DeviceId::scene(&SceneId::new("night"));
+        let unknown = DeviceId::scene(&SceneId::new("nursery"));
+
+        assert!(guest.can_see_device(&bedtime, &registry));
+        assert!(!guest.can_see_device(&night, &registry));
+        assert!(!guest.can_see_device(&unknown, &registry));
+        assert!(users
+            .user("chipp")
+            .unwrap()
+            .can_see_device(&night, &registry));
+    }
+}
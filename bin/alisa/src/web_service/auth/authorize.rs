@@ -1,46 +1,112 @@
 use std::borrow::Cow;
+use std::net::SocketAddr;
+use std::sync::Arc;
 
 use axum::{
-    http::{header::LOCATION, HeaderMap, StatusCode},
+    extract::{ConnectInfo, State},
+    http::{
+        header::{LOCATION, RETRY_AFTER},
+        HeaderMap, StatusCode,
+    },
     response::IntoResponse,
     Form,
 };
 use chrono::Duration;
-use log::{error, info};
+use log::{error, info, warn};
 use serde::Deserialize;
 use url::Url;
 
+use super::keys::JwtKeys;
+use super::login_limiter::LoginLimiter;
 use super::token::{create_token_with_expiration_in, TokenError, TokenType};
+use super::trusted_proxy::TrustedProxy;
+use crate::{TokenStore, Users};
 
-pub async fn authorize(Form(credentials): Form<Credentials<'_>>) -> impl IntoResponse {
-    if verify_credentials(&credentials) {
-        let redirect_url = match get_redirect_url_from_params(credentials) {
-            Ok(Some(redirect_url)) => redirect_url,
-            Ok(None) => return (StatusCode::BAD_REQUEST, HeaderMap::new()),
-            Err(err) => {
-                error!("failed to create authorization code: {}", err);
-                return (StatusCode::INTERNAL_SERVER_ERROR, HeaderMap::new());
-            }
-        };
+/// Failed and throttled logins are logged with this target so they can be
+/// kept apart from the rest of the log.
+const AUDIT: &str = "audit";
 
-        info!("received credentials, generating an authorization code");
+#[allow(clippy::too_many_arguments)]
+pub async fn authorize(
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request_headers: HeaderMap,
+    State(tokens): State<Arc<TokenStore>>,
+    State(users): State<Arc<Users>>,
+    State(limiter): State<Arc<LoginLimiter>>,
+    State(keys): State<Arc<JwtKeys>>,
+    State(proxy): State<TrustedProxy>,
+    Form(credentials): Form<Credentials<'_>>,
+) -> impl IntoResponse {
+    let ip = proxy.client_ip(&request_headers, peer);
+
+    if let Some(locked_for) = limiter.locked_for(ip, &credentials.user) {
+        warn!(
+            target: AUDIT,
+            "throttled login of {} from {ip} for {}s",
+            credentials.user,
+            locked_for.as_secs()
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, locked_for.as_secs().max(1).into());
+
+        return (StatusCode::TOO_MANY_REQUESTS, headers);
+    }
+
+    if let Some(user) = users.verify(&credentials.user, &credentials.password) {
+        limiter.record_success(ip, &user.id);
+
+        let user_id = user.id.clone();
+        let code_id = uuid::Uuid::new_v4().to_string();
+
+        if let Err(err) = tokens.save_code(&code_id) {
+            error!("failed to save authorization code: {}", err);
+            return (StatusCode::INTERNAL_SERVER_ERROR, HeaderMap::new());
+        }
+
+        let redirect_url =
+            match get_redirect_url_from_params(credentials, &user_id, &code_id, &keys) {
+                Ok(Some(redirect_url)) => redirect_url,
+                Ok(None) => return (StatusCode::BAD_REQUEST, HeaderMap::new()),
+                Err(err) => {
+                    error!("failed to create authorization code: {}", err);
+                    return (StatusCode::INTERNAL_SERVER_ERROR, HeaderMap::new());
+                }
+            };
+
+        info!("received credentials of {user_id}, generating an authorization code");
 
         let mut headers = HeaderMap::new();
         headers.append(LOCATION, redirect_url.as_str().parse().unwrap());
 
         (StatusCode::FOUND, headers)
     } else {
+        warn!(target: AUDIT, "failed login of {} from {ip}", credentials.user);
+        limiter.record_failure(ip, &credentials.user);
+
         (StatusCode::BAD_REQUEST, HeaderMap::new())
     }
 }
 
-fn get_redirect_url_from_params(auth: Credentials) -> Result<Option<Url>, TokenError> {
+fn get_redirect_url_from_params(
+    auth: Credentials,
+    user_id: &str,
+    code_id: &str,
+    keys: &JwtKeys,
+) -> Result<Option<Url>, TokenError> {
     let mut url = match Url::parse(auth.redirect_uri.as_ref()) {
         Ok(url) => url,
         Err(_) => return Ok(None),
     };
 
-    let code = create_token_with_expiration_in(Duration::seconds(30), TokenType::Code)?;
+    let code = create_token_with_expiration_in(
+        Duration::seconds(30),
+        TokenType::Code,
+        user_id,
+        code_id,
+        0,
+        keys,
+    )?;
     url.query_pairs_mut()
         .append_pair("state", &auth.state)
         .append_pair("code", &code);
@@ -55,10 +121,3 @@ pub struct Credentials<'a> {
     state: Cow<'a, str>,
     redirect_uri: Cow<'a, str>,
 }
-
-fn verify_credentials(credentials: &Credentials) -> bool {
-    let user = std::env::var("LISA_USER").expect("Set LISA_USER env variable");
-    let password = std::env::var("LISA_PASSWORD").expect("Set LISA_USER env variable");
-
-    credentials.user == user && credentials.password == password
-}
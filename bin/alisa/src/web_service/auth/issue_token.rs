@@ -1,13 +1,22 @@
 use std::borrow::Cow;
+use std::sync::Arc;
 
-use axum::{http::StatusCode, response::IntoResponse, Form, Json};
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Form, Json};
 use chrono::Duration;
-use log::{debug, error};
+use log::{debug, error, warn};
 use serde::{Deserialize, Serialize};
-
-use super::token::{create_token_with_expiration_in, is_valid_token, TokenError, TokenType};
-
-pub async fn issue_token(Form(client_creds): Form<Creds<'_>>) -> impl IntoResponse {
+use subtle::ConstantTimeEq;
+
+use super::keys::JwtKeys;
+use super::token::{create_token_with_expiration_in, decode_token, TokenError, TokenType};
+use crate::token_store::{Grant, Rotation};
+use crate::TokenStore;
+
+pub async fn issue_token(
+    State(tokens): State<Arc<TokenStore>>,
+    State(keys): State<Arc<JwtKeys>>,
+    Form(client_creds): Form<Creds<'_>>,
+) -> impl IntoResponse {
     if !validate_client_creds(&client_creds) {
         return (
             StatusCode::BAD_REQUEST,
@@ -17,57 +26,83 @@ pub async fn issue_token(Form(client_creds): Form<Creds<'_>>) -> impl IntoRespon
 
     match client_creds {
         Creds::AuthorizationCode { value, .. } => {
-            if is_valid_token(value, TokenType::Code) {
-                // TODO: save token version
-
-                debug!("received a valid authorization code, generating access and refresh tokens");
-                match Response::success() {
-                    Ok(response) => (StatusCode::OK, Json(response)),
-                    Err(err) => {
-                        error!("failed to issue tokens from auth code: {}", err);
-                        (
-                            StatusCode::INTERNAL_SERVER_ERROR,
-                            Json(Response::failure("internal server error".to_string())),
-                        )
-                    }
-                }
-            } else {
+            let Some(claims) = decode_token(value, TokenType::Code, &keys) else {
                 debug!("received an invalid authorization code");
+                return invalid_grant("invalid auth code");
+            };
+
+            match tokens.redeem_code(&claims.jti) {
+                Ok(true) => (),
+                Ok(false) => {
+                    warn!("received an authorization code that has already been used");
+                    return invalid_grant("invalid auth code");
+                }
+                Err(err) => return internal_error("failed to redeem auth code", err),
+            }
 
-                (
-                    StatusCode::BAD_REQUEST,
-                    Json(Response::failure("invalid auth code".to_string())),
-                )
+            debug!("received a valid authorization code, generating access and refresh tokens");
+
+            match tokens.create_grant(&claims.sub) {
+                Ok(grant) => issue(&grant, &keys),
+                Err(err) => internal_error("failed to create grant", err),
             }
         }
         Creds::RefreshToken { value, .. } => {
-            if is_valid_token(value, TokenType::Refresh) {
-                // TODO: increment token version
-
-                debug!("received a valid refresh token, generating new access and refresh tokens");
-
-                match Response::success() {
-                    Ok(response) => (StatusCode::OK, Json(response)),
-                    Err(err) => {
-                        error!("failed to issue tokens from refresh token: {}", err);
-                        (
-                            StatusCode::INTERNAL_SERVER_ERROR,
-                            Json(Response::failure("internal server error".to_string())),
-                        )
-                    }
-                }
-            } else {
+            let Some(claims) = decode_token(value, TokenType::Refresh, &keys) else {
                 debug!("received an invalid refresh token");
-
-                (
-                    StatusCode::BAD_REQUEST,
-                    Json(Response::failure("invalid refresh token".to_string())),
-                )
+                return invalid_grant("invalid refresh token");
+            };
+
+            let grant = Grant {
+                id: claims.jti,
+                user_id: claims.sub,
+                version: claims.ver,
+            };
+
+            match tokens.rotate(&grant) {
+                Ok(Rotation::Rotated(grant)) => {
+                    debug!(
+                        "received a valid refresh token, generating new access and refresh tokens"
+                    );
+                    issue(&grant, &keys)
+                }
+                Ok(Rotation::Reused) => {
+                    warn!("refresh token of grant {} reused, revoked it", grant.id);
+                    invalid_grant("invalid refresh token")
+                }
+                Ok(Rotation::Revoked) => {
+                    debug!("received a refresh token of revoked grant {}", grant.id);
+                    invalid_grant("invalid refresh token")
+                }
+                Err(err) => internal_error("failed to rotate grant", err),
             }
         }
     }
 }
 
+fn issue(grant: &Grant, keys: &JwtKeys) -> (StatusCode, Json<Response>) {
+    match Response::success(grant, keys) {
+        Ok(response) => (StatusCode::OK, Json(response)),
+        Err(err) => internal_error("failed to issue tokens", err),
+    }
+}
+
+fn invalid_grant(error: &str) -> (StatusCode, Json<Response>) {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(Response::failure(error.to_string())),
+    )
+}
+
+fn internal_error(context: &str, err: impl std::fmt::Display) -> (StatusCode, Json<Response>) {
+    error!("{context}: {err}");
+
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(Response::failure("internal server error".to_string())),
+    )
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(tag = "grant_type", rename_all = "snake_case")]
 pub enum Creds<'a> {
@@ -116,7 +151,9 @@ fn validate_client_creds(client_creds: &Creds) -> bool {
         .map(|uri| uri == "https://social.yandex.net/broker/redirect")
         .unwrap_or(true);
 
-    client_creds.client_id() == "tbd" && client_creds.client_secret() == "tbd" && redirect_uri_valid
+    let secret_valid: bool = client_creds.client_secret().as_bytes().ct_eq(b"tbd").into();
+
+    client_creds.client_id() == "tbd" && secret_valid && redirect_uri_valid
 }
 
 #[derive(Serialize)]
@@ -136,15 +173,23 @@ pub enum Response {
 }
 
 impl Response {
-    fn success() -> Result<Response, TokenError> {
+    fn success(grant: &Grant, keys: &JwtKeys) -> Result<Response, TokenError> {
         Ok(Response::Success {
             access_token: create_token_with_expiration_in(
                 ACCESS_TOKEN_EXPIRATION,
                 TokenType::Access,
+                &grant.user_id,
+                &grant.id,
+                grant.version,
+                keys,
             )?,
             refresh_token: create_token_with_expiration_in(
                 REFRESH_TOKEN_EXPIRATION,
                 TokenType::Refresh,
+                &grant.user_id,
+                &grant.id,
+                grant.version,
+                keys,
             )?,
             token_type: "Bearer".to_string(),
             expires_in: ACCESS_TOKEN_EXPIRATION,
@@ -0,0 +1,194 @@
+use std::path::{Path, PathBuf};
+
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey};
+use serde::Deserialize;
+
+use crate::{Error, Result};
+
+/// Keys the OAuth tokens are signed and verified with, loaded from a file
+/// listing PEM encoded key pairs by their `kid`:
+///
+/// ```toml
+/// signing_kid = "2026-10"
+///
+/// [[keys]]
+/// kid = "2026-10"
+/// algorithm = "EdDSA"
+/// private_key = "2026-10.pem"
+/// public_key = "2026-10.pub.pem"
+///
+/// [[keys]]
+/// kid = "2026-04"
+/// algorithm = "RS256"
+/// public_key = "2026-04.pub.pem"
+/// ```
+///
+/// New tokens are signed with the `signing_kid` key, the others only verify
+/// the tokens issued before it, so rotating a key doesn't unlink Yandex.
+/// Paths are relative to the file.
+///
+/// To rotate, generate a pair with `openssl genpkey -algorithm ed25519`,
+/// add it and point `signing_kid` at it, then drop the private key of the
+/// old one. Its public key can be removed once refresh tokens signed with
+/// it have expired, four weeks later.
+pub struct JwtKeys {
+    signing: SigningKey,
+    verifying: Vec<VerifyingKey>,
+}
+
+pub(super) struct SigningKey {
+    kid: Option<String>,
+    algorithm: Algorithm,
+    key: EncodingKey,
+}
+
+pub(super) struct VerifyingKey {
+    kid: Option<String>,
+    algorithm: Algorithm,
+    key: DecodingKey,
+}
+
+#[derive(Deserialize)]
+struct Config {
+    signing_kid: String,
+    keys: Vec<KeyConfig>,
+}
+
+#[derive(Deserialize)]
+struct KeyConfig {
+    kid: String,
+    algorithm: KeyAlgorithm,
+    private_key: Option<PathBuf>,
+    public_key: PathBuf,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+pub(super) enum KeyAlgorithm {
+    EdDSA,
+    RS256,
+}
+
+impl JwtKeys {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<JwtKeys> {
+        let path = path.as_ref();
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let config: Config = toml::from_str(&std::fs::read_to_string(path)?)?;
+
+        let mut signing = None;
+        let mut verifying = vec![];
+
+        for key in config.keys {
+            let public_key = std::fs::read(dir.join(&key.public_key))?;
+            verifying.push(VerifyingKey::from_pem(
+                &key.kid,
+                key.algorithm,
+                &public_key,
+            )?);
+
+            if key.kid != config.signing_kid {
+                continue;
+            }
+
+            let private_key = key
+                .private_key
+                .ok_or(Error::InvalidJwtKeys("signing key has no private key"))?;
+            let private_key = std::fs::read(dir.join(private_key))?;
+
+            signing = Some(SigningKey::from_pem(&key.kid, key.algorithm, &private_key)?);
+        }
+
+        let signing = signing.ok_or(Error::InvalidJwtKeys("no key with signing_kid"))?;
+
+        Ok(JwtKeys::new(signing, verifying))
+    }
+
+    /// Keys of the tokens signed with a shared HS512 secret and no `kid`.
+    pub fn hmac(secret: &str) -> JwtKeys {
+        JwtKeys {
+            signing: SigningKey {
+                kid: None,
+                algorithm: Algorithm::HS512,
+                key: EncodingKey::from_secret(secret.as_bytes()),
+            },
+            verifying: vec![legacy_key(secret)],
+        }
+    }
+
+    /// Keeps verifying the tokens signed with the shared secret alisa used
+    /// before asymmetric keys, until they expire.
+    pub fn with_legacy_secret(mut self, secret: &str) -> JwtKeys {
+        self.verifying.push(legacy_key(secret));
+        self
+    }
+
+    pub fn signing_kid(&self) -> Option<&str> {
+        self.signing.kid.as_deref()
+    }
+
+    pub(super) fn new(signing: SigningKey, verifying: Vec<VerifyingKey>) -> JwtKeys {
+        JwtKeys { signing, verifying }
+    }
+
+    pub(super) fn signing(&self) -> (Option<&str>, Algorithm, &EncodingKey) {
+        (
+            self.signing.kid.as_deref(),
+            self.signing.algorithm,
+            &self.signing.key,
+        )
+    }
+
+    /// Key verifying the tokens with `kid` in their header.
+    pub(super) fn verifying(&self, kid: Option<&str>) -> Option<(Algorithm, &DecodingKey)> {
+        self.verifying
+            .iter()
+            .find(|key| key.kid.as_deref() == kid)
+            .map(|key| (key.algorithm, &key.key))
+    }
+}
+
+impl SigningKey {
+    pub(super) fn from_pem(kid: &str, algorithm: KeyAlgorithm, pem: &[u8]) -> Result<SigningKey> {
+        let key = match algorithm {
+            KeyAlgorithm::EdDSA => EncodingKey::from_ed_pem(pem)?,
+            KeyAlgorithm::RS256 => EncodingKey::from_rsa_pem(pem)?,
+        };
+
+        Ok(SigningKey {
+            kid: Some(kid.to_string()),
+            algorithm: algorithm.into(),
+            key,
+        })
+    }
+}
+
+impl VerifyingKey {
+    pub(super) fn from_pem(kid: &str, algorithm: KeyAlgorithm, pem: &[u8]) -> Result<VerifyingKey> {
+        let key = match algorithm {
+            KeyAlgorithm::EdDSA => DecodingKey::from_ed_pem(pem)?,
+            KeyAlgorithm::RS256 => DecodingKey::from_rsa_pem(pem)?,
+        };
+
+        Ok(VerifyingKey {
+            kid: Some(kid.to_string()),
+            algorithm: algorithm.into(),
+            key,
+        })
+    }
+}
+
+impl From<KeyAlgorithm> for Algorithm {
+    fn from(algorithm: KeyAlgorithm) -> Self {
+        match algorithm {
+            KeyAlgorithm::EdDSA => Algorithm::EdDSA,
+            KeyAlgorithm::RS256 => Algorithm::RS256,
+        }
+    }
+}
+
+fn legacy_key(secret: &str) -> VerifyingKey {
+    VerifyingKey {
+        kid: None,
+        algorithm: Algorithm::HS512,
+        key: DecodingKey::from_secret(secret.as_bytes()),
+    }
+}
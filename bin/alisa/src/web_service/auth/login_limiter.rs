@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Failed logins allowed per account and address before they are locked out.
+const MAX_USER_FAILURES: u32 = 5;
+/// Failed logins allowed per address, a household shares one behind NAT.
+const MAX_IP_FAILURES: u32 = 20;
+/// Failures older than this are forgotten.
+const WINDOW: Duration = Duration::from_secs(15 * 60);
+const LOCKOUT: Duration = Duration::from_secs(15 * 60);
+
+/// Throttles password guessing on `/auth` by locking out the address, and
+/// the account on that address, that failed to log in too often. Accounts
+/// are locked per address so a stranger can't lock the household out.
+#[derive(Default)]
+pub struct LoginLimiter {
+    attempts: Mutex<HashMap<Key, Attempts>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum Key {
+    Ip(IpAddr),
+    User(String, IpAddr),
+}
+
+struct Attempts {
+    failures: u32,
+    first_failure_at: Instant,
+    locked_until: Option<Instant>,
+}
+
+impl LoginLimiter {
+    /// Time left until the login may be attempted again, `None` if it may
+    /// be attempted now.
+    pub fn locked_for(&self, ip: IpAddr, user: &str) -> Option<Duration> {
+        self.locked_for_at(ip, user, Instant::now())
+    }
+
+    pub fn record_failure(&self, ip: IpAddr, user: &str) {
+        self.record_failure_at(ip, user, Instant::now())
+    }
+
+    /// Forgets the failures of the account on the address, those of the
+    /// address stay so a known account can't be used to reset them.
+    pub fn record_success(&self, ip: IpAddr, user: &str) {
+        let mut attempts = self.attempts.lock().unwrap();
+        attempts.remove(&Key::User(user.to_string(), ip));
+    }
+
+    fn locked_for_at(&self, ip: IpAddr, user: &str, now: Instant) -> Option<Duration> {
+        let attempts = self.attempts.lock().unwrap();
+
+        [Key::Ip(ip), Key::User(user.to_string(), ip)]
+            .iter()
+            .filter_map(|key| attempts.get(key)?.locked_until)
+            .filter(|locked_until| *locked_until > now)
+            .map(|locked_until| locked_until - now)
+            .max()
+    }
+
+    fn record_failure_at(&self, ip: IpAddr, user: &str, now: Instant) {
+        let mut attempts = self.attempts.lock().unwrap();
+
+        attempts.retain(|_, attempts| !attempts.is_stale(now));
+
+        for (key, max_failures) in [
+            (Key::Ip(ip), MAX_IP_FAILURES),
+            (Key::User(user.to_string(), ip), MAX_USER_FAILURES),
+        ] {
+            let attempts = attempts.entry(key).or_insert(Attempts {
+                failures: 0,
+                first_failure_at: now,
+                locked_until: None,
+            });
+
+            if now - attempts.first_failure_at > WINDOW {
+                attempts.failures = 0;
+                attempts.first_failure_at = now;
+            }
+
+            attempts.failures += 1;
+
+            if attempts.failures >= max_failures {
+                attempts.failures = 0;
+                attempts.first_failure_at = now;
+                attempts.locked_until = Some(now + LOCKOUT);
+            }
+        }
+    }
+}
+
+impl Attempts {
+    fn is_stale(&self, now: Instant) -> bool {
+        let is_locked = self.locked_until.is_some_and(|until| until > now);
+        !is_locked && now - self.first_failure_at > WINDOW
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IP: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(192, 168, 1, 10));
+    const OTHER_IP: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(192, 168, 1, 11));
+
+    #[test]
+    fn test_user_lockout() {
+        let limiter = LoginLimiter::default();
+        let now = Instant::now();
+
+        for _ in 0..MAX_USER_FAILURES - 1 {
+            limiter.record_failure_at(IP, "chipp", now);
+        }
+        assert_eq!(limiter.locked_for_at(IP, "chipp", now), None);
+
+        limiter.record_failure_at(IP, "chipp", now);
+        assert_eq!(limiter.locked_for_at(IP, "chipp", now), Some(LOCKOUT));
+        assert_eq!(limiter.locked_for_at(OTHER_IP, "chipp", now), None);
+        assert_eq!(limiter.locked_for_at(IP, "guest", now), None);
+
+        let later = now + LOCKOUT;
+        assert_eq!(limiter.locked_for_at(IP, "chipp", later), None);
+    }
+
+    #[test]
+    fn test_ip_lockout() {
+        let limiter = LoginLimiter::default();
+        let now = Instant::now();
+
+        for index in 0..MAX_IP_FAILURES {
+            limiter.record_failure_at(IP, &format!("user_{index}"), now);
+        }
+
+        assert_eq!(limiter.locked_for_at(IP, "chipp", now), Some(LOCKOUT));
+        assert_eq!(limiter.locked_for_at(OTHER_IP, "chipp", now), None);
+    }
+
+    #[test]
+    fn test_window() {
+        let limiter = LoginLimiter::default();
+        let now = Instant::now();
+
+        for _ in 0..MAX_USER_FAILURES - 1 {
+            limiter.record_failure_at(IP, "chipp", now);
+        }
+
+        let later = now + WINDOW + Duration::from_secs(1);
+        limiter.record_failure_at(IP, "chipp", later);
+        assert_eq!(limiter.locked_for_at(IP, "chipp", later), None);
+    }
+
+    #[test]
+    fn test_success_resets_user() {
+        let limiter = LoginLimiter::default();
+        let now = Instant::now();
+
+        for _ in 0..MAX_USER_FAILURES - 1 {
+            limiter.record_failure_at(IP, "chipp", now);
+        }
+
+        limiter.record_success(OTHER_IP, "chipp");
+        limiter.record_failure_at(IP, "chipp", now);
+        assert_eq!(limiter.locked_for_at(IP, "chipp", now), Some(LOCKOUT));
+
+        for _ in 0..MAX_USER_FAILURES - 1 {
+            limiter.record_failure_at(OTHER_IP, "chipp", now);
+        }
+
+        limiter.record_success(OTHER_IP, "chipp");
+        limiter.record_failure_at(OTHER_IP, "chipp", now);
+        assert_eq!(limiter.locked_for_at(OTHER_IP, "chipp", now), None);
+    }
+}
@@ -3,6 +3,8 @@ use std::fmt;
 use chrono::Duration;
 use serde::{Deserialize, Serialize};
 
+use super::keys::JwtKeys;
+
 #[derive(Deserialize, Serialize)]
 #[serde(rename_all = "snake_case")]
 pub enum TokenType {
@@ -34,84 +36,100 @@ impl fmt::Display for TokenError {
 
 impl std::error::Error for TokenError {}
 
-pub fn is_valid_token<T: AsRef<str>>(token: T, token_type: TokenType) -> bool {
-    let secret = extract_secret_from_env();
-    is_valid_token_with_secret(token, token_type, &secret)
-}
-
-fn is_valid_token_with_secret<T: AsRef<str>>(
+/// Claims of `token` if it is a valid token of `token_type`.
+pub fn decode_token<T: AsRef<str>>(
     token: T,
     token_type: TokenType,
-    secret: &str,
-) -> bool {
-    is_valid_token_with_secret_at(token, token_type, secret, current_timestamp())
+    keys: &JwtKeys,
+) -> Option<Claims> {
+    decode_token_at(token, token_type, keys, current_timestamp())
 }
 
-fn is_valid_token_with_secret_at<T: AsRef<str>>(
+fn decode_token_at<T: AsRef<str>>(
     token: T,
     token_type: TokenType,
-    secret: &str,
+    keys: &JwtKeys,
     now_timestamp: u64,
-) -> bool {
-    use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+) -> Option<Claims> {
+    use jsonwebtoken::{decode, decode_header, Validation};
+
+    let header = match decode_header(token.as_ref()) {
+        Ok(header) => header,
+        Err(err) => {
+            log::debug!("token header decoding failed: {}", err);
+            return None;
+        }
+    };
 
-    let mut validation = Validation::new(Algorithm::HS512);
+    let Some((algorithm, key)) = keys.verifying(header.kid.as_deref()) else {
+        log::debug!("token signed with unknown key {:?}", header.kid);
+        return None;
+    };
+
+    let mut validation = Validation::new(algorithm);
     // We validate `exp` ourselves to make tests deterministic with an injected clock.
     validation.validate_exp = false;
     validation.leeway = 0;
-    validation.sub = Some("yandex".to_owned());
+    validation.set_required_spec_claims(&["exp", "sub", "aud"]);
     validation.set_audience(&[token_type.to_string()]);
 
-    let decoded = match decode::<Claims>(
-        token.as_ref(),
-        &DecodingKey::from_secret(secret.as_bytes()),
-        &validation,
-    ) {
+    let decoded = match decode::<Claims>(token.as_ref(), key, &validation) {
         Ok(decoded) => decoded,
         Err(err) => {
             log::debug!("token decoding failed: {}", err);
-            return false;
+            return None;
         }
     };
 
-    decoded.claims.exp > now_timestamp
+    if decoded.claims.exp > now_timestamp {
+        Some(decoded.claims)
+    } else {
+        None
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-struct Claims {
-    sub: String,
+pub struct Claims {
+    /// Id of the user the token has been issued to.
+    pub sub: String,
     exp: u64,
     aud: Vec<String>,
+    /// Id of the authorization code or of the grant the token belongs to.
+    pub jti: String,
+    /// Version of the grant, tokens of older versions can't be refreshed.
+    #[serde(default)]
+    pub ver: u32,
 }
 
 pub fn create_token_with_expiration_in(
     expiration: Duration,
     token_type: TokenType,
+    user_id: &str,
+    jti: &str,
+    version: u32,
+    keys: &JwtKeys,
 ) -> Result<String, TokenError> {
-    let secret = extract_secret_from_env();
-    create_token_with_expiration_in_with_secret(expiration, token_type, &secret)
-}
-
-fn create_token_with_expiration_in_with_secret(
-    expiration: Duration,
-    token_type: TokenType,
-    secret: &str,
-) -> Result<String, TokenError> {
-    create_token_with_expiration_in_with_secret_at(
+    create_token_with_expiration_in_at(
         expiration,
         token_type,
-        secret,
+        user_id,
+        jti,
+        version,
+        keys,
         current_timestamp() as i64,
     )
 }
 
-fn create_token_with_expiration_in_with_secret_at(
+fn create_token_with_expiration_in_at(
     expiration: Duration,
     token_type: TokenType,
-    secret: &str,
+    user_id: &str,
+    jti: &str,
+    version: u32,
+    keys: &JwtKeys,
     now_timestamp: i64,
 ) -> Result<String, TokenError> {
-    use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+    use jsonwebtoken::{encode, Header};
 
     let expiration = now_timestamp
         .checked_add(expiration.num_seconds())
@@ -119,23 +137,19 @@ fn create_token_with_expiration_in_with_secret_at(
     let expiration = u64::try_from(expiration).map_err(|_| TokenError::InvalidExpiration)?;
 
     let claims = Claims {
-        sub: "yandex".to_owned(),
+        sub: user_id.to_owned(),
         exp: expiration,
         aud: vec![token_type.to_string()],
+        jti: jti.to_owned(),
+        ver: version,
     };
 
-    let header = Header::new(Algorithm::HS512);
+    let (kid, algorithm, key) = keys.signing();
 
-    encode(
-        &header,
-        &claims,
-        &EncodingKey::from_secret(secret.as_bytes()),
-    )
-    .map_err(TokenError::Encoding)
-}
+    let mut header = Header::new(algorithm);
+    header.kid = kid.map(str::to_owned);
 
-fn extract_secret_from_env() -> String {
-    std::env::var("JWT_SECRET").expect("Set JWT_SECRET env variable")
+    encode(&header, &claims, key).map_err(TokenError::Encoding)
 }
 
 fn current_timestamp() -> u64 {
@@ -147,19 +161,63 @@ fn current_timestamp() -> u64 {
 
 #[cfg(test)]
 mod tests {
+    use super::super::keys::{KeyAlgorithm, SigningKey, VerifyingKey};
     use super::{
-        create_token_with_expiration_in_with_secret_at, is_valid_token_with_secret_at, TokenType,
+        create_token_with_expiration_in_at, decode_token_at, Claims, JwtKeys, TokenError, TokenType,
     };
     use chrono::Duration;
 
     const SECRET: &str = "test-secret";
     const NOW: i64 = 1_700_000_000;
+    const USER: &str = "chipp";
+    const GRANT: &str = "4f5b2a52-8d1e-4d5e-9f53-6a8e1f0c2b7d";
+
+    fn create_token_with_expiration_in_with_secret_at(
+        expiration: Duration,
+        token_type: TokenType,
+        user_id: &str,
+        jti: &str,
+        version: u32,
+        secret: &str,
+        now_timestamp: i64,
+    ) -> Result<String, TokenError> {
+        create_token_with_expiration_in_at(
+            expiration,
+            token_type,
+            user_id,
+            jti,
+            version,
+            &JwtKeys::hmac(secret),
+            now_timestamp,
+        )
+    }
+
+    fn decode_token_with_secret_at<T: AsRef<str>>(
+        token: T,
+        token_type: TokenType,
+        secret: &str,
+        now_timestamp: u64,
+    ) -> Option<Claims> {
+        decode_token_at(token, token_type, &JwtKeys::hmac(secret), now_timestamp)
+    }
+
+    fn is_valid_token_with_secret_at<T: AsRef<str>>(
+        token: T,
+        token_type: TokenType,
+        secret: &str,
+        now_timestamp: u64,
+    ) -> bool {
+        decode_token_with_secret_at(token, token_type, secret, now_timestamp).is_some()
+    }
 
     #[test]
     fn access_token_roundtrip_is_valid() {
         let token = create_token_with_expiration_in_with_secret_at(
             Duration::minutes(1),
             TokenType::Access,
+            USER,
+            GRANT,
+            0,
             SECRET,
             NOW,
         )
@@ -178,6 +236,9 @@ mod tests {
         let token = create_token_with_expiration_in_with_secret_at(
             Duration::minutes(1),
             TokenType::Access,
+            USER,
+            GRANT,
+            0,
             SECRET,
             NOW,
         )
@@ -196,6 +257,9 @@ mod tests {
         let token = create_token_with_expiration_in_with_secret_at(
             Duration::minutes(-1),
             TokenType::Access,
+            USER,
+            GRANT,
+            0,
             SECRET,
             NOW,
         )
@@ -214,6 +278,9 @@ mod tests {
         let token = create_token_with_expiration_in_with_secret_at(
             Duration::minutes(1),
             TokenType::Access,
+            USER,
+            GRANT,
+            0,
             "secret-a",
             NOW,
         )
@@ -232,6 +299,9 @@ mod tests {
         let token = create_token_with_expiration_in_with_secret_at(
             Duration::seconds(30),
             TokenType::Access,
+            USER,
+            GRANT,
+            0,
             SECRET,
             NOW,
         )
@@ -256,10 +326,185 @@ mod tests {
         let result = create_token_with_expiration_in_with_secret_at(
             Duration::seconds(1),
             TokenType::Access,
+            USER,
+            GRANT,
+            0,
             SECRET,
             i64::MAX,
         );
 
-        assert!(matches!(result, Err(super::TokenError::InvalidExpiration)));
+        assert!(matches!(result, Err(TokenError::InvalidExpiration)));
+    }
+
+    #[test]
+    fn claims_carry_grant_version() {
+        let token = create_token_with_expiration_in_with_secret_at(
+            Duration::minutes(1),
+            TokenType::Refresh,
+            USER,
+            GRANT,
+            3,
+            SECRET,
+            NOW,
+        )
+        .unwrap();
+
+        let claims =
+            decode_token_with_secret_at(token, TokenType::Refresh, SECRET, NOW as u64).unwrap();
+
+        assert_eq!(claims.sub, USER);
+        assert_eq!(claims.jti, GRANT);
+        assert_eq!(claims.ver, 3);
+    }
+
+    const ED25519_A: &[u8] = include_bytes!("test_keys/ed25519_a.pem");
+    const ED25519_A_PUB: &[u8] = include_bytes!("test_keys/ed25519_a.pub.pem");
+    const ED25519_B: &[u8] = include_bytes!("test_keys/ed25519_b.pem");
+    const ED25519_B_PUB: &[u8] = include_bytes!("test_keys/ed25519_b.pub.pem");
+    const RSA: &[u8] = include_bytes!("test_keys/rsa.pem");
+    const RSA_PUB: &[u8] = include_bytes!("test_keys/rsa.pub.pem");
+
+    /// Keys signing with `kid`, verifying with it and with the public keys
+    /// of `retired`.
+    fn keys(
+        kid: &str,
+        algorithm: KeyAlgorithm,
+        private_key: &[u8],
+        public_key: &[u8],
+        retired: &[(&str, KeyAlgorithm, &[u8])],
+    ) -> JwtKeys {
+        let signing = SigningKey::from_pem(kid, algorithm, private_key).unwrap();
+
+        let verifying = std::iter::once((kid, algorithm, public_key))
+            .chain(retired.iter().copied())
+            .map(|(kid, algorithm, pem)| VerifyingKey::from_pem(kid, algorithm, pem).unwrap())
+            .collect();
+
+        JwtKeys::new(signing, verifying)
+    }
+
+    fn create_at(keys: &JwtKeys, now_timestamp: i64) -> String {
+        create_token_with_expiration_in_at(
+            Duration::minutes(1),
+            TokenType::Access,
+            USER,
+            GRANT,
+            0,
+            keys,
+            now_timestamp,
+        )
+        .unwrap()
+    }
+
+    fn is_valid_at(token: &str, keys: &JwtKeys, now_timestamp: u64) -> bool {
+        decode_token_at(token, TokenType::Access, keys, now_timestamp).is_some()
+    }
+
+    #[test]
+    fn asymmetric_tokens_roundtrip() {
+        for (algorithm, private_key, public_key) in [
+            (KeyAlgorithm::EdDSA, ED25519_A, ED25519_A_PUB),
+            (KeyAlgorithm::RS256, RSA, RSA_PUB),
+        ] {
+            let keys = keys("current", algorithm, private_key, public_key, &[]);
+            let token = create_at(&keys, NOW);
+
+            let header = jsonwebtoken::decode_header(&token).unwrap();
+            assert_eq!(header.kid.as_deref(), Some("current"));
+
+            assert!(is_valid_at(&token, &keys, NOW as u64));
+            assert!(!is_valid_at(&token, &keys, (NOW + 60) as u64));
+        }
+    }
+
+    #[test]
+    fn rotated_key_verifies_tokens_until_expiry() {
+        let old = keys(
+            "2026-04",
+            KeyAlgorithm::EdDSA,
+            ED25519_A,
+            ED25519_A_PUB,
+            &[],
+        );
+        let old_token = create_at(&old, NOW);
+
+        let rotated = keys(
+            "2026-10",
+            KeyAlgorithm::EdDSA,
+            ED25519_B,
+            ED25519_B_PUB,
+            &[("2026-04", KeyAlgorithm::EdDSA, ED25519_A_PUB)],
+        );
+        let new_token = create_at(&rotated, NOW + 30);
+
+        assert_eq!(rotated.signing_kid(), Some("2026-10"));
+        assert_eq!(
+            jsonwebtoken::decode_header(&new_token)
+                .unwrap()
+                .kid
+                .as_deref(),
+            Some("2026-10")
+        );
+
+        assert!(is_valid_at(&old_token, &rotated, (NOW + 30) as u64));
+        assert!(!is_valid_at(&old_token, &rotated, (NOW + 60) as u64));
+        assert!(is_valid_at(&new_token, &rotated, (NOW + 60) as u64));
+        assert!(!is_valid_at(&new_token, &old, (NOW + 30) as u64));
+
+        let retired = keys(
+            "2026-10",
+            KeyAlgorithm::EdDSA,
+            ED25519_B,
+            ED25519_B_PUB,
+            &[],
+        );
+        assert!(!is_valid_at(&old_token, &retired, (NOW + 30) as u64));
+    }
+
+    #[test]
+    fn legacy_secret_tokens_stay_valid() {
+        let legacy_token = create_token_with_expiration_in_with_secret_at(
+            Duration::minutes(1),
+            TokenType::Access,
+            USER,
+            GRANT,
+            0,
+            SECRET,
+            NOW,
+        )
+        .unwrap();
+
+        let keys = keys(
+            "2026-10",
+            KeyAlgorithm::EdDSA,
+            ED25519_B,
+            ED25519_B_PUB,
+            &[],
+        );
+        assert!(!is_valid_at(&legacy_token, &keys, NOW as u64));
+
+        let keys = keys.with_legacy_secret(SECRET);
+        assert!(is_valid_at(&legacy_token, &keys, NOW as u64));
+        assert!(!is_valid_at(&legacy_token, &keys, (NOW + 60) as u64));
+    }
+
+    #[test]
+    fn token_with_swapped_key_is_invalid() {
+        let a = keys(
+            "current",
+            KeyAlgorithm::EdDSA,
+            ED25519_A,
+            ED25519_A_PUB,
+            &[],
+        );
+        let b = keys(
+            "current",
+            KeyAlgorithm::EdDSA,
+            ED25519_B,
+            ED25519_B_PUB,
+            &[],
+        );
+
+        assert!(!is_valid_at(&create_at(&a, NOW), &b, NOW as u64));
     }
 }
@@ -0,0 +1,84 @@
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+
+use axum::http::HeaderMap;
+use ipnet::{AddrParseError, IpNet};
+
+/// Address or network of the reverse proxy in front of alisa, e.g. the
+/// subnet of the `nginx-proxy_default` docker network. `X-Real-IP` is only
+/// believed when it comes from there, anyone else could set it to dodge
+/// the login throttling.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TrustedProxy(Option<IpNet>);
+
+impl TrustedProxy {
+    /// Address of the client the proxy forwards, or the peer address when
+    /// the request didn't come through the proxy.
+    pub fn client_ip(&self, headers: &HeaderMap, peer: SocketAddr) -> IpAddr {
+        let peer = peer.ip();
+
+        if !self.0.is_some_and(|proxy| proxy.contains(&peer)) {
+            return peer;
+        }
+
+        headers
+            .get("X-Real-IP")
+            .and_then(|ip| ip.to_str().ok())
+            .and_then(|ip| ip.parse().ok())
+            .unwrap_or(peer)
+    }
+}
+
+impl FromStr for TrustedProxy {
+    type Err = AddrParseError;
+
+    /// Parses a network like `172.18.0.0/16` or a single address.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let network = match s.parse::<IpAddr>() {
+            Ok(ip) => IpNet::from(ip),
+            Err(_) => s.parse()?,
+        };
+
+        Ok(TrustedProxy(Some(network)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Real-IP", "203.0.113.7".parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_proxy_network() {
+        let proxy: TrustedProxy = "172.18.0.0/16".parse().unwrap();
+
+        assert_eq!(
+            proxy.client_ip(&headers(), "172.18.0.2:40000".parse().unwrap()),
+            "203.0.113.7".parse::<IpAddr>().unwrap()
+        );
+        assert_eq!(
+            proxy.client_ip(&HeaderMap::new(), "172.18.0.2:40000".parse().unwrap()),
+            "172.18.0.2".parse::<IpAddr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_untrusted_peer() {
+        let proxy: TrustedProxy = "172.18.0.2".parse().unwrap();
+
+        assert_eq!(
+            proxy.client_ip(&headers(), "192.168.1.10:40000".parse().unwrap()),
+            "192.168.1.10".parse::<IpAddr>().unwrap()
+        );
+        assert_eq!(
+            TrustedProxy::default().client_ip(&headers(), "172.18.0.2:40000".parse().unwrap()),
+            "172.18.0.2".parse::<IpAddr>().unwrap()
+        );
+        assert!("proxy".parse::<TrustedProxy>().is_err());
+    }
+}
@@ -5,11 +5,21 @@ use axum::{
 };
 use log::{error, trace};
 
-use super::token::{is_valid_token, TokenType};
+use super::keys::JwtKeys;
+use super::token::{decode_token, TokenType};
+use crate::{TokenStore, User, Users};
+
+/// The account and the grant an access token has been issued for.
+pub struct Authorization {
+    pub grant_id: String,
+    pub user: User,
+}
 
 pub enum ValidationError {
     Expired,
+    Revoked,
     NoToken,
+    Store,
 }
 
 impl IntoResponse for ValidationError {
@@ -24,6 +34,15 @@ impl IntoResponse for ValidationError {
 
                 (StatusCode::UNAUTHORIZED, headers).into_response()
             }
+            ValidationError::Revoked => {
+                let mut headers = HeaderMap::new();
+                headers.insert(
+                    "WWW-Authenticate",
+                    "Bearer error=\"invalid_token\" error_description=\"The access token has been revoked\"".parse().unwrap(),
+                );
+
+                (StatusCode::UNAUTHORIZED, headers).into_response()
+            }
             ValidationError::NoToken => {
                 let mut headers = HeaderMap::new();
                 headers.insert(
@@ -33,20 +52,56 @@ impl IntoResponse for ValidationError {
 
                 (StatusCode::UNAUTHORIZED, headers).into_response()
             }
+            ValidationError::Store => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
         }
     }
 }
 
+/// Checks the access token of the request and that its grant and user are
+/// still there.
 pub fn validate_autorization(
     headers: &HeaderMap,
+    tokens: &TokenStore,
+    users: &Users,
+    keys: &JwtKeys,
     request_name: &'static str,
-) -> Result<(), ValidationError> {
-    match extract_token_from_headers(headers) {
-        Some(token) if is_valid_token(token, TokenType::Access) => {
-            trace!(target: request_name, "received a valid access token");
-            Ok(())
-        }
-        Some(_) => {
+) -> Result<Authorization, ValidationError> {
+    let token = extract_token_from_headers(headers);
+
+    match token.map(|token| decode_token(token, TokenType::Access, keys)) {
+        Some(Some(claims)) => match tokens.is_active(&claims.jti) {
+            Ok(true) => match users.user(&claims.sub) {
+                Some(user) => {
+                    trace!(target: request_name, "received a valid access token");
+
+                    Ok(Authorization {
+                        grant_id: claims.jti,
+                        user: user.clone(),
+                    })
+                }
+                None => {
+                    error!(
+                        target: request_name,
+                        "an access token of removed user {} has been provided", claims.sub
+                    );
+
+                    Err(ValidationError::Revoked)
+                }
+            },
+            Ok(false) => {
+                error!(
+                    target: request_name,
+                    "an access token of a revoked grant has been provided"
+                );
+
+                Err(ValidationError::Revoked)
+            }
+            Err(err) => {
+                error!(target: request_name, "failed to look up the grant: {}", err);
+                Err(ValidationError::Store)
+            }
+        },
+        Some(None) => {
             error!(
                 target: request_name,
                 "an expired access token has been provided"
@@ -0,0 +1,75 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Result};
+use axum::Json;
+use log::info;
+use transport::action::ActionResult;
+use transport::registry::Registry;
+use transport::rpc;
+use transport::scene::{self, Step, StepAction};
+
+use super::devices::registered_device;
+use super::{ApiKeys, KeyQuery};
+use crate::web_service::ServiceError;
+use crate::Gateways;
+
+const RESPONSE_DEADLINE: Duration = Duration::from_secs(10);
+
+/// Runs an action on a device, taking the same actions as scene steps:
+/// `{"action": "set_temperature", "value": 21.5}`.
+#[allow(clippy::too_many_arguments)]
+pub async fn action(
+    headers: HeaderMap,
+    Path(path): Path<(String, String)>,
+    Query(query): Query<KeyQuery>,
+    State(api_keys): State<Arc<ApiKeys>>,
+    State(registry): State<Arc<Registry>>,
+    State(gateways): State<Arc<Gateways>>,
+    State(mqtt): State<rpc::Client>,
+    Json(action): Json<StepAction>,
+) -> Result<impl IntoResponse> {
+    let key = api_keys.validate(&headers, query.api_key.as_deref())?;
+    let device = registered_device(&registry, path)?;
+
+    info!("{key}/action {device} {action:?}");
+
+    let step = Step { device, action };
+
+    if !step.is_supported() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("{} doesn't support {:?}", step.device, step.action),
+        )
+            .into());
+    }
+
+    if let Some(zone) = step.zone().filter(|zone| registry.zone(zone).is_none()) {
+        return Err((StatusCode::BAD_REQUEST, format!("unknown zone {zone}")).into());
+    }
+
+    if let Some(gateway) = gateways.offline_gateway(&registry, &step.device) {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            format!("{gateway} is offline"),
+        )
+            .into());
+    }
+
+    let reports = scene::run_steps(&mqtt, &registry, &[step], RESPONSE_DEADLINE)
+        .await
+        .map_err(ServiceError::from)?;
+
+    let Some(report) = reports.into_iter().next() else {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR.into());
+    };
+
+    let status = match report.result {
+        ActionResult::Success => StatusCode::OK,
+        ActionResult::Failure(_) => StatusCode::BAD_GATEWAY,
+    };
+
+    Ok((status, Json(report)))
+}
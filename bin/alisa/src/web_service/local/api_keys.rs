@@ -0,0 +1,131 @@
+use std::path::Path;
+
+use axum::body::Body;
+use axum::http::{HeaderMap, Response, StatusCode};
+use axum::response::IntoResponse;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::Result;
+
+/// Keys of the scripts and dashboards using the local API, stored as SHA-256
+/// digests of the keys.
+///
+/// ```toml
+/// [[keys]]
+/// name = "dashboard"
+/// sha256 = "<sha256 of the key>"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+pub struct ApiKeys {
+    #[serde(default)]
+    keys: Vec<ApiKey>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiKey {
+    name: String,
+    sha256: String,
+}
+
+/// Query of the local API requests, carrying the key for clients that
+/// can't set headers.
+#[derive(Debug, Deserialize)]
+pub struct KeyQuery {
+    pub api_key: Option<String>,
+}
+
+pub struct InvalidApiKey;
+
+impl IntoResponse for InvalidApiKey {
+    fn into_response(self) -> Response<Body> {
+        let mut headers = HeaderMap::new();
+        headers.insert("WWW-Authenticate", "Bearer realm=\"lisa\"".parse().unwrap());
+
+        (StatusCode::UNAUTHORIZED, headers).into_response()
+    }
+}
+
+impl ApiKeys {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<ApiKeys> {
+        Ok(toml::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Name of the key in the `Authorization: Bearer` header or, for clients
+    /// that can't set headers like `EventSource`, the `api_key` query
+    /// parameter.
+    pub fn validate(
+        &self,
+        headers: &HeaderMap,
+        query_key: Option<&str>,
+    ) -> std::result::Result<&str, InvalidApiKey> {
+        let key = headers
+            .get("Authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .or(query_key)
+            .ok_or(InvalidApiKey)?;
+
+        let digest = Sha256::digest(key.as_bytes())
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>();
+
+        self.keys
+            .iter()
+            .find(|key| key.sha256.eq_ignore_ascii_case(&digest))
+            .map(|key| key.name.as_str())
+            .ok_or(InvalidApiKey)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys() -> ApiKeys {
+        toml::from_str(
+            r#"
+            [[keys]]
+            name = "dashboard"
+            sha256 = "9F86D081884C7D659A2FEAA0C55AD015A3BF4F1B2B0B822CD15D6C15B0F00A08"
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_validate() {
+        let keys = keys();
+
+        let mut headers = HeaderMap::new();
+        assert!(keys.validate(&headers, None).is_err());
+        assert_eq!(
+            keys.validate(&headers, Some("test")).ok(),
+            Some("dashboard")
+        );
+        assert!(keys.validate(&headers, Some("TEST")).is_err());
+
+        headers.insert("Authorization", "Bearer test".parse().unwrap());
+        assert_eq!(keys.validate(&headers, None).ok(), Some("dashboard"));
+
+        headers.insert("Authorization", "Bearer other".parse().unwrap());
+        assert!(keys.validate(&headers, None).is_err());
+    }
+
+    #[test]
+    fn test_no_keys() {
+        let mut headers = HeaderMap::new();
+        headers.insert("Authorization", "Bearer test".parse().unwrap());
+
+        assert!(ApiKeys::default().validate(&headers, None).is_err());
+    }
+}
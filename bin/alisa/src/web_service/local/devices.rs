@@ -0,0 +1,75 @@
+use std::sync::Arc;
+
+use axum::extract::{Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Result};
+use axum::Json;
+use serde::Serialize;
+use transport::metadata;
+use transport::registry::Registry;
+use transport::{DeviceId, DeviceType, Room};
+
+use super::{ApiKeys, KeyQuery};
+use crate::Gateways;
+
+#[derive(Debug, Serialize)]
+struct Device {
+    id: DeviceId,
+    #[serde(rename = "type")]
+    device_type: DeviceType,
+    name: String,
+    room: Room,
+    room_name: String,
+    gateway: &'static str,
+    is_online: bool,
+}
+
+pub async fn devices(
+    headers: HeaderMap,
+    Query(query): Query<KeyQuery>,
+    State(api_keys): State<Arc<ApiKeys>>,
+    State(registry): State<Arc<Registry>>,
+    State(gateways): State<Arc<Gateways>>,
+) -> Result<impl IntoResponse> {
+    api_keys.validate(&headers, query.api_key.as_deref())?;
+
+    let devices = registry
+        .devices()
+        .iter()
+        .map(|config| {
+            let gateway = config.backend.gateway();
+
+            Device {
+                id: config.id(),
+                device_type: config.device_type,
+                name: config.name_in(metadata::DEFAULT_LANGUAGE).to_string(),
+                room: config.room.clone(),
+                room_name: registry
+                    .room_name(&config.room)
+                    .unwrap_or(config.room.as_str())
+                    .to_string(),
+                gateway,
+                is_online: gateways.is_online(gateway),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    Ok(Json(devices))
+}
+
+/// Id of a registry device addressed by `/{device_type}/{room}`.
+pub(super) fn registered_device(
+    registry: &Registry,
+    (device_type, room): (String, String),
+) -> std::result::Result<DeviceId, (StatusCode, String)> {
+    let not_found = || (StatusCode::NOT_FOUND, format!("no {device_type} in {room}"));
+
+    let id: DeviceId = format!("{device_type}/{room}")
+        .parse()
+        .map_err(|_| not_found())?;
+
+    match registry.device(&id) {
+        Some(_) => Ok(id),
+        None => Err(not_found()),
+    }
+}
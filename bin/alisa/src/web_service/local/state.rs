@@ -0,0 +1,116 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Result};
+use axum::Json;
+use log::info;
+use serde_json::json;
+use transport::registry::Registry;
+use transport::state::{StateRequest, StateResponse};
+use transport::{rpc, DeviceId, DeviceType, Topic};
+
+use super::devices::registered_device;
+use super::{ApiKeys, KeyQuery};
+use crate::web_service::ServiceError;
+use crate::{Gateways, StateCache};
+
+const RESPONSE_DEADLINE: Duration = Duration::from_secs(3);
+
+/// Current state of a device, asking its gateway unless the cached state is
+/// fresh.
+#[allow(clippy::too_many_arguments)]
+pub async fn state(
+    headers: HeaderMap,
+    Path(path): Path<(String, String)>,
+    Query(query): Query<KeyQuery>,
+    State(api_keys): State<Arc<ApiKeys>>,
+    State(registry): State<Arc<Registry>>,
+    State(gateways): State<Arc<Gateways>>,
+    State(cache): State<Arc<StateCache>>,
+    State(mqtt): State<rpc::Client>,
+) -> Result<impl IntoResponse> {
+    let key = api_keys.validate(&headers, query.api_key.as_deref())?;
+    let id = registered_device(&registry, path)?;
+
+    info!("{key}/state {id}");
+
+    if let Some(gateway) = gateways.offline_gateway(&registry, &id) {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            format!("{gateway} is offline"),
+        )
+            .into());
+    }
+
+    let request = StateRequest {
+        device_ids: vec![id.clone()],
+    };
+
+    let mut responses = mqtt
+        .request::<_, StateResponse>(Topic::StateRequest, &request, RESPONSE_DEADLINE)
+        .await
+        .map_err(ServiceError::from)?;
+
+    while let Some(response) = responses.next().await {
+        cache.update_with_response(&response, &registry);
+
+        if describes(&response, &id) {
+            return Ok(Json(json!({ "id": id, "state": response })));
+        }
+    }
+
+    Err((StatusCode::GATEWAY_TIMEOUT, format!("no state of {id}")).into())
+}
+
+/// Whether `response` holds the state of `id`. The vacuum cleaner answers
+/// for all of its rooms at once.
+fn describes(response: &StateResponse, id: &DeviceId) -> bool {
+    match response {
+        StateResponse::Elisa(_) => id.device_type == DeviceType::VacuumCleaner,
+        StateResponse::Elisheba(state) => {
+            id.device_type == DeviceType::Light && state.room == id.room
+        }
+        StateResponse::Elizabeth(state) => {
+            id.device_type == state.device_type && state.room == id.room
+        }
+        StateResponse::Isabel(state) => {
+            id.device_type == DeviceType::TemperatureSensor && state.room == id.room
+        }
+        StateResponse::Unknown(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use transport::{elisheba, isabel, Room};
+
+    #[test]
+    fn test_describes() {
+        let light = StateResponse::Elisheba(elisheba::State {
+            is_enabled: true,
+            room: Room::new("corridor"),
+        });
+
+        assert!(describes(
+            &light,
+            &DeviceId::light_at_room(Room::new("corridor"))
+        ));
+        assert!(!describes(
+            &light,
+            &DeviceId::light_at_room(Room::new("nursery"))
+        ));
+
+        let sensor = StateResponse::Isabel(isabel::CurrentState::new(Room::new("nursery")));
+        assert!(describes(
+            &sensor,
+            &DeviceId::temperature_sensor_at_room(Room::new("nursery"))
+        ));
+        assert!(!describes(
+            &sensor,
+            &DeviceId::thermostat_at_room(Room::new("nursery"))
+        ));
+    }
+}
@@ -0,0 +1,88 @@
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Query, State};
+use axum::http::HeaderMap;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Result};
+use futures_util::stream::{self, Stream};
+use log::{debug, info, warn};
+use tokio::sync::broadcast::{self, error::RecvError};
+use transport::state::StateUpdate;
+
+use super::{ApiKeys, KeyQuery};
+
+/// Streams state updates as server-sent events.
+pub async fn events(
+    headers: HeaderMap,
+    Query(query): Query<KeyQuery>,
+    State(api_keys): State<Arc<ApiKeys>>,
+    State(updates): State<broadcast::Sender<StateUpdate>>,
+) -> Result<impl IntoResponse> {
+    let key = api_keys.validate(&headers, query.api_key.as_deref())?;
+    info!("{key}/events");
+
+    Ok(Sse::new(event_stream(updates.subscribe())).keep_alive(KeepAlive::default()))
+}
+
+/// Streams state updates as WebSocket text messages.
+pub async fn websocket(
+    headers: HeaderMap,
+    Query(query): Query<KeyQuery>,
+    State(api_keys): State<Arc<ApiKeys>>,
+    State(updates): State<broadcast::Sender<StateUpdate>>,
+    upgrade: WebSocketUpgrade,
+) -> Result<impl IntoResponse> {
+    let key = api_keys.validate(&headers, query.api_key.as_deref())?;
+    info!("{key}/websocket");
+
+    let receiver = updates.subscribe();
+    Ok(upgrade.on_upgrade(move |socket| forward(socket, receiver)))
+}
+
+fn event_stream(
+    receiver: broadcast::Receiver<StateUpdate>,
+) -> impl Stream<Item = std::result::Result<Event, Infallible>> {
+    stream::unfold(receiver, |mut receiver| async move {
+        let update = next_update(&mut receiver).await?;
+        let event = Event::default().event("update").json_data(&update).ok()?;
+
+        Some((Ok(event), receiver))
+    })
+}
+
+async fn forward(mut socket: WebSocket, mut receiver: broadcast::Receiver<StateUpdate>) {
+    loop {
+        tokio::select! {
+            update = next_update(&mut receiver) => {
+                let Some(update) = update else { break };
+
+                let Ok(text) = serde_json::to_string(&update) else {
+                    continue;
+                };
+
+                if socket.send(Message::Text(text.into())).await.is_err() {
+                    break;
+                }
+            }
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => (),
+            },
+        }
+    }
+
+    debug!("websocket closed");
+}
+
+/// Next update, skipping the ones a slow client missed.
+async fn next_update(receiver: &mut broadcast::Receiver<StateUpdate>) -> Option<StateUpdate> {
+    loop {
+        match receiver.recv().await {
+            Ok(update) => return Some(update),
+            Err(RecvError::Lagged(count)) => warn!("client missed {count} updates"),
+            Err(RecvError::Closed) => return None,
+        }
+    }
+}
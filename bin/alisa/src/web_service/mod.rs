@@ -2,12 +2,18 @@ mod auth {
     mod auth_page;
     mod authorize;
     mod issue_token;
+    mod keys;
+    mod login_limiter;
     mod token;
+    mod trusted_proxy;
     mod validate_autorization;
 
     pub use auth_page::auth_page;
     pub use authorize::authorize;
     pub use issue_token::issue_token;
+    pub use keys::JwtKeys;
+    pub use login_limiter::LoginLimiter;
+    pub use trusted_proxy::TrustedProxy;
     pub use validate_autorization::validate_autorization;
 }
 
@@ -25,14 +31,39 @@ mod user {
     pub use unlink::unlink;
 }
 
+mod local {
+    mod action;
+    mod api_keys;
+    mod devices;
+    mod state;
+    mod updates;
+
+    pub use action::action;
+    pub use api_keys::{ApiKeys, KeyQuery};
+    pub use devices::devices;
+    pub use state::state;
+    pub use updates::{events, websocket};
+}
+
+pub use auth::{JwtKeys, TrustedProxy};
+pub use local::ApiKeys;
+
+use std::sync::Arc;
+
 use axum::body::Body;
+use axum::extract::FromRef;
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
 use axum::routing::{get, head, post};
 use axum::Router;
 use log::error;
+use tokio::sync::broadcast;
+use transport::metadata;
+use transport::registry::Registry;
+use transport::rpc;
+use transport::state::StateUpdate;
 
-use crate::Error;
+use crate::{Error, Gateways, StateCache, TokenStore, Users};
 
 pub struct ServiceError(Error, uuid::Uuid);
 
@@ -62,8 +93,141 @@ impl From<serde_json::Error> for ServiceError {
     }
 }
 
-pub fn router() -> Router {
-    Router::new()
+/// Language of the device names shown to Alice.
+#[derive(Clone, Debug)]
+pub struct Language(pub String);
+
+impl Default for Language {
+    fn default() -> Self {
+        Language(metadata::DEFAULT_LANGUAGE.to_string())
+    }
+}
+
+#[derive(Clone)]
+struct AppState {
+    registry: Arc<Registry>,
+    gateways: Arc<Gateways>,
+    cache: Arc<StateCache>,
+    mqtt: rpc::Client,
+    api_keys: Arc<ApiKeys>,
+    tokens: Arc<TokenStore>,
+    users: Arc<Users>,
+    jwt_keys: Arc<JwtKeys>,
+    login_limiter: Arc<auth::LoginLimiter>,
+    trusted_proxy: TrustedProxy,
+    language: Language,
+    updates: broadcast::Sender<StateUpdate>,
+}
+
+impl FromRef<AppState> for Arc<Registry> {
+    fn from_ref(state: &AppState) -> Self {
+        state.registry.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<Gateways> {
+    fn from_ref(state: &AppState) -> Self {
+        state.gateways.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<StateCache> {
+    fn from_ref(state: &AppState) -> Self {
+        state.cache.clone()
+    }
+}
+
+impl FromRef<AppState> for rpc::Client {
+    fn from_ref(state: &AppState) -> Self {
+        state.mqtt.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<ApiKeys> {
+    fn from_ref(state: &AppState) -> Self {
+        state.api_keys.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<TokenStore> {
+    fn from_ref(state: &AppState) -> Self {
+        state.tokens.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<Users> {
+    fn from_ref(state: &AppState) -> Self {
+        state.users.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<JwtKeys> {
+    fn from_ref(state: &AppState) -> Self {
+        state.jwt_keys.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<auth::LoginLimiter> {
+    fn from_ref(state: &AppState) -> Self {
+        state.login_limiter.clone()
+    }
+}
+
+impl FromRef<AppState> for TrustedProxy {
+    fn from_ref(state: &AppState) -> Self {
+        state.trusted_proxy
+    }
+}
+
+impl FromRef<AppState> for Language {
+    fn from_ref(state: &AppState) -> Self {
+        state.language.clone()
+    }
+}
+
+impl FromRef<AppState> for broadcast::Sender<StateUpdate> {
+    fn from_ref(state: &AppState) -> Self {
+        state.updates.clone()
+    }
+}
+
+/// Routes of the Alice skill and of the local API. The local API is served on
+/// its own listener so it never sits behind the public proxy.
+pub struct Routers {
+    pub alice: Router,
+    pub local: Router,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn routers(
+    registry: Arc<Registry>,
+    gateways: Arc<Gateways>,
+    cache: Arc<StateCache>,
+    mqtt: rpc::Client,
+    api_keys: Arc<ApiKeys>,
+    tokens: Arc<TokenStore>,
+    users: Arc<Users>,
+    jwt_keys: Arc<JwtKeys>,
+    trusted_proxy: TrustedProxy,
+    language: Language,
+    updates: broadcast::Sender<StateUpdate>,
+) -> Routers {
+    let state = AppState {
+        registry,
+        gateways,
+        cache,
+        mqtt,
+        api_keys,
+        tokens,
+        users,
+        jwt_keys,
+        login_limiter: Arc::default(),
+        trusted_proxy,
+        language,
+        updates,
+    };
+
+    let alice = Router::new()
         .route("/auth", get(auth::auth_page).post(auth::authorize))
         .route("/token", post(auth::issue_token))
         .route("/v1.0", head(user::pong).get(user::pong))
@@ -71,4 +235,18 @@ pub fn router() -> Router {
         .route("/v1.0/user/devices/query", post(user::query))
         .route("/v1.0/user/devices/action", post(user::action))
         .route("/v1.0/user/unlink", post(user::unlink))
+        .with_state(state.clone());
+
+    let local = Router::new()
+        .route("/api/v1/devices", get(local::devices))
+        .route("/api/v1/devices/{device_type}/{room}", get(local::state))
+        .route(
+            "/api/v1/devices/{device_type}/{room}/action",
+            post(local::action),
+        )
+        .route("/api/v1/updates", get(local::events))
+        .route("/api/v1/updates/ws", get(local::websocket))
+        .with_state(state);
+
+    Routers { alice, local }
 }
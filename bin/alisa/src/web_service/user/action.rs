@@ -1,4 +1,5 @@
 use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 use std::time::Duration;
 
 use alice::{
@@ -6,35 +7,40 @@ use alice::{
     UpdateStateCapability, UpdateStateErrorCode, UpdateStateRequest, UpdateStateResponse,
     UpdatedDeviceState,
 };
+use transport::action::{ActionResult, ErrorKind};
 use transport::elisa::Action as ElisaAction;
 use transport::elisheba::Action as ElishebaAction;
 use transport::elizabeth::{Action as ElizabethAction, ActionType as ElizabethActionType};
-use transport::{connect_mqtt, DeviceId, DeviceType, Room, Topic};
+use transport::registry::Registry;
+use transport::scene::{self, Report as SceneReport};
+use transport::{rpc, DeviceId, DeviceType, Room, Topic};
 
+use axum::extract::State;
 use axum::http::{HeaderMap, StatusCode};
 use axum::response::{IntoResponse, Result};
 use axum::Json;
-use futures_util::StreamExt;
 use log::{debug, error, info, trace};
-use paho_mqtt::{Message, MessageBuilder, Properties, PropertyCode, QOS_1};
 use uuid::Uuid;
 
 use crate::web_service::auth::validate_autorization;
+use crate::web_service::JwtKeys;
 use crate::web_service::ServiceError;
+use crate::{Gateways, TokenStore, Users};
 
+const RESPONSE_DEADLINE: Duration = Duration::from_secs(3);
+
+#[allow(clippy::too_many_arguments)]
 pub async fn action(
     headers: HeaderMap,
+    State(tokens): State<Arc<TokenStore>>,
+    State(users): State<Arc<Users>>,
+    State(keys): State<Arc<JwtKeys>>,
+    State(registry): State<Arc<Registry>>,
+    State(gateways): State<Arc<Gateways>>,
+    State(mqtt): State<rpc::Client>,
     Json(action): Json<UpdateStateRequest>,
 ) -> Result<impl IntoResponse> {
-    validate_autorization(&headers, "devices_action")?;
-
-    let mqtt_address = std::env::var("MQTT_ADDRESS").expect("set ENV variable MQTT_ADDRESS");
-    let mqtt_username = std::env::var("MQTT_USER").expect("set ENV variable MQTT_USER");
-    let mqtt_password = std::env::var("MQTT_PASS").expect("set ENV variable MQTT_PASS");
-
-    let mut mqtt_client = connect_mqtt(mqtt_address, mqtt_username, mqtt_password, "alisa_query")
-        .await
-        .map_err(ServiceError::from)?;
+    let auth = validate_autorization(&headers, &tokens, &users, &keys, "devices_action")?;
 
     let request_id = headers.get("X-Request-Id").unwrap().to_str().unwrap();
 
@@ -57,40 +63,101 @@ pub async fn action(
     let mut elisa_action = None;
     let elisa_action_id = Uuid::new_v4();
 
+    let mut scenes = vec![];
+
     for device in action.payload.devices {
+        if !auth.user.can_see_device(&device.id, &registry) {
+            for capability in &device.capabilities {
+                let mut capability = prepare_response_capability(capability);
+                *capability.result_mut() = StateUpdateResult::error(
+                    UpdateStateErrorCode::DeviceNotFound,
+                    format!("not available to {}", auth.user.id),
+                );
+
+                response_capabilities.insert(Uuid::new_v4(), (device.id.clone(), capability));
+            }
+
+            continue;
+        }
+
+        if let Some(gateway) = gateways.offline_gateway(&registry, &device.id) {
+            for capability in &device.capabilities {
+                let mut capability = prepare_response_capability(capability);
+                *capability.result_mut() = StateUpdateResult::error(
+                    UpdateStateErrorCode::DeviceUnreachable,
+                    format!("{gateway} is offline"),
+                );
+
+                response_capabilities.insert(Uuid::new_v4(), (device.id.clone(), capability));
+            }
+
+            continue;
+        }
+
         match device.id.device_type {
             DeviceType::Recuperator | DeviceType::Thermostat => {
                 let result = handle_elizabeth_capabilities(
                     device.id.device_type,
-                    device.id.room,
+                    &device.id.room,
                     &device.capabilities,
                 );
 
                 for (action, capability) in result {
-                    response_capabilities.insert(action.id(), (device.id, capability));
+                    if let Some(id) = action.id() {
+                        action_ids.insert(id);
+                        response_capabilities.insert(id, (device.id.clone(), capability));
+                    }
                     actions.push(action);
                 }
             }
             DeviceType::VacuumCleaner => {
                 let result = handle_elisa_capabilities(
-                    device.id.room,
+                    &device.id.room,
                     &device.capabilities,
                     &mut elisa_action,
                 );
 
                 for capability in result {
-                    response_capabilities.insert(elisa_action_id, (device.id, capability));
+                    response_capabilities.insert(elisa_action_id, (device.id.clone(), capability));
                 }
             }
             DeviceType::TemperatureSensor => (),
             DeviceType::Light => {
-                let result = handle_elisheba_capabilities(device.id.room, &device.capabilities);
+                let result = handle_elisheba_capabilities(&device.id.room, &device.capabilities);
 
                 for (action, capability) in result {
-                    response_capabilities.insert(action.id(), (device.id, capability));
+                    if let Some(id) = action.id() {
+                        action_ids.insert(id);
+                        response_capabilities.insert(id, (device.id.clone(), capability));
+                    }
                     actions.push(action);
                 }
             }
+            DeviceType::Scene => {
+                for capability in &device.capabilities {
+                    let mut response = prepare_response_capability(capability);
+
+                    let scene = device.id.scene_id().and_then(|id| registry.scene(&id));
+
+                    match (capability, scene) {
+                        (StateCapability::OnOff { value: true }, Some(scene)) => {
+                            scenes.push((device.id.clone(), scene));
+                        }
+                        (StateCapability::OnOff { value: false }, Some(_)) => {
+                            *response.result_mut() = StateUpdateResult::ok();
+                        }
+                        (_, None) => {
+                            *response.result_mut() = StateUpdateResult::error(
+                                UpdateStateErrorCode::InvalidAction,
+                                format!("unknown scene {}", device.id.room),
+                            );
+                        }
+                        _ => continue,
+                    }
+
+                    response_capabilities.insert(Uuid::new_v4(), (device.id.clone(), response));
+                }
+            }
         };
     }
 
@@ -101,46 +168,33 @@ pub async fn action(
         actions.push(transport::action::Action::Elisa(action, elisa_action_id));
     }
 
-    let request = transport::action::ActionRequest { actions };
-
-    let request_topic = Topic::ActionRequest.to_string();
-    let response_topic = Topic::ActionResponse(request_id.to_string()).to_string();
-
-    mqtt_client.subscribe(&response_topic, QOS_1);
-    let mut stream = mqtt_client.get_stream(1);
+    if !actions.is_empty() {
+        let request = transport::action::ActionRequest { actions };
 
-    let mut props = Properties::new();
-    props
-        .push_string(PropertyCode::ResponseTopic, &response_topic)
-        .map_err(ServiceError::from)?;
+        debug!("posting action request {:?}", request);
 
-    debug!("posting to {} {:?}", request_topic, request);
+        let mut responses = mqtt
+            .request(Topic::ActionRequest, &request, RESPONSE_DEADLINE)
+            .await
+            .map_err(ServiceError::from)?;
 
-    let payload = serde_json::to_vec(&request).map_err(ServiceError::from)?;
-    let request_msg = MessageBuilder::new()
-        .topic(request_topic)
-        .properties(props)
-        .payload(payload)
-        .finalize();
+        while let Some(response) = responses.next().await {
+            handle_response(response, &mut action_ids, &mut response_capabilities);
 
-    mqtt_client
-        .publish(request_msg)
-        .await
-        .map_err(ServiceError::from)?;
-
-    while let Ok(Some(msg_opt)) = tokio::time::timeout(Duration::from_secs(3), stream.next()).await
-    {
-        if let Some(msg) = msg_opt {
-            handle_message(msg, &mut action_ids, &mut response_capabilities);
-        }
-
-        if action_ids.is_empty() {
-            break;
+            if action_ids.is_empty() {
+                break;
+            }
         }
     }
 
-    mqtt_client.stop_stream();
-    mqtt_client.unsubscribe(&response_topic);
+    for (device_id, scene) in scenes {
+        let report = scene::run(&mqtt, &registry, scene, RESPONSE_DEADLINE)
+            .await
+            .map_err(ServiceError::from)?;
+
+        let capability = UpdateStateCapability::on_off(scene_result(&report));
+        response_capabilities.insert(Uuid::new_v4(), (device_id, capability));
+    }
 
     let response_devices = group(response_capabilities.into_values())
         .into_iter()
@@ -171,7 +225,7 @@ where
 
 fn handle_elizabeth_capabilities(
     device_type: DeviceType,
-    room: Room,
+    room: &Room,
     capabilities: &[StateCapability],
 ) -> Vec<(transport::action::Action, UpdateStateCapability)> {
     capabilities
@@ -181,7 +235,7 @@ fn handle_elizabeth_capabilities(
                 (
                     transport::action::Action::Elizabeth(
                         ElizabethAction {
-                            room,
+                            room: room.clone(),
                             device_type,
                             action_type,
                         },
@@ -195,7 +249,7 @@ fn handle_elizabeth_capabilities(
 }
 
 fn handle_elisa_capabilities(
-    room: Room,
+    room: &Room,
     capabilities: &[StateCapability],
     current_action: &mut Option<ElisaAction>,
 ) -> Vec<UpdateStateCapability> {
@@ -223,7 +277,7 @@ fn handle_elisa_capabilities(
 }
 
 fn handle_elisheba_capabilities(
-    room: Room,
+    room: &Room,
     capabilities: &[StateCapability],
 ) -> Vec<(transport::action::Action, UpdateStateCapability)> {
     capabilities
@@ -232,7 +286,7 @@ fn handle_elisheba_capabilities(
             StateCapability::OnOff { value } => Some((
                 transport::action::Action::Elisheba(
                     ElishebaAction {
-                        room,
+                        room: room.clone(),
                         is_enabled: *value,
                     },
                     Uuid::new_v4(),
@@ -266,11 +320,11 @@ fn map_elizabeth_action(state_capability: &StateCapability) -> Option<ElizabethA
     }
 }
 
-fn map_elisa_action(state_capability: &StateCapability, room: Room) -> Option<ElisaAction> {
+fn map_elisa_action(state_capability: &StateCapability, room: &Room) -> Option<ElisaAction> {
     match state_capability {
         StateCapability::OnOff { value } => {
             if *value {
-                Some(ElisaAction::Start(vec![room]))
+                Some(ElisaAction::Start(vec![room.clone()]))
             } else {
                 Some(ElisaAction::Stop)
             }
@@ -349,29 +403,63 @@ fn map_mode_to_cleanup_mode(mode: alice::Mode) -> Option<transport::elisa::Clean
     }
 }
 
-fn handle_message(
-    msg: Message,
+fn handle_response(
+    response: transport::action::ActionResponse,
     action_ids: &mut HashSet<Uuid>,
     devices: &mut HashMap<Uuid, (DeviceId, UpdateStateCapability)>,
 ) {
-    use transport::action::ActionResponse;
-
-    let response: ActionResponse = serde_json::from_slice(msg.payload()).unwrap();
-
     if action_ids.contains(&response.action_id) {
         action_ids.remove(&response.action_id);
     }
 
     if let Some((_, capability)) = devices.get_mut(&response.action_id) {
         *capability.result_mut() = match response.result {
-            transport::action::ActionResult::Success => StateUpdateResult::ok(),
-            transport::action::ActionResult::Failure => {
-                StateUpdateResult::error(UpdateStateErrorCode::DeviceUnreachable, String::new())
-            }
+            ActionResult::Success => StateUpdateResult::ok(),
+            ActionResult::Failure(error) => StateUpdateResult::error(
+                map_error_kind(error.kind),
+                error.message.unwrap_or_default(),
+            ),
         }
     }
 }
 
+/// A scene succeeds only if all of its steps do, otherwise the first failed
+/// step is reported.
+fn scene_result(report: &SceneReport) -> StateUpdateResult {
+    if let Some(error) = &report.error {
+        return StateUpdateResult::error(
+            map_error_kind(error.kind),
+            error.message.clone().unwrap_or_default(),
+        );
+    }
+
+    match report.first_failure() {
+        None => StateUpdateResult::ok(),
+        Some(step) => match &step.result {
+            ActionResult::Failure(error) => StateUpdateResult::error(
+                map_error_kind(error.kind),
+                format!(
+                    "{}: {}",
+                    step.device,
+                    error.message.as_deref().unwrap_or("failed")
+                ),
+            ),
+            ActionResult::Success => StateUpdateResult::ok(),
+        },
+    }
+}
+
+fn map_error_kind(kind: ErrorKind) -> UpdateStateErrorCode {
+    match kind {
+        ErrorKind::DeviceUnreachable | ErrorKind::Timeout => {
+            UpdateStateErrorCode::DeviceUnreachable
+        }
+        ErrorKind::InvalidValue => UpdateStateErrorCode::InvalidValue,
+        ErrorKind::DeviceBusy => UpdateStateErrorCode::DeviceBusy,
+        ErrorKind::NotSupported => UpdateStateErrorCode::NotSupportedInCurrentMode,
+    }
+}
+
 fn prepare_response_capability(capability: &StateCapability) -> UpdateStateCapability {
     let result = StateUpdateResult::error(
         UpdateStateErrorCode::DeviceUnreachable,
@@ -408,6 +496,7 @@ fn prepare_response_capability(capability: &StateCapability) -> UpdateStateCapab
 mod tests {
     use super::*;
     use alice::Mode;
+    use transport::SceneId;
 
     #[test]
     fn map_fan_speed() {
@@ -468,6 +557,59 @@ mod tests {
         assert!(map_mode_to_cleanup_mode(Mode::Turbo).is_none());
     }
 
+    #[test]
+    fn map_action_error_kind() {
+        assert_eq!(
+            map_error_kind(ErrorKind::DeviceUnreachable),
+            UpdateStateErrorCode::DeviceUnreachable
+        );
+        assert_eq!(
+            map_error_kind(ErrorKind::Timeout),
+            UpdateStateErrorCode::DeviceUnreachable
+        );
+        assert_eq!(
+            map_error_kind(ErrorKind::InvalidValue),
+            UpdateStateErrorCode::InvalidValue
+        );
+        assert_eq!(
+            map_error_kind(ErrorKind::DeviceBusy),
+            UpdateStateErrorCode::DeviceBusy
+        );
+        assert_eq!(
+            map_error_kind(ErrorKind::NotSupported),
+            UpdateStateErrorCode::NotSupportedInCurrentMode
+        );
+    }
+
+    #[test]
+    fn report_scene_failure() {
+        let mut report = SceneReport {
+            scene: SceneId::new("leaving"),
+            error: None,
+            steps: vec![
+                scene::StepReport {
+                    device: DeviceId::light_at_room(Room::new("corridor")),
+                    result: ActionResult::Success,
+                },
+                scene::StepReport {
+                    device: DeviceId::vacuum_cleaner_at_room(Room::new("kitchen")),
+                    result: ActionResult::failure(ErrorKind::DeviceBusy, "vacuum is docking"),
+                },
+            ],
+        };
+
+        assert_eq!(
+            scene_result(&report),
+            StateUpdateResult::error(
+                UpdateStateErrorCode::DeviceBusy,
+                "vacuum_cleaner/kitchen: vacuum is docking".to_string()
+            )
+        );
+
+        report.steps.pop();
+        assert_eq!(scene_result(&report), StateUpdateResult::ok());
+    }
+
     #[test]
     fn enable_recuperator() {
         let state_capability = StateCapability::OnOff { value: true };
@@ -554,21 +696,21 @@ mod tests {
     #[test]
     fn start_vacuum_cleaner() {
         let state_capability = StateCapability::OnOff { value: true };
-        let room = Room::LivingRoom;
+        let room = Room::new("living_room");
 
         assert_eq!(
-            map_elisa_action(&state_capability, room),
-            Some(ElisaAction::Start(vec![Room::LivingRoom]))
+            map_elisa_action(&state_capability, &room),
+            Some(ElisaAction::Start(vec![Room::new("living_room")]))
         );
     }
 
     #[test]
     fn stop_vacuum_cleaner() {
         let state_capability = StateCapability::OnOff { value: false };
-        let room = Room::LivingRoom;
+        let room = Room::new("living_room");
 
         assert_eq!(
-            map_elisa_action(&state_capability, room),
+            map_elisa_action(&state_capability, &room),
             Some(ElisaAction::Stop)
         );
     }
@@ -579,10 +721,10 @@ mod tests {
             function: ModeFunction::WorkSpeed,
             mode: Mode::Quiet,
         };
-        let room = Room::LivingRoom;
+        let room = Room::new("living_room");
 
         assert_eq!(
-            map_elisa_action(&state_capability, room),
+            map_elisa_action(&state_capability, &room),
             Some(ElisaAction::SetWorkSpeed(
                 transport::elisa::WorkSpeed::Silent
             ))
@@ -595,10 +737,10 @@ mod tests {
             function: ModeFunction::CleanupMode,
             mode: Mode::DryCleaning,
         };
-        let room = Room::LivingRoom;
+        let room = Room::new("living_room");
 
         assert_eq!(
-            map_elisa_action(&state_capability, room),
+            map_elisa_action(&state_capability, &room),
             Some(ElisaAction::SetCleanupMode(
                 transport::elisa::CleanupMode::DryCleaning
             ))
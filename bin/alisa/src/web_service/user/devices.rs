@@ -1,17 +1,33 @@
 use alice::{Device, DeviceCapability, DeviceProperty, DeviceType};
-use alice::{Mode, ModeFunction, Range, RangeFunction, TemperatureUnit, ToggleFunction};
-use transport::{DeviceId, Room};
+use alice::{ModeFunction, Range, RangeFunction, TemperatureUnit, ToggleFunction};
+use transport::metadata::Property;
+use transport::registry::{DeviceConfig, Registry};
+use transport::scene::Scene;
+use transport::DeviceId;
 
+use std::sync::Arc;
+
+use axum::extract::State;
 use axum::http::{HeaderMap, StatusCode};
 use axum::response::{IntoResponse, Result};
 use axum::Json;
 use log::info;
 use serde_json::json;
 
+use crate::reporter::{map_cleanup_mode, map_fan_speed, map_work_speed};
 use crate::web_service::auth::validate_autorization;
+use crate::web_service::{JwtKeys, Language};
+use crate::{TokenStore, User, Users};
 
-pub async fn devices(headers: HeaderMap) -> Result<impl IntoResponse> {
-    validate_autorization(&headers, "devices")?;
+pub async fn devices(
+    headers: HeaderMap,
+    State(tokens): State<Arc<TokenStore>>,
+    State(users): State<Arc<Users>>,
+    State(keys): State<Arc<JwtKeys>>,
+    State(registry): State<Arc<Registry>>,
+    State(Language(language)): State<Language>,
+) -> Result<impl IntoResponse> {
+    let auth = validate_autorization(&headers, &tokens, &users, &keys, "devices")?;
 
     let request_id = headers.get("X-Request-Id").unwrap().to_str().unwrap();
     info!("{request_id}/devices");
@@ -19,152 +35,285 @@ pub async fn devices(headers: HeaderMap) -> Result<impl IntoResponse> {
     let json = json!({
         "request_id": request_id,
         "payload": {
-            "user_id": "chipp",
-            "devices": [
-                sensor_device(Room::Bedroom),
-                sensor_device(Room::HomeOffice),
-                sensor_device(Room::Kitchen),
-                sensor_device(Room::Nursery),
-                vacuum_cleaner_device(Room::Bedroom),
-                vacuum_cleaner_device(Room::Corridor),
-                vacuum_cleaner_device(Room::Hallway),
-                vacuum_cleaner_device(Room::HomeOffice),
-                vacuum_cleaner_device(Room::Kitchen),
-                vacuum_cleaner_device(Room::LivingRoom),
-                thermostat_device(Room::Bedroom),
-                thermostat_device(Room::HomeOffice),
-                thermostat_device(Room::LivingRoom),
-                thermostat_device(Room::Nursery),
-                recuperator_device(),
-                light_device(Room::Corridor),
-                light_device(Room::Nursery),
-            ]
+            "user_id": auth.user.id,
+            "devices": alice_devices(&registry, &language, &auth.user)
         }
     });
 
     Ok((StatusCode::OK, Json(json)))
 }
 
-fn name_for_room(room: &Room) -> &'static str {
-    match room {
-        Room::Bathroom => "Ванная",
-        Room::Bedroom => "Спальня",
-        Room::Corridor => "Коридор",
-        Room::Hallway => "Прихожая",
-        Room::HomeOffice => "Кабинет",
-        Room::Kitchen => "Кухня",
-        Room::LivingRoom => "Зал",
-        Room::Nursery => "Детская",
-        Room::Toilet => "Туалет",
-    }
+/// Devices and scenes of the registry `user` can see, with their names in
+/// `language`.
+fn alice_devices(registry: &Registry, language: &str, user: &User) -> Vec<Device> {
+    registry
+        .devices()
+        .iter()
+        .filter(|config| user.can_see(&config.room))
+        .map(|config| alice_device(config, registry, language))
+        .chain(
+            registry
+                .scenes()
+                .iter()
+                .filter(|scene| user.can_see_scene(scene))
+                .map(|scene| scene_device(scene, registry, language)),
+        )
+        .collect()
 }
 
-fn sensor_device(room: Room) -> Device {
-    let room_name = name_for_room(&room).to_string();
+fn alice_device(config: &DeviceConfig, registry: &Registry, language: &str) -> Device {
+    let room_name = registry
+        .room_name_in(&config.room, language)
+        .unwrap_or(config.room.as_str())
+        .to_string();
+
+    let (device_type, properties, capabilities) = match config.device_type {
+        transport::DeviceType::TemperatureSensor => (
+            DeviceType::Sensor,
+            properties(config.device_type)
+                .map(DeviceProperty::reportable)
+                .collect(),
+            vec![],
+        ),
+        transport::DeviceType::VacuumCleaner => (
+            DeviceType::VacuumCleaner,
+            properties(config.device_type)
+                .map(|property| property.retrievable().reportable())
+                .collect(),
+            vacuum_cleaner_capabilities(config),
+        ),
+        transport::DeviceType::Thermostat => (
+            DeviceType::Thermostat,
+            properties(config.device_type)
+                .map(DeviceProperty::reportable)
+                .collect(),
+            thermostat_capabilities(config),
+        ),
+        transport::DeviceType::Recuperator => (
+            DeviceType::Ventilation,
+            vec![],
+            recuperator_capabilities(config),
+        ),
+        transport::DeviceType::Light => (
+            DeviceType::Light,
+            vec![],
+            vec![DeviceCapability::on_off(false).reportable()],
+        ),
+        transport::DeviceType::Scene => unreachable!("the registry rejects scene devices"),
+    };
 
     Device {
-        id: DeviceId::temperature_sensor_at_room(room),
-        name: "Датчик температуры".to_string(),
-        description: format!("в {}", room_name),
+        id: config.id(),
+        name: config.name_in(language).to_string(),
+        description: description(&room_name, language),
         room: room_name,
-        device_type: DeviceType::Sensor,
-        properties: vec![
-            DeviceProperty::humidity().reportable(),
-            DeviceProperty::temperature().reportable(),
-            DeviceProperty::battery_level().reportable(),
-        ],
-        capabilities: vec![],
+        device_type,
+        properties,
+        capabilities,
     }
 }
 
-fn vacuum_cleaner_device(room: Room) -> Device {
-    let room_name = name_for_room(&room).to_string();
+fn description(room_name: &str, language: &str) -> String {
+    match language {
+        "en" => format!("in {room_name}"),
+        _ => format!("в {room_name}"),
+    }
+}
 
-    Device {
-        id: DeviceId::vacuum_cleaner_at_room(room),
-        name: "Ева".to_string(),
-        description: format!("в {}", room_name),
-        room: room_name,
-        device_type: DeviceType::VacuumCleaner,
-        properties: vec![DeviceProperty::battery_level().retrievable().reportable()],
-        capabilities: vec![
-            DeviceCapability::on_off(false).retrievable().reportable(),
-            DeviceCapability::mode(
-                ModeFunction::WorkSpeed,
-                vec![Mode::Quiet, Mode::Normal, Mode::Medium, Mode::Turbo],
-            )
-            .retrievable()
-            .reportable(),
-            DeviceCapability::mode(
-                ModeFunction::CleanupMode,
-                vec![Mode::DryCleaning, Mode::MixedCleaning, Mode::WetCleaning],
-            )
+fn vacuum_cleaner_capabilities(config: &DeviceConfig) -> Vec<DeviceCapability> {
+    vec![
+        DeviceCapability::on_off(false).retrievable().reportable(),
+        DeviceCapability::mode(
+            ModeFunction::WorkSpeed,
+            config
+                .work_speeds()
+                .iter()
+                .map(|speed| map_work_speed(*speed))
+                .collect(),
+        )
+        .retrievable()
+        .reportable(),
+        DeviceCapability::mode(
+            ModeFunction::CleanupMode,
+            config
+                .cleanup_modes()
+                .iter()
+                .map(|mode| map_cleanup_mode(*mode))
+                .collect(),
+        )
+        .retrievable()
+        .reportable(),
+        DeviceCapability::toggle(ToggleFunction::Pause)
             .retrievable()
             .reportable(),
-            DeviceCapability::toggle(ToggleFunction::Pause)
-                .retrievable()
-                .reportable(),
-        ],
-    }
+    ]
 }
 
-fn thermostat_device(room: Room) -> Device {
-    let room_name = name_for_room(&room).to_string();
+fn thermostat_capabilities(config: &DeviceConfig) -> Vec<DeviceCapability> {
+    let range = config.temperature_range();
 
-    Device {
-        id: DeviceId::thermostat_at_room(room),
-        name: "Термостат".to_string(),
-        description: format!("в {}", room_name),
-        room: room_name,
-        device_type: DeviceType::Thermostat,
-        properties: vec![DeviceProperty::temperature().reportable()],
-        capabilities: vec![
-            DeviceCapability::on_off(false).reportable(),
-            DeviceCapability::range(
-                RangeFunction::Temperature,
-                TemperatureUnit::Celsius,
-                Range {
-                    min: 16.0,
-                    max: 28.0,
-                    precision: 0.5,
-                },
-            )
-            .reportable(),
-        ],
-    }
+    vec![
+        DeviceCapability::on_off(false).reportable(),
+        DeviceCapability::range(
+            RangeFunction::Temperature,
+            TemperatureUnit::Celsius,
+            Range {
+                min: range.min,
+                max: range.max,
+                precision: range.step,
+            },
+        )
+        .reportable(),
+    ]
 }
 
-fn recuperator_device() -> Device {
-    let room_name = name_for_room(&Room::LivingRoom).to_string();
+fn recuperator_capabilities(config: &DeviceConfig) -> Vec<DeviceCapability> {
+    vec![
+        DeviceCapability::on_off(false).reportable(),
+        DeviceCapability::mode(
+            ModeFunction::FanSpeed,
+            config
+                .fan_speeds()
+                .iter()
+                .map(|speed| map_fan_speed(*speed))
+                .collect(),
+        )
+        .reportable(),
+    ]
+}
+
+fn properties(device_type: transport::DeviceType) -> impl Iterator<Item = DeviceProperty> {
+    device_type
+        .properties()
+        .iter()
+        .filter_map(|property| match property {
+            Property::Temperature => Some(DeviceProperty::temperature()),
+            Property::Humidity => Some(DeviceProperty::humidity()),
+            Property::Battery => Some(DeviceProperty::battery_level()),
+            // Alice has no instance for wear, Home Assistant shows it.
+            Property::Consumables => None,
+        })
+}
+
+/// Scenes can't be queried, turning one on runs it, so Alice scenarios can
+/// use it as a regular device.
+fn scene_device(scene: &Scene, registry: &Registry, language: &str) -> Device {
+    let room_name = registry
+        .room_name_in(&scene.room, language)
+        .unwrap_or(scene.room.as_str())
+        .to_string();
+
+    let description = match language {
+        "en" => "scene",
+        _ => "сценарий",
+    };
 
     Device {
-        id: DeviceId::recuperator_at_room(Room::LivingRoom),
-        name: "Рекуператор".to_string(),
-        description: format!("в {}", room_name),
+        id: DeviceId::scene(&scene.id),
+        name: scene.name_in(language).to_string(),
+        description: description.to_string(),
         room: room_name,
-        device_type: DeviceType::Ventilation,
+        device_type: DeviceType::Other,
         properties: vec![],
-        capabilities: vec![
-            DeviceCapability::on_off(false).reportable(),
-            DeviceCapability::mode(
-                ModeFunction::FanSpeed,
-                vec![Mode::Low, Mode::Medium, Mode::High],
-            )
-            .reportable(),
-        ],
+        capabilities: vec![DeviceCapability::on_off(false)],
     }
 }
 
-fn light_device(room: Room) -> Device {
-    let room_name = name_for_room(&room).to_string();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use transport::Room;
 
-    Device {
-        id: DeviceId::light_at_room(room),
-        name: "Верхний свет".to_string(),
-        description: format!("в {}", room_name),
-        room: room_name,
-        device_type: DeviceType::Light,
-        properties: vec![],
-        capabilities: vec![DeviceCapability::on_off(false).reportable()],
+    const REGISTRY: &str = r#"
+        [[rooms]]
+        id = "living_room"
+        name = "Зал"
+        names = { en = "Living room" }
+
+        [[devices]]
+        type = "thermostat"
+        room = "living_room"
+        name = "Теплый пол"
+        names = { en = "Floor heating" }
+        gateway = "elizabeth"
+        page_id = "ef8f4a07-6fc4-4b7e-99e2-d1c71f4fd96d"
+        temperature = { min = 20.0, max = 26.0, step = 1.0 }
+
+        [[rooms]]
+        id = "nursery"
+        name = "Детская"
+
+        [[devices]]
+        type = "light"
+        room = "nursery"
+        gateway = "elisheba"
+        device_id = "1002074ed2"
+
+        [[scenes]]
+        id = "good_night"
+        room = "living_room"
+        name = "Спокойной ночи"
+        names = { en = "Good night" }
+        steps = []
+    "#;
+
+    const USERS: &str = r#"
+        [[users]]
+        id = "chipp"
+        password_hash = ""
+
+        [[users]]
+        id = "guest"
+        password_hash = ""
+        rooms = ["nursery"]
+    "#;
+
+    fn users() -> Users {
+        toml::from_str(USERS).unwrap()
+    }
+
+    #[test]
+    fn test_alice_devices() {
+        let registry: Registry = REGISTRY.parse().unwrap();
+        let users = users();
+        let chipp = users.user("chipp").unwrap();
+
+        let devices = serde_json::to_value(alice_devices(&registry, "en", chipp)).unwrap();
+
+        assert_eq!(devices[0]["name"], "Floor heating");
+        assert_eq!(devices[0]["description"], "in Living room");
+        assert_eq!(devices[0]["room"], "Living room");
+        assert_eq!(
+            devices[0]["capabilities"][1]["parameters"]["range"],
+            json!({ "min": 20.0, "max": 26.0, "precision": 1.0 })
+        );
+
+        assert_eq!(devices[2]["name"], "Good night");
+        assert_eq!(devices[2]["description"], "scene");
+
+        let devices = serde_json::to_value(alice_devices(&registry, "ru", chipp)).unwrap();
+
+        assert_eq!(devices[0]["name"], "Теплый пол");
+        assert_eq!(devices[0]["description"], "в Зал");
+        assert_eq!(devices[2]["name"], "Спокойной ночи");
+    }
+
+    #[test]
+    fn test_visible_devices() {
+        let registry: Registry = REGISTRY.parse().unwrap();
+        let users = users();
+
+        let devices = alice_devices(&registry, "ru", users.user("chipp").unwrap());
+        assert_eq!(devices.len(), 3);
+
+        let devices = alice_devices(&registry, "ru", users.user("guest").unwrap());
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].id, DeviceId::light_at_room(Room::new("nursery")));
+    }
+
+    #[test]
+    fn test_vacuum_cleaner_properties() {
+        let properties: Vec<_> = properties(transport::DeviceType::VacuumCleaner).collect();
+        assert_eq!(properties, vec![DeviceProperty::battery_level()]);
     }
 }
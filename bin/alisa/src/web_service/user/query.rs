@@ -1,40 +1,45 @@
 use std::collections::HashSet;
+use std::sync::Arc;
 use std::time::Duration;
 
-use alice::{StateDevice, StateRequest, StateResponse};
-use transport::{connect_mqtt, DeviceId, DeviceType, Topic};
+use alice::{StateDevice, StateRequest, StateResponse, UpdateStateErrorCode};
+use transport::registry::Registry;
+use transport::{rpc, DeviceId, DeviceType, Topic};
 
+use axum::extract::State;
 use axum::http::{HeaderMap, StatusCode};
 use axum::response::{IntoResponse, Result};
 use axum::Json;
-use futures_util::StreamExt;
-use log::{debug, info};
-use paho_mqtt::{Message, MessageBuilder, Properties, PropertyCode, QOS_1};
+use log::{debug, info, warn};
 
 use crate::reporter;
 use crate::web_service::auth::validate_autorization;
+use crate::web_service::JwtKeys;
 use crate::web_service::ServiceError;
+use crate::{Gateways, StateCache, TokenStore, Users};
 
+const RESPONSE_DEADLINE: Duration = Duration::from_secs(3);
+
+#[allow(clippy::too_many_arguments)]
 pub async fn query(
     headers: HeaderMap,
+    State(tokens): State<Arc<TokenStore>>,
+    State(users): State<Arc<Users>>,
+    State(keys): State<Arc<JwtKeys>>,
+    State(registry): State<Arc<Registry>>,
+    State(gateways): State<Arc<Gateways>>,
+    State(cache): State<Arc<StateCache>>,
+    State(mqtt): State<rpc::Client>,
     Json(query): Json<StateRequest>,
 ) -> Result<impl IntoResponse> {
-    validate_autorization(&headers, "devices_query")?;
-
-    let mqtt_address = std::env::var("MQTT_ADDRESS").expect("set ENV variable MQTT_ADDRESS");
-    let mqtt_username = std::env::var("MQTT_USER").expect("set ENV variable MQTT_USER");
-    let mqtt_password = std::env::var("MQTT_PASS").expect("set ENV variable MQTT_PASS");
-
-    let mut mqtt_client = connect_mqtt(mqtt_address, mqtt_username, mqtt_password, "alisa_query")
-        .await
-        .map_err(ServiceError::from)?;
+    let auth = validate_autorization(&headers, &tokens, &users, &keys, "devices_query")?;
 
     let request_id = headers.get("X-Request-Id").unwrap().to_str().unwrap();
 
     let device_ids = query
         .devices
         .iter()
-        .map(|device| device.id)
+        .map(|device| device.id.clone())
         .collect::<Vec<_>>();
 
     info!(
@@ -46,50 +51,72 @@ pub async fn query(
             .join(",")
     );
 
-    let request = transport::state::StateRequest { device_ids };
+    let mut devices = vec![];
+    let mut reachable_ids = vec![];
 
-    let request_topic = Topic::StateRequest.to_string();
-    let response_topic = Topic::StateResponse(request_id.to_string()).to_string();
+    for device_id in device_ids {
+        if !auth.user.can_see_device(&device_id, &registry) {
+            devices.push(StateDevice::new_with_error(
+                device_id,
+                UpdateStateErrorCode::DeviceNotFound,
+                format!("not available to {}", auth.user.id),
+            ));
+            continue;
+        }
 
-    mqtt_client.subscribe(&response_topic, QOS_1);
-    let mut stream = mqtt_client.get_stream(1);
+        if device_id.device_type == DeviceType::Scene {
+            devices.push(StateDevice::new_empty(device_id));
+            continue;
+        }
 
-    let mut props = Properties::new();
-    props
-        .push_string(PropertyCode::ResponseTopic, &response_topic)
-        .map_err(ServiceError::from)?;
+        match gateways.offline_gateway(&registry, &device_id) {
+            Some(gateway) => devices.push(StateDevice::new_with_error(
+                device_id,
+                UpdateStateErrorCode::DeviceUnreachable,
+                format!("{gateway} is offline"),
+            )),
+            None => reachable_ids.push(device_id),
+        }
+    }
 
-    debug!("request to {}: {:?}", request_topic, request);
-    debug!("waiting for response on {}", response_topic);
+    let mut device_ids: HashSet<_> = HashSet::from_iter(reachable_ids.iter().cloned());
 
-    let payload = serde_json::to_vec(&request).map_err(ServiceError::from)?;
-    let request_msg = MessageBuilder::new()
-        .topic(request_topic)
-        .properties(props)
-        .payload(payload)
-        .finalize();
+    for device_id in &reachable_ids {
+        if let Some(state) = cache.fresh(device_id) {
+            handle_response(state, &mut device_ids, &mut devices, &registry);
+        }
+    }
 
-    mqtt_client.publish(request_msg);
+    if !device_ids.is_empty() {
+        debug!("{request_id}/query {} stale devices", device_ids.len());
 
-    let mut device_ids: HashSet<_> = HashSet::from_iter(request.device_ids);
-    let mut devices = vec![];
+        let request = transport::state::StateRequest {
+            device_ids: device_ids.iter().cloned().collect(),
+        };
 
-    while let Ok(Some(msg_opt)) = tokio::time::timeout(Duration::from_secs(3), stream.next()).await
-    {
-        if let Some(msg) = msg_opt {
-            debug!("msg: {:?}", msg);
-            debug!("msg_str: {:?}", msg.payload_str());
+        let mut responses = mqtt
+            .request(Topic::StateRequest, &request, RESPONSE_DEADLINE)
+            .await
+            .map_err(ServiceError::from)?;
 
-            handle_message(msg, &mut device_ids, &mut devices)?;
-        }
+        while let Some(response) = responses.next().await {
+            cache.update_with_response(&response, &registry);
+            handle_response(response, &mut device_ids, &mut devices, &registry);
 
-        if device_ids.is_empty() {
-            break;
+            if device_ids.is_empty() {
+                break;
+            }
         }
     }
 
-    mqtt_client.stop_stream();
-    mqtt_client.unsubscribe(&response_topic);
+    // Better an outdated state than none for gateways that are too slow.
+    for device_id in &reachable_ids {
+        if device_ids.contains(device_id) {
+            if let Some(state) = cache.last_known(device_id) {
+                handle_response(state, &mut device_ids, &mut devices, &registry);
+            }
+        }
+    }
 
     for device_id in device_ids {
         devices.push(StateDevice::new_empty(device_id));
@@ -100,25 +127,23 @@ pub async fn query(
     Ok((StatusCode::OK, Json(response)))
 }
 
-#[allow(clippy::result_large_err)]
-fn handle_message(
-    msg: Message,
+fn handle_response(
+    response: transport::state::StateResponse,
     device_ids: &mut HashSet<DeviceId>,
     devices: &mut Vec<StateDevice>,
-) -> Result<()> {
+    registry: &Registry,
+) {
     use transport::state::StateResponse;
 
-    let response: StateResponse =
-        serde_json::from_slice(msg.payload()).map_err(ServiceError::from)?;
     debug!("got response {:?}", response);
 
     match response {
         StateResponse::Elisa(state) => {
-            let states = reporter::prepare_vacuum_updates(state);
+            let states = reporter::prepare_vacuum_updates(state, registry);
 
             for state in states {
-                if device_ids.contains(&state.id()) {
-                    device_ids.remove(&state.id());
+                if device_ids.contains(state.id()) {
+                    device_ids.remove(state.id());
                     devices.push(state);
                 }
             }
@@ -127,16 +152,16 @@ fn handle_message(
             DeviceType::Recuperator => {
                 let state = reporter::prepare_recuperator_current_state(state);
 
-                if device_ids.contains(&state.id()) {
-                    device_ids.remove(&state.id());
+                if device_ids.contains(state.id()) {
+                    device_ids.remove(state.id());
                     devices.push(state);
                 }
             }
             DeviceType::Thermostat => {
                 let state = reporter::prepare_thermostat_current_state(state);
 
-                if device_ids.contains(&state.id()) {
-                    device_ids.remove(&state.id());
+                if device_ids.contains(state.id()) {
+                    device_ids.remove(state.id());
                     devices.push(state);
                 }
             }
@@ -145,12 +170,21 @@ fn handle_message(
         StateResponse::Elisheba(state) => {
             let state = reporter::prepare_light_update(state);
 
-            if device_ids.contains(&state.id()) {
-                device_ids.remove(&state.id());
+            if device_ids.contains(state.id()) {
+                device_ids.remove(state.id());
                 devices.push(state);
             }
         }
-    }
+        StateResponse::Isabel(state) => {
+            let state = reporter::prepare_sensor_current_state(state);
 
-    Ok(())
+            if device_ids.contains(state.id()) {
+                device_ids.remove(state.id());
+                devices.push(state);
+            }
+        }
+        StateResponse::Unknown(response) => {
+            warn!("skipping unknown state response {response}");
+        }
+    }
 }
@@ -1,8 +1,23 @@
-use axum::{http::HeaderMap, response::Result};
+use std::sync::Arc;
+
+use axum::{extract::State, http::HeaderMap, response::Result};
+use log::info;
 
 use crate::web_service::auth::validate_autorization;
+use crate::web_service::JwtKeys;
+use crate::web_service::ServiceError;
+use crate::{TokenStore, Users};
+
+pub async fn unlink(
+    headers: HeaderMap,
+    State(tokens): State<Arc<TokenStore>>,
+    State(users): State<Arc<Users>>,
+    State(keys): State<Arc<JwtKeys>>,
+) -> Result<()> {
+    let auth = validate_autorization(&headers, &tokens, &users, &keys, "unlink")?;
+
+    tokens.revoke(&auth.grant_id).map_err(ServiceError::from)?;
+    info!("{} unlinked, revoked grant {}", auth.user.id, auth.grant_id);
 
-pub async fn unlink(headers: HeaderMap) -> Result<()> {
-    validate_autorization(&headers, "unlink")?;
     Ok(())
 }
@@ -0,0 +1,133 @@
+use std::collections::HashSet;
+
+use log::info;
+use paho_mqtt::{AsyncClient as MqClient, MessageBuilder};
+use transport::elisa::{Consumable, ConsumableState};
+use transport::{envelope, Topic};
+
+/// Consumables due for replacement, so each one is warned about once when it
+/// becomes due rather than on every poll of the state.
+///
+/// A consumable already due when it is first reported was warned about
+/// before elisa restarted, so it is only remembered, not warned about again.
+#[derive(Debug, Default)]
+pub struct WornOut {
+    due: HashSet<Consumable>,
+    seen: HashSet<Consumable>,
+}
+
+impl WornOut {
+    /// Consumables that became due since the last call. Those reset after a
+    /// replacement are forgotten, so they are warned about again once they
+    /// wear out.
+    pub fn newly_due<'a>(
+        &mut self,
+        consumables: &'a [ConsumableState],
+    ) -> Vec<&'a ConsumableState> {
+        let mut newly_due = vec![];
+
+        for consumable in consumables {
+            let is_first_report = self.seen.insert(consumable.consumable);
+
+            if !consumable.needs_replacement() {
+                if self.due.remove(&consumable.consumable) {
+                    info!("roborock {:?} was replaced", consumable.consumable);
+                }
+            } else if self.due.insert(consumable.consumable) {
+                if is_first_report {
+                    info!("roborock {:?} is still due", consumable.consumable);
+                } else {
+                    newly_due.push(consumable);
+                }
+            }
+        }
+
+        newly_due
+    }
+}
+
+/// Tells the other services, like the rules of elise, that `consumable`
+/// needs replacement.
+pub async fn publish_warning(
+    mqtt: &MqClient,
+    consumable: &ConsumableState,
+) -> Result<(), paho_mqtt::Error> {
+    let message = MessageBuilder::new()
+        .topic(Topic::ConsumableWarning.to_string())
+        .payload(envelope::encode(consumable))
+        .qos(paho_mqtt::QOS_1)
+        .finalize();
+
+    mqtt.publish(message).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(remaining: u8) -> ConsumableState {
+        ConsumableState {
+            consumable: Consumable::Filter,
+            work_hours: 150.0 * f32::from(100 - remaining) / 100.0,
+            remaining,
+        }
+    }
+
+    fn main_brush(remaining: u8) -> ConsumableState {
+        ConsumableState {
+            consumable: Consumable::MainBrush,
+            work_hours: 300.0 * f32::from(100 - remaining) / 100.0,
+            remaining,
+        }
+    }
+
+    #[test]
+    fn test_edge_triggering() {
+        let mut worn_out = WornOut::default();
+
+        assert!(worn_out.newly_due(&[filter(11), main_brush(50)]).is_empty());
+        assert_eq!(
+            worn_out.newly_due(&[filter(10), main_brush(50)]),
+            vec![&filter(10)]
+        );
+        assert!(worn_out.newly_due(&[filter(9), main_brush(50)]).is_empty());
+        assert_eq!(
+            worn_out.newly_due(&[filter(0), main_brush(5)]),
+            vec![&main_brush(5)]
+        );
+    }
+
+    #[test]
+    fn test_replacement() {
+        let mut worn_out = WornOut::default();
+
+        assert!(worn_out.newly_due(&[filter(50)]).is_empty());
+        assert_eq!(worn_out.newly_due(&[filter(5)]), vec![&filter(5)]);
+        assert!(worn_out.newly_due(&[filter(100)]).is_empty());
+        assert_eq!(worn_out.newly_due(&[filter(8)]), vec![&filter(8)]);
+    }
+
+    #[test]
+    fn test_restart() {
+        let mut worn_out = WornOut::default();
+
+        // Already warned about before the restart.
+        assert!(worn_out.newly_due(&[filter(5), main_brush(50)]).is_empty());
+        assert!(worn_out.newly_due(&[filter(4), main_brush(50)]).is_empty());
+        assert_eq!(
+            worn_out.newly_due(&[filter(4), main_brush(9)]),
+            vec![&main_brush(9)]
+        );
+    }
+
+    #[test]
+    fn test_unknown_consumables() {
+        let mut worn_out = WornOut::default();
+
+        assert!(worn_out.newly_due(&[filter(15)]).is_empty());
+        assert_eq!(worn_out.newly_due(&[filter(5)]), vec![&filter(5)]);
+        // Consumables that couldn't be fetched are neither due nor replaced.
+        assert!(worn_out.newly_due(&[]).is_empty());
+        assert!(worn_out.newly_due(&[filter(5)]).is_empty());
+    }
+}
@@ -1,5 +1,9 @@
 use std::fmt;
 
+use roborock::RpcError;
+use transport::action::{ActionError, ErrorKind};
+use transport::Room;
+
 #[derive(Debug)]
 pub enum Error {
     Json(serde_json::Error),
@@ -8,6 +12,9 @@ pub enum Error {
     QueueClosed,
     Join(tokio::task::JoinError),
     AddrParse(std::net::AddrParseError),
+    Registry(transport::registry::Error),
+    UnknownRooms(Vec<Room>),
+    UnsupportedAction(serde_json::Value),
 }
 
 impl From<serde_json::Error> for Error {
@@ -40,6 +47,12 @@ impl From<std::net::AddrParseError> for Error {
     }
 }
 
+impl From<transport::registry::Error> for Error {
+    fn from(err: transport::registry::Error) -> Self {
+        Self::Registry(err)
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -48,9 +61,50 @@ impl fmt::Display for Error {
             Self::Vacuum(err) => write!(f, "vacuum error: {err}"),
             Self::QueueClosed => write!(f, "vacuum queue closed"),
             Self::Join(err) => write!(f, "join error: {err}"),
+            Self::Registry(err) => write!(f, "registry error: {err}"),
             Self::AddrParse(err) => write!(f, "address parse error: {err}"),
+            Self::UnknownRooms(rooms) => {
+                let rooms: Vec<_> = rooms.iter().map(Room::as_str).collect();
+                write!(f, "no vacuum segment for rooms: {}", rooms.join(", "))
+            }
+            Self::UnsupportedAction(action) => write!(f, "unsupported action {action}"),
         }
     }
 }
 
 impl std::error::Error for Error {}
+
+impl From<&Error> for ActionError {
+    fn from(err: &Error) -> Self {
+        let kind = match err {
+            Error::Vacuum(roborock::Error::Rpc(RpcError::UnknownMethod)) => ErrorKind::NotSupported,
+            Error::Vacuum(roborock::Error::Rpc(RpcError::DeviceError)) => ErrorKind::DeviceBusy,
+            Error::Vacuum(roborock::Error::Timeout(_)) => ErrorKind::Timeout,
+            Error::UnknownRooms(_) => ErrorKind::InvalidValue,
+            Error::UnsupportedAction(_) => ErrorKind::NotSupported,
+            _ => ErrorKind::DeviceUnreachable,
+        };
+
+        ActionError::new(kind, err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_action_error() {
+        assert_eq!(
+            ActionError::from(&Error::UnknownRooms(vec![Room::new("toilet")])),
+            ActionError::new(
+                ErrorKind::InvalidValue,
+                "no vacuum segment for rooms: toilet"
+            )
+        );
+        assert_eq!(
+            ActionError::from(&Error::Vacuum(roborock::Error::Rpc(RpcError::DeviceError))).kind,
+            ErrorKind::DeviceBusy
+        );
+    }
+}
@@ -1,25 +1,48 @@
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use roborock::{CleanupMode as RoborockCleanupMode, FanSpeed, Status, Vacuum};
+use roborock::{
+    CleanupMode as RoborockCleanupMode, Consumable as RoborockConsumable, Consumables, FanSpeed,
+    Point, Status, Vacuum, Zone,
+};
 use tokio::sync::{mpsc, oneshot};
 use transport::{
-    action::{ActionRequest, ActionResponse, ActionResult},
-    elisa::{Action, CleanupMode, State, WorkSpeed},
+    action::{ActionError, ActionRequest, ActionResponse, ActionResult},
+    elisa::{Action, CleanupMode, Consumable, ConsumableState, State, WorkSpeed},
+    rpc,
     state::{StateRequest, StateResponse},
-    DeviceType,
+    DeviceType, Room,
 };
 
-use log::{debug, error, info};
-use paho_mqtt::{AsyncClient as MqClient, Message, MessageBuilder, PropertyCode};
+use log::{error, info, warn};
+use paho_mqtt::{AsyncClient as MqClient, Message};
 
 mod error;
 pub use error::Error;
 
+mod consumables;
+pub use consumables::{publish_warning, WornOut};
+
+mod segments;
+pub use segments::Segments;
+
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// How long the fetched consumables are reported before asking again, they
+/// wear by the hour.
+const CONSUMABLES_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
 enum VacuumRequest {
     Action(Action, oneshot::Sender<Result<()>>),
-    Status(oneshot::Sender<Result<(Status, Vec<u8>)>>),
+    Status(oneshot::Sender<Result<VacuumStatus>>),
+}
+
+/// Status of the vacuum with the rooms it was told to clean and the wear of
+/// its consumables, unless they couldn't be fetched.
+pub struct VacuumStatus {
+    pub status: Status,
+    pub rooms: Vec<Room>,
+    pub consumables: Option<Consumables>,
 }
 
 #[derive(Clone)]
@@ -28,22 +51,54 @@ pub struct VacuumQueue {
 }
 
 impl VacuumQueue {
-    pub fn new(mut vacuum: Vacuum) -> Self {
+    pub fn new(mut vacuum: Vacuum, segments: Segments) -> Self {
         let (tx, mut rx) = mpsc::channel(16);
 
         tokio::spawn(async move {
+            let mut consumables = None;
+            let mut consumables_fetched: Option<Instant> = None;
+
             while let Some(request) = rx.recv().await {
                 match request {
                     VacuumRequest::Action(action, responder) => {
-                        let _ = responder.send(perform_action(action, &mut vacuum).await);
+                        if matches!(action, Action::ResetConsumable(_)) {
+                            consumables_fetched = None;
+                        }
+
+                        let result = perform_action(action, &mut vacuum, &segments).await;
+                        let _ = responder.send(result);
                     }
                     VacuumRequest::Status(responder) => {
-                        let result = vacuum
-                            .status()
-                            .await
-                            .map(|status| (status, vacuum.last_cleaning_rooms().to_vec()))
-                            .map_err(Error::from);
-                        let _ = responder.send(result);
+                        let status = match vacuum.status().await {
+                            Ok(status) => status,
+                            Err(err) => {
+                                let _ = responder.send(Err(err.into()));
+                                continue;
+                            }
+                        };
+
+                        if consumables_fetched
+                            .is_none_or(|fetched| fetched.elapsed() >= CONSUMABLES_INTERVAL)
+                        {
+                            consumables_fetched = Some(Instant::now());
+                            match vacuum.consumables().await {
+                                Ok(fetched) => consumables = Some(fetched),
+                                Err(err) => warn!("unable to fetch vacuum consumables: {err}"),
+                            }
+                        }
+
+                        let rooms = vacuum
+                            .last_cleaning_rooms()
+                            .iter()
+                            .filter_map(|id| segments.room_for_segment(*id))
+                            .cloned()
+                            .collect();
+
+                        let _ = responder.send(Ok(VacuumStatus {
+                            status,
+                            rooms,
+                            consumables: consumables.clone(),
+                        }));
                     }
                 }
             }
@@ -61,7 +116,7 @@ impl VacuumQueue {
         rx.await.map_err(|_| Error::QueueClosed)?
     }
 
-    pub async fn get_status(&self) -> Result<(Status, Vec<u8>)> {
+    pub async fn get_status(&self) -> Result<VacuumStatus> {
         let (tx, rx) = oneshot::channel();
         self.tx
             .send(VacuumRequest::Status(tx))
@@ -71,71 +126,39 @@ impl VacuumQueue {
     }
 }
 
-pub async fn handle_action_request(msg: Message, mqtt: &mut MqClient, vacuum: Arc<VacuumQueue>) {
-    let request: ActionRequest = match serde_json::from_slice(msg.payload()) {
-        Ok(ids) => ids,
-        Err(err) => {
-            error!("unable to parse request: {}", err);
-            error!("{}", msg.payload_str());
-            return;
-        }
-    };
-
-    let response_topic = match msg.properties().get_string(PropertyCode::ResponseTopic) {
-        Some(topic) => topic,
-        None => {
-            error!("missing response topic");
-            return;
-        }
+pub async fn handle_action_request(msg: Message, mqtt: &MqClient, vacuum: Arc<VacuumQueue>) {
+    let (request, responder) = match rpc::serve::<ActionRequest>(&msg, mqtt) {
+        Some(request) => request,
+        None => return,
     };
 
     for action in request.actions {
-        if let transport::action::Action::Elisa(action, action_id) = action {
-            let result = match vacuum.run_action(action).await {
-                Ok(_) => ActionResult::Success,
-                Err(err) => {
-                    error!("Error updating state: {}", err);
-                    ActionResult::Failure
-                }
-            };
-
-            let response = ActionResponse { action_id, result };
-
-            debug!("publish to {}: {:?}", response_topic, response);
-
-            let payload = serde_json::to_vec(&response).unwrap();
-
-            let message = MessageBuilder::new()
-                .topic(&response_topic)
-                .payload(payload)
-                .finalize();
+        match action {
+            transport::action::Action::Elisa(action, action_id) => {
+                let result = match vacuum.run_action(action).await {
+                    Ok(_) => ActionResult::Success,
+                    Err(err) => {
+                        error!("Error updating state: {}", err);
+                        ActionResult::Failure(ActionError::from(&err))
+                    }
+                };
 
-            match mqtt.publish(message).await {
-                Ok(()) => (),
-                Err(err) => {
-                    error!("Error sending response to {}: {}", response_topic, err);
-                }
+                responder
+                    .respond(&ActionResponse { action_id, result })
+                    .await;
             }
+            transport::action::Action::Unknown(action) => {
+                warn!("skipping unknown action {action}");
+            }
+            _ => (),
         }
     }
 }
 
-pub async fn handle_state_request(msg: Message, mqtt: &mut MqClient, vacuum: Arc<VacuumQueue>) {
-    let request: StateRequest = match serde_json::from_slice(msg.payload()) {
-        Ok(ids) => ids,
-        Err(err) => {
-            error!("unable to parse request: {}", err);
-            error!("{}", msg.payload_str());
-            return;
-        }
-    };
-
-    let response_topic = match msg.properties().get_string(PropertyCode::ResponseTopic) {
-        Some(topic) => topic,
-        None => {
-            error!("missing response topic");
-            return;
-        }
+pub async fn handle_state_request(msg: Message, mqtt: &MqClient, vacuum: Arc<VacuumQueue>) {
+    let (request, responder) = match rpc::serve::<StateRequest>(&msg, mqtt) {
+        Some(request) => request,
+        None => return,
     };
 
     let should_respond = request
@@ -145,25 +168,9 @@ pub async fn handle_state_request(msg: Message, mqtt: &mut MqClient, vacuum: Arc
 
     if should_respond {
         match vacuum.get_status().await {
-            Ok((status, rooms)) => {
-                let state = prepare_state(status, &rooms);
-                debug!("publish to {}: {:?}", response_topic, state);
-
-                let response = StateResponse::Elisa(state);
-
-                let payload = serde_json::to_vec(&response).unwrap();
-
-                let message = MessageBuilder::new()
-                    .topic(&response_topic)
-                    .payload(payload)
-                    .finalize();
-
-                match mqtt.publish(message).await {
-                    Ok(()) => (),
-                    Err(err) => {
-                        error!("Error sending response to {}: {}", response_topic, err);
-                    }
-                }
+            Ok(status) => {
+                let state = prepare_state(status);
+                responder.respond(&StateResponse::Elisa(state)).await;
             }
             Err(err) => {
                 error!("Error fetching vacuum status: {}", err);
@@ -172,15 +179,46 @@ pub async fn handle_state_request(msg: Message, mqtt: &mut MqClient, vacuum: Arc
     }
 }
 
-async fn perform_action(action: Action, vacuum: &mut Vacuum) -> Result<()> {
+async fn perform_action(action: Action, vacuum: &mut Vacuum, segments: &Segments) -> Result<()> {
     match action {
         Action::Start(rooms) => {
-            let room_ids = rooms.iter().filter_map(room_id_for_room).collect();
+            let unknown: Vec<Room> = rooms
+                .iter()
+                .filter(|room| segments.segment_for_room(room).is_none())
+                .cloned()
+                .collect();
+
+            // Cleaning the whole house instead is never what was asked for.
+            if !unknown.is_empty() {
+                return Err(Error::UnknownRooms(unknown));
+            }
+
+            let room_ids = rooms
+                .iter()
+                .filter_map(|room| segments.segment_for_room(room))
+                .collect();
 
             info!("wants to start cleaning in rooms: {:?}", rooms);
             vacuum.start(room_ids).await?;
             Ok(())
         }
+        Action::CleanZones(zones) => {
+            let zones: Vec<_> = zones.iter().map(from_elisa_zone).collect();
+
+            info!("wants to clean zones: {:?}", zones);
+            vacuum.clean_zones(&zones).await?;
+            Ok(())
+        }
+        Action::GoTo(target) => {
+            let target = Point {
+                x: target.x,
+                y: target.y,
+            };
+
+            info!("wants to go to {:?}", target);
+            vacuum.go_to(target).await?;
+            Ok(())
+        }
         Action::Stop => {
             info!("wants to stop cleaning");
             vacuum.stop().await?;
@@ -211,45 +249,42 @@ async fn perform_action(action: Action, vacuum: &mut Vacuum) -> Result<()> {
             vacuum.resume().await?;
             Ok(())
         }
+        Action::ResetConsumable(consumable) => {
+            let consumable = from_elisa_consumable(consumable);
+
+            info!("wants to reset consumable {:?}", consumable);
+            vacuum.reset_consumable(consumable).await?;
+            Ok(())
+        }
+        Action::Unknown(action) => Err(Error::UnsupportedAction(action)),
     }
 }
 
-pub fn prepare_state(status: Status, rooms: &[u8]) -> State {
+pub fn prepare_state(vacuum: VacuumStatus) -> State {
+    let VacuumStatus {
+        status,
+        rooms,
+        consumables,
+    } = vacuum;
+
+    let consumables = consumables
+        .iter()
+        .flat_map(Consumables::iter)
+        .map(|(consumable, work_time)| ConsumableState {
+            consumable: from_roborock_consumable(consumable),
+            work_hours: work_time.as_secs_f32() / 3600.0,
+            remaining: Consumables::remaining(consumable, work_time),
+        })
+        .collect();
+
     State {
         battery_level: status.battery,
         is_enabled: status.state.is_enabled(),
         is_paused: status.state.is_paused(),
         work_speed: from_roborock_speed(status.fan_speed),
         cleanup_mode: from_roborock_cleanup(status.cleanup_mode),
-        rooms: rooms.iter().filter_map(room_from_id).collect(),
-    }
-}
-
-fn room_id_for_room(room: &transport::Room) -> Option<u8> {
-    match room {
-        transport::Room::Bathroom => Some(16),
-        transport::Room::Bedroom => Some(17),
-        transport::Room::Corridor => Some(23),
-        transport::Room::Hallway => Some(24),
-        transport::Room::HomeOffice => Some(21),
-        transport::Room::Kitchen => Some(19),
-        transport::Room::LivingRoom => Some(18),
-        transport::Room::Toilet => Some(22),
-        _ => None,
-    }
-}
-
-fn room_from_id(id: &u8) -> Option<transport::Room> {
-    match id {
-        16 => Some(transport::Room::Bathroom),
-        17 => Some(transport::Room::Bedroom),
-        23 => Some(transport::Room::Corridor),
-        24 => Some(transport::Room::Hallway),
-        21 => Some(transport::Room::HomeOffice),
-        19 => Some(transport::Room::Kitchen),
-        18 => Some(transport::Room::LivingRoom),
-        22 => Some(transport::Room::Toilet),
-        _ => None,
+        rooms,
+        consumables,
     }
 }
 
@@ -275,6 +310,36 @@ fn from_elisa_speed(speed: WorkSpeed) -> FanSpeed {
     }
 }
 
+fn from_elisa_zone(zone: &transport::elisa::Zone) -> Zone {
+    Zone {
+        x1: zone.x1,
+        y1: zone.y1,
+        x2: zone.x2,
+        y2: zone.y2,
+        repeat: zone.repeat,
+    }
+}
+
+fn from_roborock_consumable(consumable: RoborockConsumable) -> Consumable {
+    match consumable {
+        RoborockConsumable::MainBrush => Consumable::MainBrush,
+        RoborockConsumable::SideBrush => Consumable::SideBrush,
+        RoborockConsumable::Filter => Consumable::Filter,
+        RoborockConsumable::Sensor => Consumable::Sensor,
+        RoborockConsumable::Mop => Consumable::Mop,
+    }
+}
+
+fn from_elisa_consumable(consumable: Consumable) -> RoborockConsumable {
+    match consumable {
+        Consumable::MainBrush => RoborockConsumable::MainBrush,
+        Consumable::SideBrush => RoborockConsumable::SideBrush,
+        Consumable::Filter => RoborockConsumable::Filter,
+        Consumable::Sensor => RoborockConsumable::Sensor,
+        Consumable::Mop => RoborockConsumable::Mop,
+    }
+}
+
 fn from_roborock_cleanup(mode: RoborockCleanupMode) -> CleanupMode {
     match mode {
         RoborockCleanupMode::DryCleaning => CleanupMode::DryCleaning,
@@ -1,15 +1,18 @@
-use elisa::{handle_action_request, handle_state_request, prepare_state, Result, VacuumQueue};
+use elisa::{
+    handle_action_request, handle_state_request, prepare_state, publish_warning, Result, Segments,
+    VacuumQueue, WornOut,
+};
 use roborock::Vacuum;
-use transport::state::StateUpdate;
-use transport::{connect_mqtt, Topic};
+use transport::availability::heartbeat;
+use transport::registry::{DeviceConfig, Registry};
+use transport::state::{StateResponse, StateUpdate};
+use transport::{publish_state, DeviceId, DeviceType, MqttSession, Topic};
 
 use std::sync::Arc;
 use std::time::Duration;
 
-use futures_util::stream::StreamExt;
-use log::{error, info, warn};
+use log::{debug, error, info, warn};
 use paho_mqtt::AsyncClient as MqClient;
-use paho_mqtt::{MessageBuilder, QOS_1};
 use tokio::{task, time};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -20,6 +23,9 @@ async fn main() -> Result<()> {
 
     info!("elisa version {VERSION}");
 
+    let registry_path = std::env::var("REGISTRY_PATH").expect("set ENV variable REGISTRY_PATH");
+    let registry = Arc::new(Registry::load(registry_path)?);
+
     let vacuum_ip = std::env::var("ROBOROCK_IP")
         .unwrap_or("10.0.1.150".to_string())
         .parse()?;
@@ -28,18 +34,28 @@ async fn main() -> Result<()> {
     let vacuum_local_key =
         std::env::var("ROBOROCK_LOCAL_KEY").expect("set ENV variable ROBOROCK_LOCAL_KEY");
 
-    let vacuum = Vacuum::new(vacuum_ip, vacuum_duid, vacuum_local_key).await?;
-    let vacuum_queue = Arc::new(VacuumQueue::new(vacuum));
+    let mut vacuum = Vacuum::new(vacuum_ip, vacuum_duid, vacuum_local_key).await?;
+    let segments = discover_segments(&mut vacuum, &registry).await;
+
+    let device_ids = registry
+        .devices_of_type(DeviceType::VacuumCleaner)
+        .map(DeviceConfig::id)
+        .collect();
+    let vacuum_queue = Arc::new(VacuumQueue::new(vacuum, segments));
 
     let mqtt_address = std::env::var("MQTT_ADDRESS").expect("set ENV variable MQTT_ADDRESS");
     let mqtt_username = std::env::var("MQTT_USER").expect("set ENV variable MQTT_USER");
     let mqtt_password = std::env::var("MQTT_PASS").expect("set ENV variable MQTT_PASS");
-    let mqtt_client = connect_mqtt(mqtt_address, mqtt_username, mqtt_password, "elisa").await?;
+    let mqtt_session =
+        MqttSession::connect(mqtt_address, mqtt_username, mqtt_password, "elisa").await?;
+    let mqtt_client = mqtt_session.client().clone();
     info!("connected mqtt");
 
+    task::spawn(heartbeat(mqtt_client.clone(), "elisa", VERSION));
+
     let (set_handle, state_handle) = tokio::try_join!(
-        task::spawn(subscribe_actions(mqtt_client.clone(), vacuum_queue.clone())),
-        task::spawn(subscribe_state(mqtt_client, vacuum_queue))
+        task::spawn(subscribe_actions(mqtt_session, vacuum_queue.clone())),
+        task::spawn(subscribe_state(mqtt_client, vacuum_queue, device_ids))
     )?;
 
     set_handle?;
@@ -48,71 +64,58 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
-async fn subscribe_actions(mut mqtt: MqClient, vacuum: Arc<VacuumQueue>) -> Result<()> {
-    let mut stream = mqtt.get_stream(None);
-
-    let topics = [
-        Topic::ActionRequest.to_string(),
-        Topic::StateRequest.to_string(),
-    ];
-
-    mqtt.subscribe_many(&topics, &[QOS_1, QOS_1]).await?;
-    info!("Subscribed to topics: {:?}", topics);
-
-    while let Some(msg_opt) = stream.next().await {
-        if let Some(msg) = msg_opt {
-            let topic = if let Ok(value) = msg.topic().parse() {
-                value
-            } else {
-                continue;
-            };
-
-            match topic {
-                Topic::ActionRequest => handle_action_request(msg, &mut mqtt, vacuum.clone()).await,
-                Topic::StateRequest => handle_state_request(msg, &mut mqtt, vacuum.clone()).await,
-                _ => (),
-            }
-        } else {
-            time::sleep(Duration::from_secs(1)).await;
-            error!("Lost MQTT connection. Attempting reconnect.");
-
-            loop {
-                match time::timeout(Duration::from_secs(10), mqtt.reconnect()).await {
-                    Ok(Ok(response)) => {
-                        info!("Reconnected to MQTT! {}", response.reason_code());
-
-                        let topics = [
-                            Topic::ActionRequest.to_string(),
-                            Topic::StateRequest.to_string(),
-                        ];
-
-                        mqtt.subscribe_many(&topics, &[QOS_1, QOS_1]).await?;
-                        info!("Subscribed to topics: {:?}", topics);
-
-                        break;
-                    }
-                    Ok(Err(err)) => {
-                        error!("Error MQTT reconnecting: {}", err);
-                        time::sleep(Duration::from_secs(5)).await;
-                    }
-                    Err(err) => {
-                        error!("Error MQTT reconnecting: {}", err);
-                        time::sleep(Duration::from_secs(5)).await;
-                    }
-                }
-            }
+/// Checks the segments set in the registry against the map of the vacuum.
+async fn discover_segments(vacuum: &mut Vacuum, registry: &Registry) -> Segments {
+    let discovered = match vacuum.segments().await {
+        Ok(segments) => segments,
+        Err(err) => {
+            warn!("unable to discover vacuum segments: {err}");
+            vec![]
+        }
+    };
+
+    for segment in &discovered {
+        info!("found vacuum segment {}", segment.id);
+        debug!("segment {} polygon: {:?}", segment.id, segment.polygon);
+    }
+
+    let segments = Segments::resolve(registry, &discovered);
+    for (room, segment_id) in segments.iter() {
+        info!("cleaning {room} as segment {segment_id}");
+    }
+
+    segments
+}
+
+async fn subscribe_actions(mut session: MqttSession, vacuum: Arc<VacuumQueue>) -> Result<()> {
+    let mqtt = session.client().clone();
+
+    session.subscribe(Topic::ActionRequest).await?;
+    session.subscribe(Topic::StateRequest).await?;
+
+    while let Some((topic, msg)) = session.next().await {
+        match topic {
+            Topic::ActionRequest => handle_action_request(msg, &mqtt, vacuum.clone()).await,
+            Topic::StateRequest => handle_state_request(msg, &mqtt, vacuum.clone()).await,
+            _ => (),
         }
     }
 
     Ok(())
 }
 
-async fn subscribe_state(mqtt: MqClient, vacuum: Arc<VacuumQueue>) -> Result<()> {
+async fn subscribe_state(
+    mqtt: MqClient,
+    vacuum: Arc<VacuumQueue>,
+    device_ids: Vec<DeviceId>,
+) -> Result<()> {
     let mut timer = time::interval(Duration::from_secs(10));
+    let mut worn_out = WornOut::default();
 
     loop {
         timer.tick().await;
-        if let Ok((status, rooms)) = vacuum.get_status().await {
+        if let Ok(vacuum_status) = vacuum.get_status().await {
+            let status = &vacuum_status.status;
             let mut issues = Vec::new();
 
             if !status.error_code.is_ok() {
@@ -125,20 +128,25 @@ async fn subscribe_state(mqtt: MqClient, vacuum: Arc<VacuumQueue>) -> Result<()>
             if !issues.is_empty() {
                 warn!("roborock issues: {}", issues.join(", "));
             }
-            let state = prepare_state(status, &rooms);
-            info!("publishing state: {:?}", state);
+            let state = prepare_state(vacuum_status);
 
-            let topic = Topic::StateUpdate;
+            for consumable in worn_out.newly_due(&state.consumables) {
+                warn!(
+                    "roborock {:?} needs replacement: {}% left after {:.0} hours",
+                    consumable.consumable, consumable.remaining, consumable.work_hours
+                );
 
-            let update = StateUpdate::Elisa(state);
-            let payload = serde_json::to_vec(&update).unwrap();
+                if let Err(err) = publish_warning(&mqtt, consumable).await {
+                    error!("Error publishing consumable warning: {}", err);
+                }
+            }
+
+            info!("publishing state: {:?}", state);
 
-            let message = MessageBuilder::new()
-                .topic(topic.to_string())
-                .payload(payload)
-                .finalize();
+            let update = StateUpdate::Elisa(state.clone());
+            let state = StateResponse::Elisa(state);
 
-            match mqtt.publish(message).await {
+            match publish_state(&mqtt, &update, &state, &device_ids).await {
                 Ok(()) => (),
                 Err(err) => {
                     error!("Error publishing state: {}", err);
@@ -0,0 +1,127 @@
+use log::warn;
+use roborock::Segment;
+use transport::registry::Registry;
+use transport::{DeviceType, Room};
+
+/// Roborock segments of the rooms the vacuum cleaner is in.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Segments {
+    rooms: Vec<(Room, u8)>,
+}
+
+impl Segments {
+    /// Segments set in the registry, dropping those missing from the
+    /// `discovered` map unless the map is unavailable.
+    pub fn resolve(registry: &Registry, discovered: &[Segment]) -> Segments {
+        let rooms = registry
+            .devices_of_type(DeviceType::VacuumCleaner)
+            .filter_map(|device| {
+                let room = &device.room;
+                let Some(segment_id) = registry.segment_for_room(room) else {
+                    warn!("no vacuum segment for room {room}");
+                    return None;
+                };
+
+                let is_on_map = discovered.iter().any(|segment| segment.id == segment_id);
+                if !discovered.is_empty() && !is_on_map {
+                    warn!("vacuum segment {segment_id} of room {room} is not on the map");
+                    return None;
+                }
+
+                Some((room.clone(), segment_id))
+            })
+            .collect();
+
+        Segments { rooms }
+    }
+
+    pub fn segment_for_room(&self, room: &Room) -> Option<u8> {
+        self.rooms
+            .iter()
+            .find_map(|(r, segment_id)| (r == room).then_some(*segment_id))
+    }
+
+    pub fn room_for_segment(&self, segment_id: u8) -> Option<&Room> {
+        self.rooms
+            .iter()
+            .find_map(|(room, id)| (*id == segment_id).then_some(room))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(Room, u8)> {
+        self.rooms.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+        [[rooms]]
+        id = "kitchen"
+        name = "Кухня"
+
+        [[rooms]]
+        id = "living_room"
+        name = "Зал"
+
+        [[rooms]]
+        id = "nursery"
+        name = "Детская"
+
+        [[devices]]
+        type = "vacuum_cleaner"
+        room = "kitchen"
+        gateway = "elisa"
+        segment_id = 19
+
+        [[devices]]
+        type = "vacuum_cleaner"
+        room = "living_room"
+        gateway = "elisa"
+        segment_id = 18
+
+        [[devices]]
+        type = "vacuum_cleaner"
+        room = "nursery"
+        gateway = "elisa"
+    "#;
+
+    fn segment(id: u8) -> Segment {
+        Segment {
+            id,
+            polygon: vec![],
+        }
+    }
+
+    #[test]
+    fn test_resolve() {
+        let registry: Registry = CONFIG.parse().unwrap();
+        let discovered = [segment(16), segment(18), segment(20)];
+
+        let segments = Segments::resolve(&registry, &discovered);
+
+        assert_eq!(segments.segment_for_room(&Room::new("kitchen")), None);
+        assert_eq!(
+            segments.segment_for_room(&Room::new("living_room")),
+            Some(18)
+        );
+        assert_eq!(segments.segment_for_room(&Room::new("nursery")), None);
+        assert_eq!(segments.room_for_segment(16), None);
+        assert_eq!(
+            segments.room_for_segment(18),
+            Some(&Room::new("living_room"))
+        );
+    }
+
+    #[test]
+    fn test_resolve_without_map() {
+        let registry: Registry = CONFIG.parse().unwrap();
+        let segments = Segments::resolve(&registry, &[]);
+
+        assert_eq!(
+            segments.iter().collect::<Vec<_>>(),
+            vec![&(Room::new("kitchen"), 19), &(Room::new("living_room"), 18)]
+        );
+    }
+}
@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+
+use chrono::NaiveTime;
+use log::{debug, info};
+use serde::Deserialize;
+use transport::registry::Registry;
+use transport::state::StateUpdate;
+
+use crate::reading::{readings, Reading, Value};
+use crate::rules::{deserialize_time, Condition, Rule, Rules};
+
+/// Evaluates the rules against incoming readings, remembering what every rule
+/// has seen so far.
+pub struct Engine {
+    rules: Vec<Rule>,
+    states: HashMap<String, RuleState>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum RuleState {
+    /// A threshold fires only while armed, it re-arms past the hysteresis.
+    Threshold {
+        is_armed: bool,
+    },
+    Transition {
+        last: Option<bool>,
+    },
+}
+
+/// An update recorded together with the local time it was received at.
+#[derive(Debug, Deserialize)]
+pub struct Record {
+    #[serde(deserialize_with = "deserialize_time")]
+    pub time: NaiveTime,
+    pub update: StateUpdate,
+}
+
+impl Engine {
+    pub fn new(rules: Rules) -> Engine {
+        let mut engine = Engine {
+            rules: vec![],
+            states: HashMap::new(),
+        };
+        engine.reload(rules);
+
+        engine
+    }
+
+    /// Replaces the rules. Rules that did not change keep their state, so a
+    /// reload doesn't fire them again.
+    pub fn reload(&mut self, rules: Rules) {
+        let mut states = HashMap::new();
+
+        for rule in &rules.rules {
+            let unchanged = self.rules.iter().any(|old| old == rule);
+
+            let state = match (unchanged, self.states.get(&rule.name)) {
+                (true, Some(state)) => *state,
+                _ => RuleState::new(&rule.when),
+            };
+
+            states.insert(rule.name.clone(), state);
+        }
+
+        self.rules = rules.rules;
+        self.states = states;
+    }
+
+    /// Rules fired by `reading` received at local `time`.
+    pub fn handle(&mut self, reading: &Reading, time: NaiveTime) -> Vec<&Rule> {
+        let mut fired = vec![];
+
+        for rule in &self.rules {
+            if rule.when.device() != &reading.device {
+                continue;
+            }
+
+            let Some(state) = self.states.get_mut(&rule.name) else {
+                continue;
+            };
+
+            let in_window = rule.during.is_none_or(|window| window.contains(time));
+
+            if state.update(&rule.when, reading, in_window) {
+                info!("rule {} fired by {:?}", rule.name, reading);
+                fired.push(rule);
+            }
+        }
+
+        fired
+    }
+
+    /// Feeds recorded updates through the rules, returning the names of the
+    /// rules fired and when.
+    pub fn replay<I>(&mut self, records: I, registry: &Registry) -> Vec<(NaiveTime, String)>
+    where
+        I: IntoIterator<Item = Record>,
+    {
+        let mut fired = vec![];
+
+        for record in records {
+            for reading in readings(&record.update, registry) {
+                for rule in self.handle(&reading, record.time) {
+                    fired.push((record.time, rule.name.clone()));
+                }
+            }
+        }
+
+        fired
+    }
+}
+
+impl RuleState {
+    fn new(condition: &Condition) -> RuleState {
+        match condition {
+            Condition::Threshold { .. } => RuleState::Threshold { is_armed: true },
+            Condition::Transition { .. } => RuleState::Transition { last: None },
+        }
+    }
+
+    /// Returns whether the rule fires. A crossing outside of the time window
+    /// keeps the rule armed, so it fires once the window opens.
+    fn update(&mut self, condition: &Condition, reading: &Reading, in_window: bool) -> bool {
+        match (self, condition, reading.value) {
+            (
+                RuleState::Threshold { is_armed },
+                Condition::Threshold {
+                    value: metric,
+                    above,
+                    below,
+                    hysteresis,
+                    ..
+                },
+                Value::Number(value),
+            ) if *metric == reading.metric => {
+                let (is_crossed, is_rearmed) = match (above, below) {
+                    (Some(limit), _) => (value > *limit, value <= limit - hysteresis),
+                    (_, Some(limit)) => (value < *limit, value >= limit + hysteresis),
+                    (None, None) => (false, false),
+                };
+
+                if is_rearmed && !*is_armed {
+                    debug!("threshold re-armed at {}", value);
+                    *is_armed = true;
+                }
+
+                if is_crossed && *is_armed && in_window {
+                    *is_armed = false;
+                    return true;
+                }
+
+                false
+            }
+            (
+                RuleState::Transition { last },
+                Condition::Transition {
+                    value: metric,
+                    from,
+                    to,
+                    ..
+                },
+                Value::Bool(value),
+            ) if *metric == reading.metric => {
+                let previous = last.replace(value);
+
+                match previous {
+                    Some(previous) => {
+                        previous != value
+                            && value == *to
+                            && from.is_none_or(|from| from == previous)
+                            && in_window
+                    }
+                    None => false,
+                }
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use transport::{DeviceId, Room};
+
+    use crate::rules::Metric;
+
+    fn registry() -> Registry {
+        include_str!("../../../conf/registry.toml").parse().unwrap()
+    }
+
+    fn engine(config: &str) -> Engine {
+        let rules: Rules = config.parse().unwrap();
+        rules.validate(&registry()).unwrap();
+
+        Engine::new(rules)
+    }
+
+    fn time(s: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(s, "%H:%M").unwrap()
+    }
+
+    fn humidity(value: f32) -> Reading {
+        Reading {
+            device: DeviceId::temperature_sensor_at_room(Room::new("nursery")),
+            metric: Metric::Humidity,
+            value: Value::Number(value),
+        }
+    }
+
+    const HUMIDITY_RULE: &str = r#"
+        [[rules]]
+        name = "dry_nursery"
+        when = { type = "threshold", device = "temperature_sensor/nursery", value = "humidity", below = 35.0, hysteresis = 3.0 }
+        during = { from = "08:00", to = "21:00" }
+        then = [{ device = "light/nursery", action = "turn_on" }]
+    "#;
+
+    #[test]
+    fn test_threshold_hysteresis() {
+        let mut engine = engine(HUMIDITY_RULE);
+        let noon = time("12:00");
+
+        assert!(engine.handle(&humidity(40.0), noon).is_empty());
+        assert_eq!(engine.handle(&humidity(34.0), noon).len(), 1);
+        assert!(engine.handle(&humidity(33.0), noon).is_empty());
+        assert!(engine.handle(&humidity(36.0), noon).is_empty());
+        assert!(engine.handle(&humidity(34.5), noon).is_empty());
+        assert!(engine.handle(&humidity(38.0), noon).is_empty());
+        assert_eq!(engine.handle(&humidity(34.0), noon).len(), 1);
+    }
+
+    #[test]
+    fn test_time_window() {
+        let mut engine = engine(HUMIDITY_RULE);
+
+        assert!(engine.handle(&humidity(30.0), time("23:00")).is_empty());
+        assert_eq!(engine.handle(&humidity(30.0), time("08:05")).len(), 1);
+    }
+
+    #[test]
+    fn test_transition() {
+        let mut engine = engine(
+            r#"
+            [[rules]]
+            name = "light_off"
+            when = { type = "transition", device = "light/corridor", value = "is_enabled", from = true, to = false }
+            then = [{ device = "light/nursery", action = "turn_off" }]
+            "#,
+        );
+
+        let light = |value| Reading {
+            device: DeviceId::light_at_room(Room::new("corridor")),
+            metric: Metric::IsEnabled,
+            value: Value::Bool(value),
+        };
+        let noon = time("12:00");
+
+        assert!(engine.handle(&light(false), noon).is_empty());
+        assert!(engine.handle(&light(false), noon).is_empty());
+        assert!(engine.handle(&light(true), noon).is_empty());
+        assert_eq!(engine.handle(&light(false), noon).len(), 1);
+        assert!(engine.handle(&light(false), noon).is_empty());
+    }
+
+    #[test]
+    fn test_reload_keeps_state() {
+        let mut engine = engine(HUMIDITY_RULE);
+        let noon = time("12:00");
+
+        assert_eq!(engine.handle(&humidity(30.0), noon).len(), 1);
+
+        engine.reload(HUMIDITY_RULE.parse().unwrap());
+        assert!(engine.handle(&humidity(30.0), noon).is_empty());
+
+        let changed = HUMIDITY_RULE.replace("below = 35.0", "below = 32.0");
+        engine.reload(changed.parse().unwrap());
+        assert_eq!(engine.handle(&humidity(30.0), noon).len(), 1);
+    }
+
+    #[test]
+    fn test_replay() {
+        let rules: Rules = include_str!("../../../conf/rules.toml").parse().unwrap();
+        let mut engine = Engine::new(rules);
+
+        let records = include_str!("../replay/day.jsonl")
+            .lines()
+            .map(|line| serde_json::from_str::<Record>(line).unwrap());
+
+        assert_eq!(
+            engine.replay(records, &registry()),
+            vec![
+                (time("09:10"), "dry_nursery".to_string()),
+                (time("11:45"), "vacuum_finished".to_string()),
+                (time("22:30"), "warm_bedroom_at_night".to_string()),
+            ]
+        );
+    }
+}
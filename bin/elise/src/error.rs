@@ -0,0 +1,60 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Error {
+    Mqtt(paho_mqtt::Error),
+    Json(serde_json::Error),
+    Io(std::io::Error),
+    Toml(toml::de::Error),
+    Registry(transport::registry::Error),
+    InvalidRule(String, &'static str),
+    InvalidSchedule(String, &'static str),
+}
+
+impl From<paho_mqtt::Error> for Error {
+    fn from(err: paho_mqtt::Error) -> Self {
+        Self::Mqtt(err)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Json(err)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(err: toml::de::Error) -> Self {
+        Self::Toml(err)
+    }
+}
+
+impl From<transport::registry::Error> for Error {
+    fn from(err: transport::registry::Error) -> Self {
+        Self::Registry(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Mqtt(err) => write!(f, "mqtt error: {err}"),
+            Self::Json(err) => write!(f, "json error: {err}"),
+            Self::Io(err) => write!(f, "io error: {err}"),
+            Self::Toml(err) => write!(f, "toml error: {err}"),
+            Self::Registry(err) => write!(f, "registry error: {err}"),
+            Self::InvalidRule(name, reason) => write!(f, "rule {name} is invalid: {reason}"),
+            Self::InvalidSchedule(name, reason) => {
+                write!(f, "schedule {name} is invalid: {reason}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
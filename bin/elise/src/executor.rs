@@ -0,0 +1,46 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{error, info};
+use transport::action::ActionResult;
+use transport::registry::Registry;
+use transport::rpc;
+use transport::scene::{self, Step};
+
+const RESPONSE_DEADLINE: Duration = Duration::from_secs(10);
+
+/// Sends the steps of fired rules to the gateways.
+pub struct Executor {
+    mqtt: rpc::Client,
+    registry: Arc<Registry>,
+}
+
+impl Executor {
+    pub fn new(mqtt: rpc::Client, registry: Arc<Registry>) -> Executor {
+        Executor { mqtt, registry }
+    }
+
+    pub async fn execute(&self, name: &str, steps: &[Step]) {
+        let reports =
+            match scene::run_steps(&self.mqtt, &self.registry, steps, RESPONSE_DEADLINE).await {
+                Ok(reports) => reports,
+                Err(err) => {
+                    error!("Error running {}: {}", name, err);
+                    return;
+                }
+            };
+
+        for report in reports {
+            match report.result {
+                ActionResult::Success => info!("{}: {} succeeded", name, report.device),
+                ActionResult::Failure(err) => error!(
+                    "{}: {} failed with {:?}: {}",
+                    name,
+                    report.device,
+                    err.kind,
+                    err.message.unwrap_or_default()
+                ),
+            }
+        }
+    }
+}
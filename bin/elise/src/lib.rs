@@ -0,0 +1,18 @@
+mod engine;
+mod executor;
+mod reading;
+mod rules;
+mod schedule;
+mod sun;
+
+pub use engine::{Engine, Record};
+pub use executor::Executor;
+pub use reading::{readings, Reading, Value};
+pub use rules::{Condition, Metric, Rule, Rules, TimeWindow};
+pub use schedule::{Missed, Schedule, Scheduler, Trigger};
+pub use sun::{sun_event, Location, SunEvent};
+
+mod error;
+pub use error::Error;
+
+pub type Result<T> = std::result::Result<T, Error>;
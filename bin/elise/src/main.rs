@@ -0,0 +1,254 @@
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use chrono::Local;
+use elise::{readings, Engine, Executor, Record, Result, Rules, Scheduler};
+use transport::availability::heartbeat;
+use transport::registry::Registry;
+use transport::state::StateUpdate;
+use transport::{envelope, rpc, MqttSession, Topic};
+
+use log::{error, info};
+use tokio::task;
+use tokio::time;
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+const RELOAD_INTERVAL: Duration = Duration::from_secs(5);
+const SCHEDULE_INTERVAL: Duration = Duration::from_secs(30);
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    pretty_env_logger::init_timed();
+
+    let registry_path = std::env::var("REGISTRY_PATH").expect("set ENV variable REGISTRY_PATH");
+    let registry = Arc::new(Registry::load(registry_path)?);
+
+    let rules_path = std::env::var("RULES_PATH").expect("set ENV variable RULES_PATH");
+    let rules_path = PathBuf::from(rules_path);
+    let rules = Rules::load(&rules_path, &registry)?;
+
+    let mut args = std::env::args().skip(1);
+    if let Some("replay") = args.next().as_deref() {
+        let path = args.next().expect("usage: elise replay <recording.jsonl>");
+        return replay(path, rules, &registry);
+    }
+
+    info!("elise version {VERSION}");
+    info!(
+        "loaded {} rules and {} schedules",
+        rules.rules.len(),
+        rules.schedules.len()
+    );
+
+    let schedule_state_path =
+        std::env::var("SCHEDULE_STATE_PATH").expect("set ENV variable SCHEDULE_STATE_PATH");
+    let schedule_state_path = PathBuf::from(schedule_state_path);
+
+    let mut scheduler = Scheduler::new(rules.schedules.clone(), rules.location);
+    scheduler.restore(&schedule_state_path)?;
+
+    let scheduler = Arc::new(Mutex::new(scheduler));
+    let engine = Arc::new(Mutex::new(Engine::new(rules)));
+
+    let mqtt_address = std::env::var("MQTT_ADDRESS").expect("set ENV variable MQTT_ADDRESS");
+    let mqtt_username = std::env::var("MQTT_USER").expect("set ENV variable MQTT_USER");
+    let mqtt_password = std::env::var("MQTT_PASS").expect("set ENV variable MQTT_PASS");
+
+    let mqtt_session = MqttSession::connect(
+        mqtt_address.clone(),
+        mqtt_username.clone(),
+        mqtt_password.clone(),
+        "elise",
+    )
+    .await?;
+    let actions_client =
+        rpc::Client::connect(mqtt_address, mqtt_username, mqtt_password, "elise_actions").await?;
+    info!("connected mqtt");
+
+    task::spawn(heartbeat(mqtt_session.client().clone(), "elise", VERSION));
+    task::spawn(watch_rules(
+        rules_path,
+        engine.clone(),
+        scheduler.clone(),
+        registry.clone(),
+    ));
+
+    let record_path = std::env::var("RECORD_PATH").ok().map(PathBuf::from);
+    let executor = Arc::new(Executor::new(actions_client, registry.clone()));
+
+    task::spawn(run_schedules(
+        scheduler,
+        executor.clone(),
+        schedule_state_path,
+    ));
+
+    subscribe_state(mqtt_session, engine, executor, registry, record_path).await
+}
+
+async fn subscribe_state(
+    mut mqtt: MqttSession,
+    engine: Arc<Mutex<Engine>>,
+    executor: Arc<Executor>,
+    registry: Arc<Registry>,
+    record_path: Option<PathBuf>,
+) -> Result<()> {
+    mqtt.subscribe(Topic::StateUpdate).await?;
+
+    while let Some((topic, msg)) = mqtt.next().await {
+        if topic != Topic::StateUpdate {
+            continue;
+        }
+
+        let update: StateUpdate = match envelope::decode(msg.payload()) {
+            Ok(update) => update,
+            Err(err) => {
+                error!("unable to parse update on {}: {}", msg.topic(), err);
+                continue;
+            }
+        };
+
+        if let Some(path) = &record_path {
+            if let Err(err) = record(path, &update) {
+                error!("Error recording update: {}", err);
+            }
+        }
+
+        let now = Local::now().time();
+        let mut fired = vec![];
+
+        {
+            let mut engine = engine.lock().unwrap();
+
+            for reading in readings(&update, &registry) {
+                for rule in engine.handle(&reading, now) {
+                    fired.push((rule.name.clone(), rule.then.clone()));
+                }
+            }
+        }
+
+        for (name, steps) in fired {
+            let executor = executor.clone();
+            task::spawn(async move { executor.execute(&name, &steps).await });
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs the schedules that are due, saving when they ran so missed runs are
+/// noticed after a restart.
+async fn run_schedules(scheduler: Arc<Mutex<Scheduler>>, executor: Arc<Executor>, path: PathBuf) {
+    let mut timer = time::interval(SCHEDULE_INTERVAL);
+
+    loop {
+        timer.tick().await;
+
+        let due: Vec<_> = {
+            let mut scheduler = scheduler.lock().unwrap();
+
+            let due = scheduler
+                .due(&Local::now())
+                .into_iter()
+                .map(|schedule| (schedule.name.clone(), schedule.then.clone()))
+                .collect();
+
+            if let Err(err) = scheduler.save(&path) {
+                error!("Error saving schedule state: {}", err);
+            }
+
+            due
+        };
+
+        for (name, steps) in due {
+            info!("schedule {} is due", name);
+
+            let executor = executor.clone();
+            task::spawn(async move { executor.execute(&name, &steps).await });
+        }
+    }
+}
+
+/// Reloads the rules and schedules whenever the file changes. Invalid rules
+/// are reported and the previous ones stay in effect.
+async fn watch_rules(
+    path: PathBuf,
+    engine: Arc<Mutex<Engine>>,
+    scheduler: Arc<Mutex<Scheduler>>,
+    registry: Arc<Registry>,
+) {
+    let modified_at = |path: &PathBuf| -> Option<SystemTime> {
+        std::fs::metadata(path)
+            .and_then(|meta| meta.modified())
+            .ok()
+    };
+
+    let mut last_modified = modified_at(&path);
+    let mut timer = time::interval(RELOAD_INTERVAL);
+
+    loop {
+        timer.tick().await;
+
+        let modified = modified_at(&path);
+        if modified == last_modified {
+            continue;
+        }
+
+        last_modified = modified;
+
+        match Rules::load(&path, &registry) {
+            Ok(rules) => {
+                info!(
+                    "reloaded {} rules and {} schedules",
+                    rules.rules.len(),
+                    rules.schedules.len()
+                );
+
+                scheduler
+                    .lock()
+                    .unwrap()
+                    .reload(rules.schedules.clone(), rules.location);
+                engine.lock().unwrap().reload(rules);
+            }
+            Err(err) => error!("Error reloading rules, keeping the previous ones: {}", err),
+        }
+    }
+}
+
+fn record(path: &PathBuf, update: &StateUpdate) -> Result<()> {
+    let line = serde_json::json!({
+        "time": Local::now().time().format("%H:%M:%S").to_string(),
+        "update": update,
+    });
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{}", line)?;
+
+    Ok(())
+}
+
+/// Prints the rules a recording would have fired.
+fn replay(path: String, rules: Rules, registry: &Registry) -> Result<()> {
+    let file = std::fs::File::open(path)?;
+
+    let mut records = vec![];
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+
+        if !line.trim().is_empty() {
+            records.push(serde_json::from_str::<Record>(&line)?);
+        }
+    }
+
+    let mut engine = Engine::new(rules);
+
+    for (time, name) in engine.replay(records, registry) {
+        println!("{} {}", time.format("%H:%M:%S"), name);
+    }
+
+    Ok(())
+}
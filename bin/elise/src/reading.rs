@@ -0,0 +1,162 @@
+use transport::elizabeth::Capability;
+use transport::isabel::Property;
+use transport::registry::Registry;
+use transport::state::StateUpdate;
+use transport::{DeviceId, DeviceType};
+
+use crate::rules::Metric;
+
+/// A single value reported by a gateway.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reading {
+    pub device: DeviceId,
+    pub metric: Metric,
+    pub value: Value,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Value {
+    Number(f32),
+    Bool(bool),
+}
+
+impl Reading {
+    fn number(device: DeviceId, metric: Metric, value: f32) -> Reading {
+        Reading {
+            device,
+            metric,
+            value: Value::Number(value),
+        }
+    }
+
+    fn bool(device: DeviceId, metric: Metric, value: bool) -> Reading {
+        Reading {
+            device,
+            metric,
+            value: Value::Bool(value),
+        }
+    }
+}
+
+/// Splits an update into readings of the devices it covers. The vacuum
+/// cleaner reports once for the whole map, so every registry room gets a
+/// reading and only the rooms being cleaned count as enabled.
+pub fn readings(update: &StateUpdate, registry: &Registry) -> Vec<Reading> {
+    match update {
+        StateUpdate::Isabel(state) => {
+            let device = DeviceId::temperature_sensor_at_room(state.room.clone());
+
+            match state.property {
+                Property::Temperature(value) => {
+                    vec![Reading::number(device, Metric::Temperature, value)]
+                }
+                Property::Humidity(value) => vec![Reading::number(device, Metric::Humidity, value)],
+                Property::Battery(value) => {
+                    vec![Reading::number(device, Metric::Battery, value.into())]
+                }
+                Property::TemperatureAndHumidity(temperature, humidity) => vec![
+                    Reading::number(device.clone(), Metric::Temperature, temperature),
+                    Reading::number(device, Metric::Humidity, humidity),
+                ],
+                Property::Unknown(_) => vec![],
+            }
+        }
+        StateUpdate::Elizabeth(state) => {
+            let device = DeviceId {
+                room: state.room.clone(),
+                device_type: state.device_type,
+            };
+
+            match state.capability {
+                Capability::IsEnabled(value) => {
+                    vec![Reading::bool(device, Metric::IsEnabled, value)]
+                }
+                Capability::CurrentTemperature(value) => {
+                    vec![Reading::number(device, Metric::Temperature, value)]
+                }
+                Capability::Temperature(value) => {
+                    vec![Reading::number(device, Metric::TargetTemperature, value)]
+                }
+                Capability::FanSpeed(_) => vec![],
+            }
+        }
+        StateUpdate::Elisheba(state) => vec![Reading::bool(
+            DeviceId::light_at_room(state.room.clone()),
+            Metric::IsEnabled,
+            state.is_enabled,
+        )],
+        StateUpdate::Elisa(state) => registry
+            .devices_of_type(DeviceType::VacuumCleaner)
+            .flat_map(|config| {
+                let device = config.id();
+                let is_cleaned = state.rooms.is_empty() || state.rooms.contains(&config.room);
+
+                [
+                    Reading::number(device.clone(), Metric::Battery, state.battery_level.into()),
+                    Reading::bool(
+                        device.clone(),
+                        Metric::IsEnabled,
+                        state.is_enabled && is_cleaned,
+                    ),
+                    Reading::bool(device, Metric::IsPaused, state.is_paused && is_cleaned),
+                ]
+            })
+            .collect(),
+        StateUpdate::Unknown(_) => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use transport::{elisa, isabel, Room};
+
+    #[test]
+    fn test_vacuum_readings() {
+        let registry: Registry = include_str!("../../../conf/registry.toml").parse().unwrap();
+
+        let update = StateUpdate::Elisa(elisa::State {
+            battery_level: 80,
+            is_enabled: true,
+            is_paused: false,
+            work_speed: elisa::WorkSpeed::Standard,
+            cleanup_mode: elisa::CleanupMode::DryCleaning,
+            rooms: vec![Room::new("kitchen")],
+            consumables: vec![],
+        });
+
+        let readings = readings(&update, &registry);
+        let is_enabled = |room| {
+            readings
+                .iter()
+                .find(|reading| {
+                    reading.device == DeviceId::vacuum_cleaner_at_room(Room::new(room))
+                        && reading.metric == Metric::IsEnabled
+                })
+                .map(|reading| reading.value)
+        };
+
+        assert_eq!(readings.len(), 24);
+        assert_eq!(is_enabled("kitchen"), Some(Value::Bool(true)));
+        assert_eq!(is_enabled("bedroom"), Some(Value::Bool(false)));
+    }
+
+    #[test]
+    fn test_sensor_readings() {
+        let registry: Registry = include_str!("../../../conf/registry.toml").parse().unwrap();
+
+        let update = StateUpdate::Isabel(isabel::State {
+            room: Room::new("nursery"),
+            property: Property::TemperatureAndHumidity(22.5, 34.0),
+        });
+
+        let device = DeviceId::temperature_sensor_at_room(Room::new("nursery"));
+        assert_eq!(
+            readings(&update, &registry),
+            vec![
+                Reading::number(device.clone(), Metric::Temperature, 22.5),
+                Reading::number(device, Metric::Humidity, 34.0),
+            ]
+        );
+    }
+}
@@ -0,0 +1,365 @@
+//! Automation rules, loaded from a TOML file.
+//!
+//! ```toml
+//! [[rules]]
+//! name = "nursery_humidity"
+//! when = { type = "threshold", device = "temperature_sensor/nursery", value = "humidity", below = 35.0, hysteresis = 3.0 }
+//! during = { from = "08:00", to = "21:00" }
+//! then = [{ device = "recuperator/living_room", action = "set_fan_speed", speed = "low" }]
+//!
+//! [[rules]]
+//! name = "vacuum_finished"
+//! when = { type = "transition", device = "vacuum_cleaner/kitchen", value = "is_enabled", from = true, to = false }
+//! then = [{ device = "light/corridor", action = "turn_on" }]
+//! ```
+//!
+//! Schedules run their steps at cron times or around sunrise and sunset,
+//! the latter need the `location` of the home.
+//!
+//! ```toml
+//! location = { latitude = 54.6872, longitude = 25.2797 }
+//!
+//! [[schedules]]
+//! name = "weekday_vacuum"
+//! at = { cron = "0 30 9 * * Mon-Fri" }
+//! missed = "run_once"
+//! then = [{ device = "vacuum_cleaner/kitchen", action = "turn_on" }]
+//!
+//! [[schedules]]
+//! name = "corridor_at_dusk"
+//! at = { sun = "sunset", offset = -15 }
+//! then = [{ device = "light/corridor", action = "turn_on" }]
+//! ```
+//!
+//! `then` takes the same steps as registry scenes.
+
+use std::collections::HashSet;
+use std::path::Path;
+use std::str::FromStr;
+
+use chrono::NaiveTime;
+use serde::{de, Deserialize, Deserializer};
+use transport::registry::Registry;
+use transport::scene::Step;
+use transport::DeviceId;
+
+use crate::schedule::{Schedule, Trigger};
+use crate::sun::Location;
+use crate::{Error, Result};
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct Rules {
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+    #[serde(default)]
+    pub schedules: Vec<Schedule>,
+    pub location: Option<Location>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Rule {
+    pub name: String,
+    pub when: Condition,
+    pub during: Option<TimeWindow>,
+    pub then: Vec<Step>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Condition {
+    /// Fires once `value` crosses `above` or `below`, and again only after it
+    /// went back past the limit by `hysteresis`.
+    Threshold {
+        device: DeviceId,
+        value: Metric,
+        above: Option<f32>,
+        below: Option<f32>,
+        #[serde(default)]
+        hysteresis: f32,
+    },
+    /// Fires when `value` changes to `to`, optionally only coming from
+    /// `from`.
+    Transition {
+        device: DeviceId,
+        value: Metric,
+        from: Option<bool>,
+        to: bool,
+    },
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    Temperature,
+    TargetTemperature,
+    Humidity,
+    Battery,
+    IsEnabled,
+    IsPaused,
+}
+
+/// Local time of day the rule is allowed to fire at, `to` may be past
+/// midnight.
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
+pub struct TimeWindow {
+    #[serde(deserialize_with = "deserialize_time")]
+    pub from: NaiveTime,
+    #[serde(deserialize_with = "deserialize_time")]
+    pub to: NaiveTime,
+}
+
+impl Metric {
+    pub fn is_numeric(&self) -> bool {
+        !matches!(self, Metric::IsEnabled | Metric::IsPaused)
+    }
+}
+
+impl Condition {
+    pub fn device(&self) -> &DeviceId {
+        match self {
+            Condition::Threshold { device, .. } | Condition::Transition { device, .. } => device,
+        }
+    }
+}
+
+impl TimeWindow {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.from <= self.to {
+            self.from <= time && time < self.to
+        } else {
+            self.from <= time || time < self.to
+        }
+    }
+}
+
+impl Rules {
+    pub fn load<P: AsRef<Path>>(path: P, registry: &Registry) -> Result<Rules> {
+        let rules: Rules = std::fs::read_to_string(path)?.parse()?;
+        rules.validate(registry)?;
+
+        Ok(rules)
+    }
+
+    pub fn validate(&self, registry: &Registry) -> Result<()> {
+        let mut names = HashSet::new();
+
+        for rule in &self.rules {
+            let invalid = |reason| Err(Error::InvalidRule(rule.name.clone(), reason));
+
+            if !names.insert(&rule.name) {
+                return invalid("the name is used twice");
+            }
+
+            match &rule.when {
+                Condition::Threshold {
+                    value,
+                    above,
+                    below,
+                    hysteresis,
+                    ..
+                } => {
+                    if !value.is_numeric() {
+                        return invalid("thresholds need a numeric value");
+                    }
+
+                    if above.is_some() == below.is_some() {
+                        return invalid("thresholds need exactly one of above and below");
+                    }
+
+                    if *hysteresis < 0.0 {
+                        return invalid("hysteresis can't be negative");
+                    }
+                }
+                Condition::Transition { value, .. } => {
+                    if value.is_numeric() {
+                        return invalid("transitions need an is_enabled or is_paused value");
+                    }
+                }
+            }
+
+            if registry.device(rule.when.device()).is_none() {
+                return invalid("the condition refers to an undeclared device");
+            }
+
+            if let Err(reason) = validate_steps(&rule.then, registry) {
+                return invalid(reason);
+            }
+        }
+
+        let mut names = HashSet::new();
+
+        for schedule in &self.schedules {
+            let invalid = |reason| Err(Error::InvalidSchedule(schedule.name.clone(), reason));
+
+            if !names.insert(&schedule.name) {
+                return invalid("the name is used twice");
+            }
+
+            if matches!(schedule.at, Trigger::Sun { .. }) && self.location.is_none() {
+                return invalid("sun triggers need a location");
+            }
+
+            if let Err(reason) = validate_steps(&schedule.then, registry) {
+                return invalid(reason);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn validate_steps(steps: &[Step], registry: &Registry) -> std::result::Result<(), &'static str> {
+    if steps.is_empty() {
+        return Err("there are no steps");
+    }
+
+    for step in steps {
+        if registry.device(&step.device).is_none() {
+            return Err("a step refers to an undeclared device");
+        }
+
+        if !step.is_supported() {
+            return Err("a step is not supported by its device");
+        }
+
+        if step
+            .zone()
+            .is_some_and(|zone| registry.zone(zone).is_none())
+        {
+            return Err("a step refers to an undeclared zone");
+        }
+    }
+
+    Ok(())
+}
+
+impl FromStr for Rules {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(toml::from_str(s)?)
+    }
+}
+
+pub(crate) fn deserialize_time<'de, D>(deserializer: D) -> std::result::Result<NaiveTime, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = String::deserialize(deserializer)?;
+
+    NaiveTime::parse_from_str(&value, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(&value, "%H:%M"))
+        .map_err(|_| de::Error::invalid_value(de::Unexpected::Str(&value), &"HH:MM time"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> Registry {
+        include_str!("../../../conf/registry.toml").parse().unwrap()
+    }
+
+    #[test]
+    fn test_sample_rules() {
+        let rules: Rules = include_str!("../../../conf/rules.toml").parse().unwrap();
+
+        rules.validate(&registry()).unwrap();
+        assert_eq!(rules.rules.len(), 3);
+        assert_eq!(rules.schedules.len(), 3);
+    }
+
+    #[test]
+    fn test_time_window() {
+        let time = |s| NaiveTime::parse_from_str(s, "%H:%M").unwrap();
+
+        let day = TimeWindow {
+            from: time("08:00"),
+            to: time("21:00"),
+        };
+        assert!(day.contains(time("08:00")));
+        assert!(day.contains(time("20:59")));
+        assert!(!day.contains(time("21:00")));
+        assert!(!day.contains(time("03:00")));
+
+        let night = TimeWindow {
+            from: time("22:00"),
+            to: time("07:00"),
+        };
+        assert!(night.contains(time("23:30")));
+        assert!(night.contains(time("03:00")));
+        assert!(!night.contains(time("07:00")));
+        assert!(!night.contains(time("12:00")));
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        let registry = registry();
+
+        let both_limits = r#"
+            [[rules]]
+            name = "humidity"
+            when = { type = "threshold", device = "temperature_sensor/nursery", value = "humidity", above = 60.0, below = 35.0 }
+            then = [{ device = "light/nursery", action = "turn_on" }]
+        "#;
+
+        let numeric_transition = r#"
+            [[rules]]
+            name = "humidity"
+            when = { type = "transition", device = "temperature_sensor/nursery", value = "humidity", to = true }
+            then = [{ device = "light/nursery", action = "turn_on" }]
+        "#;
+
+        let unknown_device = r#"
+            [[rules]]
+            name = "humidity"
+            when = { type = "threshold", device = "temperature_sensor/toilet", value = "humidity", below = 35.0 }
+            then = [{ device = "light/nursery", action = "turn_on" }]
+        "#;
+
+        for config in [both_limits, numeric_transition, unknown_device] {
+            let rules: Rules = config.parse().unwrap();
+
+            assert!(matches!(
+                rules.validate(&registry),
+                Err(Error::InvalidRule(name, _)) if name == "humidity"
+            ));
+        }
+    }
+
+    #[test]
+    fn test_invalid_schedules() {
+        let registry = registry();
+
+        let no_location = r#"
+            [[schedules]]
+            name = "dusk"
+            at = { sun = "sunset" }
+            then = [{ device = "light/corridor", action = "turn_on" }]
+        "#;
+
+        let unknown_device = r#"
+            [[schedules]]
+            name = "dusk"
+            at = { cron = "0 0 20 * * *" }
+            then = [{ device = "light/toilet", action = "turn_on" }]
+        "#;
+
+        for config in [no_location, unknown_device] {
+            let rules: Rules = config.parse().unwrap();
+
+            assert!(matches!(
+                rules.validate(&registry),
+                Err(Error::InvalidSchedule(name, _)) if name == "dusk"
+            ));
+        }
+
+        let invalid_cron = r#"
+            [[schedules]]
+            name = "dusk"
+            at = { cron = "at dusk" }
+            then = [{ device = "light/corridor", action = "turn_on" }]
+        "#;
+        assert!(invalid_cron.parse::<Rules>().is_err());
+    }
+}
@@ -0,0 +1,297 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use log::{info, warn};
+use serde::{de, Deserialize, Deserializer};
+use transport::scene::Step;
+
+use crate::sun::{sun_event, Location, SunEvent};
+use crate::Result;
+
+/// How late a run may be before it counts as missed, e.g. while the service
+/// was down.
+const MISSED_AFTER: Duration = Duration::minutes(2);
+
+/// How many days ahead to look for a sun event, covering short polar nights.
+const SUN_SEARCH_DAYS: i64 = 7;
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Schedule {
+    pub name: String,
+    pub at: Trigger,
+    #[serde(default)]
+    pub missed: Missed,
+    pub then: Vec<Step>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum Trigger {
+    /// A cron expression with seconds, evaluated in local time:
+    /// `"0 30 9 * * Mon-Fri"`.
+    Cron {
+        #[serde(deserialize_with = "deserialize_cron")]
+        cron: Box<cron::Schedule>,
+    },
+    /// Sunrise or sunset at the configured location, shifted by `offset`
+    /// minutes.
+    Sun {
+        sun: SunEvent,
+        #[serde(default)]
+        offset: i64,
+    },
+}
+
+/// What to do with runs that passed while elise wasn't running.
+#[derive(Copy, Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Missed {
+    #[default]
+    Skip,
+    /// Runs once no matter how many runs were missed.
+    RunOnce,
+}
+
+impl Trigger {
+    /// The first run strictly after `after`.
+    pub fn next_after<Tz: TimeZone>(
+        &self,
+        after: &DateTime<Tz>,
+        location: Option<Location>,
+    ) -> Option<DateTime<Tz>> {
+        match self {
+            Trigger::Cron { cron } => cron.after(after).next(),
+            Trigger::Sun { sun, offset } => {
+                let location = location?;
+                let date = after.with_timezone(&Utc).date_naive();
+
+                (-1..SUN_SEARCH_DAYS)
+                    .filter_map(|days| sun_event(date + Duration::days(days), location, *sun))
+                    .map(|time| time + Duration::minutes(*offset))
+                    .find(|time| time > after)
+                    .map(|time| time.with_timezone(&after.timezone()))
+            }
+        }
+    }
+}
+
+/// Decides which schedules are due, remembering when each of them last ran.
+pub struct Scheduler {
+    schedules: Vec<Schedule>,
+    location: Option<Location>,
+    last_runs: HashMap<String, DateTime<Utc>>,
+}
+
+impl Scheduler {
+    pub fn new(schedules: Vec<Schedule>, location: Option<Location>) -> Scheduler {
+        Scheduler {
+            schedules,
+            location,
+            last_runs: HashMap::new(),
+        }
+    }
+
+    /// Replaces the schedules, keeping the last runs of the ones that stay.
+    pub fn reload(&mut self, schedules: Vec<Schedule>, location: Option<Location>) {
+        self.last_runs
+            .retain(|name, _| schedules.iter().any(|schedule| &schedule.name == name));
+
+        self.schedules = schedules;
+        self.location = location;
+    }
+
+    /// Schedules due at `now`. A schedule seen for the first time starts
+    /// counting from `now`, runs missed by more than `MISSED_AFTER` follow
+    /// the schedule's policy.
+    pub fn due<Tz: TimeZone>(&mut self, now: &DateTime<Tz>) -> Vec<&Schedule> {
+        let mut due = vec![];
+
+        for schedule in &self.schedules {
+            let last = *self
+                .last_runs
+                .entry(schedule.name.clone())
+                .or_insert_with(|| now.with_timezone(&Utc));
+
+            let last = last.with_timezone(&now.timezone());
+            let Some(next) = schedule.at.next_after(&last, self.location) else {
+                continue;
+            };
+
+            if next > *now {
+                continue;
+            }
+
+            self.last_runs
+                .insert(schedule.name.clone(), now.with_timezone(&Utc));
+
+            if now.clone() - next.clone() > MISSED_AFTER {
+                match schedule.missed {
+                    Missed::Skip => {
+                        warn!(
+                            "skipping {} missed at {}",
+                            schedule.name,
+                            next.naive_local()
+                        );
+                        continue;
+                    }
+                    Missed::RunOnce => {
+                        info!("running {} missed at {}", schedule.name, next.naive_local())
+                    }
+                }
+            }
+
+            due.push(schedule);
+        }
+
+        due
+    }
+
+    /// Restores the last runs saved by `save`, a missing file means nothing
+    /// ran yet.
+    pub fn restore<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let data = match std::fs::read(path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+
+        let last_runs: HashMap<String, i64> = serde_json::from_slice(&data)?;
+
+        self.last_runs = last_runs
+            .into_iter()
+            .filter_map(|(name, secs)| Some((name, DateTime::from_timestamp(secs, 0)?)))
+            .collect();
+
+        Ok(())
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let last_runs: HashMap<&String, i64> = self
+            .last_runs
+            .iter()
+            .map(|(name, time)| (name, time.timestamp()))
+            .collect();
+
+        std::fs::write(path, serde_json::to_vec(&last_runs)?)?;
+
+        Ok(())
+    }
+}
+
+fn deserialize_cron<'de, D>(deserializer: D) -> std::result::Result<Box<cron::Schedule>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = String::deserialize(deserializer)?;
+
+    value
+        .parse()
+        .map(Box::new)
+        .map_err(|_| de::Error::invalid_value(de::Unexpected::Str(&value), &"cron expression"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Rules;
+
+    const VILNIUS: Location = Location {
+        latitude: 54.6872,
+        longitude: 25.2797,
+    };
+
+    fn time(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    fn schedule(at: &str, missed: &str) -> Schedule {
+        let config = format!(
+            r#"
+            [[schedules]]
+            name = "morning"
+            at = {at}
+            missed = "{missed}"
+            then = [{{ device = "light/corridor", action = "turn_on" }}]
+            "#
+        );
+
+        let rules: Rules = config.parse().unwrap();
+        rules.schedules.into_iter().next().unwrap()
+    }
+
+    #[test]
+    fn test_cron_next() {
+        let trigger = schedule(r#"{ cron = "0 30 9 * * Mon-Fri" }"#, "skip").at;
+
+        assert_eq!(
+            trigger.next_after(&time("2024-06-21T10:00:00Z"), None),
+            Some(time("2024-06-24T09:30:00Z"))
+        );
+    }
+
+    #[test]
+    fn test_sun_next() {
+        let trigger = schedule(r#"{ sun = "sunset", offset = -30 }"#, "skip").at;
+
+        let next = trigger
+            .next_after(&time("2024-06-21T12:00:00Z"), Some(VILNIUS))
+            .unwrap();
+        assert_eq!(next.date_naive(), time("2024-06-21T00:00:00Z").date_naive());
+        assert!((next - time("2024-06-21T18:30:00Z")).num_minutes().abs() <= 2);
+
+        let next = trigger
+            .next_after(&time("2024-06-21T20:00:00Z"), Some(VILNIUS))
+            .unwrap();
+        assert_eq!(next.date_naive(), time("2024-06-22T00:00:00Z").date_naive());
+
+        assert_eq!(
+            trigger.next_after(&time("2024-06-21T12:00:00Z"), None),
+            None
+        );
+    }
+
+    #[test]
+    fn test_due() {
+        let mut scheduler =
+            Scheduler::new(vec![schedule(r#"{ cron = "0 30 9 * * *" }"#, "skip")], None);
+
+        assert!(scheduler.due(&time("2024-06-21T09:00:00Z")).is_empty());
+        assert!(scheduler.due(&time("2024-06-21T09:29:59Z")).is_empty());
+        assert_eq!(scheduler.due(&time("2024-06-21T09:30:30Z")).len(), 1);
+        assert!(scheduler.due(&time("2024-06-21T09:31:00Z")).is_empty());
+    }
+
+    #[test]
+    fn test_missed_runs() {
+        let cron = r#"{ cron = "0 30 9 * * *" }"#;
+
+        let mut skip = Scheduler::new(vec![schedule(cron, "skip")], None);
+        let mut run_once = Scheduler::new(vec![schedule(cron, "run_once")], None);
+
+        for scheduler in [&mut skip, &mut run_once] {
+            scheduler
+                .last_runs
+                .insert("morning".to_string(), time("2024-06-19T08:00:00Z"));
+        }
+
+        let now = time("2024-06-21T12:00:00Z");
+        assert!(skip.due(&now).is_empty());
+        assert_eq!(run_once.due(&now).len(), 1);
+        assert!(run_once.due(&now).is_empty());
+    }
+
+    #[test]
+    fn test_reload_keeps_last_runs() {
+        let morning = schedule(r#"{ cron = "0 30 9 * * *" }"#, "skip");
+        let mut scheduler = Scheduler::new(vec![morning.clone()], None);
+
+        assert!(scheduler.due(&time("2024-06-21T09:00:00Z")).is_empty());
+
+        scheduler.reload(vec![morning], None);
+        assert_eq!(scheduler.due(&time("2024-06-21T09:30:00Z")).len(), 1);
+
+        scheduler.reload(vec![], None);
+        assert!(scheduler.last_runs.is_empty());
+    }
+}
@@ -0,0 +1,120 @@
+//! Sunrise and sunset times, after the sunrise equation used by NOAA.
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Deserialize;
+
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
+pub struct Location {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SunEvent {
+    Sunrise,
+    Sunset,
+}
+
+const J2000: f64 = 2451545.0;
+const UNIX_EPOCH: f64 = 2440587.5;
+
+/// Time of `event` on `date`, `None` during polar day or night.
+pub fn sun_event(date: NaiveDate, location: Location, event: SunEvent) -> Option<DateTime<Utc>> {
+    let days = date
+        .signed_duration_since(NaiveDate::from_ymd_opt(2000, 1, 1)?)
+        .num_days() as f64;
+
+    let mean_solar_noon = days - location.longitude / 360.0;
+
+    let anomaly = (357.5291 + 0.98560028 * mean_solar_noon).rem_euclid(360.0);
+    let anomaly_rad = anomaly.to_radians();
+
+    let center = 1.9148 * anomaly_rad.sin()
+        + 0.0200 * (2.0 * anomaly_rad).sin()
+        + 0.0003 * (3.0 * anomaly_rad).sin();
+
+    let ecliptic_longitude = (anomaly + center + 180.0 + 102.9372)
+        .rem_euclid(360.0)
+        .to_radians();
+
+    let transit = J2000 + mean_solar_noon + 0.0053 * anomaly_rad.sin()
+        - 0.0069 * (2.0 * ecliptic_longitude).sin();
+
+    let declination_sin = ecliptic_longitude.sin() * 23.4397f64.to_radians().sin();
+    let declination_cos = declination_sin.asin().cos();
+
+    let latitude = location.latitude.to_radians();
+    let hour_angle_cos = ((-0.833f64).to_radians().sin() - latitude.sin() * declination_sin)
+        / (latitude.cos() * declination_cos);
+
+    if !(-1.0..=1.0).contains(&hour_angle_cos) {
+        return None;
+    }
+
+    let hour_angle = hour_angle_cos.acos().to_degrees() / 360.0;
+
+    let julian = match event {
+        SunEvent::Sunrise => transit - hour_angle,
+        SunEvent::Sunset => transit + hour_angle,
+    };
+
+    let millis = ((julian - UNIX_EPOCH) * 86_400_000.0).round() as i64;
+    DateTime::from_timestamp_millis(millis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VILNIUS: Location = Location {
+        latitude: 54.6872,
+        longitude: 25.2797,
+    };
+
+    fn assert_close(actual: DateTime<Utc>, expected: &str) {
+        let expected = DateTime::parse_from_rfc3339(expected).unwrap();
+        let diff = (actual - expected.with_timezone(&Utc)).num_minutes().abs();
+
+        assert!(diff <= 2, "{actual} is {diff} minutes off {expected}");
+    }
+
+    #[test]
+    fn test_summer_solstice() {
+        let date = NaiveDate::from_ymd_opt(2024, 6, 21).unwrap();
+
+        assert_close(
+            sun_event(date, VILNIUS, SunEvent::Sunrise).unwrap(),
+            "2024-06-21T04:42:00+03:00",
+        );
+        assert_close(
+            sun_event(date, VILNIUS, SunEvent::Sunset).unwrap(),
+            "2024-06-21T22:00:00+03:00",
+        );
+    }
+
+    #[test]
+    fn test_winter_solstice() {
+        let date = NaiveDate::from_ymd_opt(2024, 12, 21).unwrap();
+
+        assert_close(
+            sun_event(date, VILNIUS, SunEvent::Sunrise).unwrap(),
+            "2024-12-21T08:40:00+02:00",
+        );
+        assert_close(
+            sun_event(date, VILNIUS, SunEvent::Sunset).unwrap(),
+            "2024-12-21T15:54:00+02:00",
+        );
+    }
+
+    #[test]
+    fn test_polar_night() {
+        let tromso = Location {
+            latitude: 69.6492,
+            longitude: 18.9553,
+        };
+        let date = NaiveDate::from_ymd_opt(2024, 12, 21).unwrap();
+
+        assert!(sun_event(date, tromso, SunEvent::Sunrise).is_none());
+    }
+}
@@ -1,5 +1,7 @@
 use std::fmt;
 
+use transport::action::{ActionError, ErrorKind};
+
 #[derive(Debug)]
 pub enum Error {
     Sonoff(sonoff::Error),
@@ -9,6 +11,7 @@ pub enum Error {
     Timeout(tokio::time::error::Elapsed),
     Io(std::io::Error),
     UnknownDevice,
+    Registry(transport::registry::Error),
 }
 
 impl From<sonoff::Error> for Error {
@@ -47,6 +50,12 @@ impl From<std::io::Error> for Error {
     }
 }
 
+impl From<transport::registry::Error> for Error {
+    fn from(err: transport::registry::Error) -> Self {
+        Self::Registry(err)
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -57,8 +66,34 @@ impl fmt::Display for Error {
             Self::Timeout(err) => write!(f, "timeout error: {err}"),
             Self::Io(err) => write!(f, "io error: {err}"),
             Self::UnknownDevice => write!(f, "unknown device"),
+            Self::Registry(err) => write!(f, "registry error: {err}"),
         }
     }
 }
 
 impl std::error::Error for Error {}
+
+impl From<&Error> for ActionError {
+    fn from(err: &Error) -> Self {
+        let kind = match err {
+            Error::Timeout(_) => ErrorKind::Timeout,
+            Error::UnknownDevice | Error::Sonoff(sonoff::Error::UnknownDevice(_)) => {
+                ErrorKind::InvalidValue
+            }
+            _ => ErrorKind::DeviceUnreachable,
+        };
+
+        ActionError::new(kind, err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_action_error() {
+        let err = Error::Sonoff(sonoff::Error::UnknownDevice("1002074ed2".to_string()));
+        assert_eq!(ActionError::from(&err).kind, ErrorKind::InvalidValue);
+    }
+}
@@ -1,29 +1,39 @@
 use std::collections::HashMap;
+use std::sync::Arc;
 
 use sonoff::{Client, SonoffDevice};
 use transport::{
-    action::{ActionRequest, ActionResponse, ActionResult},
+    action::{ActionError, ActionRequest, ActionResponse, ActionResult},
     elisheba::{Action, State},
+    registry::Registry,
+    rpc,
     state::{StateRequest, StateResponse},
     DeviceType, Room,
 };
 
-use log::{debug, error, info};
-use paho_mqtt::{AsyncClient as MqClient, Message, MessageBuilder, PropertyCode};
+use log::{debug, error, info, warn};
+use paho_mqtt::{AsyncClient as MqClient, Message};
 
 mod error;
 pub use error::Error;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
-#[derive(Default)]
 pub struct Storage {
     lights: HashMap<Room, State>,
+    registry: Arc<Registry>,
 }
 
 impl Storage {
+    pub fn new(registry: Arc<Registry>) -> Self {
+        Self {
+            lights: HashMap::new(),
+            registry,
+        }
+    }
+
     pub fn apply(&mut self, device: &SonoffDevice) -> Option<State> {
-        let state = map_device_to_state(device)?;
+        let state = map_device_to_state(device, &self.registry)?;
 
         info!(
             "ligths at {room} are toggled {state}",
@@ -31,14 +41,14 @@ impl Storage {
             state = if state.is_enabled { "on" } else { "off" }
         );
 
-        self.lights.insert(state.room, state);
+        self.lights.insert(state.room.clone(), state.clone());
 
         Some(state)
     }
 }
 
-fn map_device_to_state(device: &SonoffDevice) -> Option<State> {
-    let room = map_id_to_room(&device.id)?;
+fn map_device_to_state(device: &SonoffDevice, registry: &Registry) -> Option<State> {
+    let room = registry.room_for_sonoff_id(&device.id)?.clone();
     let switch = device.meta["switches"][0]["switch"].as_str()?;
     let is_enabled = match switch {
         "on" => true,
@@ -52,61 +62,48 @@ fn map_device_to_state(device: &SonoffDevice) -> Option<State> {
     Some(State { is_enabled, room })
 }
 
-pub async fn handle_action_request(msg: Message, mqtt: &mut MqClient, sonoff: &mut Client) {
-    let request: ActionRequest = match serde_json::from_slice(msg.payload()) {
-        Ok(ids) => ids,
-        Err(err) => {
-            error!("unable to parse request: {}", err);
-            error!("{}", msg.payload_str());
-            return;
-        }
-    };
-
-    let response_topic = match msg.properties().get_string(PropertyCode::ResponseTopic) {
-        Some(topic) => topic,
-        None => {
-            error!("missing response topic");
-            return;
-        }
+pub async fn handle_action_request(
+    msg: Message,
+    mqtt: &MqClient,
+    sonoff: &mut Client,
+    registry: &Registry,
+) {
+    let (request, responder) = match rpc::serve::<ActionRequest>(&msg, mqtt) {
+        Some(request) => request,
+        None => return,
     };
 
     for action in request.actions {
-        if let transport::action::Action::Elisheba(action, action_id) = action {
-            let result = match update_state(action, sonoff).await {
-                Ok(_) => ActionResult::Success,
-                Err(err) => {
-                    error!("Error updating state: {}", err);
-                    ActionResult::Failure
-                }
-            };
-
-            let response = ActionResponse { action_id, result };
-
-            debug!("publish to {}: {:?}", response_topic, response);
-
-            let payload = serde_json::to_vec(&response).unwrap();
-
-            let message = MessageBuilder::new()
-                .topic(&response_topic)
-                .payload(payload)
-                .finalize();
-
-            match mqtt.publish(message).await {
-                Ok(()) => (),
-                Err(err) => {
-                    error!("Error sending response to {}: {}", response_topic, err);
-                }
+        match action {
+            transport::action::Action::Elisheba(action, action_id) => {
+                let result = match update_state(action, sonoff, registry).await {
+                    Ok(_) => ActionResult::Success,
+                    Err(err) => {
+                        error!("Error updating state: {}", err);
+                        ActionResult::Failure(ActionError::from(&err))
+                    }
+                };
+
+                responder
+                    .respond(&ActionResponse { action_id, result })
+                    .await;
+            }
+            transport::action::Action::Unknown(action) => {
+                warn!("skipping unknown action {action}");
             }
+            _ => (),
         }
     }
 }
 
-async fn update_state(action: Action, sonoff: &mut Client) -> Result<()> {
+async fn update_state(action: Action, sonoff: &mut Client, registry: &Registry) -> Result<()> {
     let state = if action.is_enabled { "on" } else { "off" };
 
     info!("wants to toggle {state} lights at {}", action.room);
 
-    let device_id = map_room_to_id(action.room).ok_or(Error::UnknownDevice)?;
+    let device_id = registry
+        .sonoff_id_for_room(&action.room)
+        .ok_or(Error::UnknownDevice)?;
     sonoff.update_state(device_id, action.is_enabled).await?;
 
     info!("successfully toggled {state} at {}", action.room);
@@ -114,22 +111,15 @@ async fn update_state(action: Action, sonoff: &mut Client) -> Result<()> {
     Ok(())
 }
 
-pub async fn handle_state_request(msg: Message, mqtt: &mut MqClient, sonoff: &mut Client) {
-    let request: StateRequest = match serde_json::from_slice(msg.payload()) {
-        Ok(ids) => ids,
-        Err(err) => {
-            error!("unable to parse request: {}", err);
-            error!("{}", msg.payload_str());
-            return;
-        }
-    };
-
-    let response_topic = match msg.properties().get_string(PropertyCode::ResponseTopic) {
-        Some(topic) => topic,
-        None => {
-            error!("missing response topic");
-            return;
-        }
+pub async fn handle_state_request(
+    msg: Message,
+    mqtt: &MqClient,
+    sonoff: &mut Client,
+    registry: &Registry,
+) {
+    let (request, responder) = match rpc::serve::<StateRequest>(&msg, mqtt) {
+        Some(request) => request,
+        None => return,
     };
 
     let ids = request
@@ -140,7 +130,7 @@ pub async fn handle_state_request(msg: Message, mqtt: &mut MqClient, sonoff: &mu
     debug!("ids: {:?}", ids.clone().collect::<Vec<_>>());
 
     for id in ids {
-        let state = match get_state(id.room, sonoff).await {
+        let state = match get_state(&id.room, sonoff, registry).await {
             Ok(state) => state,
             Err(err) => {
                 error!("Error getting state for {id}: {}", err);
@@ -148,44 +138,15 @@ pub async fn handle_state_request(msg: Message, mqtt: &mut MqClient, sonoff: &mu
             }
         };
 
-        debug!("publish to {}: {:?}", response_topic, state);
-
-        let response = StateResponse::Elisheba(state);
-        let payload = serde_json::to_vec(&response).unwrap();
-
-        let message = MessageBuilder::new()
-            .topic(&response_topic)
-            .payload(payload)
-            .finalize();
-
-        match mqtt.publish(message).await {
-            Ok(()) => (),
-            Err(err) => {
-                error!("Error sending response to {}: {}", response_topic, err);
-            }
-        }
+        responder.respond(&StateResponse::Elisheba(state)).await;
     }
 }
 
-async fn get_state(room: Room, sonoff: &mut Client) -> Result<State> {
-    let device_id = map_room_to_id(room).ok_or(Error::UnknownDevice)?;
+async fn get_state(room: &Room, sonoff: &mut Client, registry: &Registry) -> Result<State> {
+    let device_id = registry
+        .sonoff_id_for_room(room)
+        .ok_or(Error::UnknownDevice)?;
     let device = sonoff.get_state(device_id).await?;
-    let state = map_device_to_state(&device).ok_or(Error::UnknownDevice)?;
+    let state = map_device_to_state(&device, registry).ok_or(Error::UnknownDevice)?;
     Ok(state)
 }
-
-fn map_id_to_room(id: &str) -> Option<Room> {
-    match id {
-        "1002074ed2" => Some(Room::Corridor),
-        "10020750eb" => Some(Room::Nursery),
-        _ => None,
-    }
-}
-
-fn map_room_to_id(room: Room) -> Option<&'static str> {
-    match room {
-        Room::Corridor => Some("1002074ed2"),
-        Room::Nursery => Some("10020750eb"),
-        _ => None,
-    }
-}
@@ -1,18 +1,19 @@
 use std::collections::HashMap;
-use std::ops::ControlFlow;
+use std::sync::Arc;
 use std::time::Duration;
 
 use crypto::Token;
 use elisheba::{handle_action_request, handle_state_request, Result, Storage};
 use sonoff::{Client, SonoffDevice};
+use transport::availability::heartbeat;
 use transport::elisheba::State;
-use transport::state::StateUpdate;
-use transport::{connect_mqtt, Topic};
+use transport::registry::Registry;
+use transport::state::{StateResponse, StateUpdate};
+use transport::{DeviceId, MqttSession, Topic};
 
-use futures_util::StreamExt;
 use log::{error, info, trace};
-use paho_mqtt::{AsyncClient as MqClient, MessageBuilder, QOS_1};
-use tokio::{task, time};
+use paho_mqtt::AsyncClient as MqClient;
+use tokio::task;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -22,6 +23,9 @@ async fn main() -> Result<()> {
 
     info!("elisheba version {VERSION}");
 
+    let registry_path = std::env::var("REGISTRY_PATH").expect("set ENV variable REGISTRY_PATH");
+    let registry = Arc::new(Registry::load(registry_path)?);
+
     let keys = std::env::var("KEYS").expect("set ENV variable KEYS");
     let keys = parse_keys_string(keys);
 
@@ -33,13 +37,17 @@ async fn main() -> Result<()> {
     let mqtt_address = std::env::var("MQTT_ADDRESS").expect("set ENV variable MQTT_ADDRESS");
     let mqtt_username = std::env::var("MQTT_USER").expect("set ENV variable MQTT_USER");
     let mqtt_password = std::env::var("MQTT_PASS").expect("set ENV variable MQTT_PASS");
-    let mqtt_client = connect_mqtt(mqtt_address, mqtt_username, mqtt_password, "elisheba").await?;
+    let mqtt_session =
+        MqttSession::connect(mqtt_address, mqtt_username, mqtt_password, "elisheba").await?;
+    let mqtt_client = mqtt_session.client().clone();
     info!("connected mqtt");
 
-    let mut storage = Storage::default();
+    task::spawn(heartbeat(mqtt_client.clone(), "elisheba", VERSION));
+
+    let mut storage = Storage::new(registry.clone());
     send_initial_state(devices, &mut storage, mqtt_client.clone()).await?;
 
-    let set_handle = task::spawn(subscribe_action(mqtt_client.clone(), client.clone()));
+    let set_handle = task::spawn(subscribe_action(mqtt_session, client.clone(), registry));
     let state_handle = task::spawn(subscribe_state(mqtt_client, storage, client));
 
     try_join(set_handle, state_handle).await?;
@@ -59,61 +67,23 @@ async fn try_join(
     Ok(())
 }
 
-async fn subscribe_action(mut mqtt: MqClient, mut sonoff: Client) -> Result<()> {
-    let mut stream = mqtt.get_stream(None);
-
-    let topics = [
-        Topic::ActionRequest.to_string(),
-        Topic::StateRequest.to_string(),
-    ];
-
-    mqtt.subscribe_many(&topics, &[QOS_1, QOS_1]).await?;
-    info!("Subscribed to topis: {:?}", topics);
+async fn subscribe_action(
+    mut session: MqttSession,
+    mut sonoff: Client,
+    registry: Arc<Registry>,
+) -> Result<()> {
+    let mqtt = session.client().clone();
 
-    while let Some(msg_opt) = stream.next().await {
-        trace!("got message {:?}", msg_opt);
+    session.subscribe(Topic::ActionRequest).await?;
+    session.subscribe(Topic::StateRequest).await?;
 
-        if let Some(msg) = msg_opt {
-            let topic = if let Ok(value) = msg.topic().parse() {
-                value
-            } else {
-                continue;
-            };
+    while let Some((topic, msg)) = session.next().await {
+        trace!("got message {:?}", msg);
 
-            match topic {
-                Topic::ActionRequest => handle_action_request(msg, &mut mqtt, &mut sonoff).await,
-                Topic::StateRequest => handle_state_request(msg, &mut mqtt, &mut sonoff).await,
-                _ => (),
-            }
-        } else {
-            time::sleep(Duration::from_secs(1)).await;
-            error!("Lost MQTT connection. Attempting reconnect.");
-
-            loop {
-                match time::timeout(Duration::from_secs(10), mqtt.reconnect()).await {
-                    Ok(Ok(response)) => {
-                        info!("Reconnected to MQTT! {}", response.reason_code());
-
-                        let topics = [
-                            Topic::ActionRequest.to_string(),
-                            Topic::StateRequest.to_string(),
-                        ];
-
-                        mqtt.subscribe_many(&topics, &[QOS_1, QOS_1]).await?;
-                        info!("Subscribed to topis: {:?}", topics);
-
-                        break;
-                    }
-                    Ok(Err(err)) => {
-                        error!("Error MQTT reconnecting: {}", err);
-                        time::sleep(Duration::from_secs(5)).await;
-                    }
-                    Err(err) => {
-                        error!("Error MQTT reconnecting: {}", err);
-                        time::sleep(Duration::from_secs(5)).await;
-                    }
-                }
-            }
+        match topic {
+            Topic::ActionRequest => handle_action_request(msg, &mqtt, &mut sonoff, &registry).await,
+            Topic::StateRequest => handle_state_request(msg, &mqtt, &mut sonoff, &registry).await,
+            _ => (),
         }
     }
 
@@ -132,9 +102,7 @@ async fn send_initial_state(
             continue;
         };
 
-        if let ControlFlow::Break(_) = publish_state(state, &mqtt).await {
-            continue;
-        }
+        publish_state(state, &mqtt).await;
     }
 
     Ok(())
@@ -150,9 +118,7 @@ async fn subscribe_state(mqtt: MqClient, mut storage: Storage, mut sonoff: Clien
                     continue;
                 };
 
-                if let ControlFlow::Break(_) = publish_state(state, &mqtt).await {
-                    continue;
-                }
+                publish_state(state, &mqtt).await;
             }
             Err(sonoff::Error::Disconnected) => {
                 error!("Lost mDNS connection. Attempting reconnect.");
@@ -173,26 +139,14 @@ async fn subscribe_state(mqtt: MqClient, mut storage: Storage, mut sonoff: Clien
     }
 }
 
-async fn publish_state(state: State, mqtt: &MqClient) -> ControlFlow<()> {
-    let update = StateUpdate::Elisheba(state);
-    let payload = match serde_json::to_vec(&update) {
-        Ok(payload) => payload,
-        Err(err) => {
-            error!("Error serializing state update: {err}");
-            return ControlFlow::Break(());
-        }
-    };
-    let topic = Topic::StateUpdate;
-    let message = MessageBuilder::new()
-        .topic(topic.to_string())
-        .payload(payload)
-        .finalize();
-    match mqtt.publish(message).await {
-        Ok(()) => (),
-        Err(err) => error!("Error publishing state update: {err}"),
-    };
-
-    ControlFlow::Continue(())
+async fn publish_state(state: State, mqtt: &MqClient) {
+    let device_id = DeviceId::light_at_room(state.room.clone());
+    let update = StateUpdate::Elisheba(state.clone());
+    let state = StateResponse::Elisheba(state);
+
+    if let Err(err) = transport::publish_state(mqtt, &update, &state, &[device_id]).await {
+        error!("Error publishing state update: {err}");
+    }
 }
 
 fn parse_keys_string(string: String) -> HashMap<String, Token<16>> {
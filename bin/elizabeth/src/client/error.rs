@@ -5,6 +5,7 @@ use transport::{DeviceType, Room};
 #[derive(Debug)]
 pub enum Error {
     UnsupportedDevice(String),
+    UnknownDevice(DeviceType, Room),
     MissingCapability(&'static str, DeviceType, Room),
     MissingPort(&'static str, DeviceType, Room),
 }
@@ -13,6 +14,10 @@ impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Error::UnsupportedDevice(id) => f.write_fmt(format_args!("unsupported device {}", id)),
+            Error::UnknownDevice(device_type, room) => f.write_fmt(format_args!(
+                "unknown device {} in room {}",
+                device_type, room
+            )),
             Error::MissingCapability(capability, device_type, room) => f.write_fmt(format_args!(
                 "missing capability `{}` for device {} in room {}",
                 capability, device_type, room
@@ -19,7 +19,8 @@ use inspinia::{
     RegisterMessage, UpdateMessageContent, UpdateStateMessage, WsClient,
 };
 use transport::elizabeth::{self, Capability, State};
-use transport::DeviceType;
+use transport::registry::{Backend, Registry};
+use transport::{DeviceId, DeviceType, Room};
 
 #[derive(Clone)]
 pub struct Client {
@@ -30,10 +31,16 @@ pub struct Client {
     initial_state: Vec<PortState>,
     storage: Arc<Mutex<Storage>>,
     logs_path: PathBuf,
+    registry: Arc<Registry>,
 }
 
 impl Client {
-    pub async fn new(client_id: String, token: String, logs_path: PathBuf) -> Result<Client> {
+    pub async fn new(
+        client_id: String,
+        token: String,
+        logs_path: PathBuf,
+        registry: Arc<Registry>,
+    ) -> Result<Client> {
         let target_id = token_as_uuid(format!("{:x}", md5::compute(token)));
         let db_path = download_template(&target_id).await?;
 
@@ -45,7 +52,7 @@ impl Client {
         let storage = Storage::new();
 
         for state in &initial_state {
-            if let Some(state) = Self::parse_initial_state(state, &db_path) {
+            if let Some(state) = Self::parse_initial_state(state, &db_path, &registry) {
                 storage.apply_state(&state).await;
             }
         }
@@ -60,6 +67,7 @@ impl Client {
             initial_state,
             storage,
             logs_path,
+            registry,
         })
     }
 
@@ -118,9 +126,12 @@ impl Client {
         while let Some(state) = self.initial_state.pop() {
             trace!("found initial state {:?} {:?}", state.id, state.value);
 
-            if let Some(update) = Self::parse_initial_state(&state, &self.db_path) {
+            if let Some(update) = Self::parse_initial_state(&state, &self.db_path, &self.registry) {
                 trace!("prepared update {:?}", update);
 
+                let storage = self.storage.lock().await;
+                storage.apply_state(&update).await;
+
                 return Ok(update);
             }
         }
@@ -139,9 +150,12 @@ impl Client {
                             let update: UpdateMessageContent =
                                 serde_json::from_value(message).expect("valid update");
 
-                            if let Ok(update) =
-                                Self::state_payload(&update.id, &update.value, &self.db_path)
-                            {
+                            if let Ok(update) = Self::state_payload(
+                                &update.id,
+                                &update.value,
+                                &self.db_path,
+                                &self.registry,
+                            ) {
                                 let storage = self.storage.lock().await;
                                 storage.apply_state(&update).await;
                                 return Ok(update);
@@ -172,20 +186,29 @@ impl Client {
         }
     }
 
-    fn parse_initial_state(state: &PortState, db_path: &Path) -> Option<State> {
+    fn parse_initial_state(
+        state: &PortState,
+        db_path: &Path,
+        registry: &Registry,
+    ) -> Option<State> {
         let value = state.value.as_ref()?;
-        Self::state_payload(&state.id, value, db_path).ok()
+        Self::state_payload(&state.id, value, db_path, registry).ok()
     }
 
-    fn state_payload(port_id: &str, value: &str, db_path: &Path) -> Result<State> {
-        let devices = Self::get_devices(db_path)?;
+    fn state_payload(
+        port_id: &str,
+        value: &str,
+        db_path: &Path,
+        registry: &Registry,
+    ) -> Result<State> {
+        let devices = Self::get_devices(db_path, registry)?;
 
-        for (device_type, device) in devices {
+        for (id, device) in devices {
             if let Some(port) = device.ports.get(port_id) {
                 if let Some(capability) = prepare_capability(&port.name, value) {
                     return Ok(State {
-                        device_type,
-                        room: from_inspinia_room(device.room),
+                        device_type: id.device_type,
+                        room: id.room,
                         capability,
                     });
                 }
@@ -195,55 +218,38 @@ impl Client {
         Err(Error::UnsupportedDevice(port_id.to_string()).into())
     }
 
-    fn get_devices(db_path: &Path) -> Result<[(DeviceType, Device); 5]> {
+    fn get_devices(db_path: &Path, registry: &Registry) -> Result<Vec<(DeviceId, Device)>> {
         let device_manager = DeviceManager::new(db_path)?;
+        let mut devices = vec![];
 
-        Ok([
-            (
-                DeviceType::Recuperator,
-                device_manager.get_recuperator_in_room(inspinia::Room::LivingRoom)?,
-            ),
-            (
-                DeviceType::Thermostat,
-                device_manager.get_thermostat_in_room(inspinia::Room::Bedroom)?,
-            ),
-            (
-                DeviceType::Thermostat,
-                device_manager.get_thermostat_in_room(inspinia::Room::Nursery)?,
-            ),
-            (
-                DeviceType::Thermostat,
-                device_manager.get_thermostat_in_room(inspinia::Room::HomeOffice)?,
-            ),
-            (
-                DeviceType::Thermostat,
-                device_manager.get_thermostat_in_room(inspinia::Room::LivingRoom)?,
-            ),
-        ])
-    }
+        for config in registry.devices() {
+            if let Backend::Elizabeth { page_id } = &config.backend {
+                let device = get_device(&device_manager, config.device_type, page_id)?;
+                devices.push((config.id(), device));
+            }
+        }
 
-    fn get_thermostats(db_path: &Path) -> Result<[Device; 4]> {
-        let device_manager = DeviceManager::new(db_path)?;
-        Ok([
-            device_manager.get_thermostat_in_room(inspinia::Room::Bedroom)?,
-            device_manager.get_thermostat_in_room(inspinia::Room::Nursery)?,
-            device_manager.get_thermostat_in_room(inspinia::Room::HomeOffice)?,
-            device_manager.get_thermostat_in_room(inspinia::Room::LivingRoom)?,
-        ])
+        Ok(devices)
     }
 
-    fn get_recuperator(db_path: &Path) -> Result<Device> {
-        let device_manager = DeviceManager::new(db_path)?;
-        Ok(device_manager.get_recuperator_in_room(inspinia::Room::LivingRoom)?)
+    fn get_device(&self, device_type: DeviceType, room: &Room) -> Result<Device> {
+        let id = DeviceId {
+            room: room.clone(),
+            device_type,
+        };
+
+        let page_id = self
+            .registry
+            .inspinia_page_for(&id)
+            .ok_or_else(|| Error::UnknownDevice(device_type, room.clone()))?;
+
+        let device_manager = DeviceManager::new(&self.db_path)?;
+        get_device(&device_manager, device_type, page_id)
     }
 }
 
 impl Client {
-    pub async fn get_current_state(
-        &self,
-        room: transport::Room,
-        device_type: DeviceType,
-    ) -> Vec<Capability> {
+    pub async fn get_current_state(&self, room: &Room, device_type: DeviceType) -> Vec<Capability> {
         let storage = self.storage.lock().await;
 
         storage.get_capabilities(room, device_type).await
@@ -251,7 +257,7 @@ impl Client {
 }
 
 impl Client {
-    pub async fn get_thermostat_temperature_in_room(&self, room: transport::Room) -> Result<f32> {
+    pub async fn get_thermostat_temperature_in_room(&self, room: &Room) -> Result<f32> {
         let storage = self.storage.lock().await;
 
         let capabilities = storage.get_capabilities(room, DeviceType::Thermostat).await;
@@ -262,73 +268,53 @@ impl Client {
             }
         }
 
-        Err(Error::MissingCapability("Temperature", DeviceType::Thermostat, room).into())
+        Err(Error::MissingCapability("Temperature", DeviceType::Thermostat, room.clone()).into())
     }
 
-    pub async fn set_thermostat_enabled(
-        &mut self,
-        value: bool,
-        room: transport::Room,
-    ) -> Result<()> {
-        info!("toggle thermostat in room {:?} = {}", room, value);
+    pub async fn set_thermostat_enabled(&mut self, value: bool, room: &Room) -> Result<()> {
+        info!("toggle thermostat in room {} = {}", room, value);
 
         let value = if value { "1" } else { "0" };
 
-        let thermostats = Self::get_thermostats(&self.db_path)?;
+        let thermostat = self.get_device(DeviceType::Thermostat, room)?;
 
-        for thermostat in thermostats.iter() {
-            if from_inspinia_room(thermostat.room) != room {
-                continue;
-            }
-
-            if let Some((id, port)) = find_output_port(thermostat, PortName::OnOff) {
-                self.client
-                    .send_message(UpdateStateMessage::new(false, id, &port.name, value))
-                    .await?;
+        if let Some((id, port)) = find_output_port(&thermostat, PortName::OnOff) {
+            self.client
+                .send_message(UpdateStateMessage::new(false, id, &port.name, value))
+                .await?;
 
-                return Ok(());
-            }
+            return Ok(());
         }
 
-        Err(Error::MissingPort("OnOff", DeviceType::Thermostat, room).into())
+        Err(Error::MissingPort("OnOff", DeviceType::Thermostat, room.clone()).into())
     }
 
-    pub async fn set_thermostat_temperature(
-        &mut self,
-        value: f32,
-        room: transport::Room,
-    ) -> Result<()> {
+    pub async fn set_thermostat_temperature(&mut self, value: f32, room: &Room) -> Result<()> {
         let temp = value.to_string();
 
-        info!("set temperature in room {:?} = {}", room, temp);
-
-        let thermostats = Self::get_thermostats(&self.db_path)?;
+        info!("set temperature in room {} = {}", room, temp);
 
-        for thermostat in thermostats.iter() {
-            if from_inspinia_room(thermostat.room) != room {
-                continue;
-            }
+        let thermostat = self.get_device(DeviceType::Thermostat, room)?;
 
-            if let Some((id, port)) = find_output_port(thermostat, PortName::SetTemp) {
-                self.client
-                    .send_message(UpdateStateMessage::new(false, id, &port.name, &temp))
-                    .await?;
+        if let Some((id, port)) = find_output_port(&thermostat, PortName::SetTemp) {
+            self.client
+                .send_message(UpdateStateMessage::new(false, id, &port.name, &temp))
+                .await?;
 
-                return Ok(());
-            }
+            return Ok(());
         }
 
-        Err(Error::MissingPort("SetTemp", DeviceType::Thermostat, room).into())
+        Err(Error::MissingPort("SetTemp", DeviceType::Thermostat, room.clone()).into())
     }
 }
 
 impl Client {
-    pub async fn set_recuperator_enabled(&mut self, value: bool) -> Result<()> {
-        info!("toggle recuperator = {}", value);
+    pub async fn set_recuperator_enabled(&mut self, value: bool, room: &Room) -> Result<()> {
+        info!("toggle recuperator in room {} = {}", room, value);
 
         let value = if value { "1" } else { "0" };
 
-        let recuperator = Self::get_recuperator(&self.db_path)?;
+        let recuperator = self.get_device(DeviceType::Recuperator, room)?;
 
         if let Some((id, port)) = find_output_port(&recuperator, PortName::OnOff) {
             self.client
@@ -338,21 +324,22 @@ impl Client {
             return Ok(());
         }
 
-        Err(Error::MissingPort(
-            "OnOff",
-            DeviceType::Recuperator,
-            transport::Room::LivingRoom,
-        )
-        .into())
+        Err(Error::MissingPort("OnOff", DeviceType::Recuperator, room.clone()).into())
     }
 
-    pub async fn set_recuperator_fan_speed(&mut self, value: elizabeth::FanSpeed) -> Result<()> {
-        info!("change fan speed on recuperator = {:?}", value);
+    pub async fn set_recuperator_fan_speed(
+        &mut self,
+        value: elizabeth::FanSpeed,
+        room: &Room,
+    ) -> Result<()> {
+        info!(
+            "change fan speed on recuperator in room {} = {:?}",
+            room, value
+        );
 
         let value = from_elizabeth_speed(value).to_string();
 
-        let device_manager = DeviceManager::new(&self.db_path)?;
-        let recuperator = device_manager.get_recuperator_in_room(inspinia::Room::LivingRoom)?;
+        let recuperator = self.get_device(DeviceType::Recuperator, room)?;
 
         if let Some((id, port)) = find_output_port(&recuperator, PortName::FanSpeed) {
             self.client
@@ -362,12 +349,21 @@ impl Client {
             return Ok(());
         }
 
-        Err(Error::MissingPort(
-            "FanSpeed",
-            DeviceType::Recuperator,
-            transport::Room::LivingRoom,
-        )
-        .into())
+        Err(Error::MissingPort("FanSpeed", DeviceType::Recuperator, room.clone()).into())
+    }
+}
+
+fn get_device(
+    device_manager: &DeviceManager,
+    device_type: DeviceType,
+    page_id: &str,
+) -> Result<Device> {
+    let room = inspinia::Room::new(page_id);
+
+    match device_type {
+        DeviceType::Thermostat => Ok(device_manager.get_thermostat_in_room(room)?),
+        DeviceType::Recuperator => Ok(device_manager.get_recuperator_in_room(room)?),
+        _ => Err(Error::UnsupportedDevice(page_id.to_string()).into()),
     }
 }
 
@@ -400,15 +396,6 @@ fn find_output_port(device: &Device, port_name: PortName) -> Option<(&str, &Port
         })
 }
 
-fn from_inspinia_room(room: inspinia::Room) -> transport::Room {
-    match room {
-        inspinia::Room::Bedroom => transport::Room::Bedroom,
-        inspinia::Room::Nursery => transport::Room::Nursery,
-        inspinia::Room::HomeOffice => transport::Room::HomeOffice,
-        inspinia::Room::LivingRoom => transport::Room::LivingRoom,
-    }
-}
-
 fn from_inspinia_speed(fan_speed: inspinia::FanSpeed) -> elizabeth::FanSpeed {
     match fan_speed {
         inspinia::FanSpeed::Low => elizabeth::FanSpeed::Low,
@@ -452,7 +439,7 @@ mod tests {
 
         Device {
             id: "device-1".to_string(),
-            room: inspinia::Room::LivingRoom,
+            room: inspinia::Room::new("ef8f4a07-6fc4-4b7e-99e2-d1c71f4fd96d"),
             properties: inspinia::Properties {
                 controls: vec![],
                 min_temp: 0,
@@ -62,18 +62,18 @@ impl Storage {
             }
         } else {
             devices.push(Device {
-                room: state.room,
+                room: state.room.clone(),
                 device_type: state.device_type,
                 capabilities: vec![state.capability],
             });
         }
     }
 
-    pub async fn get_capabilities(&self, room: Room, device_type: DeviceType) -> Vec<Capability> {
+    pub async fn get_capabilities(&self, room: &Room, device_type: DeviceType) -> Vec<Capability> {
         let devices = self.devices.lock().await;
         let device = devices
             .iter()
-            .find(|device| device.room == room && device.device_type == device_type);
+            .find(|device| &device.room == room && device.device_type == device_type);
 
         debug!("device: {:?}", device);
 
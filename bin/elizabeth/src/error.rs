@@ -1,6 +1,7 @@
 use std::fmt;
 
 use crate::client::error::Error as ClientError;
+use transport::action::{ActionError, ErrorKind};
 
 #[derive(Debug)]
 pub enum Error {
@@ -10,6 +11,8 @@ pub enum Error {
     Mqtt(paho_mqtt::Error),
     Timeout(tokio::time::error::Elapsed),
     Join(tokio::task::JoinError),
+    Registry(transport::registry::Error),
+    UnsupportedAction(serde_json::Value),
 }
 
 impl From<ClientError> for Error {
@@ -48,6 +51,12 @@ impl From<tokio::task::JoinError> for Error {
     }
 }
 
+impl From<transport::registry::Error> for Error {
+    fn from(err: transport::registry::Error) -> Self {
+        Self::Registry(err)
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -56,9 +65,42 @@ impl fmt::Display for Error {
             Self::Json(err) => write!(f, "json error: {err}"),
             Self::Mqtt(err) => write!(f, "mqtt error: {err}"),
             Self::Timeout(err) => write!(f, "timeout error: {err}"),
+            Self::Registry(err) => write!(f, "registry error: {err}"),
             Self::Join(err) => write!(f, "join error: {err}"),
+            Self::UnsupportedAction(action) => write!(f, "unsupported action {action}"),
         }
     }
 }
 
 impl std::error::Error for Error {}
+
+impl From<&Error> for ActionError {
+    fn from(err: &Error) -> Self {
+        let kind = match err {
+            Error::Client(ClientError::UnsupportedDevice(_))
+            | Error::Client(ClientError::MissingCapability(..))
+            | Error::UnsupportedAction(_) => ErrorKind::NotSupported,
+            Error::Client(ClientError::UnknownDevice(..)) => ErrorKind::InvalidValue,
+            Error::Timeout(_) => ErrorKind::Timeout,
+            _ => ErrorKind::DeviceUnreachable,
+        };
+
+        ActionError::new(kind, err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use transport::{DeviceType, Room};
+
+    #[test]
+    fn test_action_error() {
+        let err = Error::Client(ClientError::MissingCapability(
+            "fan_speed",
+            DeviceType::Thermostat,
+            Room::new("bedroom"),
+        ));
+        assert_eq!(ActionError::from(&err).kind, ErrorKind::NotSupported);
+    }
+}
@@ -6,69 +6,49 @@ pub use error::Error;
 
 use futures_util::future::BoxFuture;
 use futures_util::FutureExt;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 
-use paho_mqtt::{AsyncClient as MqClient, Message, MessageBuilder, PropertyCode};
+use paho_mqtt::{AsyncClient as MqClient, Message};
 
-use transport::action::{ActionRequest, ActionResponse, ActionResult};
+use transport::action::{ActionError, ActionRequest, ActionResponse, ActionResult};
 use transport::elizabeth::{Action, ActionType, CurrentState};
 use transport::state::{StateRequest, StateResponse};
-use transport::DeviceType;
+use transport::{rpc, DeviceType};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
-pub async fn handle_action_request(msg: Message, mqtt: &mut MqClient, inspinia: &mut Client) {
-    let request: ActionRequest = match serde_json::from_slice(msg.payload()) {
-        Ok(ids) => ids,
-        Err(err) => {
-            error!("unable to parse request: {}", err);
-            error!("{}", msg.payload_str());
-            return;
-        }
-    };
-
-    let response_topic = match msg.properties().get_string(PropertyCode::ResponseTopic) {
-        Some(topic) => topic,
-        None => {
-            error!("missing response topic");
-            return;
-        }
+pub async fn handle_action_request(msg: Message, mqtt: &MqClient, inspinia: &mut Client) {
+    let (request, responder) = match rpc::serve::<ActionRequest>(&msg, mqtt) {
+        Some(request) => request,
+        None => return,
     };
 
     for action in request.actions {
-        if let transport::action::Action::Elizabeth(action, action_id) = action {
-            let result = match try_updating_state(action, inspinia).await {
-                Ok(_) => ActionResult::Success,
-                Err(err) => {
-                    error!("Error updating state: {}", err);
-                    ActionResult::Failure
-                }
-            };
-
-            let response = ActionResponse { action_id, result };
-
-            debug!("publish to {}: {:?}", response_topic, response);
-
-            let payload = serde_json::to_vec(&response).unwrap();
-
-            let message = MessageBuilder::new()
-                .topic(&response_topic)
-                .payload(payload)
-                .finalize();
-
-            match mqtt.publish(message).await {
-                Ok(()) => (),
-                Err(err) => {
-                    error!("Error sending response to {}: {}", response_topic, err);
-                }
+        match action {
+            transport::action::Action::Elizabeth(action, action_id) => {
+                let result = match try_updating_state(action, inspinia).await {
+                    Ok(_) => ActionResult::Success,
+                    Err(err) => {
+                        error!("Error updating state: {}", err);
+                        ActionResult::Failure(ActionError::from(&err))
+                    }
+                };
+
+                responder
+                    .respond(&ActionResponse { action_id, result })
+                    .await;
             }
+            transport::action::Action::Unknown(action) => {
+                warn!("skipping unknown action {action}");
+            }
+            _ => (),
         }
     }
 }
 
 fn try_updating_state(action: Action, inspinia: &mut Client) -> BoxFuture<'_, Result<()>> {
     async move {
-        match update_state(action, inspinia).await {
+        match update_state(action.clone(), inspinia).await {
             Ok(()) => Ok(()),
             Err(Error::Inspinia(err)) => match err {
                 inspinia::Error::StreamClosed | inspinia::Error::WebSocketError(_) => {
@@ -93,54 +73,49 @@ async fn update_state(action: Action, inspinia: &mut Client) -> Result<()> {
 
     match (action.device_type, action.action_type) {
         (DeviceType::Recuperator, ActionType::SetIsEnabled(value)) => {
-            inspinia.set_recuperator_enabled(value).await?;
+            inspinia
+                .set_recuperator_enabled(value, &action.room)
+                .await?;
         }
         (DeviceType::Recuperator, ActionType::SetFanSpeed(speed)) => {
-            inspinia.set_recuperator_fan_speed(speed).await?;
+            inspinia
+                .set_recuperator_fan_speed(speed, &action.room)
+                .await?;
         }
         (DeviceType::Thermostat, ActionType::SetIsEnabled(value)) => {
-            inspinia.set_thermostat_enabled(value, action.room).await?;
+            inspinia.set_thermostat_enabled(value, &action.room).await?;
         }
         (DeviceType::Thermostat, ActionType::SetTemperature(value, relative)) => {
             if relative {
                 let current = inspinia
-                    .get_thermostat_temperature_in_room(action.room)
+                    .get_thermostat_temperature_in_room(&action.room)
                     .await?;
 
                 debug!("current: {}", current);
                 debug!("value: {}", value);
 
                 inspinia
-                    .set_thermostat_temperature(current + value, action.room)
+                    .set_thermostat_temperature(current + value, &action.room)
                     .await?;
             } else {
                 inspinia
-                    .set_thermostat_temperature(value, action.room)
+                    .set_thermostat_temperature(value, &action.room)
                     .await?;
             }
         }
+        (_, ActionType::Unknown(action_type)) => {
+            return Err(Error::UnsupportedAction(action_type));
+        }
         _ => (),
     }
 
     Ok(())
 }
 
-pub async fn handle_state_request(msg: Message, mqtt: &mut MqClient, inspinia: &mut Client) {
-    let request: StateRequest = match serde_json::from_slice(msg.payload()) {
-        Ok(ids) => ids,
-        Err(err) => {
-            error!("unable to parse request: {}", err);
-            error!("{}", msg.payload_str());
-            return;
-        }
-    };
-
-    let response_topic = match msg.properties().get_string(PropertyCode::ResponseTopic) {
-        Some(topic) => topic,
-        None => {
-            error!("missing response topic");
-            return;
-        }
+pub async fn handle_state_request(msg: Message, mqtt: &MqClient, inspinia: &mut Client) {
+    let (request, responder) = match rpc::serve::<StateRequest>(&msg, mqtt) {
+        Some(request) => request,
+        None => return,
     };
 
     let ids = request.device_ids.into_iter().filter(|id| {
@@ -153,7 +128,7 @@ pub async fn handle_state_request(msg: Message, mqtt: &mut MqClient, inspinia: &
     debug!("ids: {:?}", ids.clone().collect::<Vec<_>>());
 
     for id in ids {
-        let capabilities = inspinia.get_current_state(id.room, id.device_type).await;
+        let capabilities = inspinia.get_current_state(&id.room, id.device_type).await;
 
         let state = CurrentState {
             room: id.room,
@@ -161,21 +136,6 @@ pub async fn handle_state_request(msg: Message, mqtt: &mut MqClient, inspinia: &
             capabilities,
         };
 
-        debug!("publish to {}: {:?}", response_topic, state);
-
-        let response = StateResponse::Elizabeth(state);
-        let payload = serde_json::to_vec(&response).unwrap();
-
-        let message = MessageBuilder::new()
-            .topic(&response_topic)
-            .payload(payload)
-            .finalize();
-
-        match mqtt.publish(message).await {
-            Ok(()) => (),
-            Err(err) => {
-                error!("Error sending response to {}: {}", response_topic, err);
-            }
-        }
+        responder.respond(&StateResponse::Elizabeth(state)).await;
     }
 }
@@ -1,15 +1,17 @@
 use elizabeth::{handle_action_request, handle_state_request, Client, Result};
-use transport::state::StateUpdate;
-use transport::{connect_mqtt, Topic};
+use transport::availability::heartbeat;
+use transport::elizabeth::CurrentState;
+use transport::registry::Registry;
+use transport::state::{StateResponse, StateUpdate};
+use transport::{publish_state, DeviceId, MqttSession, Topic};
 
+use std::sync::Arc;
 use std::time::Duration;
 
-use futures_util::stream::StreamExt;
 use log::{error, info, trace};
 use paho_mqtt::AsyncClient as MqClient;
-use paho_mqtt::{MessageBuilder, QOS_1};
 use tokio::signal::unix::{signal, SignalKind};
-use tokio::{task, time};
+use tokio::task;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -19,6 +21,9 @@ async fn main() -> Result<()> {
 
     info!("elizabeth version {VERSION}");
 
+    let registry_path = std::env::var("REGISTRY_PATH").expect("set ENV variable REGISTRY_PATH");
+    let registry = Arc::new(Registry::load(registry_path)?);
+
     let inspinia_client_id =
         std::env::var("INSPINIA_CLIENT_ID").expect("set ENV variable INSPINIA_CLIENT_ID");
     let inspinia_token = std::env::var("INSPINIA_TOKEN").expect("set ENV variable INSPINIA_TOKEN");
@@ -26,19 +31,25 @@ async fn main() -> Result<()> {
         .expect("set ENV variable INSPINIA_LOGS_PATH")
         .into();
 
-    let inspinia_client =
-        Client::new(inspinia_client_id, inspinia_token, inspinia_logs_path).await?;
+    let inspinia_client = Client::new(
+        inspinia_client_id,
+        inspinia_token,
+        inspinia_logs_path,
+        registry,
+    )
+    .await?;
 
     let mqtt_address = std::env::var("MQTT_ADDRESS").expect("set ENV variable MQTT_ADDRESS");
     let mqtt_username = std::env::var("MQTT_USER").expect("set ENV variable MQTT_USER");
     let mqtt_password = std::env::var("MQTT_PASS").expect("set ENV variable MQTT_PASS");
-    let mqtt_client = connect_mqtt(mqtt_address, mqtt_username, mqtt_password, "elizabeth").await?;
+    let mqtt_session =
+        MqttSession::connect(mqtt_address, mqtt_username, mqtt_password, "elizabeth").await?;
+    let mqtt_client = mqtt_session.client().clone();
     info!("connected mqtt");
 
-    let set_handle = task::spawn(subscribe_action(
-        mqtt_client.clone(),
-        inspinia_client.clone(),
-    ));
+    task::spawn(heartbeat(mqtt_client.clone(), "elizabeth", VERSION));
+
+    let set_handle = task::spawn(subscribe_action(mqtt_session, inspinia_client.clone()));
     let state_handle = task::spawn(subscribe_state(mqtt_client, inspinia_client));
 
     tokio::select! {
@@ -64,61 +75,19 @@ async fn try_join(
     Ok(())
 }
 
-async fn subscribe_action(mut mqtt: MqClient, mut inspinia: Client) -> Result<()> {
-    let mut stream = mqtt.get_stream(None);
+async fn subscribe_action(mut session: MqttSession, mut inspinia: Client) -> Result<()> {
+    let mqtt = session.client().clone();
 
-    let topics = [
-        Topic::ActionRequest.to_string(),
-        Topic::StateRequest.to_string(),
-    ];
+    session.subscribe(Topic::ActionRequest).await?;
+    session.subscribe(Topic::StateRequest).await?;
 
-    mqtt.subscribe_many(&topics, &[QOS_1, QOS_1]).await?;
-    info!("Subscribed to topis: {:?}", topics);
+    while let Some((topic, msg)) = session.next().await {
+        trace!("got message {:?}", msg);
 
-    while let Some(msg_opt) = stream.next().await {
-        trace!("got message {:?}", msg_opt);
-
-        if let Some(msg) = msg_opt {
-            let topic = if let Ok(value) = msg.topic().parse() {
-                value
-            } else {
-                continue;
-            };
-
-            match topic {
-                Topic::ActionRequest => handle_action_request(msg, &mut mqtt, &mut inspinia).await,
-                Topic::StateRequest => handle_state_request(msg, &mut mqtt, &mut inspinia).await,
-                _ => (),
-            }
-        } else {
-            time::sleep(Duration::from_secs(1)).await;
-            error!("Lost MQTT connection. Attempting reconnect.");
-
-            loop {
-                match time::timeout(Duration::from_secs(10), mqtt.reconnect()).await {
-                    Ok(Ok(response)) => {
-                        info!("Reconnected to MQTT! {}", response.reason_code());
-
-                        let topics = [
-                            Topic::ActionRequest.to_string(),
-                            Topic::StateRequest.to_string(),
-                        ];
-
-                        mqtt.subscribe_many(&topics, &[QOS_1, QOS_1]).await?;
-                        info!("Subscribed to topis: {:?}", topics);
-
-                        break;
-                    }
-                    Ok(Err(err)) => {
-                        error!("Error MQTT reconnecting: {}", err);
-                        time::sleep(Duration::from_secs(5)).await;
-                    }
-                    Err(err) => {
-                        error!("Error MQTT reconnecting: {}", err);
-                        time::sleep(Duration::from_secs(5)).await;
-                    }
-                }
-            }
+        match topic {
+            Topic::ActionRequest => handle_action_request(msg, &mqtt, &mut inspinia).await,
+            Topic::StateRequest => handle_state_request(msg, &mqtt, &mut inspinia).await,
+            _ => (),
         }
     }
 
@@ -128,17 +97,25 @@ async fn subscribe_action(mut mqtt: MqClient, mut inspinia: Client) -> Result<()
 async fn subscribe_state(mqtt: MqClient, mut inspinia: Client) -> Result<()> {
     loop {
         if let Ok(payload) = inspinia.read().await {
-            let update = StateUpdate::Elizabeth(payload);
+            let device_id = DeviceId {
+                room: payload.room.clone(),
+                device_type: payload.device_type,
+            };
 
-            let payload = serde_json::to_vec(&update)?;
-            let topic = Topic::StateUpdate;
+            let state = CurrentState {
+                room: payload.room.clone(),
+                device_type: payload.device_type,
+                capabilities: inspinia
+                    .get_current_state(&payload.room, payload.device_type)
+                    .await,
+            };
 
-            let message = MessageBuilder::new()
-                .topic(topic.to_string())
-                .payload(payload)
-                .finalize();
+            let update = StateUpdate::Elizabeth(payload);
+            let state = StateResponse::Elizabeth(state);
 
-            mqtt.publish(message).await?;
+            if let Err(err) = publish_state(&mqtt, &update, &state, &[device_id]).await {
+                error!("Error publishing state update: {err}");
+            }
         } else {
             error!("Lost Inspinia connection. Attempting reconnect.");
 
@@ -0,0 +1,140 @@
+//! Translates Home Assistant commands into gateway actions.
+
+use serde::de::DeserializeOwned;
+use transport::action::Action;
+use transport::elizabeth::ActionType;
+use transport::{elisa, elisheba, elizabeth, DeviceId, DeviceType};
+use uuid::Uuid;
+
+use crate::topic::Field;
+
+/// Action for `payload` received on the command topic of `field`, `None`
+/// when the device doesn't take such a command.
+pub fn action(device: &DeviceId, field: Field, payload: &str) -> Option<Action> {
+    let id = Uuid::new_v4();
+
+    match (device.device_type, field) {
+        (DeviceType::Light, Field::Power) => Some(Action::Elisheba(
+            elisheba::Action {
+                room: device.room.clone(),
+                is_enabled: parse_power(payload)?,
+            },
+            id,
+        )),
+        (DeviceType::Recuperator, Field::Power) => {
+            elizabeth(device, ActionType::SetIsEnabled(parse_power(payload)?), id)
+        }
+        (DeviceType::Recuperator, Field::Preset) => {
+            elizabeth(device, ActionType::SetFanSpeed(parse_enum(payload)?), id)
+        }
+        (DeviceType::Thermostat, Field::Mode) => {
+            let is_enabled = match payload {
+                "heat" => true,
+                "off" => false,
+                _ => return None,
+            };
+
+            elizabeth(device, ActionType::SetIsEnabled(is_enabled), id)
+        }
+        (DeviceType::Thermostat, Field::TargetTemperature) => {
+            let value = payload.parse().ok()?;
+            elizabeth(device, ActionType::SetTemperature(value, false), id)
+        }
+        (DeviceType::VacuumCleaner, Field::State) => {
+            let action = match payload {
+                "start" => elisa::Action::Start(vec![device.room.clone()]),
+                "pause" => elisa::Action::Pause,
+                "stop" | "return_to_base" => elisa::Action::Stop,
+                _ => return None,
+            };
+
+            Some(Action::Elisa(action, id))
+        }
+        (DeviceType::VacuumCleaner, Field::FanSpeed) => Some(Action::Elisa(
+            elisa::Action::SetWorkSpeed(parse_enum(payload)?),
+            id,
+        )),
+        _ => None,
+    }
+}
+
+fn elizabeth(device: &DeviceId, action_type: ActionType, id: Uuid) -> Option<Action> {
+    Some(Action::Elizabeth(
+        elizabeth::Action {
+            room: device.room.clone(),
+            device_type: device.device_type,
+            action_type,
+        },
+        id,
+    ))
+}
+
+fn parse_power(payload: &str) -> Option<bool> {
+    match payload {
+        "ON" => Some(true),
+        "OFF" => Some(false),
+        _ => None,
+    }
+}
+
+fn parse_enum<T: DeserializeOwned>(payload: &str) -> Option<T> {
+    serde_json::from_value(serde_json::Value::String(payload.to_string())).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use transport::elizabeth::FanSpeed;
+    use transport::Room;
+
+    #[test]
+    fn test_actions() {
+        let thermostat = DeviceId::thermostat_at_room(Room::new("bedroom"));
+        let Some(Action::Elizabeth(action, _)) =
+            super::action(&thermostat, Field::TargetTemperature, "21.5")
+        else {
+            panic!("expected an elizabeth action");
+        };
+        assert_eq!(action.action_type, ActionType::SetTemperature(21.5, false));
+
+        let recuperator = DeviceId::recuperator_at_room(Room::new("living_room"));
+        let Some(Action::Elizabeth(action, _)) = super::action(&recuperator, Field::Preset, "high")
+        else {
+            panic!("expected an elizabeth action");
+        };
+        assert_eq!(action.action_type, ActionType::SetFanSpeed(FanSpeed::High));
+
+        let vacuum = DeviceId::vacuum_cleaner_at_room(Room::new("kitchen"));
+        let Some(Action::Elisa(action, _)) = super::action(&vacuum, Field::State, "start") else {
+            panic!("expected an elisa action");
+        };
+        assert_eq!(action, elisa::Action::Start(vec![Room::new("kitchen")]));
+
+        let Some(Action::Elisa(action, _)) = super::action(&vacuum, Field::FanSpeed, "silent")
+        else {
+            panic!("expected an elisa action");
+        };
+        assert_eq!(
+            action,
+            elisa::Action::SetWorkSpeed(elisa::WorkSpeed::Silent)
+        );
+
+        let light = DeviceId::light_at_room(Room::new("corridor"));
+        let Some(Action::Elisheba(action, _)) = super::action(&light, Field::Power, "ON") else {
+            panic!("expected an elisheba action");
+        };
+        assert!(action.is_enabled);
+    }
+
+    #[test]
+    fn test_unsupported_commands() {
+        let light = DeviceId::light_at_room(Room::new("corridor"));
+
+        assert!(action(&light, Field::Power, "TOGGLE").is_none());
+        assert!(action(&light, Field::Preset, "low").is_none());
+
+        let thermostat = DeviceId::thermostat_at_room(Room::new("bedroom"));
+        assert!(action(&thermostat, Field::Mode, "cool").is_none());
+        assert!(action(&thermostat, Field::TargetTemperature, "warm").is_none());
+    }
+}
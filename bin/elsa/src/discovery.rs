@@ -0,0 +1,252 @@
+//! Home Assistant MQTT discovery configs of the registry devices.
+//!
+//! Lights become `light`, the recuperator a `fan`, thermostats `climate`,
+//! vacuum cleaners a `vacuum` per room and the reported properties
+//! `sensor` entities. Names, ranges and modes come from
+//! [`transport::metadata`], the same as for Alice.
+
+use serde_json::{json, Value};
+use transport::metadata::{self, Property};
+use transport::registry::{DeviceConfig, Registry};
+use transport::{DeviceId, DeviceType, Topic};
+
+use crate::topic::{command_topic, state_topic, Field, DISCOVERY_PREFIX};
+
+/// Availability value of the enveloped `availability/<service>` payloads.
+const AVAILABILITY_TEMPLATE: &str = "{{ value_json.payload.status }}";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+    pub topic: String,
+    pub payload: Value,
+}
+
+pub fn configs(registry: &Registry, service: &str) -> Vec<Config> {
+    registry
+        .devices()
+        .iter()
+        .flat_map(|config| device_configs(config, registry, service))
+        .collect()
+}
+
+fn device_configs(config: &DeviceConfig, registry: &Registry, service: &str) -> Vec<Config> {
+    let id = config.id();
+    let room_name = registry
+        .room_name(&config.room)
+        .unwrap_or(config.room.as_str());
+    let name = config.name_in(metadata::DEFAULT_LANGUAGE);
+
+    let common = json!({
+        "device": {
+            "identifiers": [unique_id(&id)],
+            "name": name,
+            "manufacturer": "lisa",
+            "model": config.device_type.to_string(),
+            "suggested_area": room_name,
+        },
+        "availability": [
+            {
+                "topic": Topic::Availability(config.backend.gateway().to_string()).to_string(),
+                "value_template": AVAILABILITY_TEMPLATE,
+            },
+            {
+                "topic": Topic::Availability(service.to_string()).to_string(),
+                "value_template": AVAILABILITY_TEMPLATE,
+            },
+        ],
+        "availability_mode": "all",
+    });
+
+    let mut configs = vec![];
+
+    let main = match config.device_type {
+        DeviceType::Light => Some(("light", light(&id))),
+        DeviceType::Recuperator => Some(("fan", fan(&id, config))),
+        DeviceType::Thermostat => Some(("climate", climate(&id, config))),
+        DeviceType::VacuumCleaner => Some(("vacuum", vacuum(&id, config))),
+        DeviceType::TemperatureSensor | DeviceType::Scene => None,
+    };
+
+    if let Some((component, payload)) = main {
+        configs.push(config_for(component, &unique_id(&id), payload, &common));
+    }
+
+    // The climate entity shows the current temperature itself.
+    if config.device_type != DeviceType::Thermostat {
+        for property in config.device_type.properties() {
+            let field = Field::from(*property);
+            let object_id = format!("{}_{}", unique_id(&id), field);
+
+            configs.push(config_for(
+                "sensor",
+                &object_id,
+                sensor(&id, *property),
+                &common,
+            ));
+        }
+    }
+
+    configs
+}
+
+fn config_for(component: &str, object_id: &str, mut payload: Value, common: &Value) -> Config {
+    payload["unique_id"] = json!(object_id);
+    payload["object_id"] = json!(object_id);
+
+    for (key, value) in common.as_object().into_iter().flatten() {
+        payload[key] = value.clone();
+    }
+
+    Config {
+        topic: format!("{DISCOVERY_PREFIX}/{component}/{object_id}/config"),
+        payload,
+    }
+}
+
+fn unique_id(id: &DeviceId) -> String {
+    format!("lisa_{}_{}", id.device_type, id.room)
+}
+
+fn light(id: &DeviceId) -> Value {
+    json!({
+        "name": null,
+        "state_topic": state_topic(id, Field::Power),
+        "command_topic": command_topic(id, Field::Power),
+    })
+}
+
+fn fan(id: &DeviceId, config: &DeviceConfig) -> Value {
+    json!({
+        "name": null,
+        "state_topic": state_topic(id, Field::Power),
+        "command_topic": command_topic(id, Field::Power),
+        "preset_mode_state_topic": state_topic(id, Field::Preset),
+        "preset_mode_command_topic": command_topic(id, Field::Preset),
+        "preset_modes": config.fan_speeds(),
+    })
+}
+
+fn climate(id: &DeviceId, config: &DeviceConfig) -> Value {
+    let range = config.temperature_range();
+
+    json!({
+        "name": null,
+        "modes": ["off", "heat"],
+        "mode_state_topic": state_topic(id, Field::Mode),
+        "mode_command_topic": command_topic(id, Field::Mode),
+        "temperature_state_topic": state_topic(id, Field::TargetTemperature),
+        "temperature_command_topic": command_topic(id, Field::TargetTemperature),
+        "current_temperature_topic": state_topic(id, Field::CurrentTemperature),
+        "min_temp": range.min,
+        "max_temp": range.max,
+        "temp_step": range.step,
+        "temperature_unit": "C",
+    })
+}
+
+fn vacuum(id: &DeviceId, config: &DeviceConfig) -> Value {
+    json!({
+        "name": null,
+        "state_topic": state_topic(id, Field::State),
+        "command_topic": command_topic(id, Field::State),
+        "set_fan_speed_topic": command_topic(id, Field::FanSpeed),
+        "fan_speed_list": config.work_speeds(),
+        "supported_features": ["start", "stop", "pause", "return_home", "fan_speed"],
+    })
+}
+
+fn sensor(id: &DeviceId, property: Property) -> Value {
+    let (device_class, unit) = match property {
+        Property::Temperature => (Some("temperature"), "°C"),
+        Property::Humidity => (Some("humidity"), "%"),
+        Property::Battery => (Some("battery"), "%"),
+        Property::Consumables => (None, "%"),
+    };
+
+    json!({
+        "state_topic": state_topic(id, Field::from(property)),
+        "device_class": device_class,
+        "unit_of_measurement": unit,
+        "state_class": "measurement",
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> Registry {
+        include_str!("../../../conf/registry.toml").parse().unwrap()
+    }
+
+    fn find<'a>(configs: &'a [Config], topic: &str) -> &'a Value {
+        &configs
+            .iter()
+            .find(|config| config.topic == topic)
+            .unwrap()
+            .payload
+    }
+
+    #[test]
+    fn test_configs() {
+        let configs = configs(&registry(), "elsa");
+
+        // 2 lights, a fan, 4 climates, 8 vacuums with their batteries and
+        // consumables and 4 sensors of 3 properties each
+        assert_eq!(configs.len(), 43);
+
+        let climate = find(
+            &configs,
+            "homeassistant/climate/lisa_thermostat_bedroom/config",
+        );
+        assert_eq!(
+            climate["temperature_command_topic"],
+            "lisa/thermostat/bedroom/target_temperature/set"
+        );
+        assert_eq!(climate["min_temp"], 16.0);
+        assert_eq!(climate["temp_step"], 0.5);
+        assert_eq!(climate["device"]["suggested_area"], "Спальня");
+        assert_eq!(
+            climate["availability"][0]["topic"],
+            "availability/elizabeth"
+        );
+        assert_eq!(climate["availability"][1]["topic"], "availability/elsa");
+
+        let fan = find(
+            &configs,
+            "homeassistant/fan/lisa_recuperator_living_room/config",
+        );
+        assert_eq!(fan["preset_modes"], json!(["low", "medium", "high"]));
+
+        let vacuum = find(
+            &configs,
+            "homeassistant/vacuum/lisa_vacuum_cleaner_kitchen/config",
+        );
+        assert_eq!(
+            vacuum["fan_speed_list"],
+            json!(["silent", "standard", "medium", "turbo"])
+        );
+        assert_eq!(vacuum["device"]["name"], "Ева");
+
+        let humidity = find(
+            &configs,
+            "homeassistant/sensor/lisa_temperature_sensor_nursery_humidity/config",
+        );
+        assert_eq!(
+            humidity["state_topic"],
+            "lisa/temperature_sensor/nursery/humidity"
+        );
+        assert_eq!(humidity["device_class"], "humidity");
+
+        let consumables = find(
+            &configs,
+            "homeassistant/sensor/lisa_vacuum_cleaner_kitchen_consumables/config",
+        );
+        assert_eq!(
+            consumables["state_topic"],
+            "lisa/vacuum_cleaner/kitchen/consumables"
+        );
+        assert_eq!(consumables["device_class"], Value::Null);
+        assert_eq!(consumables["unit_of_measurement"], "%");
+    }
+}
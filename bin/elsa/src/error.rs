@@ -0,0 +1,30 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Error {
+    Mqtt(paho_mqtt::Error),
+    Registry(transport::registry::Error),
+}
+
+impl From<paho_mqtt::Error> for Error {
+    fn from(err: paho_mqtt::Error) -> Self {
+        Self::Mqtt(err)
+    }
+}
+
+impl From<transport::registry::Error> for Error {
+    fn from(err: transport::registry::Error) -> Self {
+        Self::Registry(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Mqtt(err) => write!(f, "mqtt error: {err}"),
+            Self::Registry(err) => write!(f, "registry error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,14 @@
+mod command;
+mod discovery;
+mod state;
+mod topic;
+
+pub use command::action;
+pub use discovery::{configs, Config};
+pub use state::{from_state, from_update, Value};
+pub use topic::{parse_command_topic, Field, COMMAND_FILTER, STATUS_TOPIC};
+
+mod error;
+pub use error::Error;
+
+pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,176 @@
+use std::time::Duration;
+
+use elsa::{action, configs, from_state, from_update, parse_command_topic, Result, Value};
+use elsa::{COMMAND_FILTER, STATUS_TOPIC};
+use transport::action::{Action, ActionRequest, ActionResponse, ActionResult};
+use transport::availability::heartbeat;
+use transport::registry::Registry;
+use transport::state::{StateResponse, StateUpdate};
+use transport::{envelope, rpc, MqttSession, Topic};
+
+use log::{debug, error, info};
+use paho_mqtt::{AsyncClient, Message, MessageBuilder, QOS_1};
+use tokio::task;
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+const SERVICE: &str = "elsa";
+const RESPONSE_DEADLINE: Duration = Duration::from_secs(10);
+
+/// Retained last known state of every device.
+const DEVICE_STATE_FILTER: &str = "state/+/+";
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    pretty_env_logger::init_timed();
+
+    info!("elsa version {VERSION}");
+
+    let registry_path = std::env::var("REGISTRY_PATH").expect("set ENV variable REGISTRY_PATH");
+    let registry = Registry::load(registry_path)?;
+
+    let mqtt_address = std::env::var("MQTT_ADDRESS").expect("set ENV variable MQTT_ADDRESS");
+    let mqtt_username = std::env::var("MQTT_USER").expect("set ENV variable MQTT_USER");
+    let mqtt_password = std::env::var("MQTT_PASS").expect("set ENV variable MQTT_PASS");
+
+    let mut mqtt_session =
+        MqttSession::connect(mqtt_address, mqtt_username, mqtt_password, SERVICE).await?;
+    let rpc_client = rpc::Client::attach(&mut mqtt_session).await?;
+    info!("connected mqtt");
+
+    task::spawn(heartbeat(mqtt_session.client().clone(), SERVICE, VERSION));
+
+    bridge(mqtt_session, rpc_client, registry).await
+}
+
+async fn bridge(mut mqtt: MqttSession, rpc_client: rpc::Client, registry: Registry) -> Result<()> {
+    publish_discovery(mqtt.client(), &registry).await;
+
+    mqtt.subscribe(STATUS_TOPIC).await?;
+    mqtt.subscribe(COMMAND_FILTER).await?;
+    mqtt.subscribe(Topic::StateUpdate).await?;
+    mqtt.subscribe(DEVICE_STATE_FILTER).await?;
+
+    while let Some(msg) = mqtt.next_message().await {
+        if rpc_client.route(&msg) {
+            continue;
+        }
+
+        if msg.topic() == STATUS_TOPIC {
+            if msg.payload() == b"online" {
+                info!("home assistant is online, publishing discovery");
+                publish_discovery(mqtt.client(), &registry).await;
+            }
+
+            continue;
+        }
+
+        if let Some((device, field)) = parse_command_topic(msg.topic()) {
+            let payload = msg.payload_str();
+
+            match action(&device, field, &payload) {
+                Some(action) => {
+                    task::spawn(send_action(action, rpc_client.clone()));
+                }
+                None => error!("unsupported command {} on {}", payload, msg.topic()),
+            }
+
+            continue;
+        }
+
+        let values = match msg.topic().parse() {
+            Ok(Topic::StateUpdate) => match envelope::decode::<StateUpdate>(msg.payload()) {
+                Ok(update) => from_update(&update, &registry),
+                Err(err) => {
+                    error!("unable to parse update on {}: {}", msg.topic(), err);
+                    continue;
+                }
+            },
+            Ok(Topic::DeviceState(device)) => {
+                match envelope::decode::<StateResponse>(msg.payload()) {
+                    Ok(state) => from_state(&device, &state),
+                    Err(err) => {
+                        error!("unable to parse state on {}: {}", msg.topic(), err);
+                        continue;
+                    }
+                }
+            }
+            _ => {
+                debug!("skipping message on topic {}", msg.topic());
+                continue;
+            }
+        };
+
+        publish_values(mqtt.client(), values).await;
+    }
+
+    Ok(())
+}
+
+async fn publish_discovery(mqtt: &AsyncClient, registry: &Registry) {
+    for config in configs(registry, SERVICE) {
+        let message = retained(config.topic, config.payload.to_string());
+
+        if let Err(err) = mqtt.publish(message).await {
+            error!("Error publishing discovery config: {}", err);
+        }
+    }
+}
+
+async fn publish_values(mqtt: &AsyncClient, values: Vec<Value>) {
+    for value in values {
+        if let Err(err) = mqtt.publish(retained(value.topic, value.payload)).await {
+            error!("Error publishing state: {}", err);
+        }
+    }
+}
+
+fn retained(topic: String, payload: String) -> Message {
+    MessageBuilder::new()
+        .topic(topic)
+        .payload(payload)
+        .qos(QOS_1)
+        .retained(true)
+        .finalize()
+}
+
+/// Sends a single action and logs the gateway's answer.
+async fn send_action(action: Action, mqtt: rpc::Client) {
+    let Some(action_id) = action.id() else {
+        return;
+    };
+
+    let request = ActionRequest {
+        actions: vec![action],
+    };
+
+    let mut responses = match mqtt
+        .request::<_, ActionResponse>(Topic::ActionRequest, &request, RESPONSE_DEADLINE)
+        .await
+    {
+        Ok(responses) => responses,
+        Err(err) => {
+            error!("Error sending {:?}: {}", request.actions, err);
+            return;
+        }
+    };
+
+    while let Some(response) = responses.next().await {
+        if response.action_id != action_id {
+            continue;
+        }
+
+        match response.result {
+            ActionResult::Success => info!("{:?} succeeded", request.actions),
+            ActionResult::Failure(err) => error!(
+                "{:?} failed with {:?}: {}",
+                request.actions,
+                err.kind,
+                err.message.unwrap_or_default()
+            ),
+        }
+
+        return;
+    }
+
+    error!("no response to {:?}", request.actions);
+}
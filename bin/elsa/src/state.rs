@@ -0,0 +1,244 @@
+//! Translates gateway states into values of the Home Assistant state topics.
+
+use serde_json::json;
+use transport::elizabeth::Capability;
+use transport::isabel::Property;
+use transport::registry::Registry;
+use transport::state::{StateResponse, StateUpdate};
+use transport::{elisa, DeviceId, DeviceType};
+
+use crate::topic::{state_topic, Field};
+
+/// A retained value for a state topic.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Value {
+    pub topic: String,
+    pub payload: String,
+}
+
+impl Value {
+    fn new(device: &DeviceId, field: Field, payload: impl ToString) -> Value {
+        Value {
+            topic: state_topic(device, field),
+            payload: payload.to_string(),
+        }
+    }
+}
+
+pub fn from_update(update: &StateUpdate, registry: &Registry) -> Vec<Value> {
+    match update {
+        StateUpdate::Elisa(state) => registry
+            .devices_of_type(DeviceType::VacuumCleaner)
+            .flat_map(|config| vacuum(&config.id(), state))
+            .collect(),
+        StateUpdate::Elisheba(state) => vec![Value::new(
+            &DeviceId::light_at_room(state.room.clone()),
+            Field::Power,
+            power(state.is_enabled),
+        )],
+        StateUpdate::Elizabeth(state) => {
+            let device = DeviceId {
+                room: state.room.clone(),
+                device_type: state.device_type,
+            };
+
+            elizabeth(&device, state.capability).into_iter().collect()
+        }
+        StateUpdate::Isabel(state) => {
+            let device = DeviceId::temperature_sensor_at_room(state.room.clone());
+
+            match state.property {
+                Property::Temperature(value) => {
+                    vec![Value::new(&device, Field::Temperature, value)]
+                }
+                Property::Humidity(value) => vec![Value::new(&device, Field::Humidity, value)],
+                Property::Battery(value) => vec![Value::new(&device, Field::Battery, value)],
+                Property::TemperatureAndHumidity(temperature, humidity) => vec![
+                    Value::new(&device, Field::Temperature, temperature),
+                    Value::new(&device, Field::Humidity, humidity),
+                ],
+                Property::Unknown(_) => vec![],
+            }
+        }
+        StateUpdate::Unknown(_) => vec![],
+    }
+}
+
+/// Values of the retained last known state of `device`.
+pub fn from_state(device: &DeviceId, state: &StateResponse) -> Vec<Value> {
+    match state {
+        StateResponse::Elisa(state) => vacuum(device, state),
+        StateResponse::Elisheba(state) => {
+            vec![Value::new(device, Field::Power, power(state.is_enabled))]
+        }
+        StateResponse::Elizabeth(state) => state
+            .capabilities
+            .iter()
+            .filter_map(|capability| elizabeth(device, *capability))
+            .collect(),
+        StateResponse::Isabel(state) => [
+            state
+                .temperature
+                .map(|value| Value::new(device, Field::Temperature, value)),
+            state
+                .humidity
+                .map(|value| Value::new(device, Field::Humidity, value)),
+            state
+                .battery
+                .map(|value| Value::new(device, Field::Battery, value)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect(),
+        StateResponse::Unknown(_) => vec![],
+    }
+}
+
+/// The vacuum cleaner reports for the whole map, only the vacuums of the
+/// rooms being cleaned are cleaning.
+fn vacuum(device: &DeviceId, state: &elisa::State) -> Vec<Value> {
+    let is_cleaned = state.rooms.is_empty() || state.rooms.contains(&device.room);
+
+    let status = match (state.is_enabled && is_cleaned, state.is_paused) {
+        (true, true) => "paused",
+        (true, false) => "cleaning",
+        (false, _) if state.is_enabled => "idle",
+        (false, _) => "docked",
+    };
+
+    let payload = json!({
+        "state": status,
+        "fan_speed": state.work_speed,
+    });
+
+    let mut values = vec![
+        Value::new(device, Field::State, payload),
+        Value::new(device, Field::Battery, state.battery_level),
+    ];
+
+    if let Some(consumable) = state.most_worn() {
+        values.push(Value::new(device, Field::Consumables, consumable.remaining));
+    }
+
+    values
+}
+
+fn elizabeth(device: &DeviceId, capability: Capability) -> Option<Value> {
+    let is_thermostat = device.device_type == DeviceType::Thermostat;
+
+    match capability {
+        Capability::IsEnabled(value) if is_thermostat => Some(Value::new(
+            device,
+            Field::Mode,
+            if value { "heat" } else { "off" },
+        )),
+        Capability::IsEnabled(value) => Some(Value::new(device, Field::Power, power(value))),
+        Capability::FanSpeed(speed) => Some(Value::new(
+            device,
+            Field::Preset,
+            json!(speed).as_str().unwrap_or_default(),
+        )),
+        Capability::CurrentTemperature(value) => {
+            Some(Value::new(device, Field::CurrentTemperature, value))
+        }
+        Capability::Temperature(value) => Some(Value::new(device, Field::TargetTemperature, value)),
+    }
+}
+
+fn power(is_enabled: bool) -> &'static str {
+    if is_enabled {
+        "ON"
+    } else {
+        "OFF"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use transport::elizabeth::{CurrentState, FanSpeed};
+    use transport::{elizabeth, Room};
+
+    fn registry() -> Registry {
+        include_str!("../../../conf/registry.toml").parse().unwrap()
+    }
+
+    fn value(topic: &str, payload: &str) -> Value {
+        Value {
+            topic: topic.to_string(),
+            payload: payload.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_vacuum_update() {
+        let update = StateUpdate::Elisa(elisa::State {
+            battery_level: 80,
+            is_enabled: true,
+            is_paused: false,
+            work_speed: elisa::WorkSpeed::Turbo,
+            cleanup_mode: elisa::CleanupMode::DryCleaning,
+            rooms: vec![Room::new("kitchen")],
+            consumables: vec![
+                elisa::ConsumableState {
+                    consumable: elisa::Consumable::SideBrush,
+                    work_hours: 120.0,
+                    remaining: 40,
+                },
+                elisa::ConsumableState {
+                    consumable: elisa::Consumable::Filter,
+                    work_hours: 138.0,
+                    remaining: 8,
+                },
+            ],
+        });
+
+        let values = from_update(&update, &registry());
+        assert_eq!(values.len(), 24);
+
+        assert!(values.contains(&value(
+            "lisa/vacuum_cleaner/kitchen/state",
+            r#"{"fan_speed":"turbo","state":"cleaning"}"#
+        )));
+        assert!(values.contains(&value(
+            "lisa/vacuum_cleaner/bedroom/state",
+            r#"{"fan_speed":"turbo","state":"idle"}"#
+        )));
+        assert!(values.contains(&value("lisa/vacuum_cleaner/bedroom/battery", "80")));
+        assert!(values.contains(&value("lisa/vacuum_cleaner/bedroom/consumables", "8")));
+    }
+
+    #[test]
+    fn test_elizabeth_update() {
+        let thermostat = StateUpdate::Elizabeth(elizabeth::State {
+            room: Room::new("bedroom"),
+            device_type: DeviceType::Thermostat,
+            capability: Capability::IsEnabled(true),
+        });
+
+        assert_eq!(
+            from_update(&thermostat, &registry()),
+            vec![value("lisa/thermostat/bedroom/mode", "heat")]
+        );
+
+        let recuperator = StateResponse::Elizabeth(CurrentState {
+            room: Room::new("living_room"),
+            device_type: DeviceType::Recuperator,
+            capabilities: vec![
+                Capability::IsEnabled(false),
+                Capability::FanSpeed(FanSpeed::Medium),
+            ],
+        });
+
+        assert_eq!(
+            from_state(
+                &DeviceId::recuperator_at_room(Room::new("living_room")),
+                &recuperator
+            ),
+            vec![
+                value("lisa/recuperator/living_room/power", "OFF"),
+                value("lisa/recuperator/living_room/preset", "medium"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,99 @@
+//! Topics of the Home Assistant side of the bridge.
+//!
+//! Every value of a device gets its own retained state topic,
+//! `lisa/<device_type>/<room>/<field>`, and the values Home Assistant may
+//! change take commands on the same topic suffixed with `/set`.
+
+use serde::{Deserialize, Serialize};
+use str_derive::Str;
+use transport::metadata::Property;
+use transport::DeviceId;
+
+/// Prefix Home Assistant looks for discovery configs under.
+pub const DISCOVERY_PREFIX: &str = "homeassistant";
+
+/// Home Assistant announces itself here after a restart, expecting the
+/// discovery configs again.
+pub const STATUS_TOPIC: &str = "homeassistant/status";
+
+/// Commands for every device.
+pub const COMMAND_FILTER: &str = "lisa/+/+/+/set";
+
+const PREFIX: &str = "lisa";
+
+#[derive(Copy, Clone, Debug, Deserialize, Serialize, Str, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Field {
+    /// `ON` or `OFF` of lights and fans.
+    Power,
+    /// `heat` or `off` of thermostats.
+    Mode,
+    TargetTemperature,
+    CurrentTemperature,
+    /// Fan speed of the recuperator.
+    Preset,
+    /// JSON state of vacuum cleaners, takes vacuum commands.
+    State,
+    /// Work speed of vacuum cleaners.
+    FanSpeed,
+    /// Temperature reported by sensors.
+    Temperature,
+    Humidity,
+    Battery,
+    /// Lifetime left of the most worn consumable of vacuum cleaners.
+    Consumables,
+}
+
+impl From<Property> for Field {
+    fn from(property: Property) -> Self {
+        match property {
+            Property::Temperature => Field::Temperature,
+            Property::Humidity => Field::Humidity,
+            Property::Battery => Field::Battery,
+            Property::Consumables => Field::Consumables,
+        }
+    }
+}
+
+pub fn state_topic(device: &DeviceId, field: Field) -> String {
+    format!("{PREFIX}/{device}/{field}")
+}
+
+pub fn command_topic(device: &DeviceId, field: Field) -> String {
+    format!("{}/set", state_topic(device, field))
+}
+
+pub fn parse_command_topic(topic: &str) -> Option<(DeviceId, Field)> {
+    let topic = topic.strip_prefix(PREFIX)?.strip_prefix('/')?;
+    let (device, field) = topic.strip_suffix("/set")?.rsplit_once('/')?;
+
+    Some((device.parse().ok()?, field.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use transport::Room;
+
+    #[test]
+    fn test_topics() {
+        let device = DeviceId::thermostat_at_room(Room::new("bedroom"));
+
+        assert_eq!(
+            state_topic(&device, Field::CurrentTemperature),
+            "lisa/thermostat/bedroom/current_temperature"
+        );
+        assert_eq!(
+            command_topic(&device, Field::TargetTemperature),
+            "lisa/thermostat/bedroom/target_temperature/set"
+        );
+
+        assert_eq!(
+            parse_command_topic("lisa/thermostat/bedroom/target_temperature/set"),
+            Some((device, Field::TargetTemperature))
+        );
+        assert_eq!(parse_command_topic("lisa/thermostat/bedroom/mode"), None);
+        assert_eq!(parse_command_topic("lisa/heater/bedroom/mode/set"), None);
+        assert_eq!(parse_command_topic("state/thermostat/bedroom"), None);
+    }
+}
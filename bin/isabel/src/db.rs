@@ -35,7 +35,7 @@ impl Db {
             .prepare("INSERT INTO state (room, property, value) VALUES (?, ?, ?)")
             .unwrap();
 
-        let room = RoomSqlWrapper(*room);
+        let room = RoomSqlWrapper(room.clone());
 
         match property {
             Event::Temperature(value) => {
@@ -6,6 +6,7 @@ pub enum Error {
     Mqtt(paho_mqtt::Error),
     Json(serde_json::Error),
     Timeout(tokio::time::error::Elapsed),
+    Registry(transport::registry::Error),
 }
 
 impl From<bluetooth::Error> for Error {
@@ -32,12 +33,19 @@ impl From<tokio::time::error::Elapsed> for Error {
     }
 }
 
+impl From<transport::registry::Error> for Error {
+    fn from(err: transport::registry::Error) -> Self {
+        Self::Registry(err)
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Bluetooth(err) => write!(f, "bluetooth error: {err}"),
             Self::Mqtt(err) => write!(f, "mqtt error: {err}"),
             Self::Json(err) => write!(f, "json error: {err}"),
+            Self::Registry(err) => write!(f, "registry error: {err}"),
             Self::Timeout(err) => write!(f, "timeout error: {err}"),
         }
     }
@@ -1,18 +1,19 @@
-use bluetooth::{Event, MacAddr, Scanner, ScannerTrait};
+use bluetooth::{Event, Scanner, ScannerTrait};
 use isabel::{Db, Result};
 use transport::{
-    connect_mqtt,
-    isabel::{Property, State},
-    state::StateUpdate,
-    Room, Topic,
+    availability::heartbeat,
+    isabel::{CurrentState, Property, State},
+    publish_state,
+    registry::Registry,
+    state::{StateResponse, StateUpdate},
+    DeviceId, MqttSession,
 };
 
-use std::time::Duration;
+use std::collections::HashMap;
 
 use log::{debug, error, info};
 use paho_mqtt::AsyncClient as MqClient;
-use paho_mqtt::MessageBuilder;
-use tokio::time;
+use tokio::task;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -24,35 +25,37 @@ async fn main() -> Result<()> {
 
     let db_path = std::env::var("DB_PATH").expect("set ENV variable DB_PATH");
 
+    let registry_path = std::env::var("REGISTRY_PATH").expect("set ENV variable REGISTRY_PATH");
+    let registry = Registry::load(registry_path)?;
+
     let mqtt_address = std::env::var("MQTT_ADDRESS").expect("set ENV variable MQTT_ADDRESS");
     let mqtt_username = std::env::var("MQTT_USER").expect("set ENV variable MQTT_USER");
     let mqtt_password = std::env::var("MQTT_PASS").expect("set ENV variable MQTT_PASS");
-    let mqtt_client = connect_mqtt(mqtt_address, mqtt_username, mqtt_password, "isabel").await?;
+    let mut mqtt_session =
+        MqttSession::connect(mqtt_address, mqtt_username, mqtt_password, "isabel").await?;
+    let mqtt_client = mqtt_session.client().clone();
     info!("connected mqtt");
 
-    subscribe_state(mqtt_client, &db_path).await?;
+    task::spawn(heartbeat(mqtt_client.clone(), "isabel", VERSION));
+
+    // isabel only publishes, but the session still has to be polled to notice
+    // a lost connection and reconnect.
+    task::spawn(async move { while mqtt_session.next().await.is_some() {} });
+
+    subscribe_state(mqtt_client, &db_path, &registry).await?;
 
     Ok(())
 }
 
-async fn subscribe_state(mqtt: MqClient, db_path: &str) -> Result<()> {
+async fn subscribe_state(mqtt: MqClient, db_path: &str, registry: &Registry) -> Result<()> {
     let mut scanner = Scanner::new()?;
 
-    fn match_addr_to_room(addr: MacAddr) -> Option<Room> {
-        match addr.octets {
-            [0x58, 0x2d, 0x34, 0x39, 0x95, 0xf2] => Some(Room::Bedroom),
-            [0x4c, 0x65, 0xa8, 0xdd, 0x82, 0xcf] => Some(Room::HomeOffice),
-            [0x58, 0x2d, 0x34, 0x39, 0x97, 0x66] => Some(Room::Kitchen),
-            [0x58, 0x2d, 0x34, 0x36, 0x32, 0x9b] => Some(Room::Nursery),
-            _ => None,
-        }
-    }
-
     let mut rx = scanner.start_scan()?;
     let db = Db::new(db_path);
+    let mut sensors = HashMap::new();
 
     while let Some((addr, event)) = rx.recv().await {
-        if let Some(room) = match_addr_to_room(addr) {
+        if let Some(room) = registry.room_for_mac(&addr.octets).cloned() {
             let property = match event {
                 Event::Temperature(temperature) => Property::Temperature(temperature as f32 / 10.0),
                 Event::Humidity(humidity) => Property::Humidity(humidity as f32 / 10.0),
@@ -72,47 +75,21 @@ async fn subscribe_state(mqtt: MqClient, db_path: &str) -> Result<()> {
                 }
             }
 
+            let current_state = sensors
+                .entry(room.clone())
+                .or_insert_with(|| CurrentState::new(room.clone()));
+            current_state.apply(&property);
+
+            let device_id = DeviceId::temperature_sensor_at_room(room.clone());
             let state = State { room, property };
-            let topic = Topic::StateUpdate;
 
             debug!("sending state {:?}", state);
 
             let update = StateUpdate::Isabel(state);
+            let current_state = StateResponse::Isabel(current_state.clone());
 
-            let payload = serde_json::to_vec(&update).unwrap();
-
-            let message = MessageBuilder::new()
-                .topic(topic.to_string())
-                .payload(payload)
-                .finalize();
-
-            match mqtt.publish(message).await {
-                Ok(()) => (),
-                Err(err) => {
-                    error!("Error publishing state: {}", err);
-
-                    if !mqtt.is_connected() {
-                        time::sleep(Duration::from_secs(1)).await;
-                        error!("Lost MQTT connection. Attempting reconnect.");
-
-                        loop {
-                            match time::timeout(Duration::from_secs(10), mqtt.reconnect()).await {
-                                Ok(Ok(response)) => {
-                                    info!("Reconnected to MQTT! {}", response.reason_code());
-                                    break;
-                                }
-                                Ok(Err(err)) => {
-                                    error!("Error MQTT reconnecting: {}", err);
-                                    time::sleep(Duration::from_secs(5)).await;
-                                }
-                                Err(err) => {
-                                    error!("Error MQTT reconnecting: {}", err);
-                                    time::sleep(Duration::from_secs(5)).await;
-                                }
-                            }
-                        }
-                    }
-                }
+            if let Err(err) = publish_state(&mqtt, &update, &current_state, &[device_id]).await {
+                error!("Error publishing state: {}", err);
             }
         }
     }
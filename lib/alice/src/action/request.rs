@@ -50,7 +50,7 @@ mod tests {
         assert_eq!(devices.len(), 1);
         assert_eq!(
             devices[0].id,
-            DeviceId::thermostat_at_room(transport::Room::Kitchen)
+            DeviceId::thermostat_at_room(transport::Room::new("kitchen"))
         );
         assert_eq!(devices[0].capabilities.len(), 1);
         assert_eq!(devices[0].capabilities[0], StateCapability::on_off(true));
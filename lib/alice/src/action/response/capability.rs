@@ -38,6 +38,8 @@ pub enum ErrorCode {
     InvalidValue,
     DeviceUnreachable,
     DeviceBusy,
+    DeviceNotFound,
+    NotSupportedInCurrentMode,
 }
 
 #[derive(Debug, PartialEq)]
@@ -38,6 +38,8 @@ pub enum DeviceType {
     Light,
     #[serde(rename = "devices.types.ventilation")]
     Ventilation,
+    #[serde(rename = "devices.types.other")]
+    Other,
 }
 
 #[cfg(test)]
@@ -73,6 +75,10 @@ mod tests {
             to_value(&DeviceType::Ventilation).unwrap(),
             json!("devices.types.ventilation")
         );
+        assert_eq!(
+            to_value(&DeviceType::Other).unwrap(),
+            json!("devices.types.other")
+        );
 
         assert_eq!(
             from_value::<DeviceType>(json!("devices.types.sensor")).unwrap(),
@@ -104,7 +110,7 @@ mod tests {
     fn test_device() {
         assert_eq!(
             to_value(Device {
-                id: DeviceId::vacuum_cleaner_at_room(Room::Kitchen),
+                id: DeviceId::vacuum_cleaner_at_room(Room::new("kitchen")),
                 name: "Test Device".to_string(),
                 description: "Test Description".to_string(),
                 room: "Test Room".to_string(),
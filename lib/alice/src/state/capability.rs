@@ -46,6 +46,25 @@ impl Capability {
             relative: false,
         }
     }
+
+    /// Whether both report the same capability instance, regardless of its
+    /// value.
+    pub fn is_same_instance(&self, other: &Capability) -> bool {
+        match (self, other) {
+            (Capability::OnOff { .. }, Capability::OnOff { .. }) => true,
+            (Capability::Mode { function: lhs, .. }, Capability::Mode { function: rhs, .. }) => {
+                lhs == rhs
+            }
+            (
+                Capability::Toggle { function: lhs, .. },
+                Capability::Toggle { function: rhs, .. },
+            ) => lhs == rhs,
+            (Capability::Range { function: lhs, .. }, Capability::Range { function: rhs, .. }) => {
+                lhs == rhs
+            }
+            _ => false,
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize)]
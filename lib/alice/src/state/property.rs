@@ -3,7 +3,7 @@ use serde::Serialize;
 
 use crate::PropertyType;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Property {
     Humidity { value: f32 },
     Temperature { value: f32 },
@@ -22,6 +22,11 @@ impl Property {
     pub fn battery_level(value: f32) -> Property {
         Property::BatteryLevel { value }
     }
+
+    /// Whether both report the same property, regardless of its value.
+    pub fn is_same_instance(&self, other: &Property) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
 }
 
 impl serde::ser::Serialize for Property {
@@ -1,6 +1,7 @@
 use transport::DeviceId;
 
 use super::{Capability, Property};
+use crate::action::ErrorCode;
 use serde::Serialize;
 
 #[derive(Debug, Serialize)]
@@ -28,7 +29,7 @@ impl Response {
 
     pub fn notification_body(
         timestamp: i64,
-        user: &'static str,
+        user: String,
         devices: Vec<ResponseDevice>,
     ) -> Response {
         Response {
@@ -45,21 +46,79 @@ impl Response {
 #[derive(Debug, Serialize)]
 struct ResponsePayload {
     #[serde(rename = "user_id", skip_serializing_if = "Option::is_none")]
-    user: Option<&'static str>,
+    user: Option<String>,
 
     devices: Vec<ResponseDevice>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Clone, Debug, Serialize)]
 pub struct ResponseDevice {
     id: DeviceId,
     properties: Vec<Property>,
     capabilities: Vec<Capability>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_code: Option<ErrorCode>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_message: Option<String>,
 }
 
 impl ResponseDevice {
-    pub fn id(&self) -> DeviceId {
-        self.id
+    pub fn id(&self) -> &DeviceId {
+        &self.id
+    }
+
+    /// Takes the values of `update`, keeping those it doesn't report.
+    pub fn merge(&mut self, update: ResponseDevice) {
+        for property in update.properties {
+            match self
+                .properties
+                .iter_mut()
+                .find(|current| current.is_same_instance(&property))
+            {
+                Some(current) => *current = property,
+                None => self.properties.push(property),
+            }
+        }
+
+        for capability in update.capabilities {
+            match self
+                .capabilities
+                .iter_mut()
+                .find(|current| current.is_same_instance(&capability))
+            {
+                Some(current) => *current = capability,
+                None => self.capabilities.push(capability),
+            }
+        }
+    }
+
+    /// Values that differ from `reported`, `None` if there are none.
+    pub fn changes_since(&self, reported: &ResponseDevice) -> Option<ResponseDevice> {
+        let properties: Vec<_> = self
+            .properties
+            .iter()
+            .filter(|property| !reported.properties.contains(property))
+            .cloned()
+            .collect();
+
+        let capabilities: Vec<_> = self
+            .capabilities
+            .iter()
+            .filter(|capability| !reported.capabilities.contains(capability))
+            .cloned()
+            .collect();
+
+        if properties.is_empty() && capabilities.is_empty() {
+            None
+        } else {
+            Some(ResponseDevice::new_with_properties_and_capabilities(
+                self.id.clone(),
+                properties,
+                capabilities,
+            ))
+        }
     }
 }
 
@@ -69,6 +128,8 @@ impl ResponseDevice {
             id,
             properties: vec![],
             capabilities: vec![],
+            error_code: None,
+            error_message: None,
         }
     }
 
@@ -77,6 +138,8 @@ impl ResponseDevice {
             id,
             properties,
             capabilities: vec![],
+            error_code: None,
+            error_message: None,
         }
     }
 
@@ -85,6 +148,8 @@ impl ResponseDevice {
             id,
             capabilities,
             properties: vec![],
+            error_code: None,
+            error_message: None,
         }
     }
 
@@ -97,6 +162,99 @@ impl ResponseDevice {
             id,
             properties,
             capabilities,
+            error_code: None,
+            error_message: None,
         }
     }
+
+    pub fn new_with_error(id: DeviceId, code: ErrorCode, message: String) -> ResponseDevice {
+        ResponseDevice {
+            id,
+            properties: vec![],
+            capabilities: vec![],
+            error_code: Some(code),
+            error_message: Some(message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Mode, ModeFunction};
+    use serde_json::json;
+    use transport::Room;
+
+    #[test]
+    fn test_device_error() {
+        let device = ResponseDevice::new_with_error(
+            DeviceId::light_at_room(Room::new("nursery")),
+            ErrorCode::DeviceUnreachable,
+            "elisheba is offline".to_string(),
+        );
+
+        assert_eq!(
+            serde_json::to_value(device).unwrap(),
+            json!({
+                "id": "light/nursery",
+                "properties": [],
+                "capabilities": [],
+                "error_code": "DEVICE_UNREACHABLE",
+                "error_message": "elisheba is offline",
+            })
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let id = DeviceId::temperature_sensor_at_room(Room::new("nursery"));
+
+        let mut device = ResponseDevice::new_with_properties(
+            id.clone(),
+            vec![Property::temperature(22.0), Property::humidity(40.0)],
+        );
+        device.merge(ResponseDevice::new_with_properties(
+            id.clone(),
+            vec![Property::humidity(45.0), Property::battery_level(90.0)],
+        ));
+
+        assert_eq!(
+            device.properties,
+            vec![
+                Property::temperature(22.0),
+                Property::humidity(45.0),
+                Property::battery_level(90.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_changes_since() {
+        let id = DeviceId::recuperator_at_room(Room::new("living_room"));
+
+        let reported = ResponseDevice::new_with_capabilities(
+            id.clone(),
+            vec![
+                Capability::on_off(true),
+                Capability::mode(ModeFunction::FanSpeed, Mode::Low),
+            ],
+        );
+
+        assert!(reported.changes_since(&reported).is_none());
+
+        let device = ResponseDevice::new_with_capabilities(
+            id.clone(),
+            vec![
+                Capability::on_off(true),
+                Capability::mode(ModeFunction::FanSpeed, Mode::High),
+            ],
+        );
+
+        let changes = device.changes_since(&reported).unwrap();
+        assert_eq!(
+            changes.capabilities,
+            vec![Capability::mode(ModeFunction::FanSpeed, Mode::High)]
+        );
+        assert!(changes.properties.is_empty());
+    }
 }
@@ -12,10 +12,10 @@ impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::NoThermostatInRoom(room) => {
-                f.write_fmt(format_args!("No thermostat in room {:?}", room))
+                f.write_fmt(format_args!("No thermostat in room {}", room))
             }
             Self::NoRecuperatorInRoom(room) => {
-                f.write_fmt(format_args!("No recuperator in room {:?}", room))
+                f.write_fmt(format_args!("No recuperator in room {}", room))
             }
         }
     }
@@ -11,6 +11,7 @@ pub enum Error {
     Timeout(tokio::time::error::Elapsed),
     Rpc(RpcError),
     ConnectionClosed,
+    UnsupportedProtocol,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -26,6 +27,9 @@ pub enum DecodeError {
     UnknownVersion,
     MissingAckNonce,
     GcmDecryptFailed(aes_gcm::Error),
+    EcbDecryptFailed,
+    CbcDecryptFailed,
+    MalformedMap,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -96,6 +100,7 @@ impl fmt::Display for Error {
             Self::Timeout(err) => write!(f, "timeout error: {err}"),
             Self::Rpc(err) => write!(f, "rpc error: {err}"),
             Self::ConnectionClosed => write!(f, "connection closed"),
+            Self::UnsupportedProtocol => write!(f, "device speaks no known protocol"),
         }
     }
 }
@@ -116,6 +121,9 @@ impl fmt::Display for DecodeError {
             Self::UnknownVersion => write!(f, "unknown version"),
             Self::MissingAckNonce => write!(f, "missing ack nonce"),
             Self::GcmDecryptFailed(err) => write!(f, "gcm decrypt failed: {err}"),
+            Self::EcbDecryptFailed => write!(f, "ecb decrypt failed"),
+            Self::CbcDecryptFailed => write!(f, "cbc decrypt failed"),
+            Self::MalformedMap => write!(f, "malformed map"),
         }
     }
 }
@@ -1,13 +1,19 @@
 mod local;
+mod map;
 mod protocol;
 mod util;
 mod vacuum;
 
 mod error;
-pub use error::Error;
+pub use error::{Error, RpcError};
+
+pub use local::{LocalConnection, TcpLocalConnection};
+pub use map::{Map, Point, Segment};
+pub use protocol::LocalProtocolVersion;
 
 pub use vacuum::{
-    CleanupMode, DockErrorCode, ErrorCode, FanSpeed, State, Status, Vacuum, WashPhase, WashStatus,
+    CleanupMode, Consumable, Consumables, DockErrorCode, ErrorCode, FanSpeed, State, Status,
+    Vacuum, WashPhase, WashStatus, Zone,
 };
 
 pub type Result<T> = std::result::Result<T, Error>;
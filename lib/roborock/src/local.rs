@@ -8,14 +8,17 @@ use tokio::net::TcpStream;
 use tokio::time::timeout;
 
 use crate::protocol::{
-    decode_rpc_response, LocalCodec, LocalProtocolVersion, MessageProtocol, RoborockMessage,
-    RpcRequest,
+    a01_payload, a01_query_payload, decode_map_response, decode_rpc_response, LocalCodec,
+    LocalProtocolVersion, MessageProtocol, RoborockMessage, RpcRequest,
 };
-use crate::{Error, Result};
+use crate::{Error, Result, RpcError};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 const DEFAULT_PORT: u16 = 58867;
 const READ_TIMEOUT: Duration = Duration::from_secs(15);
+/// Time for the device to answer a hello of a version it speaks, those of
+/// other versions are left unanswered.
+const HELLO_TIMEOUT: Duration = Duration::from_secs(5);
 #[allow(dead_code)]
 const PING_INTERVAL: Duration = Duration::from_secs(10);
 
@@ -64,22 +67,35 @@ where
             protocol_version: LocalProtocolVersion::L01,
         };
 
-        debug!("roborock local connect: trying L01 hello");
-        let ack_nonce = connection.hello(hello_seq, hello_random).await?;
-        debug!(
-            "roborock local connect: L01 hello ok, ack_nonce={}",
-            ack_nonce
-        );
-        connection.codec = connection.codec.with_ack_nonce(ack_nonce);
-        connection.protocol_version = LocalProtocolVersion::L01;
+        for version in LocalProtocolVersion::ALL {
+            debug!("roborock local connect: trying {:?} hello", version);
+
+            let hello = connection.hello(version, hello_seq, hello_random);
+            let Ok(result) = timeout(HELLO_TIMEOUT, hello).await else {
+                debug!("roborock local connect: {:?} hello timed out", version);
+                continue;
+            };
 
-        Ok(connection)
+            let (version, ack_nonce) = result?;
+            debug!(
+                "roborock local connect: {:?} hello ok, ack_nonce={}",
+                version, ack_nonce
+            );
+            connection.codec = connection.codec.with_ack_nonce(ack_nonce);
+            connection.protocol_version = version;
+
+            return Ok(connection);
+        }
+
+        Err(Error::UnsupportedProtocol)
     }
 
     pub fn protocol_version(&self) -> LocalProtocolVersion {
         self.protocol_version
     }
 
+    /// Calls `method` of an L01 or 1.0 device, A01 ones exchange data points
+    /// instead.
     pub async fn send_rpc(
         &mut self,
         request_id: u32,
@@ -88,6 +104,10 @@ where
         method: &str,
         params: serde_json::Value,
     ) -> Result<serde_json::Value> {
+        if self.protocol_version == LocalProtocolVersion::A01 {
+            return Err(RpcError::UnknownMethod.into());
+        }
+
         let request = RpcRequest::new(request_id, method, params);
         let payload = request.to_payload()?;
         let payload_log = String::from_utf8_lossy(&payload).to_string();
@@ -124,7 +144,7 @@ where
                     continue;
                 }
             };
-            let rpc_response = decode_rpc_response(&payload)?;
+            let rpc_response = decode_rpc_response(self.protocol_version, &payload)?;
             if rpc_response.id == Some(request_id) {
                 debug!("roborock rpc match: request_id={}", request_id);
                 debug!("roborock rpc result: {}", rpc_response.result);
@@ -140,6 +160,137 @@ where
         }
     }
 
+    /// Gunzipped map of an L01 or 1.0 device, which sends it encrypted with
+    /// `nonce` in a map response rather than answering the call.
+    pub async fn get_map(
+        &mut self,
+        request_id: u32,
+        seq: u32,
+        random: u32,
+        endpoint: &str,
+        nonce: [u8; 16],
+    ) -> Result<Vec<u8>> {
+        if self.protocol_version == LocalProtocolVersion::A01 {
+            return Err(RpcError::UnknownMethod.into());
+        }
+
+        let request = RpcRequest::new(request_id, "get_map_v1", serde_json::json!([]))
+            .with_security(endpoint, &nonce);
+        let message = RoborockMessage::new(
+            self.protocol_version,
+            MessageProtocol::GeneralRequest,
+            seq,
+            random,
+            Some(request.to_payload()?),
+        );
+
+        debug!("roborock map send: request_id={}", request_id);
+        self.send_message(message).await?;
+
+        loop {
+            let response = self.next_message().await?;
+            let Some(payload) = response.payload else {
+                continue;
+            };
+
+            match response.protocol {
+                MessageProtocol::MapResponse => {
+                    let (response_id, map) = decode_map_response(&payload, &nonce)?;
+                    if response_id == request_id as u16 {
+                        debug!("roborock map recv: bytes={}", map.len());
+                        return Ok(map);
+                    }
+                    debug!(
+                        "roborock map ignore: response_id={}, request_id={}",
+                        response_id, request_id
+                    );
+                }
+                MessageProtocol::GeneralResponse | MessageProtocol::RpcResponse => {
+                    let rpc_response = decode_rpc_response(self.protocol_version, &payload)?;
+                    if rpc_response.id == Some(request_id) {
+                        if let Some(error) = rpc_response.error {
+                            return Err(error.into());
+                        }
+                    }
+                }
+                _ => debug!("roborock map skip: protocol={:?}", response.protocol),
+            }
+        }
+    }
+
+    /// Values of the data points of an A01 device, keyed by their ids.
+    pub async fn query_data_points(
+        &mut self,
+        seq: u32,
+        random: u32,
+        data_points: &[u32],
+    ) -> Result<serde_json::Value> {
+        let payload = a01_query_payload(data_points)?;
+        self.send_data_points(seq, random, payload, data_points)
+            .await
+    }
+
+    /// Sets the data points of an A01 device, returning those it reports
+    /// back.
+    pub async fn set_data_points(
+        &mut self,
+        seq: u32,
+        random: u32,
+        data_points: &[(u32, serde_json::Value)],
+    ) -> Result<serde_json::Value> {
+        let payload = a01_payload(data_points)?;
+        let ids: Vec<_> = data_points.iter().map(|(id, _)| *id).collect();
+        self.send_data_points(seq, random, payload, &ids).await
+    }
+
+    /// A01 responses carry no id of the request, and the device pushes its
+    /// changed data points on its own, so the response is the first message
+    /// reporting every one of `ids`.
+    async fn send_data_points(
+        &mut self,
+        seq: u32,
+        random: u32,
+        payload: Vec<u8>,
+        ids: &[u32],
+    ) -> Result<serde_json::Value> {
+        debug!(
+            "roborock a01 send: payload={}",
+            String::from_utf8_lossy(&payload)
+        );
+        let message = RoborockMessage::new(
+            LocalProtocolVersion::A01,
+            MessageProtocol::RpcRequest,
+            seq,
+            random,
+            Some(payload),
+        );
+        self.send_message(message).await?;
+
+        loop {
+            let response = self.next_message().await?;
+            if response.protocol != MessageProtocol::RpcResponse {
+                debug!("roborock a01 skip: protocol={:?}", response.protocol);
+                continue;
+            }
+            let Some(payload) = response.payload else {
+                debug!("roborock a01 skip: empty payload");
+                continue;
+            };
+
+            let response = decode_rpc_response(LocalProtocolVersion::A01, &payload)?;
+            let answers = ids
+                .iter()
+                .all(|id| response.result.get(id.to_string()).is_some());
+            if !answers {
+                debug!("roborock a01 skip: result={}", response.result);
+                continue;
+            }
+
+            debug!("roborock a01 result: {}", response.result);
+            return Ok(response.result);
+        }
+    }
+
     #[allow(dead_code)]
     pub async fn ping(&mut self, seq: u32, random: u32) -> Result<()> {
         let message = RoborockMessage::new(
@@ -174,9 +325,15 @@ where
         }
     }
 
-    async fn hello(&mut self, seq: u32, random: u32) -> Result<u32> {
+    /// The version the device answers with and its ack nonce.
+    async fn hello(
+        &mut self,
+        version: LocalProtocolVersion,
+        seq: u32,
+        random: u32,
+    ) -> Result<(LocalProtocolVersion, u32)> {
         let message = RoborockMessage {
-            version: LocalProtocolVersion::L01,
+            version,
             seq,
             random,
             timestamp: unix_timestamp(),
@@ -184,15 +341,18 @@ where
             payload: None,
         };
         let seq = message.seq;
-        debug!("roborock hello send: version=L01, seq={}", seq);
+        debug!("roborock hello send: version={:?}, seq={}", version, seq);
         self.send_message(message).await?;
 
         loop {
             let response = self.next_message().await?;
             if response.protocol == MessageProtocol::HelloResponse && response.seq == seq {
-                debug!("roborock hello recv: version=L01, seq={}", seq);
-                info!("connected with protocol L01");
-                return Ok(response.random);
+                debug!(
+                    "roborock hello recv: version={:?}, seq={}",
+                    response.version, seq
+                );
+                info!("connected with protocol {:?}", response.version);
+                return Ok((response.version, response.random));
             }
             debug!(
                 "roborock hello skip: protocol={:?}, seq={}, expect_seq={}",
@@ -316,6 +476,54 @@ mod tests {
         server_task.await.unwrap();
     }
 
+    #[tokio::test(start_paused = true)]
+    async fn test_connect_v1_hello() {
+        let local_key = "0123456789abcdef".to_string();
+        let connect_nonce = 11111;
+        let ack_nonce = 22222;
+        let hello_seq = 100;
+        let hello_random = 200;
+        let (client, mut server) = duplex(4096);
+
+        let server_key = local_key.clone();
+        let server_task = tokio::spawn(async move {
+            let codec = LocalCodec::new(server_key, connect_nonce, None);
+            let mut buffer = Vec::new();
+
+            // A 1.0 device doesn't answer an L01 hello.
+            let request = read_next(&codec, &mut server, &mut buffer).await;
+            assert_eq!(request.protocol, MessageProtocol::HelloRequest);
+            assert_eq!(request.version, LocalProtocolVersion::L01);
+
+            let request = read_next(&codec, &mut server, &mut buffer).await;
+            assert_eq!(request.protocol, MessageProtocol::HelloRequest);
+            assert_eq!(request.version, LocalProtocolVersion::V1);
+
+            let response = RoborockMessage {
+                version: request.version,
+                seq: request.seq,
+                random: ack_nonce,
+                timestamp: request.timestamp,
+                protocol: MessageProtocol::HelloResponse,
+                payload: None,
+            };
+            let frame = codec.build_message(&response).unwrap();
+            server.write_all(&frame).await.unwrap();
+        });
+
+        let connection = LocalConnection::connect_with_stream(
+            client,
+            local_key,
+            connect_nonce,
+            hello_seq,
+            hello_random,
+        )
+        .await
+        .unwrap();
+        assert_eq!(connection.protocol_version(), LocalProtocolVersion::V1);
+        server_task.await.unwrap();
+    }
+
     #[tokio::test]
     async fn test_rpc_roundtrip() {
         let local_key = "0123456789abcdef".to_string();
@@ -388,4 +596,85 @@ mod tests {
         assert_eq!(response["ok"], true);
         server_task.await.unwrap();
     }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_a01_data_points() {
+        let local_key = "0123456789abcdef".to_string();
+        let connect_nonce = 12345;
+        let ack_nonce = 44444;
+        let (client, mut server) = duplex(4096);
+
+        let server_key = local_key.clone();
+        let server_task = tokio::spawn(async move {
+            let codec = LocalCodec::new(server_key, connect_nonce, None);
+            let mut buffer = Vec::new();
+
+            let hello = loop {
+                let hello = read_next(&codec, &mut server, &mut buffer).await;
+                assert_eq!(hello.protocol, MessageProtocol::HelloRequest);
+                if hello.version == LocalProtocolVersion::A01 {
+                    break hello;
+                }
+            };
+            let response = RoborockMessage {
+                version: hello.version,
+                seq: hello.seq,
+                random: ack_nonce,
+                timestamp: hello.timestamp,
+                protocol: MessageProtocol::HelloResponse,
+                payload: None,
+            };
+            let frame = codec.build_message(&response).unwrap();
+            server.write_all(&frame).await.unwrap();
+
+            let request = read_next(&codec, &mut server, &mut buffer).await;
+            assert_eq!(request.version, LocalProtocolVersion::A01);
+            assert_eq!(request.protocol, MessageProtocol::RpcRequest);
+            let payload: serde_json::Value =
+                serde_json::from_slice(&request.payload.unwrap()).unwrap();
+            assert_eq!(payload, json!({ "dps": { "10000": "[200,203]" } }));
+
+            // A data point the device pushes on its own isn't the response.
+            let push = RoborockMessage {
+                version: request.version,
+                seq: 900,
+                random: request.random,
+                timestamp: request.timestamp,
+                protocol: MessageProtocol::RpcResponse,
+                payload: Some(serde_json::to_vec(&json!({ "dps": { "200": 5 }, "t": 1 })).unwrap()),
+            };
+            let frame = codec.build_message(&push).unwrap();
+            server.write_all(&frame).await.unwrap();
+
+            let response_payload = json!({ "dps": { "200": 1, "203": 0 }, "t": 1 });
+            let response = RoborockMessage {
+                version: request.version,
+                seq: request.seq,
+                random: request.random,
+                timestamp: request.timestamp,
+                protocol: MessageProtocol::RpcResponse,
+                payload: Some(serde_json::to_vec(&response_payload).unwrap()),
+            };
+            let frame = codec.build_message(&response).unwrap();
+            server.write_all(&frame).await.unwrap();
+        });
+
+        let mut connection =
+            LocalConnection::connect_with_stream(client, local_key, connect_nonce, 1, 2)
+                .await
+                .unwrap();
+        assert_eq!(connection.protocol_version(), LocalProtocolVersion::A01);
+
+        let data_points = connection
+            .query_data_points(3, 4, &[200, 203])
+            .await
+            .unwrap();
+        assert_eq!(data_points, json!({ "200": 1, "203": 0 }));
+
+        assert!(connection
+            .send_rpc(5, 6, 7, "get_status", json!([]))
+            .await
+            .is_err());
+        server_task.await.unwrap();
+    }
 }
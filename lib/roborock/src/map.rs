@@ -0,0 +1,277 @@
+use std::collections::BTreeMap;
+
+use crate::error::DecodeError;
+use crate::Result;
+
+const MAGIC: &[u8] = b"rr";
+const IMAGE_BLOCK: u16 = 2;
+/// Millimetres per pixel of the map image.
+const PIXEL_SIZE: i32 = 50;
+
+/// Map the device answers `get_map_v1` with, once gunzipped: a header
+/// followed by blocks of the image, the path, the zones and so on. Only the
+/// image, whose pixels tell the segment they belong to, is decoded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Map {
+    pub segments: Vec<Segment>,
+}
+
+/// Room of the map the device cleans with `app_segment_clean`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub id: u8,
+    /// Convex hull of the segment in millimetres, counterclockwise.
+    pub polygon: Vec<Point>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
+}
+
+struct Image<'a> {
+    top: i32,
+    left: i32,
+    width: usize,
+    height: usize,
+    pixels: &'a [u8],
+}
+
+impl Map {
+    pub fn parse(data: &[u8]) -> Result<Map> {
+        if data.len() < 4 || &data[0..2] != MAGIC {
+            return Err(DecodeError::MalformedMap.into());
+        }
+
+        let mut position = read_u16(data, 2)? as usize;
+        let mut segments = vec![];
+
+        while position < data.len() {
+            let block_type = read_u16(data, position)?;
+            let header_len = read_u16(data, position + 2)? as usize;
+            let data_len = read_u32(data, position + 4)? as usize;
+
+            let end = position + header_len + data_len;
+            if end > data.len() {
+                return Err(DecodeError::MalformedMap.into());
+            }
+
+            if block_type == IMAGE_BLOCK {
+                let image = Image::parse(&data[position..end], header_len)?;
+                segments = image.segments();
+            }
+
+            position = end;
+        }
+
+        Ok(Map { segments })
+    }
+}
+
+impl<'a> Image<'a> {
+    fn parse(block: &'a [u8], header_len: usize) -> Result<Image<'a>> {
+        // Newer firmwares add the count of segments before the dimensions.
+        let offset = if header_len > 24 { 4 } else { 0 };
+
+        let top = read_u32(block, 8 + offset)? as i32;
+        let left = read_u32(block, 12 + offset)? as i32;
+        let height = read_u32(block, 16 + offset)? as usize;
+        let width = read_u32(block, 20 + offset)? as usize;
+
+        let pixels = &block[header_len..];
+        if pixels.len() < width * height {
+            return Err(DecodeError::MalformedMap.into());
+        }
+
+        Ok(Image {
+            top,
+            left,
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    fn segments(&self) -> Vec<Segment> {
+        // Leftmost and rightmost pixels of every row of a segment.
+        let mut rows: BTreeMap<u8, Vec<(usize, usize, usize)>> = BTreeMap::new();
+
+        for row in 0..self.height {
+            let line = &self.pixels[row * self.width..(row + 1) * self.width];
+
+            for (column, pixel) in line.iter().enumerate() {
+                if pixel & 0x07 != 0x07 {
+                    continue;
+                }
+
+                let bounds = rows.entry(pixel >> 3).or_default();
+                match bounds.last_mut() {
+                    Some((last_row, _, max)) if *last_row == row => *max = column,
+                    _ => bounds.push((row, column, column)),
+                }
+            }
+        }
+
+        rows.into_iter()
+            .map(|(id, bounds)| {
+                let corners = bounds
+                    .into_iter()
+                    .flat_map(|(row, min, max)| {
+                        [
+                            (min, row),
+                            (min, row + 1),
+                            (max + 1, row),
+                            (max + 1, row + 1),
+                        ]
+                    })
+                    .map(|(x, y)| Point {
+                        x: (self.left + x as i32) * PIXEL_SIZE,
+                        y: (self.top + y as i32) * PIXEL_SIZE,
+                    })
+                    .collect();
+
+                Segment {
+                    id,
+                    polygon: convex_hull(corners),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Andrew's monotone chain, dropping collinear points.
+fn convex_hull(mut points: Vec<Point>) -> Vec<Point> {
+    points.sort();
+    points.dedup();
+
+    if points.len() < 3 {
+        return points;
+    }
+
+    let cross = |o: Point, a: Point, b: Point| {
+        (a.x - o.x) as i64 * (b.y - o.y) as i64 - (a.y - o.y) as i64 * (b.x - o.x) as i64
+    };
+
+    let mut hull: Vec<Point> = Vec::with_capacity(points.len() * 2);
+
+    for pass in [points.clone(), points.into_iter().rev().collect()] {
+        let start = hull.len();
+
+        for point in pass {
+            while hull.len() >= start + 2
+                && cross(hull[hull.len() - 2], hull[hull.len() - 1], point) <= 0
+            {
+                hull.pop();
+            }
+            hull.push(point);
+        }
+
+        hull.pop();
+    }
+
+    hull
+}
+
+fn read_u16(data: &[u8], position: usize) -> Result<u16> {
+    data.get(position..position + 2)
+        .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+        .ok_or(DecodeError::MalformedMap.into())
+}
+
+fn read_u32(data: &[u8], position: usize) -> Result<u32> {
+    data.get(position..position + 4)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or(DecodeError::MalformedMap.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROOM: u8 = (16 << 3) | 0x07;
+    const OTHER_ROOM: u8 = (17 << 3) | 0x07;
+    const WALL: u8 = 0x01;
+
+    fn block(block_type: u16, header: &[u8], data: &[u8]) -> Vec<u8> {
+        let header_len = 8 + header.len();
+        let mut block = Vec::new();
+        block.extend_from_slice(&block_type.to_le_bytes());
+        block.extend_from_slice(&(header_len as u16).to_le_bytes());
+        block.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        block.extend_from_slice(header);
+        block.extend_from_slice(data);
+        block
+    }
+
+    fn image(top: i32, left: i32, rows: &[&[u8]]) -> Vec<u8> {
+        let mut header = Vec::new();
+        header.extend_from_slice(&0u32.to_le_bytes()); // segment count
+        for value in [top, left, rows.len() as i32, rows[0].len() as i32] {
+            header.extend_from_slice(&value.to_le_bytes());
+        }
+        block(IMAGE_BLOCK, &header, &rows.concat())
+    }
+
+    fn map(blocks: &[Vec<u8>]) -> Vec<u8> {
+        let mut map = b"rr".to_vec();
+        map.extend_from_slice(&20u16.to_le_bytes());
+        map.resize(20, 0);
+        map.extend_from_slice(&blocks.concat());
+        map
+    }
+
+    fn point(x: i32, y: i32) -> Point {
+        Point { x, y }
+    }
+
+    #[test]
+    fn test_segments() {
+        let data = map(&[
+            block(1, &[0; 4], &[0; 8]), // charger
+            image(
+                100,
+                200,
+                &[
+                    &[WALL, ROOM, ROOM, WALL, 0],
+                    &[WALL, ROOM, ROOM, WALL, OTHER_ROOM],
+                    &[WALL, ROOM, WALL, WALL, 0],
+                ],
+            ),
+        ]);
+
+        let map = Map::parse(&data).unwrap();
+
+        assert_eq!(map.segments.len(), 2);
+        assert_eq!(map.segments[0].id, 16);
+        assert_eq!(
+            map.segments[0].polygon,
+            vec![
+                point(10_050, 5_000),
+                point(10_150, 5_000),
+                point(10_150, 5_100),
+                point(10_100, 5_150),
+                point(10_050, 5_150),
+            ]
+        );
+        assert_eq!(map.segments[1].id, 17);
+        assert_eq!(
+            map.segments[1].polygon,
+            vec![
+                point(10_200, 5_050),
+                point(10_250, 5_050),
+                point(10_250, 5_100),
+                point(10_200, 5_100),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_malformed() {
+        assert!(Map::parse(b"xx\x14\x00").is_err());
+
+        let mut data = map(&[image(0, 0, &[&[ROOM, ROOM]])]);
+        data.truncate(data.len() - 1);
+        assert!(Map::parse(&data).is_err());
+    }
+}
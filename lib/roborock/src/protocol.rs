@@ -1,24 +1,49 @@
+use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use aes::Aes128;
 use aes_gcm::{aead::Aead, aead::KeyInit, Aes256Gcm, Nonce};
 use crc32fast::Hasher as Crc32;
+use flate2::read::GzDecoder;
 use log::debug;
+use md5::Md5;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::io::Read;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use crate::error::{DecodeError, EncodeError, RpcError};
 use crate::{Error, Result};
 
 const SALT: &[u8] = b"TXdfu$jyZ#TZHsg4";
+const A01_SALT: &str = "726f626f726f636b2d67a6d6da";
+const MAP_RESPONSE_HEADER_LEN: usize = 24;
+
+/// Data point A01 devices answer with the values of the data points listed
+/// in it.
+pub const A01_QUERY: u32 = 10000;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LocalProtocolVersion {
+    /// AES-128-ECB with MD5 derived keys, spoken by older models.
+    V1,
+    /// AES-256-GCM with SHA-256 derived keys.
     L01,
+    /// AES-128-CBC keyed with the local key, spoken by newer product lines
+    /// exchanging data points instead of RPC calls.
+    A01,
 }
 
 impl LocalProtocolVersion {
+    pub const ALL: [LocalProtocolVersion; 3] = [
+        LocalProtocolVersion::L01,
+        LocalProtocolVersion::V1,
+        LocalProtocolVersion::A01,
+    ];
+
     pub fn as_bytes(self) -> [u8; 3] {
         match self {
+            LocalProtocolVersion::V1 => *b"1.0",
             LocalProtocolVersion::L01 => *b"L01",
+            LocalProtocolVersion::A01 => *b"A01",
         }
     }
 }
@@ -34,6 +59,7 @@ pub enum MessageProtocol {
     GeneralResponse = 5,
     RpcRequest = 101,
     RpcResponse = 102,
+    MapResponse = 301,
 }
 
 #[derive(Debug, Clone)]
@@ -137,8 +163,8 @@ impl LocalCodec {
                 break;
             }
 
-            if buffer.len() >= 7 && &buffer[4..7] != b"L01" {
-                if let Some((prefix_index, data_index)) = find_l01_prefix(buffer) {
+            if buffer.len() >= 7 && parse_version(&buffer[4..7]).is_err() {
+                if let Some((prefix_index, data_index)) = find_version_prefix(buffer) {
                     if prefix_index > 0 {
                         debug!("roborock resync: skipping {} bytes", prefix_index);
                         buffer.drain(0..prefix_index);
@@ -238,6 +264,7 @@ impl LocalCodec {
                 5 => MessageProtocol::GeneralResponse,
                 101 => MessageProtocol::RpcRequest,
                 102 => MessageProtocol::RpcResponse,
+                301 => MessageProtocol::MapResponse,
                 _ => return Err(DecodeError::UnknownProtocol.into()),
             };
 
@@ -260,6 +287,16 @@ pub struct RpcRequest {
     pub id: u32,
     pub method: String,
     pub params: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub security: Option<RpcSecurity>,
+}
+
+/// Endpoint and nonce of a request the device answers with a
+/// [`MessageProtocol::MapResponse`] encrypted with the nonce.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RpcSecurity {
+    pub endpoint: String,
+    pub nonce: String,
 }
 
 impl RpcRequest {
@@ -268,15 +305,27 @@ impl RpcRequest {
             id,
             method: method.into(),
             params,
+            security: None,
         }
     }
 
+    pub fn with_security(mut self, endpoint: &str, nonce: &[u8; 16]) -> Self {
+        self.security = Some(RpcSecurity {
+            endpoint: endpoint.to_string(),
+            nonce: hex_bytes(nonce),
+        });
+        self
+    }
+
     pub fn to_payload(&self) -> Result<Vec<u8>> {
-        let inner = serde_json::json!({
+        let mut inner = serde_json::json!({
             "id": self.id,
             "method": self.method,
             "params": self.params,
         });
+        if let Some(security) = &self.security {
+            inner["security"] = serde_json::to_value(security)?;
+        }
 
         let outer = serde_json::json!({
             "dps": {"101": serde_json::to_string(&inner)?},
@@ -287,6 +336,22 @@ impl RpcRequest {
     }
 }
 
+/// Payload of an A01 request setting the data points to their values.
+pub fn a01_payload(data_points: &[(u32, serde_json::Value)]) -> Result<Vec<u8>> {
+    let dps: serde_json::Map<_, _> = data_points
+        .iter()
+        .map(|(id, value)| (id.to_string(), value.clone()))
+        .collect();
+
+    Ok(serde_json::to_vec(&serde_json::json!({ "dps": dps }))?)
+}
+
+/// Payload of an A01 request for the values of the data points.
+pub fn a01_query_payload(data_points: &[u32]) -> Result<Vec<u8>> {
+    let query = serde_json::to_string(data_points)?;
+    a01_payload(&[(A01_QUERY, serde_json::Value::String(query))])
+}
+
 #[derive(Debug, Clone)]
 pub struct RpcResponse {
     pub id: Option<u32>,
@@ -294,12 +359,29 @@ pub struct RpcResponse {
     pub error: Option<RpcError>,
 }
 
-pub fn decode_rpc_response(payload: &[u8]) -> Result<RpcResponse> {
+/// A01 devices answer with their data points, returned as the result keyed
+/// by their ids, with no id of the request.
+pub fn decode_rpc_response(version: LocalProtocolVersion, payload: &[u8]) -> Result<RpcResponse> {
     let payload: serde_json::Value = serde_json::from_slice(payload)?;
     let dps = payload
         .get("dps")
         .and_then(|value| value.as_object())
         .ok_or(DecodeError::MissingDps)?;
+
+    if version == LocalProtocolVersion::A01 {
+        let data_points = dps
+            .iter()
+            .filter(|(id, _)| id.parse::<u32>().is_ok())
+            .map(|(id, value)| (id.clone(), value.clone()))
+            .collect();
+
+        return Ok(RpcResponse {
+            id: None,
+            result: serde_json::Value::Object(data_points),
+            error: None,
+        });
+    }
+
     let data_point = dps
         .get("102")
         .and_then(|value| value.as_str())
@@ -352,9 +434,32 @@ pub fn decode_rpc_response(payload: &[u8]) -> Result<RpcResponse> {
     Ok(RpcResponse { id, result, error })
 }
 
+/// Request id and gunzipped map of a [`MessageProtocol::MapResponse`]: a
+/// 24 bytes header with the endpoint and the id, then the gzipped map
+/// encrypted with AES-128-CBC keyed with the nonce of the request.
+pub fn decode_map_response(payload: &[u8], nonce: &[u8; 16]) -> Result<(u16, Vec<u8>)> {
+    if payload.len() < MAP_RESPONSE_HEADER_LEN {
+        return Err(DecodeError::MalformedMap.into());
+    }
+
+    let request_id = u16::from_le_bytes([payload[16], payload[17]]);
+    let encrypted = &payload[MAP_RESPONSE_HEADER_LEN..];
+
+    let gzipped = MapDecryptor::new(nonce.into(), &[0u8; 16].into())
+        .decrypt_padded_vec_mut::<Pkcs7>(encrypted)
+        .map_err(|_| DecodeError::CbcDecryptFailed)?;
+
+    let mut map = Vec::new();
+    GzDecoder::new(gzipped.as_slice()).read_to_end(&mut map)?;
+
+    Ok((request_id, map))
+}
+
 fn parse_version(bytes: &[u8]) -> Result<LocalProtocolVersion> {
     match bytes {
+        b"1.0" => Ok(LocalProtocolVersion::V1),
         b"L01" => Ok(LocalProtocolVersion::L01),
+        b"A01" => Ok(LocalProtocolVersion::A01),
         _ => Err(DecodeError::UnknownVersion.into()),
     }
 }
@@ -381,10 +486,10 @@ fn hex_bytes(data: &[u8]) -> String {
     out
 }
 
-fn find_l01_prefix(buffer: &[u8]) -> Option<(usize, usize)> {
+fn find_version_prefix(buffer: &[u8]) -> Option<(usize, usize)> {
     buffer
         .windows(3)
-        .position(|window| window == b"L01")
+        .position(|window| parse_version(window).is_ok())
         .map(|index| {
             if index >= 4 {
                 (index - 4, index)
@@ -416,6 +521,8 @@ fn encrypt_payload(
     payload: &[u8],
 ) -> Result<Vec<u8>> {
     match version {
+        LocalProtocolVersion::V1 => Ok(encrypt_ecb_v1(local_key, timestamp, payload)),
+        LocalProtocolVersion::A01 => encrypt_cbc_a01(local_key, nonce, payload),
         LocalProtocolVersion::L01 => encrypt_gcm_l01(
             local_key,
             timestamp,
@@ -440,6 +547,8 @@ fn decrypt_payload(
     payload: &[u8],
 ) -> Result<Vec<u8>> {
     match version {
+        LocalProtocolVersion::V1 => decrypt_ecb_v1(local_key, timestamp, payload),
+        LocalProtocolVersion::A01 => decrypt_cbc_a01(local_key, nonce, payload),
         LocalProtocolVersion::L01 => decrypt_gcm_l01(
             local_key,
             timestamp,
@@ -452,6 +561,57 @@ fn decrypt_payload(
     }
 }
 
+fn v1_key(local_key: &str, timestamp: u32) -> [u8; 16] {
+    let mut data = Vec::with_capacity(8 + local_key.len() + SALT.len());
+    data.extend_from_slice(&encode_timestamp(timestamp));
+    data.extend_from_slice(local_key.as_bytes());
+    data.extend_from_slice(SALT);
+    let digest = Md5::digest(&data);
+    digest.into()
+}
+
+fn encrypt_ecb_v1(local_key: &str, timestamp: u32, payload: &[u8]) -> Vec<u8> {
+    let key = v1_key(local_key, timestamp);
+    ecb::Encryptor::<Aes128>::new(&key.into()).encrypt_padded_vec_mut::<Pkcs7>(payload)
+}
+
+fn decrypt_ecb_v1(local_key: &str, timestamp: u32, payload: &[u8]) -> Result<Vec<u8>> {
+    let key = v1_key(local_key, timestamp);
+    let decrypted = ecb::Decryptor::<Aes128>::new(&key.into())
+        .decrypt_padded_vec_mut::<Pkcs7>(payload)
+        .map_err(|_| DecodeError::EcbDecryptFailed)?;
+    Ok(decrypted)
+}
+
+/// Hex digits of the MD5 digest of the nonce, used as they are.
+fn a01_iv(nonce: u32) -> [u8; 16] {
+    let digest = Md5::digest(format!("{:08x}{}", nonce, A01_SALT));
+    let mut out = [0u8; 16];
+    out.copy_from_slice(&hex_bytes(&digest).as_bytes()[8..24]);
+    out
+}
+
+type A01Encryptor = cbc::Encryptor<Aes128>;
+type A01Decryptor = cbc::Decryptor<Aes128>;
+type MapDecryptor = cbc::Decryptor<Aes128>;
+
+fn encrypt_cbc_a01(local_key: &str, nonce: u32, payload: &[u8]) -> Result<Vec<u8>> {
+    let iv = a01_iv(nonce);
+    let cipher = A01Encryptor::new_from_slices(local_key.as_bytes(), &iv)
+        .map_err(|_| Error::CryptoKeyLength)?;
+    Ok(cipher.encrypt_padded_vec_mut::<Pkcs7>(payload))
+}
+
+fn decrypt_cbc_a01(local_key: &str, nonce: u32, payload: &[u8]) -> Result<Vec<u8>> {
+    let iv = a01_iv(nonce);
+    let cipher = A01Decryptor::new_from_slices(local_key.as_bytes(), &iv)
+        .map_err(|_| Error::CryptoKeyLength)?;
+    let decrypted = cipher
+        .decrypt_padded_vec_mut::<Pkcs7>(payload)
+        .map_err(|_| DecodeError::CbcDecryptFailed)?;
+    Ok(decrypted)
+}
+
 fn l01_key(local_key: &str, timestamp: u32) -> [u8; 32] {
     let mut data = Vec::with_capacity(8 + local_key.len() + SALT.len());
     data.extend_from_slice(&encode_timestamp(timestamp));
@@ -580,6 +740,101 @@ mod tests {
         assert_eq!(decoded.payload.as_deref(), message.payload.as_deref());
     }
 
+    #[test]
+    fn test_v1_roundtrip() {
+        let codec = LocalCodec::new("0123456789abcdef".to_string(), 12345, None);
+        let message = RoborockMessage {
+            version: LocalProtocolVersion::V1,
+            seq: 43,
+            random: 4343,
+            timestamp: 1_700_000_050,
+            protocol: MessageProtocol::GeneralRequest,
+            payload: Some(b"{\"hello\":1}".to_vec()),
+        };
+
+        let frame = codec.build_message(&message).unwrap();
+        assert_eq!(&frame[4..7], b"1.0");
+        // Padded to the 16 bytes AES block.
+        assert_eq!(u16::from_be_bytes([frame[21], frame[22]]), 16);
+
+        let mut buffer = frame.clone();
+        let decoded = codec.decode_messages(&mut buffer).unwrap();
+        assert_eq!(decoded.len(), 1);
+        let decoded = &decoded[0];
+        assert_eq!(decoded.version, message.version);
+        assert_eq!(decoded.seq, message.seq);
+        assert_eq!(decoded.random, message.random);
+        assert_eq!(decoded.timestamp, message.timestamp);
+        assert_eq!(decoded.protocol, message.protocol);
+        assert_eq!(decoded.payload.as_deref(), message.payload.as_deref());
+    }
+
+    #[test]
+    fn test_v1_wrong_key() {
+        let codec = LocalCodec::new("0123456789abcdef".to_string(), 12345, None);
+        let message = RoborockMessage {
+            version: LocalProtocolVersion::V1,
+            seq: 44,
+            random: 4444,
+            timestamp: 1_700_000_060,
+            protocol: MessageProtocol::GeneralRequest,
+            payload: Some(b"{\"hello\":1}".to_vec()),
+        };
+
+        let mut buffer = codec.build_message(&message).unwrap();
+        let codec = LocalCodec::new("fedcba9876543210".to_string(), 12345, None);
+        assert!(codec.decode_messages(&mut buffer).is_err());
+    }
+
+    #[test]
+    fn test_a01_roundtrip() {
+        let codec = LocalCodec::new("0123456789abcdef".to_string(), 12345, None);
+        let message = RoborockMessage {
+            version: LocalProtocolVersion::A01,
+            seq: 45,
+            random: 4545,
+            timestamp: 1_700_000_070,
+            protocol: MessageProtocol::RpcRequest,
+            payload: Some(a01_query_payload(&[200, 201]).unwrap()),
+        };
+
+        let frame = codec.build_message(&message).unwrap();
+        assert_eq!(&frame[4..7], b"A01");
+
+        let mut buffer = frame.clone();
+        let decoded = codec.decode_messages(&mut buffer).unwrap();
+        assert_eq!(decoded.len(), 1);
+        let decoded = &decoded[0];
+        assert_eq!(decoded.version, message.version);
+        assert_eq!(decoded.seq, message.seq);
+        assert_eq!(decoded.random, message.random);
+        assert_eq!(decoded.timestamp, message.timestamp);
+        assert_eq!(decoded.protocol, message.protocol);
+        assert_eq!(decoded.payload.as_deref(), message.payload.as_deref());
+
+        let payload: serde_json::Value =
+            serde_json::from_slice(decoded.payload.as_deref().unwrap()).unwrap();
+        assert_eq!(payload, serde_json::json!({"dps": {"10000": "[200,201]"}}));
+    }
+
+    #[test]
+    fn test_a01_key_length() {
+        let codec = LocalCodec::new("short".to_string(), 12345, None);
+        let message = RoborockMessage {
+            version: LocalProtocolVersion::A01,
+            seq: 46,
+            random: 4646,
+            timestamp: 1_700_000_080,
+            protocol: MessageProtocol::RpcRequest,
+            payload: Some(b"{}".to_vec()),
+        };
+
+        assert!(matches!(
+            codec.build_message(&message),
+            Err(Error::CryptoKeyLength)
+        ));
+    }
+
     #[test]
     fn test_no_payload_roundtrip() {
         let codec = LocalCodec::new("0123456789abcdef".to_string(), 54321, None);
@@ -640,6 +895,17 @@ mod tests {
         let decoded = codec.decode_messages(&mut buffer).unwrap();
         assert_eq!(decoded.len(), 1);
         assert_eq!(decoded[0].protocol, message.protocol);
+
+        let message = RoborockMessage {
+            version: LocalProtocolVersion::V1,
+            ..message
+        };
+        let frame = codec.build_message(&message).unwrap();
+        let mut buffer = b"junk".to_vec();
+        buffer.extend_from_slice(&frame);
+        let decoded = codec.decode_messages(&mut buffer).unwrap();
+        assert_eq!(decoded.len(), 1);
+        assert_eq!(decoded[0].version, LocalProtocolVersion::V1);
     }
 
     #[test]
@@ -651,7 +917,7 @@ mod tests {
         });
 
         let payload_bytes = serde_json::to_vec(&payload).unwrap();
-        let response = decode_rpc_response(&payload_bytes).unwrap();
+        let response = decode_rpc_response(LocalProtocolVersion::L01, &payload_bytes).unwrap();
         assert_eq!(response.id, Some(123));
         assert_eq!(response.error, None);
         assert_eq!(response.result, serde_json::json!({"ok": true}));
@@ -666,7 +932,7 @@ mod tests {
         });
 
         let payload_bytes = serde_json::to_vec(&payload).unwrap();
-        let response = decode_rpc_response(&payload_bytes).unwrap();
+        let response = decode_rpc_response(LocalProtocolVersion::L01, &payload_bytes).unwrap();
         assert_eq!(response.id, Some(321));
         assert_eq!(response.error, Some(RpcError::UnknownMethod));
     }
@@ -680,9 +946,49 @@ mod tests {
         });
 
         let payload_bytes = serde_json::to_vec(&payload).unwrap();
-        let response = decode_rpc_response(&payload_bytes).unwrap();
+        let response = decode_rpc_response(LocalProtocolVersion::L01, &payload_bytes).unwrap();
         assert_eq!(response.id, Some(555));
         assert_eq!(response.error, Some(RpcError::UnknownMethod));
         assert_eq!(response.result, serde_json::json!({}));
     }
+
+    #[test]
+    fn test_decode_map_response() {
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+
+        let nonce = *b"0123456789abcdef";
+        let map = b"rr\x14\x00 map".to_vec();
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&map).unwrap();
+        let gzipped = encoder.finish().unwrap();
+
+        let mut payload = b"endpoint".to_vec();
+        payload.extend_from_slice(&[0; 8]);
+        payload.extend_from_slice(&1234u16.to_le_bytes());
+        payload.extend_from_slice(&[0; 6]);
+        payload.extend_from_slice(
+            &cbc::Encryptor::<Aes128>::new(&nonce.into(), &[0u8; 16].into())
+                .encrypt_padded_vec_mut::<Pkcs7>(&gzipped),
+        );
+
+        assert_eq!(decode_map_response(&payload, &nonce).unwrap(), (1234, map));
+        assert!(decode_map_response(&payload, b"fedcba9876543210").is_err());
+        assert!(decode_map_response(&payload[..20], &nonce).is_err());
+    }
+
+    #[test]
+    fn test_decode_a01_response() {
+        let payload = serde_json::json!({
+            "dps": {"200": 1, "201": "ok", "status": 2},
+            "t": 1_700_000_000,
+        });
+
+        let payload_bytes = serde_json::to_vec(&payload).unwrap();
+        let response = decode_rpc_response(LocalProtocolVersion::A01, &payload_bytes).unwrap();
+        assert_eq!(response.id, None);
+        assert_eq!(response.error, None);
+        assert_eq!(response.result, serde_json::json!({"200": 1, "201": "ok"}));
+    }
 }
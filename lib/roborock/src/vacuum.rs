@@ -1,9 +1,12 @@
 use std::net::Ipv4Addr;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use log::{info, warn};
+use md5::{Digest, Md5};
+use sha2::Sha256;
 
 use crate::local::TcpLocalConnection;
+use crate::map::{Map, Point, Segment};
 use crate::util::Counter;
 use crate::{Error, Result};
 
@@ -335,6 +338,85 @@ pub struct Status {
     pub clean_percent: i64,
 }
 
+/// Rectangle of the map in millimetres, cleaned `repeat` times.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Zone {
+    pub x1: i32,
+    pub y1: i32,
+    pub x2: i32,
+    pub y2: i32,
+    pub repeat: u8,
+}
+
+/// Part of the vacuum worn by cleaning, replaced or, for the sensors,
+/// cleaned once it has worked for its lifetime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Consumable {
+    MainBrush,
+    SideBrush,
+    Filter,
+    Sensor,
+    Mop,
+}
+
+impl Consumable {
+    /// Key of the work time in `get_consumable`, also reset by it.
+    fn key(self) -> &'static str {
+        match self {
+            Consumable::MainBrush => "main_brush_work_time",
+            Consumable::SideBrush => "side_brush_work_time",
+            Consumable::Filter => "filter_work_time",
+            Consumable::Sensor => "sensor_dirty_time",
+            Consumable::Mop => "moproller_work_time",
+        }
+    }
+
+    /// Work time the Roborock app recommends replacing the part after.
+    pub fn lifetime(self) -> Duration {
+        const HOUR: u64 = 60 * 60;
+
+        match self {
+            Consumable::MainBrush => Duration::from_secs(300 * HOUR),
+            Consumable::SideBrush => Duration::from_secs(200 * HOUR),
+            Consumable::Filter => Duration::from_secs(150 * HOUR),
+            Consumable::Sensor => Duration::from_secs(30 * HOUR),
+            Consumable::Mop => Duration::from_secs(300 * HOUR),
+        }
+    }
+}
+
+/// Time the consumables have worked since they were last reset. Devices
+/// without a mop roller don't report it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Consumables {
+    pub main_brush: Duration,
+    pub side_brush: Duration,
+    pub filter: Duration,
+    pub sensor: Duration,
+    pub mop: Option<Duration>,
+}
+
+impl Consumables {
+    pub fn iter(&self) -> impl Iterator<Item = (Consumable, Duration)> {
+        [
+            (Consumable::MainBrush, Some(self.main_brush)),
+            (Consumable::SideBrush, Some(self.side_brush)),
+            (Consumable::Filter, Some(self.filter)),
+            (Consumable::Sensor, Some(self.sensor)),
+            (Consumable::Mop, self.mop),
+        ]
+        .into_iter()
+        .filter_map(|(consumable, work_time)| Some((consumable, work_time?)))
+    }
+
+    /// Percentage of the lifetime of `consumable` left, 0 once it is over.
+    pub fn remaining(consumable: Consumable, work_time: Duration) -> u8 {
+        let lifetime = consumable.lifetime().as_secs();
+        let left = lifetime.saturating_sub(work_time.as_secs());
+        (left * 100 / lifetime) as u8
+    }
+}
+
 pub struct Vacuum {
     ip: Ipv4Addr,
     duid: String,
@@ -453,6 +535,21 @@ impl Vacuum {
         Ok(())
     }
 
+    pub async fn clean_zones(&mut self, zones: &[Zone]) -> Result<()> {
+        self.last_cleaning_rooms = vec![];
+        let params: Vec<_> = zones.iter().map(zone_params).collect();
+        self.send_rpc_with_retry("app_zoned_clean", serde_json::json!(params))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn go_to(&mut self, target: Point) -> Result<()> {
+        self.last_cleaning_rooms = vec![];
+        self.send_rpc_with_retry("app_goto_target", serde_json::json!([target.x, target.y]))
+            .await?;
+        Ok(())
+    }
+
     pub async fn stop(&mut self) -> Result<()> {
         self.send_rpc_with_retry("app_stop", serde_json::json!([]))
             .await?;
@@ -477,6 +574,41 @@ impl Vacuum {
         Ok(())
     }
 
+    pub async fn consumables(&mut self) -> Result<Consumables> {
+        let result = self
+            .send_rpc_with_retry("get_consumable", serde_json::json!([]))
+            .await?;
+        Ok(consumables_from_result(result))
+    }
+
+    pub async fn reset_consumable(&mut self, consumable: Consumable) -> Result<()> {
+        self.send_rpc_with_retry("reset_consumable", serde_json::json!([consumable.key()]))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_map(&mut self) -> Result<Map> {
+        let request_id = self.id_counter.next();
+        let nonce = map_nonce(&self.duid, self.random_counter.next());
+        let endpoint = map_endpoint(&self.duid);
+        let map = self
+            .connection
+            .get_map(
+                request_id,
+                self.seq_counter.next(),
+                self.random_counter.next(),
+                &endpoint,
+                nonce,
+            )
+            .await?;
+        Map::parse(&map)
+    }
+
+    /// Segments of the current map.
+    pub async fn segments(&mut self) -> Result<Vec<Segment>> {
+        Ok(self.get_map().await?.segments)
+    }
+
     async fn send_rpc_with_retry(
         &mut self,
         method: &str,
@@ -535,6 +667,63 @@ impl Vacuum {
     }
 }
 
+/// Work times in seconds, wrapped in an array like the status.
+fn consumables_from_result(result: serde_json::Value) -> Consumables {
+    let consumables = status_from_result(result);
+    let work_time = |consumable: Consumable| {
+        consumables
+            .get(consumable.key())
+            .and_then(|value| value.as_u64())
+            .map(Duration::from_secs)
+    };
+
+    Consumables {
+        main_brush: work_time(Consumable::MainBrush).unwrap_or_default(),
+        side_brush: work_time(Consumable::SideBrush).unwrap_or_default(),
+        filter: work_time(Consumable::Filter).unwrap_or_default(),
+        sensor: work_time(Consumable::Sensor).unwrap_or_default(),
+        mop: work_time(Consumable::Mop),
+    }
+}
+
+/// `[x1, y1, x2, y2, repeat]` with the corners ordered as the device expects
+/// them and the repeat count it supports.
+fn zone_params(zone: &Zone) -> [i32; 5] {
+    [
+        zone.x1.min(zone.x2),
+        zone.y1.min(zone.y2),
+        zone.x1.max(zone.x2),
+        zone.y1.max(zone.y2),
+        zone.repeat.clamp(1, 3) as i32,
+    ]
+}
+
+/// Endpoint the device echoes in the header of the map response.
+fn map_endpoint(duid: &str) -> String {
+    let digest = Md5::digest(duid.as_bytes());
+    digest[..4]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Key the device encrypts the map with, unique to the request.
+fn map_nonce(duid: &str, random: u32) -> [u8; 16] {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+
+    let mut hasher = Sha256::new();
+    hasher.update(duid.as_bytes());
+    hasher.update(random.to_be_bytes());
+    hasher.update(nanos.to_be_bytes());
+
+    let mut nonce = [0u8; 16];
+    nonce.copy_from_slice(&hasher.finalize()[..16]);
+    nonce
+}
+
 fn state_from_code(code: i64) -> State {
     match code {
         1 | 4 | 5 | 7 | 11 | 16 | 17 | 18 | 22 | 23 | 25 | 29 | 6301..=6309 => State::Cleaning,
@@ -657,3 +846,54 @@ fn should_retry(err: &Error) -> bool {
         _ => false,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zone_params() {
+        let zone = Zone {
+            x1: 27000,
+            y1: 24000,
+            x2: 25000,
+            y2: 25500,
+            repeat: 5,
+        };
+
+        assert_eq!(zone_params(&zone), [25000, 24000, 27000, 25500, 3]);
+        assert_eq!(zone_params(&Zone { repeat: 0, ..zone })[4], 1);
+    }
+
+    #[test]
+    fn test_consumables_from_result() {
+        let result = serde_json::json!([{
+            "main_brush_work_time": 540000,
+            "side_brush_work_time": 720000,
+            "filter_work_time": 600000,
+            "sensor_dirty_time": 3600,
+            "strainer_work_times": 12
+        }]);
+
+        let consumables = consumables_from_result(result);
+        assert_eq!(consumables.main_brush, Duration::from_secs(540_000));
+        assert_eq!(consumables.mop, None);
+
+        let remaining: Vec<_> = consumables
+            .iter()
+            .map(|(consumable, work_time)| {
+                (consumable, Consumables::remaining(consumable, work_time))
+            })
+            .collect();
+
+        assert_eq!(
+            remaining,
+            vec![
+                (Consumable::MainBrush, 50),
+                (Consumable::SideBrush, 0),
+                (Consumable::Filter, 0),
+                (Consumable::Sensor, 96),
+            ]
+        );
+    }
+}
@@ -15,14 +15,25 @@ pub enum Action {
     Elisa(elisa::Action, Uuid),
     Elisheba(elisheba::Action, Uuid),
     Elizabeth(elizabeth::Action, Uuid),
+    /// Action of a newer schema, kept as is so it can be logged and skipped.
+    #[serde(untagged, deserialize_with = "unknown_action")]
+    Unknown(serde_json::Value),
+}
+
+fn unknown_action<'de, D>(deserializer: D) -> Result<serde_json::Value, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    crate::envelope::unknown_variant(deserializer, &["elisa", "elisheba", "elizabeth"])
 }
 
 impl Action {
-    pub fn id(&self) -> Uuid {
+    pub fn id(&self) -> Option<Uuid> {
         match self {
-            Action::Elisa(_, id) => *id,
-            Action::Elisheba(_, id) => *id,
-            Action::Elizabeth(_, id) => *id,
+            Action::Elisa(_, id) => Some(*id),
+            Action::Elisheba(_, id) => Some(*id),
+            Action::Elizabeth(_, id) => Some(*id),
+            Action::Unknown(_) => None,
         }
     }
 }
@@ -39,18 +50,18 @@ mod tests {
     fn test_action_id() {
         let id = uuid::Uuid::new_v4();
         let action = Action::Elisa(elisa::Action::Stop, id);
-        assert_eq!(action.id(), id);
+        assert_eq!(action.id(), Some(id));
 
         let id = uuid::Uuid::new_v4();
         let action = Action::Elizabeth(
             elizabeth::Action {
-                room: Room::Bathroom,
+                room: Room::new("bathroom"),
                 device_type: DeviceType::Recuperator,
                 action_type: ActionType::SetIsEnabled(true),
             },
             id,
         );
-        assert_eq!(action.id(), id);
+        assert_eq!(action.id(), Some(id));
     }
 
     #[test]
@@ -58,7 +69,7 @@ mod tests {
         let id = uuid!("CFF182E2-2BCB-4C19-A070-43D43EF7C104");
         let action = Action::Elizabeth(
             elizabeth::Action {
-                room: Room::Bathroom,
+                room: Room::new("bathroom"),
                 device_type: DeviceType::Recuperator,
                 action_type: ActionType::SetIsEnabled(true),
             },
@@ -84,13 +95,50 @@ mod tests {
         );
 
         let id = uuid!("48FE7DE3-C3A9-47BA-A1A3-3E9C3FFC910E");
-        let action = Action::Elisa(elisa::Action::Start(vec![Room::Bathroom, Room::Toilet]), id);
+        let action = Action::Elisa(
+            elisa::Action::Start(vec![Room::new("bathroom"), Room::new("toilet")]),
+            id,
+        );
 
         let serialized = serde_json::to_string(&action).unwrap();
         assert_eq!(
             serialized,
             r#"{"elisa":[{"start":["bathroom","toilet"]},"48fe7de3-c3a9-47ba-a1a3-3e9c3ffc910e"]}"#
         );
+
+        let zone = elisa::Zone {
+            x1: 25000,
+            y1: 24000,
+            x2: 27000,
+            y2: 25500,
+            repeat: 2,
+        };
+        let action = Action::Elisa(elisa::Action::CleanZones(vec![zone]), id);
+
+        let serialized = serde_json::to_string(&action).unwrap();
+        assert_eq!(
+            serialized,
+            r#"{"elisa":[{"clean_zones":[{"x1":25000,"y1":24000,"x2":27000,"y2":25500,"repeat":2}]},"48fe7de3-c3a9-47ba-a1a3-3e9c3ffc910e"]}"#
+        );
+
+        let action = Action::Elisa(elisa::Action::GoTo(zone.center()), id);
+
+        let serialized = serde_json::to_string(&action).unwrap();
+        assert_eq!(
+            serialized,
+            r#"{"elisa":[{"go_to":{"x":26000,"y":24750}},"48fe7de3-c3a9-47ba-a1a3-3e9c3ffc910e"]}"#
+        );
+
+        let action = Action::Elisa(
+            elisa::Action::ResetConsumable(elisa::Consumable::MainBrush),
+            id,
+        );
+
+        let serialized = serde_json::to_string(&action).unwrap();
+        assert_eq!(
+            serialized,
+            r#"{"elisa":[{"reset_consumable":"main_brush"},"48fe7de3-c3a9-47ba-a1a3-3e9c3ffc910e"]}"#
+        );
     }
 
     #[test]
@@ -98,7 +146,7 @@ mod tests {
         let id = uuid!("FBD5E476-AD19-4932-98B3-608136B670FF");
         let action = Action::Elisheba(
             elisheba::Action {
-                room: Room::Bathroom,
+                room: Room::new("bathroom"),
                 is_enabled: true,
             },
             id,
@@ -127,7 +175,10 @@ mod tests {
         assert_eq!(deserialized, action);
 
         let id = uuid!("48FE7DE3-C3A9-47BA-A1A3-3E9C3FFC910E");
-        let action = Action::Elisa(elisa::Action::Start(vec![Room::Bathroom, Room::Toilet]), id);
+        let action = Action::Elisa(
+            elisa::Action::Start(vec![Room::new("bathroom"), Room::new("toilet")]),
+            id,
+        );
 
         let json = json!({
             "elisa": [
@@ -147,7 +198,7 @@ mod tests {
         let id = uuid!("CFF182E2-2BCB-4C19-A070-43D43EF7C104");
         let action = Action::Elizabeth(
             elizabeth::Action {
-                room: Room::Bathroom,
+                room: Room::new("bathroom"),
                 device_type: DeviceType::Recuperator,
                 action_type: ActionType::SetIsEnabled(true),
             },
@@ -173,7 +224,7 @@ mod tests {
         let id = uuid!("FBD5E476-AD19-4932-98B3-608136B670FF");
         let action = Action::Elisheba(
             elisheba::Action {
-                room: Room::Bathroom,
+                room: Room::new("bathroom"),
                 is_enabled: true,
             },
             id,
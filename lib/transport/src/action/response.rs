@@ -11,7 +11,41 @@ pub struct Response {
 #[serde(rename_all = "snake_case")]
 pub enum ActionResult {
     Success,
-    Failure,
+    Failure(ActionError),
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct ActionError {
+    pub kind: ErrorKind,
+    /// Human-readable reason, passed on to the user as is.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    DeviceUnreachable,
+    InvalidValue,
+    DeviceBusy,
+    NotSupported,
+    Timeout,
+}
+
+impl ActionError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> ActionError {
+        ActionError {
+            kind,
+            message: Some(message.into()),
+        }
+    }
+}
+
+impl ActionResult {
+    pub fn failure(kind: ErrorKind, message: impl Into<String>) -> ActionResult {
+        ActionResult::Failure(ActionError::new(kind, message))
+    }
 }
 
 #[cfg(test)]
@@ -53,4 +87,43 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn test_failure_serialization() {
+        let id = uuid!("CFF182E2-2BCB-4C19-A070-43D43EF7C104");
+        let response = Response {
+            action_id: id,
+            result: ActionResult::failure(ErrorKind::DeviceBusy, "vacuum is docking"),
+        };
+
+        let json = json!({
+            "action_id": "cff182e2-2bcb-4c19-a070-43d43ef7c104",
+            "result": {
+                "failure": {
+                    "kind": "device_busy",
+                    "message": "vacuum is docking",
+                },
+            },
+        });
+
+        assert_eq!(serde_json::to_value(&response).unwrap(), json);
+        assert_eq!(serde_json::from_value::<Response>(json).unwrap(), response);
+    }
+
+    #[test]
+    fn test_failure_without_message() {
+        let json = json!({
+            "action_id": "e0f2c0a6-3a3b-4c8c-9c7d-4f8e0a7d8d4f",
+            "result": { "failure": { "kind": "timeout" } },
+        });
+
+        let response: Response = serde_json::from_value(json).unwrap();
+        assert_eq!(
+            response.result,
+            ActionResult::Failure(ActionError {
+                kind: ErrorKind::Timeout,
+                message: None,
+            })
+        );
+    }
 }
@@ -0,0 +1,116 @@
+//! Liveness of the services connected to the broker.
+//!
+//! The main [`MqttSession`](crate::MqttSession) of every service publishes
+//! a retained [`Availability::Online`] to `availability/<client_id>` once
+//! connected and registers [`Availability::Offline`] as its last will, so the
+//! broker announces a crashed service on its behalf. Auxiliary connections,
+//! like the RPC clients, announce nothing. Gateways additionally send heartbeats with
+//! their version and uptime.
+
+use std::time::Duration;
+
+use log::error;
+use paho_mqtt::{AsyncClient, Message, MessageBuilder, QOS_1};
+use serde::{Deserialize, Serialize};
+use tokio::time::{self, Instant};
+
+use crate::{envelope, Topic};
+
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Availability {
+    Online {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        version: Option<String>,
+        /// Seconds since the service started.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        uptime: Option<u64>,
+    },
+    Offline,
+}
+
+impl Availability {
+    pub fn is_online(&self) -> bool {
+        matches!(self, Availability::Online { .. })
+    }
+
+    pub(crate) fn message(&self, service: &str) -> Message {
+        MessageBuilder::new()
+            .topic(Topic::Availability(service.to_string()).to_string())
+            .payload(envelope::encode(self))
+            .qos(QOS_1)
+            .retained(true)
+            .finalize()
+    }
+}
+
+pub(crate) async fn publish_online(
+    mqtt: &AsyncClient,
+    service: &str,
+) -> Result<(), paho_mqtt::Error> {
+    let online = Availability::Online {
+        version: None,
+        uptime: None,
+    };
+
+    mqtt.publish(online.message(service)).await
+}
+
+/// Publishes a heartbeat for `service` every [`HEARTBEAT_INTERVAL`].
+pub async fn heartbeat(mqtt: AsyncClient, service: &'static str, version: &'static str) {
+    let started_at = Instant::now();
+    let mut timer = time::interval(HEARTBEAT_INTERVAL);
+
+    loop {
+        timer.tick().await;
+
+        let heartbeat = Availability::Online {
+            version: Some(version.to_string()),
+            uptime: Some(started_at.elapsed().as_secs()),
+        };
+
+        if let Err(err) = mqtt.publish(heartbeat.message(service)).await {
+            error!("Error publishing heartbeat: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_serialization() {
+        let heartbeat = Availability::Online {
+            version: Some("0.1.0".to_string()),
+            uptime: Some(3600),
+        };
+
+        assert_eq!(
+            serde_json::to_value(&heartbeat).unwrap(),
+            json!({"status": "online", "version": "0.1.0", "uptime": 3600})
+        );
+        assert_eq!(
+            serde_json::to_value(Availability::Offline).unwrap(),
+            json!({"status": "offline"})
+        );
+
+        let online: Availability = serde_json::from_value(json!({"status": "online"})).unwrap();
+        assert!(online.is_online());
+    }
+
+    #[test]
+    fn test_message() {
+        let message = Availability::Offline.message("elisa");
+
+        assert_eq!(message.topic(), "availability/elisa");
+        assert!(message.retained());
+        assert_eq!(
+            envelope::decode::<Availability>(message.payload()).unwrap(),
+            Availability::Offline
+        );
+    }
+}
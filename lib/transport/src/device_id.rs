@@ -6,9 +6,9 @@ use serde::{
     Deserialize, Serialize,
 };
 
-use crate::{DeviceType, Room};
+use crate::{DeviceType, Room, SceneId};
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct DeviceId {
     pub room: Room,
     pub device_type: DeviceType,
@@ -49,6 +49,18 @@ impl DeviceId {
             device_type: DeviceType::Light,
         }
     }
+
+    pub fn scene(id: &SceneId) -> DeviceId {
+        DeviceId {
+            room: Room::new(id.as_str()),
+            device_type: DeviceType::Scene,
+        }
+    }
+
+    /// Id of the scene the device stands for.
+    pub fn scene_id(&self) -> Option<SceneId> {
+        (self.device_type == DeviceType::Scene).then(|| SceneId::new(self.room.as_str()))
+    }
 }
 
 impl fmt::Display for DeviceId {
@@ -66,6 +78,37 @@ impl Serialize for DeviceId {
     }
 }
 
+#[derive(Debug)]
+pub struct InvalidDeviceId(String);
+
+impl fmt::Display for InvalidDeviceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid device id `{}`, expected device_type/room",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for InvalidDeviceId {}
+
+impl FromStr for DeviceId {
+    type Err = InvalidDeviceId;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (device_type, room) = s
+            .split_once('/')
+            .ok_or_else(|| InvalidDeviceId(s.to_string()))?;
+
+        let device_type =
+            DeviceType::from_str(device_type).map_err(|_| InvalidDeviceId(s.to_string()))?;
+        let room = Room::from_str(room).map_err(|_| InvalidDeviceId(s.to_string()))?;
+
+        Ok(DeviceId { device_type, room })
+    }
+}
+
 impl<'de> Deserialize<'de> for DeviceId {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -84,31 +127,29 @@ impl<'de> Deserialize<'de> for DeviceId {
             where
                 E: de::Error,
             {
-                let mut parts = value.splitn(2, '/');
-
-                let device_type = parts.next().ok_or_else(|| {
-                    de::Error::invalid_value(Unexpected::Str(value), &"device_type/room")
-                })?;
-
-                let device_type = DeviceType::from_str(device_type).map_err(|err| {
-                    de::Error::invalid_value(
-                        Unexpected::Str(device_type),
-                        &err.to_string().as_str(),
-                    )
-                })?;
-
-                let room = parts.next().ok_or_else(|| {
+                DeviceId::from_str(value).map_err(|_| {
                     de::Error::invalid_value(Unexpected::Str(value), &"device_type/room")
-                })?;
-
-                let room = Room::from_str(room).map_err(|err| {
-                    de::Error::invalid_value(Unexpected::Str(room), &err.to_string().as_str())
-                })?;
-
-                Ok(DeviceId { device_type, room })
+                })
             }
         }
 
         deserializer.deserialize_str(DeviceIdVisitor)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!(
+            DeviceId::from_str("thermostat/living_room").unwrap(),
+            DeviceId::thermostat_at_room(Room::new("living_room"))
+        );
+
+        assert!(DeviceId::from_str("thermostat").is_err());
+        assert!(DeviceId::from_str("kettle/kitchen").is_err());
+        assert!(DeviceId::from_str("light/kitchen/ceiling").is_err());
+    }
+}
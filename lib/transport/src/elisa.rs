@@ -6,11 +6,68 @@ use crate::Room;
 #[serde(rename_all = "snake_case")]
 pub enum Action {
     Start(Vec<Room>),
+    CleanZones(Vec<Zone>),
+    GoTo(Point),
     Stop,
     SetWorkSpeed(WorkSpeed),
     SetCleanupMode(CleanupMode),
     Pause,
     Resume,
+    ResetConsumable(Consumable),
+    /// Action of a newer schema, answered as not supported.
+    #[serde(untagged, deserialize_with = "unknown_action")]
+    Unknown(serde_json::Value),
+}
+
+fn unknown_action<'de, D>(deserializer: D) -> Result<serde_json::Value, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    crate::envelope::unknown_variant(
+        deserializer,
+        &[
+            "start",
+            "clean_zones",
+            "go_to",
+            "stop",
+            "set_work_speed",
+            "set_cleanup_mode",
+            "pause",
+            "resume",
+            "reset_consumable",
+        ],
+    )
+}
+
+/// Rectangle of the vacuum map in millimetres, cleaned `repeat` times.
+#[derive(Copy, Clone, Debug, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub struct Zone {
+    pub x1: i32,
+    pub y1: i32,
+    pub x2: i32,
+    pub y2: i32,
+    #[serde(default = "Zone::default_repeat")]
+    pub repeat: u8,
+}
+
+/// Point of the vacuum map in millimetres.
+#[derive(Copy, Clone, Debug, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Zone {
+    fn default_repeat() -> u8 {
+        1
+    }
+
+    pub fn center(&self) -> Point {
+        Point {
+            x: (self.x1 + self.x2) / 2,
+            y: (self.y1 + self.y2) / 2,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
@@ -22,6 +79,47 @@ pub struct State {
     pub work_speed: WorkSpeed,
     pub cleanup_mode: CleanupMode,
     pub rooms: Vec<Room>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub consumables: Vec<ConsumableState>,
+}
+
+impl State {
+    /// The consumable with the least of its lifetime left.
+    pub fn most_worn(&self) -> Option<&ConsumableState> {
+        self.consumables
+            .iter()
+            .min_by_key(|consumable| consumable.remaining)
+    }
+}
+
+/// Part of the vacuum cleaner to replace, or clean for the sensors, once it
+/// has worked for its lifetime.
+#[derive(Copy, Clone, Debug, Deserialize, Serialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Consumable {
+    MainBrush,
+    SideBrush,
+    Filter,
+    Sensor,
+    Mop,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct ConsumableState {
+    pub consumable: Consumable,
+    /// Hours worked since the part was last reset.
+    pub work_hours: f32,
+    /// Percentage of the lifetime left.
+    pub remaining: u8,
+}
+
+impl ConsumableState {
+    /// Percentage of the lifetime left from which the part is due.
+    pub const REPLACEMENT_THRESHOLD: u8 = 10;
+
+    pub fn needs_replacement(&self) -> bool {
+        self.remaining <= Self::REPLACEMENT_THRESHOLD
+    }
 }
 
 #[derive(Copy, Clone, Debug, Deserialize, Serialize, PartialEq, Eq, Hash)]
@@ -2,7 +2,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::{DeviceType, Room};
 
-#[derive(Copy, Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub struct Action {
     pub room: Room,
@@ -10,15 +10,28 @@ pub struct Action {
     pub action_type: ActionType,
 }
 
-#[derive(Copy, Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum ActionType {
     SetIsEnabled(bool),
     SetFanSpeed(FanSpeed),
     SetTemperature(f32, bool),
+    /// Action of a newer schema, answered as not supported.
+    #[serde(untagged, deserialize_with = "unknown_action_type")]
+    Unknown(serde_json::Value),
 }
 
-#[derive(Copy, Clone, Debug, Deserialize, Serialize, PartialEq)]
+fn unknown_action_type<'de, D>(deserializer: D) -> Result<serde_json::Value, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    crate::envelope::unknown_variant(
+        deserializer,
+        &["set_is_enabled", "set_fan_speed", "set_temperature"],
+    )
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 pub struct State {
     pub room: Room,
     pub device_type: DeviceType,
@@ -0,0 +1,285 @@
+//! Versioned wrapper for every payload sent over MQTT.
+//!
+//! ```json
+//! {"version": 1, "payload": {"elisheba": {"room": "nursery", "is_enabled": true}}}
+//! ```
+//!
+//! Payloads of a newer version are still decoded, unknown variants end up in
+//! the `Unknown` variant of the payload enums instead of failing the message.
+//! Known variants with a malformed body still fail it.
+
+use std::fmt;
+
+use log::debug;
+use serde::de::{self, DeserializeOwned, Deserializer};
+use serde::{Deserialize, Serialize};
+
+/// Version of the payloads produced by this build.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct Envelope<T> {
+    pub version: u32,
+    pub payload: T,
+}
+
+pub fn encode<T: Serialize>(payload: &T) -> Vec<u8> {
+    let envelope = Envelope {
+        version: SCHEMA_VERSION,
+        payload,
+    };
+
+    serde_json::to_vec(&envelope).expect("serializable payload")
+}
+
+/// Decodes an enveloped payload. Bare payloads of gateways predating the
+/// envelope are accepted as well, even those with a `version` of their own.
+pub fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, DecodeError> {
+    let value: serde_json::Value = serde_json::from_slice(bytes).map_err(DecodeError)?;
+
+    if value.get("version").is_none() || value.get("payload").is_none() {
+        return serde_json::from_value(value).map_err(DecodeError);
+    }
+
+    let envelope: Envelope<T> = serde_json::from_value(value).map_err(DecodeError)?;
+
+    if envelope.version > SCHEMA_VERSION {
+        debug!(
+            "decoded payload of newer schema version {}",
+            envelope.version
+        );
+    }
+
+    Ok(envelope.payload)
+}
+
+/// Deserializes the `Unknown` variant of a payload enum, failing instead
+/// when the tag is one of the `known` variants whose body didn't match.
+pub(crate) fn unknown_variant<'de, D>(
+    deserializer: D,
+    known: &[&str],
+) -> Result<serde_json::Value, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = serde_json::Value::deserialize(deserializer)?;
+
+    let tag = match &value {
+        serde_json::Value::String(tag) => Some(tag.as_str()),
+        serde_json::Value::Object(map) if map.len() == 1 => map.keys().next().map(String::as_str),
+        _ => None,
+    };
+
+    match tag {
+        Some(tag) if known.contains(&tag) => {
+            Err(de::Error::custom(format!("malformed variant `{tag}`")))
+        }
+        _ => Ok(value),
+    }
+}
+
+#[derive(Debug)]
+pub struct DecodeError(serde_json::Error);
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "decode error: {}", self.0)
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+#[cfg(test)]
+mod tests {
+    use uuid::uuid;
+
+    use super::*;
+    use crate::action::{Action, ActionRequest, ActionResponse, ActionResult, ErrorKind};
+    use crate::availability::Availability;
+    use crate::isabel::{Property, State as IsabelState};
+    use crate::state::{StateResponse, StateUpdate};
+    use crate::{elisa, elisheba, elizabeth, DeviceType, Room};
+
+    fn assert_golden<T>(value: T, golden: &str)
+    where
+        T: Serialize + DeserializeOwned + PartialEq + fmt::Debug,
+    {
+        let golden: serde_json::Value = serde_json::from_str(golden).unwrap();
+        let encoded: serde_json::Value = serde_json::from_slice(&encode(&value)).unwrap();
+
+        assert_eq!(encoded, golden);
+        assert_eq!(decode::<T>(golden.to_string().as_bytes()).unwrap(), value);
+    }
+
+    #[test]
+    fn test_state_update() {
+        assert_golden(
+            StateUpdate::Isabel(IsabelState {
+                room: Room::new("kitchen"),
+                property: Property::TemperatureAndHumidity(22.5, 40.0),
+            }),
+            include_str!("../golden/state_update.json"),
+        );
+    }
+
+    #[test]
+    fn test_state_response() {
+        assert_golden(
+            StateResponse::Elisa(elisa::State {
+                battery_level: 87,
+                is_enabled: true,
+                is_paused: false,
+                work_speed: elisa::WorkSpeed::Standard,
+                cleanup_mode: elisa::CleanupMode::DryCleaning,
+                rooms: vec![Room::new("kitchen"), Room::new("hallway")],
+                consumables: vec![],
+            }),
+            include_str!("../golden/state_response.json"),
+        );
+    }
+
+    #[test]
+    fn test_action_request() {
+        assert_golden(
+            ActionRequest {
+                actions: vec![
+                    Action::Elisheba(
+                        elisheba::Action {
+                            room: Room::new("nursery"),
+                            is_enabled: true,
+                        },
+                        uuid!("FBD5E476-AD19-4932-98B3-608136B670FF"),
+                    ),
+                    Action::Elizabeth(
+                        elizabeth::Action {
+                            room: Room::new("bedroom"),
+                            device_type: DeviceType::Thermostat,
+                            action_type: elizabeth::ActionType::SetTemperature(21.5, false),
+                        },
+                        uuid!("CFF182E2-2BCB-4C19-A070-43D43EF7C104"),
+                    ),
+                ],
+            },
+            include_str!("../golden/action_request.json"),
+        );
+    }
+
+    #[test]
+    fn test_action_response() {
+        assert_golden(
+            ActionResponse {
+                action_id: uuid!("CFF182E2-2BCB-4C19-A070-43D43EF7C104"),
+                result: ActionResult::failure(ErrorKind::DeviceBusy, "vacuum is docking"),
+            },
+            include_str!("../golden/action_response.json"),
+        );
+    }
+
+    #[test]
+    fn test_bare_payload() {
+        let update: StateUpdate =
+            decode(br#"{"elisheba":{"room":"nursery","is_enabled":false}}"#).unwrap();
+
+        assert_eq!(
+            update,
+            StateUpdate::Elisheba(elisheba::State {
+                room: Room::new("nursery"),
+                is_enabled: false,
+            })
+        );
+    }
+
+    #[test]
+    fn test_unknown_variants() {
+        let update: StateUpdate =
+            decode(br#"{"version":2,"payload":{"eleanor":{"room":"attic"}}}"#).unwrap();
+        assert!(matches!(update, StateUpdate::Unknown(_)));
+
+        let request: ActionRequest = decode(
+            br#"{"version":2,"payload":{"actions":[
+                {"eleanor":[{"room":"attic"},"fbd5e476-ad19-4932-98b3-608136b670ff"]},
+                {"elisa":["stop","2e363d79-5d42-4f11-955e-7b2046319943"]}
+            ]}}"#,
+        )
+        .unwrap();
+        assert!(matches!(request.actions[0], Action::Unknown(_)));
+        assert_eq!(
+            request.actions[1],
+            Action::Elisa(
+                elisa::Action::Stop,
+                uuid!("2E363D79-5D42-4F11-955E-7B2046319943")
+            )
+        );
+
+        let state: IsabelState =
+            serde_json::from_str(r#"{"room":"kitchen","property":{"co2":600}}"#).unwrap();
+        assert!(matches!(state.property, Property::Unknown(_)));
+    }
+
+    #[test]
+    fn test_unknown_nested_variants() {
+        let golden = include_str!("../golden/unknown_nested_action_request.json");
+        let request: ActionRequest = decode(golden.as_bytes()).unwrap();
+
+        assert_eq!(
+            request.actions[0],
+            Action::Elisa(
+                elisa::Action::Unknown(serde_json::json!({"mop": {"intensity": "high"}})),
+                uuid!("2E363D79-5D42-4F11-955E-7B2046319943")
+            )
+        );
+        assert_eq!(
+            request.actions[1],
+            Action::Elisa(
+                elisa::Action::Unknown(serde_json::json!("dock")),
+                uuid!("48FE7DE3-C3A9-47BA-A1A3-3E9C3FFC910E")
+            )
+        );
+
+        let Action::Elizabeth(action, id) = &request.actions[2] else {
+            panic!("expected an elizabeth action, got {:?}", request.actions[2]);
+        };
+        assert_eq!(
+            action.action_type,
+            elizabeth::ActionType::Unknown(serde_json::json!({"set_humidity": 45}))
+        );
+        assert_eq!(*id, uuid!("CFF182E2-2BCB-4C19-A070-43D43EF7C104"));
+    }
+
+    #[test]
+    fn test_malformed_variants() {
+        let golden = include_str!("../golden/malformed_state_update.json");
+        assert!(decode::<StateUpdate>(golden.as_bytes()).is_err());
+
+        assert!(decode::<StateResponse>(
+            br#"{"version":1,"payload":{"elisa":{"battery_level":87}}}"#
+        )
+        .is_err());
+
+        assert!(decode::<ActionRequest>(
+            br#"{"version":1,"payload":{"actions":[
+                {"elisa":[{"start":"kitchen"},"2e363d79-5d42-4f11-955e-7b2046319943"]}
+            ]}}"#
+        )
+        .is_err());
+
+        assert!(serde_json::from_str::<IsabelState>(
+            r#"{"room":"kitchen","property":{"temperature":"warm"}}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_bare_payload_with_version() {
+        let heartbeat: Availability =
+            decode(br#"{"status":"online","version":"0.1.0","uptime":3600}"#).unwrap();
+
+        assert_eq!(
+            heartbeat,
+            Availability::Online {
+                version: Some("0.1.0".to_string()),
+                uptime: Some(3600),
+            }
+        );
+    }
+}
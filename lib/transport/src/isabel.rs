@@ -15,4 +15,78 @@ pub enum Property {
     Humidity(f32),
     Battery(u8),
     TemperatureAndHumidity(f32, f32),
+    /// Property of a newer schema, kept as is so it can be logged and skipped.
+    #[serde(untagged, deserialize_with = "unknown_property")]
+    Unknown(serde_json::Value),
+}
+
+fn unknown_property<'de, D>(deserializer: D) -> Result<serde_json::Value, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    crate::envelope::unknown_variant(
+        deserializer,
+        &[
+            "temperature",
+            "humidity",
+            "battery",
+            "temperature_and_humidity",
+        ],
+    )
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct CurrentState {
+    pub room: Room,
+    pub temperature: Option<f32>,
+    pub humidity: Option<f32>,
+    pub battery: Option<u8>,
+}
+
+impl CurrentState {
+    pub fn new(room: Room) -> Self {
+        Self {
+            room,
+            temperature: None,
+            humidity: None,
+            battery: None,
+        }
+    }
+
+    pub fn apply(&mut self, property: &Property) {
+        match *property {
+            Property::Temperature(value) => self.temperature = Some(value),
+            Property::Humidity(value) => self.humidity = Some(value),
+            Property::Battery(value) => self.battery = Some(value),
+            Property::TemperatureAndHumidity(temperature, humidity) => {
+                self.temperature = Some(temperature);
+                self.humidity = Some(humidity);
+            }
+            Property::Unknown(_) => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply() {
+        let mut state = CurrentState::new(Room::new("kitchen"));
+
+        state.apply(&Property::TemperatureAndHumidity(22.5, 40.0));
+        state.apply(&Property::Battery(87));
+        state.apply(&Property::Humidity(41.5));
+
+        assert_eq!(
+            state,
+            CurrentState {
+                room: Room::new("kitchen"),
+                temperature: Some(22.5),
+                humidity: Some(41.5),
+                battery: Some(87),
+            }
+        );
+    }
 }
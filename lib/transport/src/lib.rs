@@ -9,8 +9,8 @@ pub mod action {
 
     pub use request::Action;
     pub use request::Request as ActionRequest;
-    pub use response::ActionResult;
     pub use response::Response as ActionResponse;
+    pub use response::{ActionError, ActionResult, ErrorKind};
 }
 
 pub mod state {
@@ -23,14 +23,34 @@ pub mod state {
     pub use update::Update as StateUpdate;
 }
 
+pub mod availability;
+pub use availability::Availability;
+
+pub mod envelope;
+
+pub mod metadata;
+
 mod device_id;
 
 pub use device_id::DeviceId;
 
+pub mod registry;
+
+mod slug;
+pub use slug::{Room, SceneId, ZoneId};
+
+pub mod rpc;
+
+pub mod scene;
+
+mod session;
+pub use session::MqttSession;
+
 mod topic;
 pub use topic::Topic;
 
 use log::debug;
+use paho_mqtt::MessageBuilder;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 use str_derive::Str;
@@ -43,53 +63,44 @@ pub enum DeviceType {
     Thermostat,
     VacuumCleaner,
     Light,
+    /// A registry scene, read its id with [`DeviceId::scene_id`].
+    Scene,
 }
 
-#[derive(Copy, Clone, Debug, Deserialize, Serialize, Str, PartialEq, Eq, Hash)]
-#[serde(rename_all = "snake_case")]
-pub enum Room {
-    Bathroom,
-    Bedroom,
-    Corridor,
-    Hallway,
-    HomeOffice,
-    Kitchen,
-    LivingRoom,
-    Nursery,
-    Toilet,
-}
-
-impl Room {
-    pub const fn all_rooms() -> [Room; 9] {
-        [
-            Room::Bathroom,
-            Room::Bedroom,
-            Room::Corridor,
-            Room::Hallway,
-            Room::HomeOffice,
-            Room::Kitchen,
-            Room::LivingRoom,
-            Room::Nursery,
-            Room::Toilet,
-        ]
-    }
-}
-
+/// Connects an auxiliary client, which doesn't announce its availability
+/// like the main [`MqttSession`] of a service does.
 pub async fn connect_mqtt(
     address: String,
     username: String,
     password: String,
     client_id: &str,
 ) -> Result<paho_mqtt::AsyncClient, paho_mqtt::Error> {
-    let client = {
-        let create_opts = paho_mqtt::CreateOptionsBuilder::new()
-            .server_uri(address)
-            .client_id(client_id)
-            .finalize();
+    let client = create_client(address, client_id)?;
+    connect_client(&client, username, password, None).await?;
 
-        paho_mqtt::AsyncClient::new(create_opts)?
-    };
+    Ok(client)
+}
 
+fn create_client(
+    address: String,
+    client_id: &str,
+) -> Result<paho_mqtt::AsyncClient, paho_mqtt::Error> {
+    let create_opts = paho_mqtt::CreateOptionsBuilder::new()
+        .server_uri(address)
+        .client_id(client_id)
+        .finalize();
+
+    paho_mqtt::AsyncClient::new(create_opts)
+}
+
+/// Connects `client`, announcing the availability of the `service` with a
+/// last will and a retained online message when given.
+async fn connect_client(
+    client: &paho_mqtt::AsyncClient,
+    username: String,
+    password: String,
+    service: Option<&str>,
+) -> Result<(), paho_mqtt::Error> {
     let conn_opts = {
         let mut ssl_opts = paho_mqtt::SslOptionsBuilder::new();
 
@@ -98,12 +109,18 @@ pub async fn connect_mqtt(
             let _ = ssl_opts.trust_store(cert_file);
         }
 
-        paho_mqtt::ConnectOptionsBuilder::new_v5()
+        let mut conn_opts = paho_mqtt::ConnectOptionsBuilder::new_v5();
+        conn_opts
             .keep_alive_interval(Duration::from_secs(30))
             .ssl_options(ssl_opts.finalize())
             .user_name(username)
-            .password(password)
-            .finalize()
+            .password(password);
+
+        if let Some(service) = service {
+            conn_opts.will_message(Availability::Offline.message(service));
+        }
+
+        conn_opts.finalize()
     };
 
     let response = client.connect(conn_opts).await?;
@@ -112,5 +129,42 @@ pub async fn connect_mqtt(
     debug!("client mqtt version {}", client.mqtt_version());
     debug!("server mqtt version {}", response.mqtt_version);
 
-    Ok(client)
+    if let Some(service) = service {
+        availability::publish_online(client, service).await?;
+    }
+
+    Ok(())
+}
+
+/// Publishes `update` to the legacy `state/update` topic and `state` as the
+/// retained last known value of every device in `device_ids`.
+pub async fn publish_state(
+    mqtt: &paho_mqtt::AsyncClient,
+    update: &state::StateUpdate,
+    state: &state::StateResponse,
+    device_ids: &[DeviceId],
+) -> Result<(), paho_mqtt::Error> {
+    let payload = envelope::encode(update);
+
+    let message = MessageBuilder::new()
+        .topic(Topic::StateUpdate.to_string())
+        .payload(payload)
+        .finalize();
+
+    mqtt.publish(message).await?;
+
+    let payload = envelope::encode(state);
+
+    for device_id in device_ids {
+        let message = MessageBuilder::new()
+            .topic(Topic::DeviceState(device_id.clone()).to_string())
+            .payload(payload.clone())
+            .qos(paho_mqtt::QOS_1)
+            .retained(true)
+            .finalize();
+
+        mqtt.publish(message).await?;
+    }
+
+    Ok(())
 }
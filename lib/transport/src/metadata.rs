@@ -0,0 +1,90 @@
+//! What the devices look like to the smart home frontends: their default
+//! names, the values they report and the ranges and modes the gateways
+//! accept. Alice and Home Assistant describe the devices from this, the
+//! registry may narrow the ranges and modes per device.
+
+use serde::Deserialize;
+
+use crate::elisa::{CleanupMode, WorkSpeed};
+use crate::elizabeth::FanSpeed;
+use crate::DeviceType;
+
+/// Language of the names without a translation.
+pub const DEFAULT_LANGUAGE: &str = "ru";
+
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
+pub struct Range {
+    pub min: f32,
+    pub max: f32,
+    pub step: f32,
+}
+
+/// A measurement reported next to the device controls.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Property {
+    Temperature,
+    Humidity,
+    Battery,
+    /// Lifetime left of the most worn consumable in percent. Alice has no
+    /// instance for wear, so only Home Assistant shows it.
+    Consumables,
+}
+
+pub const THERMOSTAT_TEMPERATURE: Range = Range {
+    min: 16.0,
+    max: 28.0,
+    step: 0.5,
+};
+
+pub const FAN_SPEEDS: &[FanSpeed] = &[FanSpeed::Low, FanSpeed::Medium, FanSpeed::High];
+
+/// `WorkSpeed::Min` is left out, the vacuum cleaner only reports it.
+pub const WORK_SPEEDS: &[WorkSpeed] = &[
+    WorkSpeed::Silent,
+    WorkSpeed::Standard,
+    WorkSpeed::Medium,
+    WorkSpeed::Turbo,
+];
+
+pub const CLEANUP_MODES: &[CleanupMode] = &[
+    CleanupMode::DryCleaning,
+    CleanupMode::MixedCleaning,
+    CleanupMode::WetCleaning,
+];
+
+impl DeviceType {
+    /// Name of the device unless the registry gives one.
+    pub fn default_name(&self) -> &'static str {
+        self.default_name_in(DEFAULT_LANGUAGE)
+    }
+
+    /// Like [`DeviceType::default_name`] in `language`, names of languages
+    /// without a translation fall back to the default language.
+    pub fn default_name_in(&self, language: &str) -> &'static str {
+        match (self, language) {
+            (DeviceType::Recuperator, "en") => "Recuperator",
+            (DeviceType::TemperatureSensor, "en") => "Temperature sensor",
+            (DeviceType::Thermostat, "en") => "Thermostat",
+            (DeviceType::VacuumCleaner, "en") => "Eva",
+            (DeviceType::Light, "en") => "Ceiling light",
+            (DeviceType::Scene, "en") => "Scene",
+            (DeviceType::Recuperator, _) => "Рекуператор",
+            (DeviceType::TemperatureSensor, _) => "Датчик температуры",
+            (DeviceType::Thermostat, _) => "Термостат",
+            (DeviceType::VacuumCleaner, _) => "Ева",
+            (DeviceType::Light, _) => "Верхний свет",
+            (DeviceType::Scene, _) => "Сценарий",
+        }
+    }
+
+    pub fn properties(&self) -> &'static [Property] {
+        match self {
+            DeviceType::TemperatureSensor => {
+                &[Property::Humidity, Property::Temperature, Property::Battery]
+            }
+            DeviceType::VacuumCleaner => &[Property::Battery, Property::Consumables],
+            DeviceType::Thermostat => &[Property::Temperature],
+            DeviceType::Recuperator | DeviceType::Light | DeviceType::Scene => &[],
+        }
+    }
+}
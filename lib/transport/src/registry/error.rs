@@ -0,0 +1,68 @@
+use std::fmt;
+
+use crate::{DeviceId, Room, SceneId, ZoneId};
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Toml(toml::de::Error),
+    DuplicateRoom(Room),
+    DuplicateDevice(DeviceId),
+    UnknownRoom(DeviceId),
+    UnsupportedGateway(DeviceId),
+    InvalidCapabilities(DeviceId, &'static str),
+    DuplicateScene(SceneId),
+    UnknownSceneRoom(SceneId),
+    UnknownSceneDevice(SceneId, DeviceId),
+    UnsupportedSceneStep(SceneId, DeviceId),
+    UnknownSceneZone(SceneId, ZoneId),
+    DuplicateZone(ZoneId),
+    EmptyZone(ZoneId),
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(err: toml::de::Error) -> Self {
+        Self::Toml(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "io error: {err}"),
+            Self::Toml(err) => write!(f, "toml error: {err}"),
+            Self::DuplicateRoom(room) => write!(f, "room {room} is declared twice"),
+            Self::DuplicateDevice(id) => write!(f, "device {id} is declared twice"),
+            Self::UnknownRoom(id) => write!(f, "device {id} refers to an undeclared room"),
+            Self::UnsupportedGateway(id) => {
+                write!(f, "device {id} is not supported by its gateway")
+            }
+            Self::InvalidCapabilities(id, reason) => {
+                write!(f, "device {id} has invalid capabilities: {reason}")
+            }
+            Self::DuplicateScene(scene) => write!(f, "scene {scene} is declared twice"),
+            Self::UnknownSceneRoom(scene) => {
+                write!(f, "scene {scene} refers to an undeclared room")
+            }
+            Self::UnknownSceneDevice(scene, id) => {
+                write!(f, "scene {scene} refers to an undeclared device {id}")
+            }
+            Self::UnsupportedSceneStep(scene, id) => {
+                write!(f, "scene {scene} has a step device {id} does not support")
+            }
+            Self::UnknownSceneZone(scene, zone) => {
+                write!(f, "scene {scene} refers to an undeclared zone {zone}")
+            }
+            Self::DuplicateZone(zone) => write!(f, "zone {zone} is declared twice"),
+            Self::EmptyZone(zone) => write!(f, "zone {zone} has no areas"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
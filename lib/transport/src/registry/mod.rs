@@ -0,0 +1,910 @@
+//! Rooms and devices of the house, loaded from a TOML file.
+//!
+//! ```toml
+//! [[rooms]]
+//! id = "kitchen"
+//! name = "Кухня"
+//! names = { en = "Kitchen" }
+//!
+//! [[devices]]
+//! type = "vacuum_cleaner"
+//! room = "kitchen"
+//! gateway = "elisa"
+//! segment_id = 19
+//! work_speeds = ["silent", "standard"]
+//!
+//! [[zones]]
+//! id = "dining_table"
+//! name = "Под обеденным столом"
+//! areas = [{ x1 = 25000, y1 = 24000, x2 = 27000, y2 = 25500, repeat = 2 }]
+//! target = { x = 26000, y = 24750 }
+//!
+//! [[scenes]]
+//! id = "cleanup"
+//! name = "Уборка"
+//! room = "kitchen"
+//! steps = [{ device = "vacuum_cleaner/kitchen", action = "turn_on" }]
+//! ```
+//!
+//! `name` is in [`metadata::DEFAULT_LANGUAGE`], `names` holds its
+//! translations. Devices support the ranges and modes of
+//! [`crate::metadata`] unless they narrow them with `temperature`,
+//! `fan_speeds`, `work_speeds` or `cleanup_modes`.
+//!
+//! Zones are areas of the vacuum map in millimetres, cleaned by the
+//! `clean_zone` step and sent to by the `go_to` one.
+//!
+//! See [`crate::scene`] for the scene steps.
+
+mod error;
+pub use error::Error;
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::{de, Deserialize, Deserializer};
+
+use crate::elisa::{CleanupMode, Point, WorkSpeed, Zone};
+use crate::elizabeth::FanSpeed;
+use crate::metadata::{self, Range};
+use crate::scene::Scene;
+use crate::{DeviceId, DeviceType, Room, SceneId, ZoneId};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Translations of a name by language code.
+pub type Names = BTreeMap<String, String>;
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Registry {
+    #[serde(default)]
+    rooms: Vec<RoomConfig>,
+    #[serde(default)]
+    devices: Vec<DeviceConfig>,
+    #[serde(default)]
+    zones: Vec<ZoneConfig>,
+    #[serde(default)]
+    scenes: Vec<Scene>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct RoomConfig {
+    pub id: Room,
+    pub name: String,
+    #[serde(default)]
+    pub names: Names,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct DeviceConfig {
+    #[serde(rename = "type")]
+    pub device_type: DeviceType,
+    pub room: Room,
+    pub name: Option<String>,
+    #[serde(default)]
+    pub names: Names,
+    /// Target temperatures of a thermostat.
+    pub temperature: Option<Range>,
+    pub fan_speeds: Option<Vec<FanSpeed>>,
+    pub work_speeds: Option<Vec<WorkSpeed>>,
+    pub cleanup_modes: Option<Vec<CleanupMode>>,
+    #[serde(flatten)]
+    pub backend: Backend,
+}
+
+/// Named areas of the vacuum map.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct ZoneConfig {
+    pub id: ZoneId,
+    pub name: String,
+    #[serde(default)]
+    pub names: Names,
+    pub areas: Vec<Zone>,
+    /// Where `go_to` sends the vacuum cleaner, the center of the first area
+    /// when omitted.
+    pub target: Option<Point>,
+}
+
+impl ZoneConfig {
+    pub fn target(&self) -> Point {
+        // The registry rejects zones without areas.
+        self.target.unwrap_or_else(|| self.areas[0].center())
+    }
+}
+
+/// Gateway owning a device together with the id the gateway knows it by.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(tag = "gateway", rename_all = "snake_case")]
+pub enum Backend {
+    /// Roborock segment id, the room can't be cleaned alone when omitted.
+    Elisa {
+        #[serde(default)]
+        segment_id: Option<u8>,
+    },
+    /// Sonoff device id.
+    Elisheba { device_id: String },
+    /// Inspinia page (room) id the controls belong to.
+    Elizabeth { page_id: String },
+    /// BLE MAC address of the sensor.
+    Isabel {
+        #[serde(deserialize_with = "deserialize_mac")]
+        mac: [u8; 6],
+    },
+}
+
+impl Backend {
+    /// MQTT client id of the gateway service.
+    pub fn gateway(&self) -> &'static str {
+        match self {
+            Backend::Elisa { .. } => "elisa",
+            Backend::Elisheba { .. } => "elisheba",
+            Backend::Elizabeth { .. } => "elizabeth",
+            Backend::Isabel { .. } => "isabel",
+        }
+    }
+}
+
+impl DeviceConfig {
+    pub fn id(&self) -> DeviceId {
+        DeviceId {
+            room: self.room.clone(),
+            device_type: self.device_type,
+        }
+    }
+
+    /// Name in `language`, falling back to the untranslated name and then to
+    /// the default name of the device type.
+    pub fn name_in(&self, language: &str) -> &str {
+        self.names
+            .get(language)
+            .or(self.name.as_ref())
+            .map(String::as_str)
+            .unwrap_or(self.device_type.default_name_in(language))
+    }
+
+    pub fn temperature_range(&self) -> Range {
+        self.temperature.unwrap_or(metadata::THERMOSTAT_TEMPERATURE)
+    }
+
+    pub fn fan_speeds(&self) -> &[FanSpeed] {
+        self.fan_speeds.as_deref().unwrap_or(metadata::FAN_SPEEDS)
+    }
+
+    pub fn work_speeds(&self) -> &[WorkSpeed] {
+        self.work_speeds.as_deref().unwrap_or(metadata::WORK_SPEEDS)
+    }
+
+    pub fn cleanup_modes(&self) -> &[CleanupMode] {
+        self.cleanup_modes
+            .as_deref()
+            .unwrap_or(metadata::CLEANUP_MODES)
+    }
+
+    /// Reason the capability overrides don't fit the device.
+    fn invalid_capabilities(&self) -> Option<&'static str> {
+        let is_type = |device_type| self.device_type == device_type;
+
+        if self.temperature.is_some() && !is_type(DeviceType::Thermostat) {
+            return Some("only thermostats take a temperature range");
+        }
+
+        if self.fan_speeds.is_some() && !is_type(DeviceType::Recuperator) {
+            return Some("only recuperators take fan speeds");
+        }
+
+        if (self.work_speeds.is_some() || self.cleanup_modes.is_some())
+            && !is_type(DeviceType::VacuumCleaner)
+        {
+            return Some("only vacuum cleaners take work speeds and cleanup modes");
+        }
+
+        let range = self.temperature_range();
+        let defaults = metadata::THERMOSTAT_TEMPERATURE;
+
+        if range.step <= 0.0 || range.min >= range.max {
+            return Some("temperature range is empty");
+        }
+
+        if range.min < defaults.min || range.max > defaults.max {
+            return Some("temperature range exceeds what thermostats accept");
+        }
+
+        if self.fan_speeds().is_empty()
+            || self.work_speeds().is_empty()
+            || self.cleanup_modes().is_empty()
+        {
+            return Some("modes can't be empty");
+        }
+
+        let is_subset = |modes: &[_], supported: &[_]| modes.iter().all(|m| supported.contains(m));
+
+        if !is_subset(self.work_speeds(), metadata::WORK_SPEEDS) {
+            return Some("work speeds include one the vacuum cleaner only reports");
+        }
+
+        None
+    }
+
+    fn is_supported_by_backend(&self) -> bool {
+        matches!(
+            (&self.backend, self.device_type),
+            (Backend::Elisa { .. }, DeviceType::VacuumCleaner)
+                | (Backend::Elisheba { .. }, DeviceType::Light)
+                | (
+                    Backend::Elizabeth { .. },
+                    DeviceType::Thermostat | DeviceType::Recuperator
+                )
+                | (Backend::Isabel { .. }, DeviceType::TemperatureSensor)
+        )
+    }
+}
+
+impl Registry {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Registry> {
+        std::fs::read_to_string(path)?.parse()
+    }
+
+    pub fn rooms(&self) -> &[RoomConfig] {
+        &self.rooms
+    }
+
+    pub fn devices(&self) -> &[DeviceConfig] {
+        &self.devices
+    }
+
+    pub fn zones(&self) -> &[ZoneConfig] {
+        &self.zones
+    }
+
+    pub fn zone(&self, id: &ZoneId) -> Option<&ZoneConfig> {
+        self.zones.iter().find(|zone| &zone.id == id)
+    }
+
+    pub fn scenes(&self) -> &[Scene] {
+        &self.scenes
+    }
+
+    pub fn scene(&self, id: &SceneId) -> Option<&Scene> {
+        self.scenes.iter().find(|scene| &scene.id == id)
+    }
+
+    pub fn room_name(&self, room: &Room) -> Option<&str> {
+        self.room_name_in(room, metadata::DEFAULT_LANGUAGE)
+    }
+
+    /// Name of `room` in `language`, falling back to the untranslated name.
+    pub fn room_name_in(&self, room: &Room, language: &str) -> Option<&str> {
+        let config = self.rooms.iter().find(|config| &config.id == room)?;
+
+        Some(config.names.get(language).unwrap_or(&config.name).as_str())
+    }
+
+    pub fn device(&self, id: &DeviceId) -> Option<&DeviceConfig> {
+        self.devices
+            .iter()
+            .find(|device| device.room == id.room && device.device_type == id.device_type)
+    }
+
+    pub fn devices_of_type(
+        &self,
+        device_type: DeviceType,
+    ) -> impl Iterator<Item = &DeviceConfig> + '_ {
+        self.devices
+            .iter()
+            .filter(move |device| device.device_type == device_type)
+    }
+
+    pub fn segment_for_room(&self, room: &Room) -> Option<u8> {
+        self.devices.iter().find_map(|device| match device.backend {
+            Backend::Elisa {
+                segment_id: Some(segment_id),
+            } if &device.room == room => Some(segment_id),
+            _ => None,
+        })
+    }
+
+    pub fn room_for_segment(&self, segment_id: u8) -> Option<&Room> {
+        self.devices.iter().find_map(|device| match device.backend {
+            Backend::Elisa {
+                segment_id: Some(id),
+            } if id == segment_id => Some(&device.room),
+            _ => None,
+        })
+    }
+
+    pub fn sonoff_id_for_room(&self, room: &Room) -> Option<&str> {
+        self.devices
+            .iter()
+            .find_map(|device| match &device.backend {
+                Backend::Elisheba { device_id } if &device.room == room => Some(device_id.as_str()),
+                _ => None,
+            })
+    }
+
+    pub fn room_for_sonoff_id(&self, id: &str) -> Option<&Room> {
+        self.devices
+            .iter()
+            .find_map(|device| match &device.backend {
+                Backend::Elisheba { device_id } if device_id == id => Some(&device.room),
+                _ => None,
+            })
+    }
+
+    pub fn room_for_mac(&self, octets: &[u8; 6]) -> Option<&Room> {
+        self.devices
+            .iter()
+            .find_map(|device| match &device.backend {
+                Backend::Isabel { mac } if mac == octets => Some(&device.room),
+                _ => None,
+            })
+    }
+
+    pub fn inspinia_page_for(&self, id: &DeviceId) -> Option<&str> {
+        match &self.device(id)?.backend {
+            Backend::Elizabeth { page_id } => Some(page_id.as_str()),
+            _ => None,
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        let mut rooms = HashSet::new();
+
+        for room in &self.rooms {
+            if !rooms.insert(&room.id) {
+                return Err(Error::DuplicateRoom(room.id.clone()));
+            }
+        }
+
+        let mut devices = HashSet::new();
+
+        for device in &self.devices {
+            let id = device.id();
+
+            if !rooms.contains(&device.room) {
+                return Err(Error::UnknownRoom(id));
+            }
+
+            if !device.is_supported_by_backend() {
+                return Err(Error::UnsupportedGateway(id));
+            }
+
+            if let Some(reason) = device.invalid_capabilities() {
+                return Err(Error::InvalidCapabilities(id, reason));
+            }
+
+            if !devices.insert(id.clone()) {
+                return Err(Error::DuplicateDevice(id));
+            }
+        }
+
+        let mut zones = HashSet::new();
+
+        for zone in &self.zones {
+            if !zones.insert(&zone.id) {
+                return Err(Error::DuplicateZone(zone.id.clone()));
+            }
+
+            if zone.areas.is_empty() {
+                return Err(Error::EmptyZone(zone.id.clone()));
+            }
+        }
+
+        let mut scenes = HashSet::new();
+
+        for scene in &self.scenes {
+            if !scenes.insert(&scene.id) {
+                return Err(Error::DuplicateScene(scene.id.clone()));
+            }
+
+            if !rooms.contains(&scene.room) {
+                return Err(Error::UnknownSceneRoom(scene.id.clone()));
+            }
+
+            for step in &scene.steps {
+                if !devices.contains(&step.device) {
+                    return Err(Error::UnknownSceneDevice(
+                        scene.id.clone(),
+                        step.device.clone(),
+                    ));
+                }
+
+                if !step.is_supported() {
+                    return Err(Error::UnsupportedSceneStep(
+                        scene.id.clone(),
+                        step.device.clone(),
+                    ));
+                }
+
+                if let Some(zone) = step.zone().filter(|zone| !zones.contains(zone)) {
+                    return Err(Error::UnknownSceneZone(scene.id.clone(), zone.clone()));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Registry {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let registry: Registry = toml::from_str(s)?;
+        registry.validate()?;
+
+        Ok(registry)
+    }
+}
+
+fn deserialize_mac<'de, D>(deserializer: D) -> std::result::Result<[u8; 6], D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = String::deserialize(deserializer)?;
+    let invalid = || de::Error::invalid_value(de::Unexpected::Str(&value), &"MAC address");
+
+    let parts = value
+        .split(':')
+        .map(|part| u8::from_str_radix(part, 16))
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|_| invalid())?;
+
+    parts.try_into().map_err(|_| invalid())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+        [[rooms]]
+        id = "kitchen"
+        name = "Кухня"
+
+        [[rooms]]
+        id = "living_room"
+        name = "Зал"
+
+        [[devices]]
+        type = "vacuum_cleaner"
+        room = "kitchen"
+        gateway = "elisa"
+        segment_id = 19
+
+        [[devices]]
+        type = "temperature_sensor"
+        room = "kitchen"
+        gateway = "isabel"
+        mac = "58:2d:34:39:97:66"
+
+        [[devices]]
+        type = "vacuum_cleaner"
+        room = "living_room"
+        gateway = "elisa"
+
+        [[devices]]
+        type = "light"
+        room = "living_room"
+        gateway = "elisheba"
+        device_id = "1002074ed2"
+
+        [[devices]]
+        type = "thermostat"
+        room = "living_room"
+        name = "Теплый пол"
+        gateway = "elizabeth"
+        page_id = "ef8f4a07-6fc4-4b7e-99e2-d1c71f4fd96d"
+
+        [[devices]]
+        type = "recuperator"
+        room = "living_room"
+        gateway = "elizabeth"
+        page_id = "ef8f4a07-6fc4-4b7e-99e2-d1c71f4fd96d"
+    "#;
+
+    #[test]
+    fn test_parse() {
+        let registry: Registry = CONFIG.parse().unwrap();
+
+        assert_eq!(registry.rooms().len(), 2);
+        assert_eq!(registry.devices().len(), 6);
+        assert_eq!(registry.room_name(&Room::new("living_room")), Some("Зал"));
+        assert_eq!(registry.room_name(&Room::new("toilet")), None);
+
+        let thermostat = registry
+            .device(&DeviceId::thermostat_at_room(Room::new("living_room")))
+            .unwrap();
+        assert_eq!(thermostat.name.as_deref(), Some("Теплый пол"));
+        assert_eq!(thermostat.backend.gateway(), "elizabeth");
+        assert_eq!(
+            thermostat.backend,
+            Backend::Elizabeth {
+                page_id: "ef8f4a07-6fc4-4b7e-99e2-d1c71f4fd96d".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_sample_config() {
+        let registry: Registry = include_str!("../../../../conf/registry.toml")
+            .parse()
+            .unwrap();
+
+        assert_eq!(registry.rooms().len(), 9);
+        assert_eq!(registry.devices().len(), 19);
+        assert_eq!(registry.scenes().len(), 2);
+    }
+
+    #[test]
+    fn test_lookups() {
+        let registry: Registry = CONFIG.parse().unwrap();
+        let kitchen = Room::new("kitchen");
+        let living_room = Room::new("living_room");
+
+        assert_eq!(registry.segment_for_room(&kitchen), Some(19));
+        assert_eq!(registry.segment_for_room(&living_room), None);
+        assert_eq!(registry.room_for_segment(19), Some(&kitchen));
+        assert_eq!(registry.room_for_segment(16), None);
+        assert_eq!(
+            registry
+                .device(&DeviceId::vacuum_cleaner_at_room(living_room.clone()))
+                .map(|device| &device.backend),
+            Some(&Backend::Elisa { segment_id: None })
+        );
+
+        assert_eq!(
+            registry.room_for_mac(&[0x58, 0x2d, 0x34, 0x39, 0x97, 0x66]),
+            Some(&kitchen)
+        );
+        assert_eq!(registry.room_for_mac(&[0; 6]), None);
+
+        assert_eq!(
+            registry.sonoff_id_for_room(&living_room),
+            Some("1002074ed2")
+        );
+        assert_eq!(
+            registry.room_for_sonoff_id("1002074ed2"),
+            Some(&living_room)
+        );
+
+        assert_eq!(
+            registry.inspinia_page_for(&DeviceId::recuperator_at_room(living_room)),
+            Some("ef8f4a07-6fc4-4b7e-99e2-d1c71f4fd96d")
+        );
+        assert_eq!(
+            registry
+                .devices_of_type(DeviceType::Thermostat)
+                .map(DeviceConfig::id)
+                .collect::<Vec<_>>(),
+            vec![DeviceId::thermostat_at_room(Room::new("living_room"))]
+        );
+    }
+
+    #[test]
+    fn test_unknown_room() {
+        let config = r#"
+            [[devices]]
+            type = "light"
+            room = "attic"
+            gateway = "elisheba"
+            device_id = "1002074ed2"
+        "#;
+
+        assert!(matches!(
+            config.parse::<Registry>(),
+            Err(Error::UnknownRoom(_))
+        ));
+    }
+
+    #[test]
+    fn test_duplicate_device() {
+        let config = r#"
+            [[rooms]]
+            id = "attic"
+            name = "Чердак"
+
+            [[devices]]
+            type = "light"
+            room = "attic"
+            gateway = "elisheba"
+            device_id = "1002074ed2"
+
+            [[devices]]
+            type = "light"
+            room = "attic"
+            gateway = "elisheba"
+            device_id = "10020750eb"
+        "#;
+
+        assert!(matches!(
+            config.parse::<Registry>(),
+            Err(Error::DuplicateDevice(_))
+        ));
+    }
+
+    #[test]
+    fn test_unsupported_gateway() {
+        let config = r#"
+            [[rooms]]
+            id = "attic"
+            name = "Чердак"
+
+            [[devices]]
+            type = "thermostat"
+            room = "attic"
+            gateway = "elisa"
+            segment_id = 1
+        "#;
+
+        assert!(matches!(
+            config.parse::<Registry>(),
+            Err(Error::UnsupportedGateway(_))
+        ));
+    }
+
+    #[test]
+    fn test_scenes() {
+        let config = format!(
+            r#"{CONFIG}
+            [[scenes]]
+            id = "evening"
+            name = "Вечер"
+            room = "living_room"
+            steps = [
+                {{ device = "light/living_room", action = "turn_on" }},
+                {{ device = "thermostat/living_room", action = "set_temperature", value = 23.0 }},
+            ]
+            "#
+        );
+
+        let registry: Registry = config.parse().unwrap();
+
+        let scene = registry.scene(&SceneId::new("evening")).unwrap();
+        assert_eq!(scene.name, "Вечер");
+        assert_eq!(scene.steps.len(), 2);
+        assert!(registry.scene(&SceneId::new("morning")).is_none());
+    }
+
+    #[test]
+    fn test_invalid_scene_steps() {
+        let unknown_device = format!(
+            r#"{CONFIG}
+            [[scenes]]
+            id = "evening"
+            name = "Вечер"
+            room = "living_room"
+            steps = [{{ device = "light/kitchen", action = "turn_on" }}]
+            "#
+        );
+
+        assert!(matches!(
+            unknown_device.parse::<Registry>(),
+            Err(Error::UnknownSceneDevice(_, _))
+        ));
+
+        let unsupported_step = format!(
+            r#"{CONFIG}
+            [[scenes]]
+            id = "evening"
+            name = "Вечер"
+            room = "living_room"
+            steps = [{{ device = "light/living_room", action = "set_fan_speed", speed = "low" }}]
+            "#
+        );
+
+        assert!(matches!(
+            unsupported_step.parse::<Registry>(),
+            Err(Error::UnsupportedSceneStep(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_zones() {
+        let config = format!(
+            r#"{CONFIG}
+            [[zones]]
+            id = "dining_table"
+            name = "Под обеденным столом"
+            areas = [{{ x1 = 25000, y1 = 24000, x2 = 27000, y2 = 25500 }}]
+
+            [[zones]]
+            id = "sofa"
+            name = "Диван"
+            areas = [{{ x1 = 20000, y1 = 20000, x2 = 22000, y2 = 21000, repeat = 3 }}]
+            target = {{ x = 21000, y = 19500 }}
+
+            [[scenes]]
+            id = "after_dinner"
+            name = "После ужина"
+            room = "living_room"
+            steps = [{{ device = "vacuum_cleaner/kitchen", action = "clean_zone", zone = "dining_table" }}]
+            "#
+        );
+
+        let registry: Registry = config.parse().unwrap();
+
+        let dining_table = registry.zone(&ZoneId::new("dining_table")).unwrap();
+        assert_eq!(dining_table.areas[0].repeat, 1);
+        assert_eq!(dining_table.target(), Point { x: 26000, y: 24750 });
+
+        let sofa = registry.zone(&ZoneId::new("sofa")).unwrap();
+        assert_eq!(sofa.areas[0].repeat, 3);
+        assert_eq!(sofa.target(), Point { x: 21000, y: 19500 });
+
+        assert!(registry.zone(&ZoneId::new("bed")).is_none());
+    }
+
+    #[test]
+    fn test_invalid_zones() {
+        let unknown_zone = format!(
+            r#"{CONFIG}
+            [[scenes]]
+            id = "after_dinner"
+            name = "После ужина"
+            room = "living_room"
+            steps = [{{ device = "vacuum_cleaner/kitchen", action = "go_to", zone = "dining_table" }}]
+            "#
+        );
+
+        assert!(matches!(
+            unknown_zone.parse::<Registry>(),
+            Err(Error::UnknownSceneZone(_, _))
+        ));
+
+        let empty_zone = format!(
+            r#"{CONFIG}
+            [[zones]]
+            id = "dining_table"
+            name = "Под обеденным столом"
+            areas = []
+            "#
+        );
+
+        assert!(matches!(
+            empty_zone.parse::<Registry>(),
+            Err(Error::EmptyZone(_))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_mac() {
+        let config = r#"
+            [[rooms]]
+            id = "attic"
+            name = "Чердак"
+
+            [[devices]]
+            type = "temperature_sensor"
+            room = "attic"
+            gateway = "isabel"
+            mac = "58:2d:34:39"
+        "#;
+
+        assert!(matches!(config.parse::<Registry>(), Err(Error::Toml(_))));
+    }
+
+    #[test]
+    fn test_names() {
+        let config = r#"
+            [[rooms]]
+            id = "living_room"
+            name = "Зал"
+            names = { en = "Living room" }
+
+            [[devices]]
+            type = "thermostat"
+            room = "living_room"
+            name = "Теплый пол"
+            names = { en = "Floor heating" }
+            gateway = "elizabeth"
+            page_id = "ef8f4a07-6fc4-4b7e-99e2-d1c71f4fd96d"
+
+            [[devices]]
+            type = "recuperator"
+            room = "living_room"
+            gateway = "elizabeth"
+            page_id = "ef8f4a07-6fc4-4b7e-99e2-d1c71f4fd96d"
+        "#;
+
+        let registry: Registry = config.parse().unwrap();
+        let living_room = Room::new("living_room");
+
+        assert_eq!(registry.room_name(&living_room), Some("Зал"));
+        assert_eq!(
+            registry.room_name_in(&living_room, "en"),
+            Some("Living room")
+        );
+        assert_eq!(registry.room_name_in(&living_room, "de"), Some("Зал"));
+
+        let thermostat = registry
+            .device(&DeviceId::thermostat_at_room(living_room.clone()))
+            .unwrap();
+        assert_eq!(thermostat.name_in("ru"), "Теплый пол");
+        assert_eq!(thermostat.name_in("en"), "Floor heating");
+
+        let recuperator = registry
+            .device(&DeviceId::recuperator_at_room(living_room))
+            .unwrap();
+        assert_eq!(recuperator.name_in("ru"), "Рекуператор");
+        assert_eq!(recuperator.name_in("en"), "Recuperator");
+    }
+
+    #[test]
+    fn test_capability_overrides() {
+        let config = r#"
+            [[rooms]]
+            id = "living_room"
+            name = "Зал"
+
+            [[devices]]
+            type = "thermostat"
+            room = "living_room"
+            gateway = "elizabeth"
+            page_id = "ef8f4a07-6fc4-4b7e-99e2-d1c71f4fd96d"
+            temperature = { min = 20.0, max = 26.0, step = 0.5 }
+
+            [[devices]]
+            type = "recuperator"
+            room = "living_room"
+            gateway = "elizabeth"
+            page_id = "ef8f4a07-6fc4-4b7e-99e2-d1c71f4fd96d"
+            fan_speeds = ["low", "high"]
+        "#;
+
+        let registry: Registry = config.parse().unwrap();
+        let living_room = Room::new("living_room");
+
+        let thermostat = registry
+            .device(&DeviceId::thermostat_at_room(living_room.clone()))
+            .unwrap();
+        assert_eq!(
+            thermostat.temperature_range(),
+            Range {
+                min: 20.0,
+                max: 26.0,
+                step: 0.5
+            }
+        );
+
+        let recuperator = registry
+            .device(&DeviceId::recuperator_at_room(living_room))
+            .unwrap();
+        assert_eq!(recuperator.fan_speeds(), &[FanSpeed::Low, FanSpeed::High]);
+        assert_eq!(
+            recuperator.temperature_range(),
+            metadata::THERMOSTAT_TEMPERATURE
+        );
+    }
+
+    #[test]
+    fn test_invalid_capabilities() {
+        let device = |overrides: &str| {
+            format!(
+                r#"
+                [[rooms]]
+                id = "living_room"
+                name = "Зал"
+
+                [[devices]]
+                type = "thermostat"
+                room = "living_room"
+                gateway = "elizabeth"
+                page_id = "ef8f4a07-6fc4-4b7e-99e2-d1c71f4fd96d"
+                {overrides}
+                "#
+            )
+        };
+
+        for overrides in [
+            r#"fan_speeds = ["low"]"#,
+            "temperature = { min = 30.0, max = 20.0, step = 1.0 }",
+            "temperature = { min = 0.0, max = 100.0, step = 1.0 }",
+        ] {
+            assert!(matches!(
+                device(overrides).parse::<Registry>(),
+                Err(Error::InvalidCapabilities(_, _))
+            ));
+        }
+    }
+}
@@ -0,0 +1,361 @@
+//! Request/response over MQTT v5.
+//!
+//! A request is published with a `ResponseTopic` and `CorrelationData`
+//! property. Every gateway interested in the request answers on that topic,
+//! echoing the correlation data, so a single request may yield several
+//! responses.
+//!
+//! A [`Client`] serves any number of concurrent requests over one connection.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use log::{debug, error};
+use paho_mqtt::{AsyncClient, Message, MessageBuilder, Properties, PropertyCode};
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::sync::mpsc;
+use tokio::time::{self, Instant};
+use uuid::Uuid;
+
+use crate::{envelope, MqttSession, Topic};
+
+/// Pending requests of a [`Client`] by their response topic.
+type Pending = Arc<Mutex<HashMap<String, mpsc::UnboundedSender<Message>>>>;
+
+/// Long-lived requester shared by concurrent callers.
+///
+/// The client stays subscribed to the responses of every request topic and
+/// routes each response to its request by the response topic, which carries
+/// the correlation id. Reconnects are handled by the underlying
+/// [`MqttSession`].
+#[derive(Clone)]
+pub struct Client {
+    mqtt: AsyncClient,
+    pending: Pending,
+}
+
+impl Client {
+    pub async fn connect(
+        address: String,
+        username: String,
+        password: String,
+        client_id: &str,
+    ) -> Result<Client, paho_mqtt::Error> {
+        let mut session =
+            MqttSession::connect_auxiliary(address, username, password, client_id).await?;
+        let client = Client::attach(&mut session).await?;
+
+        tokio::spawn(route_responses(session, client.clone()));
+
+        Ok(client)
+    }
+
+    /// Client sending its requests over `session`, which is also used for
+    /// other messages. Its owner passes every message to [`Client::route`].
+    pub async fn attach(session: &mut MqttSession) -> Result<Client, paho_mqtt::Error> {
+        for topic in [Topic::StateRequest, Topic::ActionRequest, Topic::SceneRun] {
+            if let Some(responses) = topic.response_topic("+".to_string()) {
+                session.subscribe(responses).await?;
+            }
+        }
+
+        Ok(Client {
+            mqtt: session.client().clone(),
+            pending: Pending::default(),
+        })
+    }
+
+    /// Hands `msg` over to the request waiting for it. Returns `false` when
+    /// it isn't a response to a pending request.
+    pub fn route(&self, msg: &Message) -> bool {
+        let pending = self.pending.lock().unwrap();
+
+        // Responses to requests of other clients or to ones that are over.
+        let Some(sender) = pending.get(msg.topic()) else {
+            return false;
+        };
+
+        let _ = sender.send(msg.clone());
+        true
+    }
+
+    /// Publishes `payload` to the request `topic` and collects responses
+    /// until `deadline` passes.
+    pub async fn request<Req, Resp>(
+        &self,
+        topic: Topic,
+        payload: &Req,
+        deadline: Duration,
+    ) -> Result<ClientResponses<Resp>, paho_mqtt::Error>
+    where
+        Req: Serialize,
+        Resp: DeserializeOwned,
+    {
+        let correlation_id = Uuid::new_v4();
+        let response_topic = response_topic(&topic, &correlation_id)?;
+        let message = request_message(&topic, &response_topic, &correlation_id, payload)?;
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.pending
+            .lock()
+            .unwrap()
+            .insert(response_topic.clone(), sender);
+
+        let responses = ClientResponses {
+            receiver,
+            pending: self.pending.clone(),
+            response_topic,
+            correlation_id,
+            deadline: Instant::now() + deadline,
+            _response: std::marker::PhantomData,
+        };
+
+        self.mqtt.publish(message).await?;
+
+        Ok(responses)
+    }
+}
+
+/// Responses to a single [`Client::request`].
+pub struct ClientResponses<Resp> {
+    receiver: mpsc::UnboundedReceiver<Message>,
+    pending: Pending,
+    response_topic: String,
+    correlation_id: Uuid,
+    deadline: Instant,
+    _response: std::marker::PhantomData<Resp>,
+}
+
+impl<Resp: DeserializeOwned> ClientResponses<Resp> {
+    /// Waits for the next response. Returns `None` once the deadline passes.
+    pub async fn next(&mut self) -> Option<Resp> {
+        loop {
+            let msg = match time::timeout_at(self.deadline, self.receiver.recv()).await {
+                Ok(Some(msg)) => msg,
+                Ok(None) | Err(_) => return None,
+            };
+
+            if !is_correlated(&msg, &self.correlation_id) {
+                continue;
+            }
+
+            if let Some(response) = decode_response(&msg) {
+                return Some(response);
+            }
+        }
+    }
+}
+
+impl<Resp> Drop for ClientResponses<Resp> {
+    fn drop(&mut self) {
+        self.pending.lock().unwrap().remove(&self.response_topic);
+    }
+}
+
+async fn route_responses(mut session: MqttSession, client: Client) {
+    while let Some(msg) = session.next_message().await {
+        client.route(&msg);
+    }
+}
+
+fn response_topic(topic: &Topic, correlation_id: &Uuid) -> Result<String, paho_mqtt::Error> {
+    topic
+        .response_topic(correlation_id.to_string())
+        .map(|topic| topic.to_string())
+        .ok_or(paho_mqtt::Error::General("not a request topic"))
+}
+
+fn request_message<Req: Serialize>(
+    topic: &Topic,
+    response_topic: &str,
+    correlation_id: &Uuid,
+    payload: &Req,
+) -> Result<Message, paho_mqtt::Error> {
+    let mut props = Properties::new();
+    props.push_string(PropertyCode::ResponseTopic, response_topic)?;
+    props.push_binary(
+        PropertyCode::CorrelationData,
+        correlation_id.as_bytes().to_vec(),
+    )?;
+
+    debug!("request to {}, waiting on {}", topic, response_topic);
+
+    let payload = envelope::encode(payload);
+
+    Ok(MessageBuilder::new()
+        .topic(topic.to_string())
+        .properties(props)
+        .payload(payload)
+        .finalize())
+}
+
+fn decode_response<Resp: DeserializeOwned>(msg: &Message) -> Option<Resp> {
+    debug!("response: {}", msg.payload_str());
+
+    match envelope::decode(msg.payload()) {
+        Ok(response) => Some(response),
+        Err(err) => {
+            error!("unable to parse response: {}", err);
+            None
+        }
+    }
+}
+
+/// Responses of gateways that do not echo correlation data are accepted, the
+/// response topic alone identifies the request then.
+fn is_correlated(msg: &Message, correlation_id: &Uuid) -> bool {
+    match msg.properties().get_binary(PropertyCode::CorrelationData) {
+        Some(data) => data == correlation_id.as_bytes(),
+        None => true,
+    }
+}
+
+/// Parses an incoming request, returning it together with a [`Responder`]
+/// for answering it. Malformed requests are logged and dropped.
+pub fn serve<Req: DeserializeOwned>(msg: &Message, mqtt: &AsyncClient) -> Option<(Req, Responder)> {
+    let request = match envelope::decode(msg.payload()) {
+        Ok(request) => request,
+        Err(err) => {
+            error!("unable to parse request: {}", err);
+            error!("{}", msg.payload_str());
+            return None;
+        }
+    };
+
+    let topic = match msg.properties().get_string(PropertyCode::ResponseTopic) {
+        Some(topic) => topic,
+        None => {
+            error!("missing response topic");
+            return None;
+        }
+    };
+
+    let responder = Responder {
+        mqtt: mqtt.clone(),
+        topic,
+        correlation_data: msg.properties().get_binary(PropertyCode::CorrelationData),
+    };
+
+    Some((request, responder))
+}
+
+/// Sends responses to the request it was created for.
+pub struct Responder {
+    mqtt: AsyncClient,
+    topic: String,
+    correlation_data: Option<Vec<u8>>,
+}
+
+impl Responder {
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    /// Publishes `response`, logging failures since the requester gives up
+    /// after its deadline anyway.
+    pub async fn respond<Resp: Serialize + std::fmt::Debug>(&self, response: &Resp) {
+        debug!("publish to {}: {:?}", self.topic, response);
+
+        let message = match self.message(response) {
+            Ok(message) => message,
+            Err(err) => {
+                error!("Error preparing response to {}: {}", self.topic, err);
+                return;
+            }
+        };
+
+        if let Err(err) = self.mqtt.publish(message).await {
+            error!("Error sending response to {}: {}", self.topic, err);
+        }
+    }
+
+    fn message<Resp: Serialize>(&self, response: &Resp) -> Result<Message, paho_mqtt::Error> {
+        let mut props = Properties::new();
+
+        if let Some(data) = &self.correlation_data {
+            props.push_binary(PropertyCode::CorrelationData, data.clone())?;
+        }
+
+        let payload = envelope::encode(response);
+
+        Ok(MessageBuilder::new()
+            .topic(&self.topic)
+            .properties(props)
+            .payload(payload)
+            .finalize())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::StateRequest;
+    use crate::{DeviceId, Room};
+
+    fn client() -> AsyncClient {
+        crate::create_client("tcp://localhost:1883".to_string(), "rpc_test").unwrap()
+    }
+
+    fn request_message(correlation_id: &Uuid) -> Message {
+        let mut props = Properties::new();
+        props
+            .push_string(PropertyCode::ResponseTopic, "state/response/1")
+            .unwrap();
+        props
+            .push_binary(
+                PropertyCode::CorrelationData,
+                correlation_id.as_bytes().to_vec(),
+            )
+            .unwrap();
+
+        let request = StateRequest {
+            device_ids: vec![DeviceId::light_at_room(Room::new("kitchen"))],
+        };
+
+        MessageBuilder::new()
+            .topic(Topic::StateRequest.to_string())
+            .properties(props)
+            .payload(envelope::encode(&request))
+            .finalize()
+    }
+
+    #[test]
+    fn test_serve() {
+        let correlation_id = Uuid::new_v4();
+        let msg = request_message(&correlation_id);
+
+        let (request, responder) = serve::<StateRequest>(&msg, &client()).unwrap();
+
+        assert_eq!(
+            request.device_ids,
+            vec![DeviceId::light_at_room(Room::new("kitchen"))]
+        );
+        assert_eq!(responder.topic(), "state/response/1");
+
+        let response = responder.message(&"ok").unwrap();
+        assert_eq!(response.topic(), "state/response/1");
+        assert!(is_correlated(&response, &correlation_id));
+        assert!(!is_correlated(&response, &Uuid::new_v4()));
+    }
+
+    #[test]
+    fn test_serve_without_response_topic() {
+        let msg = MessageBuilder::new()
+            .topic(Topic::StateRequest.to_string())
+            .payload(r#"{"device_ids":[]}"#)
+            .finalize();
+
+        assert!(serve::<StateRequest>(&msg, &client()).is_none());
+    }
+
+    #[test]
+    fn test_uncorrelated_response() {
+        let msg = MessageBuilder::new()
+            .topic("state/response/1")
+            .payload("{}")
+            .finalize();
+
+        assert!(is_correlated(&msg, &Uuid::new_v4()));
+    }
+}
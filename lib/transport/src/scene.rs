@@ -0,0 +1,587 @@
+//! Named batches of actions spanning several gateways, declared in the
+//! registry.
+//!
+//! ```toml
+//! [[scenes]]
+//! id = "leaving"
+//! name = "Ухожу"
+//! room = "hallway"
+//! steps = [
+//!     { device = "light/corridor", action = "turn_off" },
+//!     { device = "thermostat/bedroom", action = "set_temperature", value = 18.0 },
+//!     { device = "vacuum_cleaner/kitchen", action = "turn_on" },
+//!     { device = "vacuum_cleaner/living_room", action = "clean_zone", zone = "dining_table" },
+//! ]
+//! ```
+//!
+//! `clean_zone` and `go_to` steps refer to the zones of the registry,
+//! `reset_consumable` tells the vacuum cleaner a part was replaced:
+//! `{ device = "vacuum_cleaner/kitchen", action = "reset_consumable", consumable = "filter" }`.
+//!
+//! A scene is sent as a single [`ActionRequest`], so every gateway executes
+//! its part concurrently with the others.
+
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::action::{Action, ActionError, ActionRequest, ActionResponse, ActionResult, ErrorKind};
+use crate::elizabeth::{ActionType, FanSpeed};
+use crate::registry::{Names, Registry};
+use crate::{elisa, elisheba, elizabeth, rpc, DeviceId, DeviceType, Room, SceneId, Topic, ZoneId};
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Scene {
+    pub id: SceneId,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Names::is_empty")]
+    pub names: Names,
+    /// Room the scene is shown in by Alice.
+    pub room: Room,
+    pub steps: Vec<Step>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Step {
+    pub device: DeviceId,
+    #[serde(flatten)]
+    pub action: StepAction,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum StepAction {
+    TurnOn,
+    TurnOff,
+    SetTemperature { value: f32 },
+    SetFanSpeed { speed: FanSpeed },
+    CleanZone { zone: ZoneId },
+    GoTo { zone: ZoneId },
+    ResetConsumable { consumable: elisa::Consumable },
+}
+
+/// Request published to [`Topic::SceneRun`], answered with a [`Report`].
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Request {
+    pub scene: SceneId,
+}
+
+/// Outcome of every step of a scene run, in declaration order.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Report {
+    pub scene: SceneId,
+    /// Why the scene didn't run at all, e.g. it isn't in the registry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ActionError>,
+    pub steps: Vec<StepReport>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct StepReport {
+    pub device: DeviceId,
+    pub result: ActionResult,
+}
+
+impl Step {
+    pub fn is_supported(&self) -> bool {
+        matches!(
+            (self.device.device_type, &self.action),
+            (
+                DeviceType::Light | DeviceType::VacuumCleaner,
+                StepAction::TurnOn | StepAction::TurnOff
+            ) | (
+                DeviceType::VacuumCleaner,
+                StepAction::CleanZone { .. }
+                    | StepAction::GoTo { .. }
+                    | StepAction::ResetConsumable { .. }
+            ) | (
+                DeviceType::Thermostat,
+                StepAction::TurnOn | StepAction::TurnOff | StepAction::SetTemperature { .. }
+            ) | (
+                DeviceType::Recuperator,
+                StepAction::TurnOn | StepAction::TurnOff | StepAction::SetFanSpeed { .. }
+            )
+        )
+    }
+
+    /// Zone of the registry the step refers to.
+    pub fn zone(&self) -> Option<&ZoneId> {
+        match &self.action {
+            StepAction::CleanZone { zone } | StepAction::GoTo { zone } => Some(zone),
+            _ => None,
+        }
+    }
+}
+
+impl Report {
+    /// Report of a scene that didn't run at all.
+    pub fn failure(scene: SceneId, kind: ErrorKind, message: impl Into<String>) -> Report {
+        Report {
+            scene,
+            error: Some(ActionError::new(kind, message)),
+            steps: vec![],
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.error.is_none() && self.first_failure().is_none()
+    }
+
+    pub fn first_failure(&self) -> Option<&StepReport> {
+        self.steps
+            .iter()
+            .find(|step| matches!(step.result, ActionResult::Failure(_)))
+    }
+}
+
+/// Gateway actions a scene expands into, together with the action every
+/// step waits for.
+#[derive(Debug)]
+pub struct Plan {
+    pub actions: Vec<Action>,
+    step_ids: Vec<Uuid>,
+    /// Why the steps left without an action can't run.
+    failures: HashMap<Uuid, ActionResult>,
+}
+
+impl Scene {
+    /// Name in `language`, falling back to the untranslated name.
+    pub fn name_in(&self, language: &str) -> &str {
+        self.names.get(language).unwrap_or(&self.name)
+    }
+
+    pub fn plan(&self, registry: &Registry) -> Plan {
+        Plan::new(&self.steps, registry)
+    }
+}
+
+impl Plan {
+    /// Expands `steps` into gateway actions. Vacuum cleaner rooms are merged
+    /// into a single start, the robot cleans them in one go.
+    pub fn new(steps: &[Step], registry: &Registry) -> Plan {
+        let mut actions = vec![];
+        let mut step_ids = vec![];
+        let mut failures = HashMap::new();
+
+        let mut vacuum_start: Option<(Vec<Room>, Uuid)> = None;
+
+        for step in steps {
+            let room = step.device.room.clone();
+            let id = Uuid::new_v4();
+
+            let action = match (step.device.device_type, &step.action) {
+                (DeviceType::Light, StepAction::TurnOn | StepAction::TurnOff) => Action::Elisheba(
+                    elisheba::Action {
+                        room,
+                        is_enabled: step.action == StepAction::TurnOn,
+                    },
+                    id,
+                ),
+                (DeviceType::VacuumCleaner, StepAction::TurnOn) => {
+                    let (rooms, id) = vacuum_start.get_or_insert_with(|| (vec![], id));
+                    rooms.push(room);
+                    step_ids.push(*id);
+                    continue;
+                }
+                (DeviceType::VacuumCleaner, StepAction::TurnOff) => {
+                    Action::Elisa(elisa::Action::Stop, id)
+                }
+                (
+                    DeviceType::VacuumCleaner,
+                    StepAction::CleanZone { zone } | StepAction::GoTo { zone },
+                ) => {
+                    let Some(zone) = registry.zone(zone) else {
+                        warn!("skipping step with unknown zone {:?}", step);
+                        let message = format!("unknown zone {zone}");
+                        failures
+                            .insert(id, ActionResult::failure(ErrorKind::InvalidValue, message));
+                        step_ids.push(id);
+                        continue;
+                    };
+
+                    let action = match step.action {
+                        StepAction::CleanZone { .. } => {
+                            elisa::Action::CleanZones(zone.areas.clone())
+                        }
+                        _ => elisa::Action::GoTo(zone.target()),
+                    };
+
+                    Action::Elisa(action, id)
+                }
+                (DeviceType::VacuumCleaner, StepAction::ResetConsumable { consumable }) => {
+                    Action::Elisa(elisa::Action::ResetConsumable(*consumable), id)
+                }
+                (device_type @ (DeviceType::Thermostat | DeviceType::Recuperator), action) => {
+                    let action_type = match action {
+                        StepAction::TurnOn => ActionType::SetIsEnabled(true),
+                        StepAction::TurnOff => ActionType::SetIsEnabled(false),
+                        StepAction::SetTemperature { value } => {
+                            ActionType::SetTemperature(*value, false)
+                        }
+                        StepAction::SetFanSpeed { speed } => ActionType::SetFanSpeed(*speed),
+                        StepAction::CleanZone { .. }
+                        | StepAction::GoTo { .. }
+                        | StepAction::ResetConsumable { .. } => {
+                            warn!("skipping unsupported step {:?}", step);
+                            failures.insert(id, unsupported(step));
+                            step_ids.push(id);
+                            continue;
+                        }
+                    };
+
+                    Action::Elizabeth(
+                        elizabeth::Action {
+                            room,
+                            device_type,
+                            action_type,
+                        },
+                        id,
+                    )
+                }
+                _ => {
+                    warn!("skipping unsupported step {:?}", step);
+                    failures.insert(id, unsupported(step));
+                    step_ids.push(id);
+                    continue;
+                }
+            };
+
+            actions.push(action);
+            step_ids.push(id);
+        }
+
+        if let Some((rooms, id)) = vacuum_start {
+            actions.push(Action::Elisa(elisa::Action::Start(rooms), id));
+        }
+
+        Plan {
+            actions,
+            step_ids,
+            failures,
+        }
+    }
+
+    /// Pairs `steps` the plan was made of with the responses received. Steps
+    /// left out of the plan fail with the reason they were left out, those
+    /// without a response with [`ErrorKind::Timeout`].
+    pub fn report(&self, steps: &[Step], results: &HashMap<Uuid, ActionResult>) -> Vec<StepReport> {
+        steps
+            .iter()
+            .zip(&self.step_ids)
+            .map(|(step, id)| StepReport {
+                device: step.device.clone(),
+                result: results
+                    .get(id)
+                    .or_else(|| self.failures.get(id))
+                    .cloned()
+                    .unwrap_or_else(|| {
+                        ActionResult::failure(ErrorKind::Timeout, "no response from gateway")
+                    }),
+            })
+            .collect()
+    }
+}
+
+fn unsupported(step: &Step) -> ActionResult {
+    let message = format!("{:?} is not supported by {}", step.action, step.device);
+    ActionResult::failure(ErrorKind::NotSupported, message)
+}
+
+/// Runs `scene` and waits up to `deadline` for the gateways to report back.
+pub async fn run(
+    mqtt: &rpc::Client,
+    registry: &Registry,
+    scene: &Scene,
+    deadline: Duration,
+) -> Result<Report, paho_mqtt::Error> {
+    debug!("running scene {}", scene.id);
+
+    Ok(Report {
+        scene: scene.id.clone(),
+        error: None,
+        steps: run_steps(mqtt, registry, &scene.steps, deadline).await?,
+    })
+}
+
+/// Runs `steps` as a single [`ActionRequest`] and reports every step in
+/// order.
+pub async fn run_steps(
+    mqtt: &rpc::Client,
+    registry: &Registry,
+    steps: &[Step],
+    deadline: Duration,
+) -> Result<Vec<StepReport>, paho_mqtt::Error> {
+    let plan = Plan::new(steps, registry);
+    let mut pending: HashSet<Uuid> = plan.actions.iter().filter_map(Action::id).collect();
+    let mut results = HashMap::new();
+
+    debug!("running {:?}", plan.actions);
+
+    if !pending.is_empty() {
+        let request = ActionRequest {
+            actions: plan.actions.clone(),
+        };
+
+        let mut responses = mqtt
+            .request::<_, ActionResponse>(Topic::ActionRequest, &request, deadline)
+            .await?;
+
+        while let Some(response) = responses.next().await {
+            pending.remove(&response.action_id);
+            results.insert(response.action_id, response.result);
+
+            if pending.is_empty() {
+                break;
+            }
+        }
+    }
+
+    Ok(plan.report(steps, &results))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scene() -> Scene {
+        toml::from_str(
+            r#"
+            id = "leaving"
+            name = "Ухожу"
+            room = "hallway"
+            steps = [
+                { device = "light/corridor", action = "turn_off" },
+                { device = "vacuum_cleaner/kitchen", action = "turn_on" },
+                { device = "thermostat/bedroom", action = "set_temperature", value = 18.0 },
+                { device = "recuperator/living_room", action = "set_fan_speed", speed = "low" },
+                { device = "vacuum_cleaner/hallway", action = "turn_on" },
+            ]
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn registry() -> Registry {
+        r#"
+        [[zones]]
+        id = "dining_table"
+        name = "Под обеденным столом"
+        areas = [
+            { x1 = 25000, y1 = 24000, x2 = 27000, y2 = 25500, repeat = 2 },
+            { x1 = 27000, y1 = 24000, x2 = 28000, y2 = 25000 },
+        ]
+        "#
+        .parse()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        let scene = scene();
+
+        assert_eq!(scene.id, SceneId::new("leaving"));
+        assert_eq!(scene.steps.len(), 5);
+        assert_eq!(
+            scene.steps[2],
+            Step {
+                device: DeviceId::thermostat_at_room(Room::new("bedroom")),
+                action: StepAction::SetTemperature { value: 18.0 },
+            }
+        );
+        assert!(scene.steps.iter().all(Step::is_supported));
+    }
+
+    #[test]
+    fn test_unsupported_step() {
+        let step = Step {
+            device: DeviceId::light_at_room(Room::new("corridor")),
+            action: StepAction::SetTemperature { value: 18.0 },
+        };
+        assert!(!step.is_supported());
+
+        let step = Step {
+            device: DeviceId::temperature_sensor_at_room(Room::new("kitchen")),
+            action: StepAction::TurnOn,
+        };
+        assert!(!step.is_supported());
+    }
+
+    #[test]
+    fn test_plan() {
+        let plan = scene().plan(&registry());
+
+        let actions: Vec<_> = plan
+            .actions
+            .iter()
+            .map(|action| match action {
+                Action::Elisa(action, _) => format!("{:?}", action),
+                Action::Elisheba(action, _) => format!("{:?}", action),
+                Action::Elizabeth(action, _) => format!("{:?}", action.action_type),
+                Action::Unknown(_) => unreachable!(),
+            })
+            .collect();
+
+        assert_eq!(
+            actions,
+            vec![
+                r#"Action { room: Room("corridor"), is_enabled: false }"#,
+                "SetTemperature(18.0, false)",
+                "SetFanSpeed(Low)",
+                r#"Start([Room("kitchen"), Room("hallway")])"#,
+            ]
+        );
+
+        assert_eq!(plan.step_ids[1], plan.step_ids[4]);
+        assert_eq!(plan.actions[3].id(), Some(plan.step_ids[1]));
+    }
+
+    #[test]
+    fn test_zone_plan() {
+        let step = |action| Step {
+            device: DeviceId::vacuum_cleaner_at_room(Room::new("living_room")),
+            action,
+        };
+        let zone = ZoneId::new("dining_table");
+        let steps = [
+            step(StepAction::CleanZone { zone: zone.clone() }),
+            step(StepAction::GoTo { zone }),
+            step(StepAction::GoTo {
+                zone: ZoneId::new("sofa"),
+            }),
+            step(StepAction::ResetConsumable {
+                consumable: elisa::Consumable::Filter,
+            }),
+        ];
+        assert!(steps.iter().all(Step::is_supported));
+
+        let plan = Plan::new(&steps, &registry());
+        let actions: Vec<_> = plan
+            .actions
+            .iter()
+            .map(|action| match action {
+                Action::Elisa(action, _) => action.clone(),
+                _ => unreachable!(),
+            })
+            .collect();
+
+        assert_eq!(
+            actions,
+            vec![
+                elisa::Action::CleanZones(vec![
+                    elisa::Zone {
+                        x1: 25000,
+                        y1: 24000,
+                        x2: 27000,
+                        y2: 25500,
+                        repeat: 2
+                    },
+                    elisa::Zone {
+                        x1: 27000,
+                        y1: 24000,
+                        x2: 28000,
+                        y2: 25000,
+                        repeat: 1
+                    },
+                ]),
+                elisa::Action::GoTo(elisa::Point { x: 26000, y: 24750 }),
+                elisa::Action::ResetConsumable(elisa::Consumable::Filter),
+            ]
+        );
+        assert_eq!(plan.step_ids.len(), 4);
+
+        let results = plan
+            .actions
+            .iter()
+            .filter_map(Action::id)
+            .map(|id| (id, ActionResult::Success))
+            .collect();
+        let report = plan.report(&steps, &results);
+
+        assert_eq!(report[1].result, ActionResult::Success);
+        assert_eq!(
+            report[2].result,
+            ActionResult::failure(ErrorKind::InvalidValue, "unknown zone sofa")
+        );
+    }
+
+    #[test]
+    fn test_unsupported_step_report() {
+        let steps = [Step {
+            device: DeviceId::temperature_sensor_at_room(Room::new("kitchen")),
+            action: StepAction::TurnOn,
+        }];
+
+        let plan = Plan::new(&steps, &registry());
+        assert!(plan.actions.is_empty());
+
+        assert_eq!(
+            plan.report(&steps, &HashMap::new())[0].result,
+            ActionResult::failure(
+                ErrorKind::NotSupported,
+                "TurnOn is not supported by temperature_sensor/kitchen"
+            )
+        );
+    }
+
+    #[test]
+    fn test_report() {
+        let scene = scene();
+        let plan = scene.plan(&registry());
+
+        let mut results = HashMap::new();
+        for action in &plan.actions {
+            results.insert(action.id().unwrap(), ActionResult::Success);
+        }
+        results.insert(
+            plan.step_ids[1],
+            ActionResult::failure(ErrorKind::DeviceBusy, "vacuum is docking"),
+        );
+        results.remove(&plan.step_ids[2]);
+
+        let report = Report {
+            scene: scene.id.clone(),
+            error: None,
+            steps: plan.report(&scene.steps, &results),
+        };
+
+        assert!(!report.is_success());
+        assert_eq!(report.steps[0].result, ActionResult::Success);
+        assert_eq!(
+            report.steps[4].result,
+            ActionResult::failure(ErrorKind::DeviceBusy, "vacuum is docking")
+        );
+        assert_eq!(
+            report.steps[2].result,
+            ActionResult::Failure(ActionError::new(
+                ErrorKind::Timeout,
+                "no response from gateway"
+            ))
+        );
+        assert_eq!(
+            report.first_failure().unwrap().device,
+            DeviceId::vacuum_cleaner_at_room(Room::new("kitchen"))
+        );
+    }
+
+    #[test]
+    fn test_unknown_scene_report() {
+        let report = Report::failure(
+            SceneId::new("party"),
+            ErrorKind::InvalidValue,
+            "unknown scene party",
+        );
+
+        assert!(!report.is_success());
+        assert_eq!(
+            serde_json::to_value(&report).unwrap(),
+            serde_json::json!({
+                "scene": "party",
+                "error": { "kind": "invalid_value", "message": "unknown scene party" },
+                "steps": [],
+            })
+        );
+    }
+}
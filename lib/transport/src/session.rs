@@ -0,0 +1,197 @@
+use std::fmt;
+use std::time::Duration;
+
+use log::{debug, error, info};
+use paho_mqtt::{AsyncClient, AsyncReceiver, Message, QOS_1};
+use tokio::time;
+
+use crate::availability::publish_online;
+use crate::{connect_client, create_client, Topic};
+
+const RECONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// MQTT connection that remembers its subscriptions and transparently
+/// reconnects, re-subscribing once the broker is reachable again.
+pub struct MqttSession {
+    client: AsyncClient,
+    stream: AsyncReceiver<Option<Message>>,
+    subscriptions: Vec<String>,
+    /// Service whose availability the session announces.
+    service: Option<String>,
+}
+
+impl MqttSession {
+    /// Main session of the service `client_id`, announcing its availability.
+    pub async fn connect(
+        address: String,
+        username: String,
+        password: String,
+        client_id: &str,
+    ) -> Result<MqttSession, paho_mqtt::Error> {
+        Self::connect_with(address, username, password, client_id, Some(client_id)).await
+    }
+
+    /// Session of an auxiliary connection of a service, e.g. for its RPC
+    /// requests, which leaves the availability to the main session.
+    pub async fn connect_auxiliary(
+        address: String,
+        username: String,
+        password: String,
+        client_id: &str,
+    ) -> Result<MqttSession, paho_mqtt::Error> {
+        Self::connect_with(address, username, password, client_id, None).await
+    }
+
+    async fn connect_with(
+        address: String,
+        username: String,
+        password: String,
+        client_id: &str,
+        service: Option<&str>,
+    ) -> Result<MqttSession, paho_mqtt::Error> {
+        let mut client = create_client(address, client_id)?;
+        let stream = client.get_stream(None);
+
+        connect_client(&client, username, password, service).await?;
+
+        Ok(MqttSession {
+            client,
+            stream,
+            subscriptions: vec![],
+            service: service.map(str::to_string),
+        })
+    }
+
+    /// Client for publishing. Publishing while disconnected fails until the
+    /// session has reconnected.
+    pub fn client(&self) -> &AsyncClient {
+        &self.client
+    }
+
+    /// Subscribes to `filter`, either a [`Topic`] or a raw MQTT filter, and
+    /// keeps the subscription across reconnects.
+    pub async fn subscribe(&mut self, filter: impl fmt::Display) -> Result<(), paho_mqtt::Error> {
+        let filter = filter.to_string();
+
+        self.client.subscribe(&filter, QOS_1).await?;
+        info!("Subscribed to topic: {}", filter);
+
+        self.subscriptions.push(filter);
+
+        Ok(())
+    }
+
+    /// Waits for the next message with a known [`Topic`], reconnecting when
+    /// the connection is lost. Returns `None` once the client is dropped.
+    pub async fn next(&mut self) -> Option<(Topic, Message)> {
+        loop {
+            let msg = self.next_message().await?;
+
+            match msg.topic().parse() {
+                Ok(topic) => return Some((topic, msg)),
+                Err(_) => debug!("skipping message on topic {}", msg.topic()),
+            }
+        }
+    }
+
+    /// Like [`MqttSession::next`], but also returns messages on topics
+    /// outside of [`Topic`], e.g. ones subscribed with a raw filter.
+    pub async fn next_message(&mut self) -> Option<Message> {
+        loop {
+            match self.stream.recv().await {
+                Ok(Some(msg)) => return Some(msg),
+                Ok(None) => self.reconnect().await,
+                Err(_) => return None,
+            }
+        }
+    }
+
+    async fn reconnect(&mut self) {
+        error!("Lost MQTT connection. Attempting reconnect.");
+
+        let mut backoff = Backoff::default();
+
+        loop {
+            time::sleep(backoff.next_delay()).await;
+
+            match time::timeout(RECONNECT_TIMEOUT, self.client.reconnect()).await {
+                Ok(Ok(response)) => {
+                    info!("Reconnected to MQTT! {}", response.reason_code());
+
+                    if let Some(service) = &self.service {
+                        if let Err(err) = publish_online(&self.client, service).await {
+                            error!("Error MQTT publishing availability: {}", err);
+                        }
+                    }
+
+                    match self.resubscribe().await {
+                        Ok(()) => return,
+                        Err(err) => error!("Error MQTT resubscribing: {}", err),
+                    }
+                }
+                Ok(Err(err)) => error!("Error MQTT reconnecting: {}", err),
+                Err(err) => error!("Error MQTT reconnecting: {}", err),
+            }
+        }
+    }
+
+    async fn resubscribe(&self) -> Result<(), paho_mqtt::Error> {
+        if self.subscriptions.is_empty() {
+            return Ok(());
+        }
+
+        let qos = vec![QOS_1; self.subscriptions.len()];
+        self.client
+            .subscribe_many(&self.subscriptions, &qos)
+            .await?;
+        info!("Subscribed to topics: {:?}", self.subscriptions);
+
+        Ok(())
+    }
+}
+
+/// Exponential backoff with jitter: every delay is picked at random from the
+/// upper half of a window that doubles after each attempt.
+struct Backoff {
+    window: Duration,
+    max: Duration,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff {
+            window: Duration::from_secs(1),
+            max: Duration::from_secs(60),
+        }
+    }
+}
+
+impl Backoff {
+    fn next_delay(&mut self) -> Duration {
+        let window = self.window;
+        self.window = (self.window * 2).min(self.max);
+
+        let half = window / 2;
+        half + half.mul_f64(rand::random::<f64>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_grows_until_max() {
+        let mut backoff = Backoff::default();
+
+        let windows = [1, 2, 4, 8, 16, 32, 60, 60];
+
+        for window in windows {
+            let window = Duration::from_secs(window);
+            let delay = backoff.next_delay();
+
+            assert!(delay >= window / 2, "{delay:?} < {:?}", window / 2);
+            assert!(delay <= window, "{delay:?} > {window:?}");
+        }
+    }
+}
@@ -0,0 +1,145 @@
+//! Slugs of the rooms, scenes and zones declared in the device registry.
+//!
+//! Slugs are lowercase ASCII letters, digits and underscores, which keeps them
+//! safe to embed in `device_type/room` ids and MQTT topics.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{de, Deserialize, Serialize};
+
+macro_rules! slug {
+    ($(#[$meta:meta])* $name:ident, $error:ident, $kind:literal) => {
+        $(#[$meta])*
+        #[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+        #[serde(transparent)]
+        pub struct $name(String);
+
+        impl $name {
+            #[doc = concat!("Creates a ", $kind, " from a known-valid slug.")]
+            ///
+            /// Panics if `slug` is not a valid slug, use [`str::parse`] for
+            /// untrusted input.
+            pub fn new(slug: &str) -> $name {
+                match slug.parse() {
+                    Ok(value) => value,
+                    Err(err) => panic!("{err}"),
+                }
+            }
+
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        #[derive(Debug)]
+        pub struct $error(String);
+
+        impl fmt::Display for $error {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, concat!("invalid ", $kind, " slug `{}`"), self.0)
+            }
+        }
+
+        impl std::error::Error for $error {}
+
+        impl FromStr for $name {
+            type Err = $error;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                if is_slug(s) {
+                    Ok($name(s.to_string()))
+                } else {
+                    Err($error(s.to_string()))
+                }
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: serde::Deserializer<'de>,
+            {
+                let value = String::deserialize(deserializer)?;
+                value.parse().map_err(de::Error::custom)
+            }
+        }
+    };
+}
+
+slug!(
+    /// A room slug declared in the device registry, e.g. `living_room`.
+    Room,
+    InvalidRoom,
+    "room"
+);
+
+slug!(
+    /// A scene slug declared in the device registry, e.g. `leaving`.
+    SceneId,
+    InvalidSceneId,
+    "scene"
+);
+
+slug!(
+    /// A vacuum zone slug declared in the device registry, e.g. `dining_table`.
+    ZoneId,
+    InvalidZoneId,
+    "zone"
+);
+
+fn is_slug(s: &str) -> bool {
+    !s.is_empty()
+        && s.bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Room::from_str("living_room").unwrap().as_str(),
+            "living_room"
+        );
+        assert_eq!(
+            Room::from_str("guest_room_2").unwrap().as_str(),
+            "guest_room_2"
+        );
+
+        assert!(Room::from_str("").is_err());
+        assert!(Room::from_str("Living Room").is_err());
+        assert!(Room::from_str("living/room").is_err());
+    }
+
+    #[test]
+    fn test_serde() {
+        let room = Room::new("home_office");
+
+        assert_eq!(serde_json::to_string(&room).unwrap(), "\"home_office\"");
+        assert_eq!(
+            serde_json::from_str::<Room>("\"home_office\"").unwrap(),
+            room
+        );
+        assert!(serde_json::from_str::<Room>("\"home/office\"").is_err());
+    }
+
+    #[test]
+    fn test_scene_id() {
+        let scene = SceneId::new("good_night");
+
+        assert_eq!(serde_json::to_string(&scene).unwrap(), "\"good_night\"");
+        assert_eq!(
+            SceneId::from_str("Good Night").unwrap_err().to_string(),
+            "invalid scene slug `Good Night`"
+        );
+    }
+}
@@ -27,10 +27,10 @@ mod tests {
 
         let request = Request {
             device_ids: vec![
-                DeviceId::recuperator_at_room(Room::Bathroom),
-                DeviceId::temperature_sensor_at_room(Room::Bathroom),
-                DeviceId::thermostat_at_room(Room::Bathroom),
-                DeviceId::vacuum_cleaner_at_room(Room::Bathroom),
+                DeviceId::recuperator_at_room(Room::new("bathroom")),
+                DeviceId::temperature_sensor_at_room(Room::new("bathroom")),
+                DeviceId::thermostat_at_room(Room::new("bathroom")),
+                DeviceId::vacuum_cleaner_at_room(Room::new("bathroom")),
             ],
         };
 
@@ -53,10 +53,10 @@ mod tests {
             request,
             Request {
                 device_ids: vec![
-                    DeviceId::recuperator_at_room(Room::Bathroom),
-                    DeviceId::temperature_sensor_at_room(Room::Bathroom),
-                    DeviceId::thermostat_at_room(Room::Bathroom),
-                    DeviceId::vacuum_cleaner_at_room(Room::Bathroom),
+                    DeviceId::recuperator_at_room(Room::new("bathroom")),
+                    DeviceId::temperature_sensor_at_room(Room::new("bathroom")),
+                    DeviceId::thermostat_at_room(Room::new("bathroom")),
+                    DeviceId::vacuum_cleaner_at_room(Room::new("bathroom")),
                 ],
             }
         );
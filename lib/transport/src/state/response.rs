@@ -1,4 +1,4 @@
-use crate::{elisa, elisheba, elizabeth};
+use crate::{elisa, elisheba, elizabeth, isabel};
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
@@ -7,4 +7,15 @@ pub enum Response {
     Elisa(elisa::State),
     Elisheba(elisheba::State),
     Elizabeth(elizabeth::CurrentState),
+    Isabel(isabel::CurrentState),
+    /// Payload of a newer schema, kept as is so it can be logged and skipped.
+    #[serde(untagged, deserialize_with = "unknown_response")]
+    Unknown(serde_json::Value),
+}
+
+fn unknown_response<'de, D>(deserializer: D) -> Result<serde_json::Value, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    crate::envelope::unknown_variant(deserializer, &["elisa", "elisheba", "elizabeth", "isabel"])
 }
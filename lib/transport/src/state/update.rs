@@ -7,4 +7,14 @@ pub enum Update {
     Elisheba(crate::elisheba::State),
     Elizabeth(crate::elizabeth::State),
     Isabel(crate::isabel::State),
+    /// Payload of a newer schema, kept as is so it can be logged and skipped.
+    #[serde(untagged, deserialize_with = "unknown_update")]
+    Unknown(serde_json::Value),
+}
+
+fn unknown_update<'de, D>(deserializer: D) -> Result<serde_json::Value, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    crate::envelope::unknown_variant(deserializer, &["elisa", "elisheba", "elizabeth", "isabel"])
 }
@@ -3,23 +3,50 @@ use std::str::FromStr;
 
 use serde::de::{value, Error};
 
+use crate::DeviceId;
+
 #[derive(Debug, PartialEq)]
 pub enum Topic {
     StateUpdate,
+    /// Retained last known state of a single device.
+    DeviceState(DeviceId),
     StateRequest,
     StateResponse(String),
     ActionRequest,
     ActionResponse(String),
+    /// Liveness of a service, `+` subscribes to all of them.
+    Availability(String),
+    SceneRun,
+    SceneResponse(String),
+    /// A consumable of the vacuum cleaner became due for replacement.
+    ConsumableWarning,
+}
+
+impl Topic {
+    /// Topic carrying the responses to a request published on `self`.
+    pub fn response_topic(&self, id: String) -> Option<Topic> {
+        match self {
+            Topic::StateRequest => Some(Topic::StateResponse(id)),
+            Topic::ActionRequest => Some(Topic::ActionResponse(id)),
+            Topic::SceneRun => Some(Topic::SceneResponse(id)),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for Topic {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Topic::StateUpdate => write!(f, "state/update"),
+            Topic::DeviceState(device_id) => write!(f, "state/{}", device_id),
             Topic::StateRequest => write!(f, "state/request"),
             Topic::StateResponse(device_id) => write!(f, "state/response/{}", device_id),
             Topic::ActionRequest => write!(f, "action/request"),
             Topic::ActionResponse(device_id) => write!(f, "action/response/{}", device_id),
+            Topic::Availability(service) => write!(f, "availability/{}", service),
+            Topic::SceneRun => write!(f, "scene/run"),
+            Topic::SceneResponse(id) => write!(f, "scene/response/{}", id),
+            Topic::ConsumableWarning => write!(f, "warning/consumable"),
         }
     }
 }
@@ -29,12 +56,15 @@ impl FromStr for Topic {
 
     fn from_str(s: &str) -> std::result::Result<Topic, Self::Err> {
         const ERROR_MSG: &str = "supported topics are state, state/request, action/request, \
-            state/response/<id> and action/response/<id>";
+            state/<device_type>/<room>, state/response/<id>, action/response/<id>, \
+            availability/<service>, scene/run, scene/response/<id> and warning/consumable";
 
         match s {
             "state/update" => Ok(Topic::StateUpdate),
             "state/request" => Ok(Topic::StateRequest),
             "action/request" => Ok(Topic::ActionRequest),
+            "scene/run" => Ok(Topic::SceneRun),
+            "warning/consumable" => Ok(Topic::ConsumableWarning),
             _ => {
                 let (topic, id) = s
                     .rsplit_once('/')
@@ -43,7 +73,13 @@ impl FromStr for Topic {
                 match topic {
                     "state/response" => Ok(Topic::StateResponse(id.to_string())),
                     "action/response" => Ok(Topic::ActionResponse(id.to_string())),
-                    _ => Err(value::Error::custom(ERROR_MSG)),
+                    "availability" => Ok(Topic::Availability(id.to_string())),
+                    "scene/response" => Ok(Topic::SceneResponse(id.to_string())),
+                    _ => s
+                        .strip_prefix("state/")
+                        .and_then(|id| id.parse().ok())
+                        .map(Topic::DeviceState)
+                        .ok_or_else(|| value::Error::custom(ERROR_MSG)),
                 }
             }
         }
@@ -53,6 +89,7 @@ impl FromStr for Topic {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::Room;
 
     #[test]
     fn test_serialization() {
@@ -62,6 +99,9 @@ mod tests {
         let topic = Topic::StateRequest;
         assert_eq!(topic.to_string(), "state/request");
 
+        let topic = Topic::DeviceState(DeviceId::thermostat_at_room(Room::new("living_room")));
+        assert_eq!(topic.to_string(), "state/thermostat/living_room");
+
         let topic = Topic::StateResponse("656997EA-01B8-4F64-84E8-9603F12FD448".to_string());
         assert_eq!(
             topic.to_string(),
@@ -76,6 +116,15 @@ mod tests {
             topic.to_string(),
             "action/response/AD56F627-ABF2-4F3C-B098-FF8D76DE4F72"
         );
+
+        let topic = Topic::Availability("elisa".to_string());
+        assert_eq!(topic.to_string(), "availability/elisa");
+
+        let topic = Topic::SceneRun;
+        assert_eq!(topic.to_string(), "scene/run");
+
+        let topic = Topic::ConsumableWarning;
+        assert_eq!(topic.to_string(), "warning/consumable");
     }
 
     #[test]
@@ -86,6 +135,14 @@ mod tests {
         let topic = Topic::from_str("state/request").unwrap();
         assert_eq!(topic, Topic::StateRequest);
 
+        let topic = Topic::from_str("state/vacuum_cleaner/kitchen").unwrap();
+        assert_eq!(
+            topic,
+            Topic::DeviceState(DeviceId::vacuum_cleaner_at_room(Room::new("kitchen")))
+        );
+
+        assert!(Topic::from_str("state/kettle/kitchen").is_err());
+
         let topic = Topic::from_str("state/response/8E1E559B-27A4-4D2D-990F-AE8D5FF9B074").unwrap();
         assert_eq!(
             topic,
@@ -101,5 +158,34 @@ mod tests {
             topic,
             Topic::ActionResponse("D4A49F18-5F23-4848-B3CB-1A37E206D64E".to_string())
         );
+
+        let topic = Topic::from_str("availability/elisheba").unwrap();
+        assert_eq!(topic, Topic::Availability("elisheba".to_string()));
+
+        let topic = Topic::from_str("scene/run").unwrap();
+        assert_eq!(topic, Topic::SceneRun);
+
+        let topic = Topic::from_str("scene/response/1").unwrap();
+        assert_eq!(topic, Topic::SceneResponse("1".to_string()));
+
+        let topic = Topic::from_str("warning/consumable").unwrap();
+        assert_eq!(topic, Topic::ConsumableWarning);
+    }
+
+    #[test]
+    fn test_response_topic() {
+        assert_eq!(
+            Topic::StateRequest.response_topic("1".to_string()),
+            Some(Topic::StateResponse("1".to_string()))
+        );
+        assert_eq!(
+            Topic::ActionRequest.response_topic("2".to_string()),
+            Some(Topic::ActionResponse("2".to_string()))
+        );
+        assert_eq!(
+            Topic::SceneRun.response_topic("3".to_string()),
+            Some(Topic::SceneResponse("3".to_string()))
+        );
+        assert_eq!(Topic::StateUpdate.response_topic("4".to_string()), None);
     }
 }
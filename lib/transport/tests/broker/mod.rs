@@ -0,0 +1,268 @@
+//! Minimal in-process MQTT v5 broker standing in for mosquitto.
+//!
+//! Supports just what the transport clients use: connecting, (un)subscribing
+//! with `+` and `#` filters and publishing with QoS up to 2. Messages are
+//! forwarded at QoS 0 with their properties intact; retained messages and
+//! wills are not kept.
+
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::OwnedReadHalf;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+
+const CONNECT: u8 = 1;
+const CONNACK: u8 = 2;
+const PUBLISH: u8 = 3;
+const PUBACK: u8 = 4;
+const PUBREC: u8 = 5;
+const PUBREL: u8 = 6;
+const PUBCOMP: u8 = 7;
+const SUBSCRIBE: u8 = 8;
+const SUBACK: u8 = 9;
+const UNSUBSCRIBE: u8 = 10;
+const UNSUBACK: u8 = 11;
+const PINGREQ: u8 = 12;
+const PINGRESP: u8 = 13;
+const DISCONNECT: u8 = 14;
+
+struct Subscription {
+    connection: usize,
+    filter: String,
+    sender: mpsc::UnboundedSender<Vec<u8>>,
+}
+
+type Subscriptions = Arc<Mutex<Vec<Subscription>>>;
+
+/// Starts the broker on a random local port, returning its `tcp://` address.
+pub async fn start() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address: SocketAddr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        let subscriptions = Subscriptions::default();
+
+        for connection in 0.. {
+            let Ok((stream, _)) = listener.accept().await else {
+                return;
+            };
+
+            tokio::spawn(serve(connection, stream, subscriptions.clone()));
+        }
+    });
+
+    format!("tcp://{address}")
+}
+
+async fn serve(connection: usize, stream: TcpStream, subscriptions: Subscriptions) {
+    let (mut reader, mut writer) = stream.into_split();
+    let (sender, mut receiver) = mpsc::unbounded_channel::<Vec<u8>>();
+
+    tokio::spawn(async move {
+        while let Some(packet) = receiver.recv().await {
+            if writer.write_all(&packet).await.is_err() {
+                return;
+            }
+        }
+    });
+
+    while let Some((header, body)) = read_packet(&mut reader).await {
+        match header >> 4 {
+            CONNECT => {
+                let _ = sender.send(packet(CONNACK << 4, &[0, 0, 0]));
+            }
+            PUBLISH => {
+                let qos = (header >> 1) & 0b11;
+                let (publish, packet_id) = forwarded_publish(&body, qos);
+
+                match qos {
+                    1 => {
+                        let _ = sender.send(packet(PUBACK << 4, &packet_id));
+                    }
+                    2 => {
+                        let _ = sender.send(packet(PUBREC << 4, &packet_id));
+                    }
+                    _ => (),
+                }
+
+                let topic = read_string(&body, 0).0;
+                deliver(&subscriptions, &topic, &publish);
+            }
+            PUBREL => {
+                let _ = sender.send(packet(PUBCOMP << 4, &body[..2]));
+            }
+            SUBSCRIBE => {
+                let filters = read_filters(&body, true);
+                let mut suback = body[..2].to_vec();
+                suback.push(0);
+                suback.extend(filters.iter().map(|_| 0));
+
+                let mut subscriptions = subscriptions.lock().unwrap();
+                for filter in filters {
+                    subscriptions.push(Subscription {
+                        connection,
+                        filter,
+                        sender: sender.clone(),
+                    });
+                }
+
+                let _ = sender.send(packet(SUBACK << 4, &suback));
+            }
+            UNSUBSCRIBE => {
+                let filters = read_filters(&body, false);
+                let mut unsuback = body[..2].to_vec();
+                unsuback.push(0);
+                unsuback.extend(filters.iter().map(|_| 0));
+
+                subscriptions.lock().unwrap().retain(|subscription| {
+                    subscription.connection != connection || !filters.contains(&subscription.filter)
+                });
+
+                let _ = sender.send(packet(UNSUBACK << 4, &unsuback));
+            }
+            PINGREQ => {
+                let _ = sender.send(packet(PINGRESP << 4, &[]));
+            }
+            DISCONNECT => break,
+            _ => (),
+        }
+    }
+
+    subscriptions
+        .lock()
+        .unwrap()
+        .retain(|subscription| subscription.connection != connection);
+}
+
+fn deliver(subscriptions: &Subscriptions, topic: &str, publish: &[u8]) {
+    let subscriptions = subscriptions.lock().unwrap();
+    let mut delivered = vec![];
+
+    for subscription in subscriptions.iter() {
+        if delivered.contains(&subscription.connection) || !matches(&subscription.filter, topic) {
+            continue;
+        }
+
+        delivered.push(subscription.connection);
+        let _ = subscription.sender.send(publish.to_vec());
+    }
+}
+
+/// QoS 0 copy of a received publish and the packet id to acknowledge.
+fn forwarded_publish(body: &[u8], qos: u8) -> (Vec<u8>, Vec<u8>) {
+    let (_, topic_end) = read_string(body, 0);
+
+    let (packet_id, rest) = if qos > 0 {
+        (body[topic_end..topic_end + 2].to_vec(), topic_end + 2)
+    } else {
+        (vec![], topic_end)
+    };
+
+    let mut forwarded = body[..topic_end].to_vec();
+    forwarded.extend_from_slice(&body[rest..]);
+
+    (packet(PUBLISH << 4, &forwarded), packet_id)
+}
+
+/// Topic filters of a (un)subscribe body, skipping the packet id, the
+/// properties and, for subscriptions, the options of every filter.
+fn read_filters(body: &[u8], has_options: bool) -> Vec<String> {
+    let (properties_len, mut offset) = read_varint(body, 2);
+    offset += properties_len;
+
+    let mut filters = vec![];
+
+    while offset < body.len() {
+        let (filter, end) = read_string(body, offset);
+        filters.push(filter);
+
+        offset = if has_options { end + 1 } else { end };
+    }
+
+    filters
+}
+
+fn matches(filter: &str, topic: &str) -> bool {
+    let mut filter = filter.split('/');
+    let mut topic = topic.split('/');
+
+    loop {
+        match (filter.next(), topic.next()) {
+            (Some("#"), _) => return true,
+            (Some("+"), Some(_)) => (),
+            (Some(filter), Some(topic)) if filter == topic => (),
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+async fn read_packet(reader: &mut OwnedReadHalf) -> Option<(u8, Vec<u8>)> {
+    let header = reader.read_u8().await.ok()?;
+
+    let mut len = 0;
+    for shift in (0..28).step_by(7) {
+        let byte = reader.read_u8().await.ok()?;
+        len |= ((byte & 0x7f) as usize) << shift;
+
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+
+    let mut body = vec![0; len];
+    reader.read_exact(&mut body).await.ok()?;
+
+    Some((header, body))
+}
+
+fn packet(header: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![header];
+
+    let mut len = body.len();
+    loop {
+        let mut byte = (len % 128) as u8;
+        len /= 128;
+
+        if len > 0 {
+            byte |= 0x80;
+        }
+
+        packet.push(byte);
+
+        if len == 0 {
+            break;
+        }
+    }
+
+    packet.extend_from_slice(body);
+    packet
+}
+
+fn read_string(bytes: &[u8], offset: usize) -> (String, usize) {
+    let len = u16::from_be_bytes([bytes[offset], bytes[offset + 1]]) as usize;
+    let start = offset + 2;
+
+    (
+        String::from_utf8_lossy(&bytes[start..start + len]).into_owned(),
+        start + len,
+    )
+}
+
+fn read_varint(bytes: &[u8], mut offset: usize) -> (usize, usize) {
+    let mut value = 0;
+
+    for shift in (0..28).step_by(7) {
+        let byte = bytes[offset];
+        offset += 1;
+        value |= ((byte & 0x7f) as usize) << shift;
+
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+
+    (value, offset)
+}
@@ -0,0 +1,204 @@
+mod broker;
+
+use std::time::Duration;
+
+use futures_util::future::join_all;
+use transport::action::{Action, ActionRequest, ActionResponse, ActionResult};
+use transport::state::{StateRequest, StateResponse};
+use transport::{elisheba, rpc, DeviceId, MqttSession, Room, Topic};
+use uuid::Uuid;
+
+const DEADLINE: Duration = Duration::from_secs(5);
+const REQUESTS: usize = 16;
+
+/// Answers state requests for lights and actions on them, the later a
+/// request came in the sooner, so responses interleave.
+async fn gateway(address: String) {
+    let mut mqtt = MqttSession::connect(address, String::new(), String::new(), "gateway")
+        .await
+        .unwrap();
+
+    mqtt.subscribe(Topic::StateRequest).await.unwrap();
+    mqtt.subscribe(Topic::ActionRequest).await.unwrap();
+
+    tokio::spawn(async move {
+        let mut count = 0;
+
+        while let Some((topic, msg)) = mqtt.next().await {
+            count += 1;
+            let delay = Duration::from_millis(200u64.saturating_sub(count * 10));
+
+            match topic {
+                Topic::StateRequest => {
+                    let (request, responder) =
+                        rpc::serve::<StateRequest>(&msg, mqtt.client()).unwrap();
+
+                    tokio::spawn(async move {
+                        tokio::time::sleep(delay).await;
+
+                        for id in request.device_ids {
+                            let state = StateResponse::Elisheba(elisheba::State {
+                                is_enabled: true,
+                                room: id.room,
+                            });
+
+                            responder.respond(&state).await;
+                        }
+                    });
+                }
+                Topic::ActionRequest => {
+                    let (request, responder) =
+                        rpc::serve::<ActionRequest>(&msg, mqtt.client()).unwrap();
+
+                    tokio::spawn(async move {
+                        tokio::time::sleep(delay).await;
+
+                        for action in request.actions {
+                            let response = ActionResponse {
+                                action_id: action.id().unwrap(),
+                                result: ActionResult::Success,
+                            };
+
+                            responder.respond(&response).await;
+                        }
+                    });
+                }
+                _ => (),
+            }
+        }
+    });
+}
+
+async fn query(mqtt: &rpc::Client, room: Room) -> StateResponse {
+    let request = StateRequest {
+        device_ids: vec![DeviceId::light_at_room(room)],
+    };
+
+    let mut responses = mqtt
+        .request(Topic::StateRequest, &request, DEADLINE)
+        .await
+        .unwrap();
+
+    responses.next().await.unwrap()
+}
+
+async fn action(mqtt: &rpc::Client, action_id: Uuid) -> ActionResponse {
+    let request = ActionRequest {
+        actions: vec![Action::Elisheba(
+            elisheba::Action {
+                room: Room::new("corridor"),
+                is_enabled: true,
+            },
+            action_id,
+        )],
+    };
+
+    let mut responses = mqtt
+        .request(Topic::ActionRequest, &request, DEADLINE)
+        .await
+        .unwrap();
+
+    responses.next().await.unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_parallel_requests() {
+    let address = broker::start().await;
+    gateway(address.clone()).await;
+
+    let mqtt = rpc::Client::connect(address, String::new(), String::new(), "requester")
+        .await
+        .unwrap();
+
+    let rooms: Vec<_> = (0..REQUESTS)
+        .map(|index| Room::new(&format!("room_{index}")))
+        .collect();
+    let action_ids: Vec<_> = (0..REQUESTS).map(|_| Uuid::new_v4()).collect();
+
+    let queries = join_all(rooms.iter().map(|room| query(&mqtt, room.clone())));
+    let actions = join_all(action_ids.iter().map(|id| action(&mqtt, *id)));
+
+    let (states, responses) =
+        tokio::time::timeout(DEADLINE, async { tokio::join!(queries, actions) })
+            .await
+            .unwrap();
+
+    for (room, state) in rooms.into_iter().zip(states) {
+        assert_eq!(
+            state,
+            StateResponse::Elisheba(elisheba::State {
+                is_enabled: true,
+                room,
+            })
+        );
+    }
+
+    for (action_id, response) in action_ids.into_iter().zip(responses) {
+        assert_eq!(
+            response,
+            ActionResponse {
+                action_id,
+                result: ActionResult::Success,
+            }
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_availability_of_main_sessions() {
+    let address = broker::start().await;
+
+    let mut watcher =
+        MqttSession::connect_auxiliary(address.clone(), String::new(), String::new(), "watcher")
+            .await
+            .unwrap();
+    watcher.subscribe("availability/+").await.unwrap();
+
+    let _requester =
+        rpc::Client::connect(address.clone(), String::new(), String::new(), "requester")
+            .await
+            .unwrap();
+    let _gateway = MqttSession::connect(address, String::new(), String::new(), "gateway")
+        .await
+        .unwrap();
+
+    let message = tokio::time::timeout(DEADLINE, watcher.next_message())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(message.topic(), "availability/gateway");
+
+    let next = tokio::time::timeout(Duration::from_millis(500), watcher.next_message()).await;
+    assert!(next.is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_client_on_main_session() {
+    let address = broker::start().await;
+    gateway(address.clone()).await;
+
+    let mut session = MqttSession::connect(address, String::new(), String::new(), "bridge")
+        .await
+        .unwrap();
+    let mqtt = rpc::Client::attach(&mut session).await.unwrap();
+
+    let client = mqtt.clone();
+    tokio::spawn(async move {
+        while let Some(msg) = session.next_message().await {
+            client.route(&msg);
+        }
+    });
+
+    let action_id = Uuid::new_v4();
+    let response = tokio::time::timeout(DEADLINE, action(&mqtt, action_id))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        response,
+        ActionResponse {
+            action_id,
+            result: ActionResult::Success,
+        }
+    );
+}
@@ -114,6 +114,8 @@ install -m 644 "$repo_dir/packaging/deb/systemd/$service.service" \
   "$package_root/lib/systemd/system/$service.service"
 install -m 644 "$repo_dir/packaging/deb/env/$service.env.example" \
   "$package_root/etc/lisa/$service.env.example"
+install -m 644 "$repo_dir/conf/registry.toml" \
+  "$package_root/etc/lisa/registry.toml.example"
 
 install -d "$output_dir"
 dpkg-deb --build --root-owner-group "$package_root" \
@@ -19,6 +19,7 @@ MQTT_PASS={{ op://Private/elisa mqtt/password }}
 ROBOROCK_IP=10.0.1.150
 ROBOROCK_DUID={{ op://Private/Vacuum Roborock/username }}
 ROBOROCK_LOCAL_KEY={{ op://Private/Vacuum Roborock/credential }}
+REGISTRY_PATH=/etc/lisa/registry.toml
 EOF
 
 cat <<'EOF' | op inject --account "$op_account" --out-file "$output_dir/elisheba.env"
@@ -27,6 +28,7 @@ MQTT_ADDRESS=mqtts://mq.chipp.dev:8880
 MQTT_USER=elisheba
 MQTT_PASS={{ op://Private/elisheba mqtt/password }}
 KEYS={{ op://Private/elisheba devices/notesPlain }}
+REGISTRY_PATH=/etc/lisa/registry.toml
 EOF
 
 cat <<'EOF' | op inject --account "$op_account" --out-file "$output_dir/isabel.env"
@@ -35,6 +37,7 @@ MQTT_ADDRESS=mqtts://mq.chipp.dev:8880
 MQTT_USER=isabel
 MQTT_PASS={{ op://Private/isabel mqtt/password }}
 DB_PATH=/var/lib/lisa/isabel/isabel.db
+REGISTRY_PATH=/etc/lisa/registry.toml
 EOF
 
 chmod 600 "$output_dir"/*.env